@@ -78,33 +78,12 @@
 1110 RETURN
 
 2000 REM Get param - Input: QUERY$, PNAME$, Output: PVAL$
-2010 SEARCH$ = PNAME$ + "="
-2020 POS = INSTR(QUERY$, SEARCH$)
-2030 IF POS = 0 THEN 2035
-2034 GOTO 2040
-2035 PVAL$ = ""
-2036 RETURN
-2040 START = POS + LEN(SEARCH$)
-2050 REST$ = MID$(QUERY$, START, 200)
-2060 AMP = INSTR(REST$, "&")
-2070 IF AMP = 0 THEN 2075
-2074 GOTO 2080
-2075 PVAL$ = REST$
-2076 RETURN
-2080 PVAL$ = LEFT$(REST$, AMP - 1)
-2090 RETURN
+2010 PVAL$ = GETFIELD$(QUERY$, PNAME$)
+2020 RETURN
 
 2100 REM URL decode - Input/Output: PVAL$
-2110 RESULT$ = ""
-2120 FOR I = 1 TO LEN(PVAL$)
-2130 CH$ = MID$(PVAL$, I, 1)
-2140 IF CH$ = "+" THEN 2145
-2144 GOTO 2150
-2145 CH$ = " "
-2150 RESULT$ = RESULT$ + CH$
-2160 NEXT I
-2170 PVAL$ = RESULT$
-2180 RETURN
+2110 PVAL$ = URLDECODE$(PVAL$)
+2120 RETURN
 
 3000 REM Build response - Input: STATUS, BODY$, Output: RESP$
 3005 RESP$ = ""
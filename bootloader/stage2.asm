@@ -10,7 +10,12 @@ KERNEL_ADDR         equ 0x100000    ; Load kernel at 1MB
 ; Kernel loading - use multiple small reads to avoid BIOS issues
 ; Each track has 18 sectors, reading within track boundaries is safest
 KERNEL_LOAD_SEG     equ 0x1000      ; Load to 0x10000
-    KERNEL_SECTORS      equ 480         ; max (kernel + exec table + programs)
+KERNEL_SECTORS      equ 1024        ; max (kernel + exec table + programs)
+BOOT_STACK          equ 0x90000     ; Stack top once in protected/long mode
+
+; The bounce buffer at 0x10000 must end at or below the boot stack;
+; a negative TIMES count stops the build if KERNEL_SECTORS outgrows it
+times -(((KERNEL_LOAD_SEG << 4) + KERNEL_SECTORS * 512) > BOOT_STACK) db 0
 
 ; Page table locations (must be 4KB aligned)
 PML4_ADDR           equ 0x1000
@@ -368,7 +373,7 @@ protected_mode:
     mov fs, ax
     mov gs, ax
     mov ss, ax
-    mov esp, 0x90000        ; Set up stack
+    mov esp, BOOT_STACK     ; Set up stack
 
     ; Copy kernel from 0x10000 to 0x100000
     mov esi, (KERNEL_LOAD_SEG << 4)         ; Source: 0x10000
@@ -456,7 +461,7 @@ long_mode:
     mov ss, ax
 
     ; Set up stack
-    mov rsp, 0x90000
+    mov rsp, BOOT_STACK
 
     ; Jump to kernel at 1MB
     mov rax, KERNEL_ADDR
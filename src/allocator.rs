@@ -123,9 +123,25 @@ impl LinkedListAllocator {
         (addr + align - 1) & !(align - 1)
     }
 
+    /// Where an allocation needing `align` would start inside the free
+    /// block at `block_start`
+    ///
+    /// The header always sits right before the user data, so for alignments
+    /// above 8 the allocation moves into the block. The gap in front stays
+    /// a free block, so it is pushed out further if it would be too small
+    /// to hold a FreeBlock header.
+    fn aligned_start(block_start: usize, align: usize) -> usize {
+        let start = Self::align_up(block_start + HEADER_SIZE, align) - HEADER_SIZE;
+        if start != block_start && start - block_start < MIN_BLOCK_SIZE {
+            start + align
+        } else {
+            start
+        }
+    }
+
     /// Allocate memory with the given layout
     pub fn allocate(&mut self, layout: Layout) -> *mut u8 {
-        assert!(layout.align() <= ALIGNMENT);
+        let align = layout.align().max(ALIGNMENT);
 
         // We need space for header + user data, rounded so blocks always remain 8-byte aligned.
         let user_size = layout.size().max(1);
@@ -142,18 +158,28 @@ impl LinkedListAllocator {
             debug_assert!(block_start % ALIGNMENT == 0);
             debug_assert!(block_size % ALIGNMENT == 0);
 
+            let alloc_start = Self::aligned_start(block_start, align);
+            let front = alloc_start - block_start;
+
             // Check if block is large enough
-            if block_size >= total_size {
-                // This block works! Remove it from the free list
-                let next = block.next;
-
-                // Update previous block's next pointer (or head)
-                match prev {
-                    Some(mut prev_ptr) => unsafe {
-                        prev_ptr.as_mut().next = next;
-                    },
-                    None => {
-                        self.head = next;
+            if block_size >= front + total_size {
+                if front == 0 {
+                    // This block works! Remove it from the free list
+                    let next = block.next;
+
+                    // Update previous block's next pointer (or head)
+                    match prev {
+                        Some(mut prev_ptr) => unsafe {
+                            prev_ptr.as_mut().next = next;
+                        },
+                        None => {
+                            self.head = next;
+                        }
+                    }
+                } else {
+                    // Keep the alignment gap in front as a smaller free block
+                    unsafe {
+                        (*block_ptr.as_ptr()).size = front;
                     }
                 }
 
@@ -161,12 +187,13 @@ impl LinkedListAllocator {
                 //
                 // If the tail is too small to hold a FreeBlock header, we "eat" it
                 // as part of this allocation so the heap still partitions cleanly.
-                let (alloc_block_size, remaining) = if block_size - total_size >= MIN_BLOCK_SIZE {
-                    (total_size, block_size - total_size)
+                let available = block_size - front;
+                let (alloc_block_size, remaining) = if available - total_size >= MIN_BLOCK_SIZE {
+                    (total_size, available - total_size)
                 } else {
-                    (block_size, 0)
+                    (available, 0)
                 };
-                let used_end = block_start + alloc_block_size;
+                let used_end = alloc_start + alloc_block_size;
                 if remaining >= MIN_BLOCK_SIZE {
                     // Create a new free block for remaining space
                     debug_assert!(used_end % ALIGNMENT == 0);
@@ -175,17 +202,17 @@ impl LinkedListAllocator {
                 }
 
                 // Write the allocation header at the start of the block
-                let header = block_start as *mut AllocationHeader;
+                let header = alloc_start as *mut AllocationHeader;
                 unsafe {
                     (*header).magic = HEADER_MAGIC;
                     (*header).task_id = encode_task_id(get_current_task_id());
                     (*header).block_size = alloc_block_size;
                 }
 
-                // Notify memory visualizer of allocation (from block_start)
-                crate::memvis::on_alloc(block_start, alloc_block_size);
+                // Notify memory visualizer of allocation (from alloc_start)
+                crate::memvis::on_alloc(alloc_start, alloc_block_size);
 
-                let user_addr = Self::align_up(block_start + HEADER_SIZE, ALIGNMENT);
+                let user_addr = alloc_start + HEADER_SIZE;
                 return user_addr as *mut u8;
             }
 
@@ -203,18 +230,9 @@ impl LinkedListAllocator {
     /// - ptr must have been allocated by this allocator
     /// - layout must match the original allocation
     pub unsafe fn deallocate(&mut self, ptr: *mut u8, _layout: Layout) {
-        let user_addr = ptr as usize;
-
-        // Header is immediately before user data, and lives at block_start.
-        let header = (user_addr - HEADER_SIZE) as *mut AllocationHeader;
-        if unsafe { (*header).magic } != HEADER_MAGIC {
-            panic!("Invalid heap allocation header");
-        }
-
-        let block_start = header as usize;
-        let block_size = unsafe { (*header).block_size };
-        debug_assert!(block_start % ALIGNMENT == 0);
-        debug_assert!(block_size % ALIGNMENT == 0);
+        let Some((block_start, block_size)) = self.checked_block(ptr, "free") else {
+            return;
+        };
 
         // Notify memory visualizer of deallocation
         crate::memvis::on_dealloc(block_start, block_size);
@@ -227,6 +245,163 @@ impl LinkedListAllocator {
         self.merge_free_blocks();
     }
 
+    /// Resize an allocation
+    ///
+    /// Grows in place when the free block directly after this one is large
+    /// enough: the header's `block_size` is extended and the neighbor is
+    /// shrunk or taken whole. Shrinking keeps the block as is. Otherwise
+    /// falls back to allocate, copy, free; the new block keeps the original
+    /// owner's task ID.
+    ///
+    /// # Safety
+    /// - ptr must have been allocated by this allocator with `layout`
+    pub unsafe fn reallocate(&mut self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let Some((block_start, block_size)) = self.checked_block(ptr, "realloc") else {
+            return ptr::null_mut();
+        };
+        let header = block_start as *mut AllocationHeader;
+        let total_size = Self::align_up(HEADER_SIZE + new_size.max(1), ALIGNMENT).max(MIN_BLOCK_SIZE);
+        if total_size <= block_size {
+            return ptr;
+        }
+
+        if let Some(grown_size) = self.grow_in_place(block_start, block_size, total_size) {
+            unsafe {
+                (*header).block_size = grown_size;
+            }
+            crate::memvis::on_resize(block_start, block_size, grown_size);
+            return ptr;
+        }
+
+        let new_layout = unsafe { Layout::from_size_align_unchecked(new_size, layout.align()) };
+        let new_ptr = self.allocate(new_layout);
+        if new_ptr.is_null() {
+            return new_ptr;
+        }
+        unsafe {
+            let new_header = (new_ptr as usize - HEADER_SIZE) as *mut AllocationHeader;
+            (*new_header).task_id = (*header).task_id;
+            ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+            self.deallocate(ptr, layout);
+        }
+        new_ptr
+    }
+
+    /// Extend the block at `block_start` to at least `total_size` bytes by
+    /// taking space from the free block that immediately follows it
+    ///
+    /// Returns the new block size. Like `allocate`, a leftover too small to
+    /// hold a FreeBlock header is eaten, so this may exceed `total_size`.
+    fn grow_in_place(&mut self, block_start: usize, block_size: usize, total_size: usize) -> Option<usize> {
+        let block_end = block_start + block_size;
+        let mut prev: Option<NonNull<FreeBlock>> = None;
+        let mut current = self.head;
+
+        while let Some(block_ptr) = current {
+            let addr = block_ptr.as_ptr() as usize;
+            if addr > block_end {
+                return None;
+            }
+            if addr == block_end {
+                break;
+            }
+            prev = current;
+            current = unsafe { block_ptr.as_ref().next };
+        }
+        let neighbor = current?;
+
+        let (neighbor_size, next) = unsafe { (neighbor.as_ref().size, neighbor.as_ref().next) };
+        let needed = total_size - block_size;
+        if neighbor_size < needed {
+            return None;
+        }
+
+        // Shrink the neighbor from the front, or unlink it if the rest
+        // would be too small to stand as a free block.
+        let (replacement, grown_size) = if neighbor_size - needed >= MIN_BLOCK_SIZE {
+            let shrunk = unsafe { FreeBlock::new(block_start + total_size, neighbor_size - needed) };
+            unsafe {
+                (*shrunk.as_ptr()).next = next;
+            }
+            (Some(shrunk), total_size)
+        } else {
+            (next, block_size + neighbor_size)
+        };
+        match prev {
+            Some(mut prev_ptr) => unsafe {
+                prev_ptr.as_mut().next = replacement;
+            },
+            None => {
+                self.head = replacement;
+            }
+        }
+        Some(grown_size)
+    }
+
+    /// Validate a pointer handed back to `deallocate` or `reallocate`
+    ///
+    /// Returns the block's (start, size), or logs the address and the
+    /// calling task and returns None if the pointer is outside the heap,
+    /// misaligned, lacks a valid header, or lies in a block that is
+    /// already free. Rejecting it here keeps a double free or a stray
+    /// pointer from corrupting the free list.
+    fn checked_block(&self, ptr: *mut u8, op: &str) -> Option<(usize, usize)> {
+        let user_addr = ptr as usize;
+        match self.block_problem(user_addr) {
+            None => {
+                let header = Self::get_header(user_addr);
+                Some((user_addr - HEADER_SIZE, header.block_size))
+            }
+            Some(reason) => {
+                crate::println!(
+                    "[heap] rejected {} of {:#x} by task {:?}: {}",
+                    op, user_addr, get_current_task_id(), reason);
+                None
+            }
+        }
+    }
+
+    /// Why `user_addr` is not a live allocation, or None if it is
+    fn block_problem(&self, user_addr: usize) -> Option<&'static str> {
+        if user_addr < self.heap_start + HEADER_SIZE || user_addr >= self.heap_end {
+            return Some("outside the heap");
+        }
+        if !user_addr.is_multiple_of(ALIGNMENT) {
+            return Some("misaligned");
+        }
+
+        // Header is immediately before user data, and lives at block_start.
+        let block_start = user_addr - HEADER_SIZE;
+        let header = Self::get_header(user_addr);
+        if header.magic != HEADER_MAGIC {
+            return Some("no allocation header");
+        }
+        let block_size = header.block_size;
+        if block_size < MIN_BLOCK_SIZE
+            || !block_size.is_multiple_of(ALIGNMENT)
+            || block_size > self.heap_end - block_start
+        {
+            return Some("corrupt block size");
+        }
+
+        // A freed block keeps its old header once merged into the free
+        // block before it, so also check containment, not just the start.
+        let block_end = block_start + block_size;
+        let mut current = self.head;
+        while let Some(block_ptr) = current {
+            let free_start = block_ptr.as_ptr() as usize;
+            if free_start >= block_end {
+                break;
+            }
+            let block = unsafe { block_ptr.as_ref() };
+            if free_start + block.size > block_start {
+                return Some("already free");
+            }
+            current = block.next;
+        }
+        None
+    }
+
     /// Add a free block to the list (sorted by address for merging)
     fn add_free_block(&mut self, new_block: NonNull<FreeBlock>) {
         let new_addr = new_block.as_ptr() as usize;
@@ -320,6 +495,10 @@ unsafe impl GlobalAlloc for LockedAllocator {
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
         self.inner.lock().deallocate(ptr, layout);
     }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        self.inner.lock().reallocate(ptr, layout, new_size)
+    }
 }
 
 /// Check if interrupts are currently enabled
@@ -458,6 +637,33 @@ pub fn get_heap_stats() -> (usize, usize) {
     (used, free)
 }
 
+/// Get heap fragmentation statistics
+///
+/// Returns (largest_free, free_block_count, smallest_free). A heap with
+/// plenty of free bytes can still fail a large allocation when the largest
+/// free block is small.
+pub fn get_fragmentation_stats() -> (usize, usize, usize) {
+    let allocator = ALLOCATOR.inner.lock();
+
+    let mut largest = 0;
+    let mut count = 0;
+    let mut smallest = usize::MAX;
+    let mut current = allocator.head;
+
+    while let Some(block_ptr) = current {
+        let block = unsafe { block_ptr.as_ref() };
+        largest = largest.max(block.size);
+        smallest = smallest.min(block.size);
+        count += 1;
+        current = block.next;
+    }
+
+    if count == 0 {
+        smallest = 0;
+    }
+    (largest, count, smallest)
+}
+
 /// Find the allocation that contains the given address
 ///
 /// Returns Some((start, end, task_id)) if the address is in an allocated region,
@@ -792,3 +998,60 @@ pub fn get_task_heap_allocations(task_id: Option<TaskId>) -> alloc::vec::Vec<(us
     let count = snapshot_task_heap_allocations(task_id, &mut snapshot);
     snapshot[..count].to_vec()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_HEAP_SIZE: usize = 4096;
+
+    #[repr(C, align(64))]
+    struct TestHeap([u8; TEST_HEAP_SIZE]);
+
+    fn fill(ptr: *mut u8, len: usize, byte: u8) {
+        unsafe { core::ptr::write_bytes(ptr, byte, len) }
+    }
+
+    fn holds(ptr: *mut u8, len: usize, byte: u8) -> bool {
+        (0..len).all(|i| unsafe { *ptr.add(i) } == byte)
+    }
+
+    #[test]
+    fn test_large_alignment() {
+        let mut heap = TestHeap([0; TEST_HEAP_SIZE]);
+        let mut allocator = LinkedListAllocator::new();
+        unsafe { allocator.init(heap.0.as_mut_ptr() as usize, TEST_HEAP_SIZE) };
+
+        let small = Layout::from_size_align(24, 8).unwrap();
+        let wide = Layout::from_size_align(64, 64).unwrap();
+
+        let before = allocator.allocate(small);
+        fill(before, 24, 0xAA);
+        let aligned = allocator.allocate(wide);
+        assert_eq!(aligned as usize % 64, 0);
+        fill(aligned, 64, 0x55);
+        let after = allocator.allocate(small);
+        fill(after, 24, 0xBB);
+
+        // Freeing the aligned block leaves its neighbors alone
+        unsafe { allocator.deallocate(aligned, wide) };
+        assert!(holds(before, 24, 0xAA));
+        assert!(holds(after, 24, 0xBB));
+
+        // The alignment gap went back to the free list and can be reused
+        let again = allocator.allocate(wide);
+        assert_eq!(again as usize % 64, 0);
+        unsafe {
+            allocator.deallocate(again, wide);
+            allocator.deallocate(before, small);
+            allocator.deallocate(after, small);
+        }
+
+        // Everything merged back into one block spanning the heap
+        let head = allocator.head.unwrap();
+        let block = unsafe { head.as_ref() };
+        assert_eq!(head.as_ptr() as usize, heap.0.as_ptr() as usize);
+        assert_eq!(block.size, TEST_HEAP_SIZE);
+        assert!(block.next.is_none());
+    }
+}
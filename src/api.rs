@@ -3,18 +3,28 @@
 //! Provides a stable interface for programs to call kernel functions.
 //! Programs receive a pointer to this API struct at startup.
 
+use crate::caps::{self, CAP_ALLOC, CAP_NET};
 use crate::scheduler;
 use crate::task::TaskId;
-use crate::executable::{self, LoadedProgram};
-use crate::net::tcp;
+use crate::executable;
+use crate::env;
+use crate::net::{ipv4, tcp};
+use crate::pipe::{self, PipeRead};
+use crate::rand;
+use crate::timer;
+use crate::allocator::Spinlock;
+use alloc::vec::Vec;
 
 /// Kernel API version
-pub const API_VERSION: u32 = 4;
+pub const API_VERSION: u32 = 11;
 
 /// Kernel API structure passed to programs
 ///
 /// This struct is passed to program entry points. Programs use these
 /// function pointers to access kernel services.
+///
+/// A task spawned without a capability (see `caps`) gets -1 from every
+/// net_* call (CAP_NET) or null from alloc (CAP_ALLOC).
 #[repr(C)]
 pub struct KernelApi {
     /// API version number
@@ -37,6 +47,10 @@ pub struct KernelApi {
     /// Create a TCP socket, returns socket handle or -1 on error
     pub net_socket: extern "C" fn() -> i32,
     /// Start TCP connection (non-blocking), returns 0 on success, -1 on error
+    ///
+    /// `ip` is in host order with the first octet in the most significant
+    /// byte (10.0.2.2 is 0x0A000202), as returned by make_ip/parse_ip.
+    /// Don't pass it through htonl. `port` is in host order too.
     pub net_connect: extern "C" fn(sock: i32, ip: u32, port: u16) -> i32,
     /// Get socket status: 0=connecting, 1=connected, 2=closed, -1=error
     pub net_status: extern "C" fn(sock: i32) -> i32,
@@ -52,6 +66,68 @@ pub struct KernelApi {
     pub net_listen: extern "C" fn(sock: i32, port: u16) -> i32,
     /// Accept connection (non-blocking), returns new socket, 0 if none pending, -1 on error
     pub net_accept: extern "C" fn(sock: i32) -> i32,
+
+    // Pipe API (v5+)
+
+    /// Read one line from stdin (the pipe feeding this program), waiting
+    /// for it; returns its length without the newline, or -1 at end of
+    /// input or if stdin is not a pipe. Longer lines arrive in pieces.
+    pub read_line: extern "C" fn(buf: *mut u8, len: usize) -> i32,
+
+    // Environment API (v6+)
+
+    /// Copy a variable's value into buf (truncated to len); returns the
+    /// full value length, or -1 if the variable is unset
+    pub getenv: extern "C" fn(name: *const u8, name_len: usize, buf: *mut u8, len: usize) -> i32,
+    /// Set a variable (an empty value unsets it); returns 0 on success,
+    /// -1 if the name/value is invalid or too long or the store is full
+    pub setenv: extern "C" fn(name: *const u8, name_len: usize, value: *const u8, value_len: usize) -> i32,
+
+    // Random numbers (v7+)
+
+    /// Next pseudo-random number from this task's own sequence
+    pub rand: extern "C" fn() -> u32,
+    /// Restart this task's sequence from a seed (same seed, same numbers)
+    pub srand: extern "C" fn(seed: u32),
+
+    // Time (v8+)
+
+    /// Timer ticks since boot (100 per second, i.e. 10ms resolution, unless
+    /// the kernel changed the rate; see ticks_per_second)
+    pub ticks: extern "C" fn() -> u64,
+    /// Current timer tick rate
+    pub ticks_per_second: extern "C" fn() -> u64,
+    /// Milliseconds since boot (tick resolution, unaffected by rate changes)
+    pub uptime_ms: extern "C" fn() -> u64,
+
+    // Address helpers (v9+)
+
+    /// Host to network (big-endian) byte order, for building raw packets
+    pub htonl: extern "C" fn(u32) -> u32,
+    pub htons: extern "C" fn(u16) -> u16,
+    /// a.b.c.d as a host-order address for net_connect
+    pub make_ip: extern "C" fn(a: u8, b: u8, c: u8, d: u8) -> u32,
+    /// Parse "a.b.c.d" into a host-order address; 0 if malformed
+    pub parse_ip: extern "C" fn(s: *const u8, len: usize) -> u32,
+    /// Format a host-order address as "a.b.c.d" into buf; returns the
+    /// length, or -1 if buf is too small (16 bytes always suffice)
+    pub ip_to_str: extern "C" fn(ip: u32, buf: *mut u8, len: usize) -> i32,
+
+    // Socket addresses (v10+)
+
+    /// Store the peer's host-order IP and port; returns 0, or -1 if the
+    /// socket is not connected
+    pub net_getpeername: extern "C" fn(sock: i32, ip: *mut u32, port: *mut u16) -> i32,
+    /// Store the local host-order IP and port; returns 0, or -1 if the
+    /// socket is not bound
+    pub net_getsockname: extern "C" fn(sock: i32, ip: *mut u32, port: *mut u16) -> i32,
+
+    // Blocking connect (v11+)
+
+    /// Connect and wait (yielding) for the result: 0 when connected, -1 if
+    /// refused/reset or the connect couldn't start, -2 after timeout_ms.
+    /// The socket stays allocated either way; close it on failure.
+    pub net_connect_wait: extern "C" fn(sock: i32, ip: u32, port: u16, timeout_ms: u64) -> i32,
 }
 
 // API implementation functions
@@ -62,11 +138,44 @@ extern "C" fn api_print(ptr: *const u8, len: usize) {
     }
 
     let bytes = unsafe { core::slice::from_raw_parts(ptr, len) };
+
+    // Output of the first program in a pipeline feeds the second
+    if let Some(pipe) = scheduler::current_task_id().and_then(pipe::stdout_of) {
+        pipe::write(pipe, bytes);
+        return;
+    }
+
     if let Ok(s) = core::str::from_utf8(bytes) {
         crate::print!("{}", s);
     }
 }
 
+extern "C" fn api_read_line(buf: *mut u8, len: usize) -> i32 {
+    if buf.is_null() || len == 0 {
+        return -1;
+    }
+    let Some(pipe) = scheduler::current_task_id().and_then(pipe::stdin_of) else {
+        return -1;
+    };
+
+    let buffer = unsafe { core::slice::from_raw_parts_mut(buf, len) };
+    let mut n = 0;
+    while n < len {
+        let mut byte = [0u8; 1];
+        match pipe::read(pipe, &mut byte) {
+            PipeRead::Data(_) if byte[0] == b'\n' => return n as i32,
+            PipeRead::Data(_) => {
+                buffer[n] = byte[0];
+                n += 1;
+            }
+            PipeRead::Empty => scheduler::yield_now(),
+            PipeRead::Eof if n == 0 => return -1,
+            PipeRead::Eof => break,
+        }
+    }
+    n as i32
+}
+
 extern "C" fn api_yield() {
     scheduler::yield_now();
 }
@@ -84,7 +193,7 @@ extern "C" fn api_exit() -> ! {
 }
 
 extern "C" fn api_alloc(size: usize) -> *mut u8 {
-    if size == 0 {
+    if size == 0 || !caps::current_has(CAP_ALLOC) {
         return core::ptr::null_mut();
     }
 
@@ -116,6 +225,9 @@ extern "C" fn api_free(ptr: *mut u8) {
 // Network API implementation functions
 
 extern "C" fn api_net_socket() -> i32 {
+    if !caps::current_has(CAP_NET) {
+        return -1;
+    }
     match tcp::socket() {
         Some(sock) => sock as i32,
         None => -1,
@@ -123,6 +235,9 @@ extern "C" fn api_net_socket() -> i32 {
 }
 
 extern "C" fn api_net_connect(sock: i32, ip: u32, port: u16) -> i32 {
+    if !caps::current_has(CAP_NET) {
+        return -1;
+    }
     if sock < 0 {
         return -1;
     }
@@ -136,6 +251,9 @@ extern "C" fn api_net_connect(sock: i32, ip: u32, port: u16) -> i32 {
 }
 
 extern "C" fn api_net_status(sock: i32) -> i32 {
+    if !caps::current_has(CAP_NET) {
+        return -1;
+    }
     if sock < 0 {
         return -1;
     }
@@ -152,6 +270,9 @@ extern "C" fn api_net_status(sock: i32) -> i32 {
 }
 
 extern "C" fn api_net_send(sock: i32, data: *const u8, len: usize) -> i32 {
+    if !caps::current_has(CAP_NET) {
+        return -1;
+    }
     if sock < 0 || data.is_null() {
         return -1;
     }
@@ -160,6 +281,9 @@ extern "C" fn api_net_send(sock: i32, data: *const u8, len: usize) -> i32 {
 }
 
 extern "C" fn api_net_recv(sock: i32, buf: *mut u8, len: usize) -> i32 {
+    if !caps::current_has(CAP_NET) {
+        return -1;
+    }
     if sock < 0 || buf.is_null() {
         return -1;
     }
@@ -168,6 +292,9 @@ extern "C" fn api_net_recv(sock: i32, buf: *mut u8, len: usize) -> i32 {
 }
 
 extern "C" fn api_net_available(sock: i32) -> i32 {
+    if !caps::current_has(CAP_NET) {
+        return -1;
+    }
     if sock < 0 {
         return 0;
     }
@@ -175,12 +302,18 @@ extern "C" fn api_net_available(sock: i32) -> i32 {
 }
 
 extern "C" fn api_net_close(sock: i32) {
+    if !caps::current_has(CAP_NET) {
+        return;
+    }
     if sock >= 0 {
         tcp::close(sock as usize);
     }
 }
 
 extern "C" fn api_net_listen(sock: i32, port: u16) -> i32 {
+    if !caps::current_has(CAP_NET) {
+        return -1;
+    }
     if sock < 0 {
         return -1;
     }
@@ -192,6 +325,9 @@ extern "C" fn api_net_listen(sock: i32, port: u16) -> i32 {
 }
 
 extern "C" fn api_net_accept(sock: i32) -> i32 {
+    if !caps::current_has(CAP_NET) {
+        return -1;
+    }
     if sock < 0 {
         return -1;
     }
@@ -201,6 +337,153 @@ extern "C" fn api_net_accept(sock: i32) -> i32 {
     }
 }
 
+// Environment API implementation functions
+
+/// Borrow a UTF-8 string passed by a program
+fn api_str<'a>(ptr: *const u8, len: usize) -> Option<&'a str> {
+    if ptr.is_null() {
+        return None;
+    }
+    let bytes = unsafe { core::slice::from_raw_parts(ptr, len) };
+    core::str::from_utf8(bytes).ok()
+}
+
+extern "C" fn api_getenv(name: *const u8, name_len: usize, buf: *mut u8, len: usize) -> i32 {
+    let Some(value) = api_str(name, name_len).and_then(env::get) else {
+        return -1;
+    };
+    if !buf.is_null() {
+        let n = value.len().min(len);
+        unsafe { core::ptr::copy_nonoverlapping(value.as_ptr(), buf, n) };
+    }
+    value.len() as i32
+}
+
+extern "C" fn api_setenv(name: *const u8, name_len: usize, value: *const u8, value_len: usize) -> i32 {
+    let (Some(name), Some(value)) = (api_str(name, name_len), api_str(value, value_len)) else {
+        return -1;
+    };
+    match env::set(name, value) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+// Random number API implementation functions
+
+extern "C" fn api_rand() -> u32 {
+    scheduler::current_task_id().map(rand::next).unwrap_or(0)
+}
+
+extern "C" fn api_srand(seed: u32) {
+    if let Some(task_id) = scheduler::current_task_id() {
+        rand::seed(task_id, seed);
+    }
+}
+
+// Time API implementation functions
+
+extern "C" fn api_ticks() -> u64 {
+    timer::ticks()
+}
+
+extern "C" fn api_ticks_per_second() -> u64 {
+    timer::ticks_per_second()
+}
+
+extern "C" fn api_uptime_ms() -> u64 {
+    timer::uptime_ms()
+}
+
+extern "C" fn api_net_connect_wait(sock: i32, ip: u32, port: u16, timeout_ms: u64) -> i32 {
+    if api_net_connect(sock, ip, port) != 0 {
+        return -1;
+    }
+
+    let deadline = timer::uptime_ms().saturating_add(timeout_ms);
+    loop {
+        match tcp::get_state(sock as usize) {
+            tcp::TcpState::Established => return 0,
+            tcp::TcpState::SynSent | tcp::TcpState::SynReceived => {}
+            // Refused (RST) or torn down before it was established
+            _ => return -1,
+        }
+        if timer::uptime_ms() >= deadline {
+            return -2;
+        }
+        scheduler::yield_now();
+    }
+}
+
+// Socket address implementation functions
+
+/// Store a socket address for net_getpeername/net_getsockname
+fn store_sock_addr(addr: Option<([u8; 4], u16)>, ip: *mut u32, port: *mut u16) -> i32 {
+    let Some((addr_ip, addr_port)) = addr else {
+        return -1;
+    };
+    unsafe {
+        if !ip.is_null() {
+            *ip = u32::from_be_bytes(addr_ip);
+        }
+        if !port.is_null() {
+            *port = addr_port;
+        }
+    }
+    0
+}
+
+extern "C" fn api_net_getpeername(sock: i32, ip: *mut u32, port: *mut u16) -> i32 {
+    if !caps::current_has(CAP_NET) {
+        return -1;
+    }
+    if sock < 0 {
+        return -1;
+    }
+    store_sock_addr(tcp::peer_addr(sock as usize), ip, port)
+}
+
+extern "C" fn api_net_getsockname(sock: i32, ip: *mut u32, port: *mut u16) -> i32 {
+    if !caps::current_has(CAP_NET) {
+        return -1;
+    }
+    if sock < 0 {
+        return -1;
+    }
+    store_sock_addr(tcp::local_addr(sock as usize), ip, port)
+}
+
+// Address helper implementation functions
+
+extern "C" fn api_htonl(x: u32) -> u32 {
+    x.to_be()
+}
+
+extern "C" fn api_htons(x: u16) -> u16 {
+    x.to_be()
+}
+
+extern "C" fn api_make_ip(a: u8, b: u8, c: u8, d: u8) -> u32 {
+    u32::from_be_bytes([a, b, c, d])
+}
+
+extern "C" fn api_parse_ip(s: *const u8, len: usize) -> u32 {
+    api_str(s, len)
+        .and_then(ipv4::parse_addr)
+        .map(u32::from_be_bytes)
+        .unwrap_or(0)
+}
+
+extern "C" fn api_ip_to_str(ip: u32, buf: *mut u8, len: usize) -> i32 {
+    let [a, b, c, d] = ip.to_be_bytes();
+    let text = alloc::format!("{}.{}.{}.{}", a, b, c, d);
+    if buf.is_null() || text.len() > len {
+        return -1;
+    }
+    unsafe { core::ptr::copy_nonoverlapping(text.as_ptr(), buf, text.len()) };
+    text.len() as i32
+}
+
 /// Global kernel API instance
 pub static KERNEL_API: KernelApi = KernelApi {
     version: API_VERSION,
@@ -220,6 +503,29 @@ pub static KERNEL_API: KernelApi = KernelApi {
     net_close: api_net_close,
     net_listen: api_net_listen,
     net_accept: api_net_accept,
+    // Pipe API
+    read_line: api_read_line,
+    // Environment API
+    getenv: api_getenv,
+    setenv: api_setenv,
+    // Random numbers
+    rand: api_rand,
+    srand: api_srand,
+    // Time
+    ticks: api_ticks,
+    ticks_per_second: api_ticks_per_second,
+    uptime_ms: api_uptime_ms,
+    // Address helpers
+    htonl: api_htonl,
+    htons: api_htons,
+    make_ip: api_make_ip,
+    parse_ip: api_parse_ip,
+    ip_to_str: api_ip_to_str,
+    // Socket addresses
+    net_getpeername: api_net_getpeername,
+    net_getsockname: api_net_getsockname,
+    // Blocking connect
+    net_connect_wait: api_net_connect_wait,
 };
 
 /// Program entry point type
@@ -233,9 +539,8 @@ pub type ProgramEntry = extern "C" fn(api: &'static KernelApi, argv: *const *con
 ///
 /// This is what gets registered as the task entry point.
 /// It sets up the API pointer and argv, then calls the actual program.
-fn program_wrapper(entry: usize) {
+fn program_wrapper(entry: usize, argv: *const *const u8) {
     let entry_fn: ProgramEntry = unsafe { core::mem::transmute(entry) };
-    let argv = get_pending_argv();
     entry_fn(&KERNEL_API, argv);
 }
 
@@ -249,7 +554,7 @@ pub fn spawn_program(name: &'static str, extra_args: &[&str]) -> Result<TaskId,
     let program = executable::load(name)?;
 
     // Spawn the task
-    let task_id = spawn_program_task(name, &program)
+    let task_id = spawn_program_task(name)
         .ok_or(executable::ExecError::AllocationFailed)?;
 
     // Register program memory for cleanup
@@ -258,7 +563,7 @@ pub fn spawn_program(name: &'static str, extra_args: &[&str]) -> Result<TaskId,
     // Allocate and set up argv in the task's memory
     let argv = allocate_args_for_task(task_id, name, extra_args)
         .ok_or(executable::ExecError::AllocationFailed)?;
-    set_pending_argv(argv);
+    set_pending_start(task_id, program.entry, argv);
 
     Ok(task_id)
 }
@@ -272,7 +577,7 @@ pub fn spawn_program_dynamic(name: &str, extra_args: &[&str]) -> Result<TaskId,
     let program = executable::load(name)?;
 
     // Spawn the task with a generic static name
-    let task_id = spawn_program_task("program", &program)
+    let task_id = spawn_program_task("program")
         .ok_or(executable::ExecError::AllocationFailed)?;
 
     // Register program memory for cleanup
@@ -281,42 +586,55 @@ pub fn spawn_program_dynamic(name: &str, extra_args: &[&str]) -> Result<TaskId,
     // Allocate and set up argv in the task's memory
     let argv = allocate_args_for_task(task_id, name, extra_args)
         .ok_or(executable::ExecError::AllocationFailed)?;
-    set_pending_argv(argv);
+    set_pending_start(task_id, program.entry, argv);
 
     Ok(task_id)
 }
 
-/// Internal: spawn a task for a loaded program
-fn spawn_program_task(name: &'static str, program: &LoadedProgram) -> Option<TaskId> {
-    // We need to create a task that will call program_wrapper with the entry point
-    // The trick is we can't capture the entry point in a closure since spawn takes fn()
-
-    // Store the entry point in a static that the task can read
-    // This is safe because we're single-threaded cooperative
-    set_pending_entry(program.entry);
-
-    // Spawn using a static wrapper
-    scheduler::spawn(name, pending_program_entry)
+/// Spawn a pipeline: `writer`'s output becomes `reader`'s input
+///
+/// Each side is a program name and its extra arguments. Both tasks are
+/// spawned before either runs, so no output is lost. Returns the task IDs
+/// of (writer, reader).
+pub fn spawn_pipeline(
+    writer: (&str, &[&str]),
+    reader: (&str, &[&str]),
+) -> Result<(TaskId, TaskId), executable::ExecError> {
+    let writer_id = spawn_program_dynamic(writer.0, writer.1)?;
+    let reader_id = match spawn_program_dynamic(reader.0, reader.1) {
+        Ok(id) => id,
+        Err(e) => {
+            scheduler::kill(writer_id);
+            return Err(e);
+        }
+    };
+    pipe::create(writer_id, reader_id);
+    Ok((writer_id, reader_id))
 }
 
-// Pending entry point and argv storage
-static mut PENDING_ENTRY: usize = 0;
-static mut PENDING_ARGV: *const *const u8 = core::ptr::null();
-
-fn set_pending_entry(entry: usize) {
-    unsafe { PENDING_ENTRY = entry; }
+/// Internal: spawn a task for a loaded program
+///
+/// The task can't capture the entry point (spawn takes a plain fn()), so
+/// the caller records it with `set_pending_start` once argv is set up.
+fn spawn_program_task(name: &'static str) -> Option<TaskId> {
+    scheduler::spawn(name, pending_program_entry)
 }
 
-fn get_pending_entry() -> usize {
-    unsafe { PENDING_ENTRY }
-}
+/// Entry point and argv of spawned programs that haven't started yet
+///
+/// Keyed by task ID so several programs can be spawned before any of them
+/// runs (as a pipeline does). Pointers are stored as usize.
+static PENDING_STARTS: Spinlock<Vec<(TaskId, usize, usize)>> = Spinlock::new(Vec::new());
 
-fn set_pending_argv(argv: *const *const u8) {
-    unsafe { PENDING_ARGV = argv; }
+fn set_pending_start(task_id: TaskId, entry: usize, argv: *const *const u8) {
+    PENDING_STARTS.lock().push((task_id, entry, argv as usize));
 }
 
-fn get_pending_argv() -> *const *const u8 {
-    unsafe { PENDING_ARGV }
+fn take_pending_start(task_id: TaskId) -> Option<(usize, *const *const u8)> {
+    let mut pending = PENDING_STARTS.lock();
+    let idx = pending.iter().position(|&(id, _, _)| id == task_id)?;
+    let (_, entry, argv) = pending.swap_remove(idx);
+    Some((entry, argv as *const *const u8))
 }
 
 /// Allocate argv array and strings in the task's memory
@@ -364,8 +682,9 @@ fn allocate_args_for_task(
     Some(argv_base as *const *const u8)
 }
 
-/// Entry point for pending program (reads from PENDING_ENTRY)
+/// Entry point for a spawned program (looks up its PENDING_STARTS entry)
 fn pending_program_entry() {
-    let entry = get_pending_entry();
-    program_wrapper(entry);
+    if let Some((entry, argv)) = scheduler::current_task_id().and_then(take_pending_start) {
+        program_wrapper(entry, argv);
+    }
 }
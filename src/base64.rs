@@ -0,0 +1,112 @@
+//! Base64 encoding and decoding (RFC 4648, standard alphabet with padding)
+//!
+//! Used by the BASIC BASE64$/UNBASE64$ functions and the serial screenshot dump.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Standard base64 alphabet
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Length of the encoding of `len` bytes, including padding
+pub fn encoded_len(len: usize) -> usize {
+    len.div_ceil(3) * 4
+}
+
+/// Encode up to 3 bytes as one 4-character group, padded with '='
+pub fn encode_group(chunk: &[u8]) -> [u8; 4] {
+    let mut bytes = [0u8; 3];
+    let n = chunk.len().min(3);
+    bytes[..n].copy_from_slice(&chunk[..n]);
+    let v = ((bytes[0] as u32) << 16) | ((bytes[1] as u32) << 8) | bytes[2] as u32;
+
+    let mut out = [b'='; 4];
+    for (i, ch) in out.iter_mut().enumerate().take(n + 1) {
+        *ch = ALPHABET[((v >> (18 - 6 * i)) & 0x3F) as usize];
+    }
+    out
+}
+
+/// Encode bytes as base64
+pub fn encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(encoded_len(data.len()));
+    for chunk in data.chunks(3) {
+        for &ch in encode_group(chunk).iter() {
+            out.push(ch as char);
+        }
+    }
+    out
+}
+
+/// Value of a base64 character, or None if it isn't one
+fn decode_char(c: u8) -> Option<u32> {
+    match c {
+        b'A'..=b'Z' => Some((c - b'A') as u32),
+        b'a'..=b'z' => Some((c - b'a') as u32 + 26),
+        b'0'..=b'9' => Some((c - b'0') as u32 + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+/// Decode base64, ignoring whitespace (so wrapped lines decode)
+///
+/// Padding is optional, but nothing but whitespace and '=' may follow it.
+/// Returns None on any other character or a truncated final group.
+pub fn decode(s: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(s.len() / 4 * 3);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    let mut padding = false;
+
+    for &c in s.as_bytes() {
+        if c.is_ascii_whitespace() {
+            continue;
+        }
+        if c == b'=' {
+            padding = true;
+            continue;
+        }
+        if padding {
+            return None;
+        }
+        acc = (acc << 6) | decode_char(c)?;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+
+    // A lone trailing character carries only 6 bits: not a whole byte
+    if bits >= 6 {
+        return None;
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode(b""), "");
+        assert_eq!(encode(b"f"), "Zg==");
+        assert_eq!(encode(b"fo"), "Zm8=");
+        assert_eq!(encode(b"foo"), "Zm9v");
+        assert_eq!(encode(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("Zm9vYmFy").unwrap(), b"foobar");
+        assert_eq!(decode("Zg==").unwrap(), b"f");
+        assert_eq!(decode("Zm8").unwrap(), b"fo");
+        assert_eq!(decode(" Zm9v\r\nYmFy\n").unwrap(), b"foobar");
+        assert!(decode("Zm9v!").is_none());
+        assert!(decode("Zg==Zg==").is_none());
+        assert!(decode("Z").is_none());
+    }
+}
@@ -2,15 +2,54 @@
 //!
 //! Executes BASIC programs with step-by-step execution for cooperative scheduling.
 
-use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeMap, BTreeSet};
 use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
 use core::fmt;
 use super::value::Value;
-use super::parser::{Statement, Expr, BinaryOp, ForState, Parser};
+use super::parser::{Statement, Expr, BinaryOp, ForState, OpenTarget, Parser};
+use super::terminal::{self, LineStatus, SocketTerminal, Terminal};
 use crate::allocator;
 use crate::api;
+use crate::base64;
+use crate::net::checksum;
+use crate::rand::{self, Xorshift32};
+use crate::timer;
+use crate::vga;
+
+/// Default maximum length of a string value in bytes (see `set_max_string_len`).
+///
+/// Strings live on the shared kernel heap, so a runaway `A$ = A$ + X$` loop
+/// must fail with a BASIC error rather than exhausting memory for every task.
+pub const DEFAULT_MAX_STRING_LEN: usize = 64 * 1024;
+
+/// Longest array PUSH will grow (each element costs at least a `String`)
+pub const MAX_ARRAY_LEN: usize = 4096;
+
+/// ERR code for a file that doesn't exist (as in Microsoft BASIC)
+const ERR_FILE_NOT_FOUND: i64 = 53;
+
+/// ERR code for a statement abandoned by TIMEOUT ("Device timeout")
+const ERR_TIMEOUT: i64 = 24;
+
+/// Longest HTTPSEND/SERVEFILE may wait for a peer that isn't reading,
+/// when no TIMEOUT gives a shorter limit
+const HTTP_SEND_TIMEOUT_MS: u64 = 30_000;
+
+/// Longest SLEEPUS delay in microseconds; it busy-waits, so every other
+/// task is stalled for the duration.
+pub const MAX_SLEEPUS: i64 = 1_000_000;
+
+/// Longest KEEPALIVE idle time in seconds (the TCP_KEEPIDLE limit on Linux)
+pub const MAX_KEEPALIVE_IDLE: i64 = 32_767;
+
+/// Widest tab stop spacing TABWIDTH accepts
+pub const MAX_TAB_WIDTH: i64 = 80;
+
+/// Default number of statements a running program executes between yields
+pub const DEFAULT_YIELD_INTERVAL: u32 = 64;
 
 /// Execution status after running a statement
 #[derive(Clone, Debug, PartialEq)]
@@ -21,6 +60,8 @@ pub enum ExecutionStatus {
     Sleeping(u64),
     /// Program has ended normally
     Finished,
+    /// Program is paused (STOP) and can be resumed with CONT or STEP
+    Stopped,
     /// Waiting for input (interactive mode)
     WaitingForInput,
     /// Runtime error occurred
@@ -39,12 +80,78 @@ pub struct Interpreter {
     variables: BTreeMap<String, Value>,
     /// FOR loop stack
     for_stack: Vec<ForState>,
+    /// Line indices of the active WHILE loops
+    while_stack: Vec<usize>,
     /// GOSUB return stack
     return_stack: Vec<usize>,
     /// Current execution status
     status: ExecutionStatus,
+    /// Line the last runtime error happened on (None in immediate mode)
+    error_line: Option<u32>,
     /// Whether program is running
     running: bool,
+    /// Whether the program is paused mid-run (resumable at current_idx)
+    stopped: bool,
+    /// Line numbers that stop the program before they execute
+    breakpoints: BTreeSet<u32>,
+    /// Don't stop at a breakpoint on the next step (resuming from it)
+    skip_breakpoint: bool,
+    /// Open I/O channels (OPEN ch AS ...)
+    channels: RefCell<BTreeMap<i64, Channel>>,
+    /// The INPUT or INPUT# waiting for a line
+    input: Option<PendingInput>,
+    /// Statements to execute between cooperative yields
+    yield_interval: u32,
+    /// Statements executed since the last yield (or sleep)
+    steps_since_yield: u32,
+    /// Longest string a statement may build, in bytes
+    max_string_len: usize,
+    /// RND generator, per interpreter so sessions get independent streams
+    rnd: RefCell<Rnd>,
+    /// What ERR returns: the outcome of the last file function
+    err: Cell<i64>,
+    /// DEBUG ON: trace key values as statements run
+    debug: Cell<bool>,
+}
+
+/// State behind RND and RANDOMIZE
+struct Rnd {
+    rng: Xorshift32,
+    /// What RND(0) returns
+    last: Value,
+}
+
+impl Rnd {
+    fn new(seed: u32) -> Self {
+        Rnd {
+            rng: Xorshift32::new(seed),
+            last: Value::Integer(0),
+        }
+    }
+
+    /// Draw RND(n)
+    fn draw(&mut self, n: i64) -> Result<Value, String> {
+        let value = match n {
+            0 => return Ok(self.last.clone()),
+            1 => Value::Float(self.rng.next_u32() as f64 / 4_294_967_296.0),
+            n if n > 1 => {
+                // Scale rather than take a remainder, which would favour small results
+                let x = self.rng.next_u32() as u128;
+                Value::Integer(((x * n as u128) >> 32) as i64)
+            }
+            _ => return Err("RND argument must not be negative".into()),
+        };
+        self.last = value.clone();
+        Ok(value)
+    }
+}
+
+/// Where a PRINT#/INPUT# channel reads and writes
+enum Channel {
+    /// The terminal the interpreter is running on
+    Console,
+    /// A TCP socket
+    Socket(SocketTerminal),
 }
 
 impl Interpreter {
@@ -56,28 +163,45 @@ impl Interpreter {
             current_idx: None,
             variables: BTreeMap::new(),
             for_stack: Vec::new(),
+            while_stack: Vec::new(),
             return_stack: Vec::new(),
             status: ExecutionStatus::Ready,
+            error_line: None,
             running: false,
+            stopped: false,
+            breakpoints: BTreeSet::new(),
+            skip_breakpoint: false,
+            channels: RefCell::new(BTreeMap::new()),
+            input: None,
+            yield_interval: DEFAULT_YIELD_INTERVAL,
+            steps_since_yield: 0,
+            max_string_len: DEFAULT_MAX_STRING_LEN,
+            rnd: RefCell::new(Rnd::new(rand::clock_seed())),
+            err: Cell::new(0),
+            debug: Cell::new(false),
         }
     }
 
-    /// Load a program from source
+    /// Load a program from source, merging it into the current program
+    ///
+    /// Lines without a line number are ignored. Nothing is changed if any
+    /// line fails to parse.
     pub fn load_program(&mut self, source: &str) -> Result<usize, String> {
         let mut parser = Parser::new(source);
-        let mut loaded = 0usize;
+        let mut lines = Vec::new();
         loop {
             match parser.parse_line() {
                 Ok(Some((line_num, stmt))) => {
                     if let Some(num) = line_num {
-                        self.program.insert(num, stmt);
-                        loaded += 1;
+                        lines.push((num, stmt));
                     }
                 }
                 Ok(None) => break,
                 Err(e) => return Err(e.0),
             }
         }
+        let loaded = lines.len();
+        self.program.extend(lines);
         self.rebuild_line_order();
         Ok(loaded)
     }
@@ -100,8 +224,13 @@ impl Interpreter {
         self.line_order.clear();
         self.variables.clear();
         self.for_stack.clear();
+        self.while_stack.clear();
         self.current_idx = None;
         self.running = false;
+        self.stopped = false;
+        self.breakpoints.clear();
+        self.channels.get_mut().clear();
+        self.input = None;
     }
 
     fn rebuild_line_order(&mut self) {
@@ -110,6 +239,7 @@ impl Interpreter {
 
     /// Start program execution
     pub fn run(&mut self) {
+        self.error_line = None;
         if self.line_order.is_empty() {
             self.status = ExecutionStatus::Error("No program".into());
             return;
@@ -117,9 +247,130 @@ impl Interpreter {
         self.current_idx = Some(0);
         self.variables.clear();
         self.for_stack.clear();
+        self.while_stack.clear();
         self.return_stack.clear();
+        self.channels.get_mut().clear();
+        self.input = None;
+        self.running = true;
+        self.stopped = false;
+        self.skip_breakpoint = false;
+        self.err.set(0);
+        self.status = ExecutionStatus::Ready;
+    }
+
+    /// Resume a stopped program. Returns false if nothing is stopped.
+    pub fn cont(&mut self) -> bool {
+        if !self.stopped {
+            return false;
+        }
+        self.stopped = false;
         self.running = true;
+        self.skip_breakpoint = true;
         self.status = ExecutionStatus::Ready;
+        true
+    }
+
+    /// Set a breakpoint on a line
+    pub fn add_breakpoint(&mut self, line_num: u32) {
+        self.breakpoints.insert(line_num);
+    }
+
+    /// Remove a breakpoint. Returns false if none was set on that line.
+    pub fn remove_breakpoint(&mut self, line_num: u32) -> bool {
+        self.breakpoints.remove(&line_num)
+    }
+
+    /// Statements a running program executes between yields
+    pub fn yield_interval(&self) -> u32 {
+        self.yield_interval
+    }
+
+    /// Set how many statements run between yields (at least 1)
+    pub fn set_yield_interval(&mut self, interval: u32) {
+        self.yield_interval = interval.max(1);
+    }
+
+    /// Longest string a statement may build, in bytes
+    pub fn max_string_len(&self) -> usize {
+        self.max_string_len
+    }
+
+    /// Set the longest string a statement may build (at least 1 byte)
+    pub fn set_max_string_len(&mut self, len: usize) {
+        self.max_string_len = len.max(1);
+    }
+
+    /// Count a statement that returned Ready; true when the run loop should
+    /// yield to other tasks
+    ///
+    /// A program that never SLEEPs still gives up the CPU every
+    /// `yield_interval` statements, so heavy computation can't freeze the
+    /// network task or other sessions.
+    pub fn yield_due(&mut self) -> bool {
+        self.steps_since_yield += 1;
+        if self.steps_since_yield >= self.yield_interval {
+            self.steps_since_yield = 0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Active breakpoints in line order
+    pub fn breakpoints(&self) -> impl Iterator<Item = &u32> {
+        self.breakpoints.iter()
+    }
+
+    /// Execute exactly one statement of a stopped program, leaving it stopped.
+    ///
+    /// If no program is stopped, the program is started from the top.
+    pub fn single_step(&mut self, out: &mut dyn Terminal) -> ExecutionStatus {
+        if !self.cont() {
+            self.run();
+            if !self.running {
+                return self.status.clone();
+            }
+        }
+        self.skip_breakpoint = true;
+        let status = self.step(out);
+        if self.running {
+            self.running = false;
+            self.stopped = true;
+        }
+        status
+    }
+
+    /// Check if program is stopped (paused mid-run)
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Line number of the next statement to execute, if running or stopped
+    pub fn current_line(&self) -> Option<u32> {
+        if !self.running && !self.stopped {
+            return None;
+        }
+        self.current_idx.and_then(|i| self.line_order.get(i).copied())
+    }
+
+    /// Format a single program line as LIST would show it
+    pub fn format_line(&self, line_num: u32) -> Option<String> {
+        self.program
+            .get(&line_num)
+            .map(|stmt| alloc::format!("{} {}", line_num, format_statement(stmt)))
+    }
+
+    /// The whole program as source text, one line per statement as LIST
+    /// shows it (without the stop marker)
+    pub fn source(&self) -> String {
+        let mut source = String::new();
+        for &line_num in &self.line_order {
+            if let Some(line) = self.format_line(line_num) {
+                source.push_str(&line);
+                source.push('\n');
+            }
+        }
+        source
     }
 
     /// Check if program is currently running
@@ -137,8 +388,16 @@ impl Interpreter {
         &self.status
     }
 
+    /// Line the current `ExecutionStatus::Error` was raised on
+    pub fn error_line(&self) -> Option<u32> {
+        match self.status {
+            ExecutionStatus::Error(_) => self.error_line,
+            _ => None,
+        }
+    }
+
     /// Execute one statement (for cooperative scheduling)
-    pub fn step(&mut self, out: &mut dyn fmt::Write) -> ExecutionStatus {
+    pub fn step(&mut self, out: &mut dyn Terminal) -> ExecutionStatus {
         if !self.running {
             return ExecutionStatus::Finished;
         }
@@ -158,6 +417,15 @@ impl Interpreter {
         }
 
         let line_num = self.line_order[idx];
+
+        // Stop before executing a breakpoint line (unless resuming from it)
+        if !core::mem::take(&mut self.skip_breakpoint) && self.breakpoints.contains(&line_num) {
+            self.running = false;
+            self.stopped = true;
+            self.status = ExecutionStatus::Stopped;
+            return self.status.clone();
+        }
+
         let stmt = match self.program.get(&line_num) {
             Some(s) => s,
             None => {
@@ -168,15 +436,26 @@ impl Interpreter {
         };
 
         // Execute the statement (split borrow: stmt from program, mutable state separate)
+        let env = Env {
+            line: line_num,
+            idx,
+            line_order: &self.line_order,
+            channels: &self.channels,
+            cursor: out.cursor(),
+            rnd: &self.rnd,
+            err: &self.err,
+            debug: &self.debug,
+            deadline: u64::MAX,
+            max_string_len: self.max_string_len,
+        };
         match execute_statement(
             out,
+            &env,
             &mut self.variables,
             &mut self.for_stack,
+            &mut self.while_stack,
             &mut self.return_stack,
-            &self.line_order,
             stmt,
-            line_num,
-            idx,
         ) {
             Ok(action) => {
                 match action {
@@ -196,6 +475,7 @@ impl Interpreter {
                             self.status = ExecutionStatus::Ready;
                         } else {
                             self.running = false;
+                            self.error_line = Some(line_num);
                             self.status =
                                 ExecutionStatus::Error(alloc::format!("Line {} not found", target));
                         }
@@ -204,18 +484,51 @@ impl Interpreter {
                         self.current_idx = Some(new_idx);
                         self.status = ExecutionStatus::Ready;
                     }
+                    NextAction::SkipWhile => match self.find_wend(idx) {
+                        Some(wend_idx) => {
+                            self.current_idx = Some(wend_idx + 1);
+                            if wend_idx + 1 >= self.line_order.len() {
+                                self.running = false;
+                                self.status = ExecutionStatus::Finished;
+                            } else {
+                                self.status = ExecutionStatus::Ready;
+                            }
+                        }
+                        None => {
+                            self.running = false;
+                            self.error_line = Some(line_num);
+                            self.status = ExecutionStatus::Error("WHILE without WEND".into());
+                        }
+                    },
+                    NextAction::AwaitInput { var, channel } => {
+                        self.current_idx = Some(idx + 1);
+                        self.input = Some(PendingInput { var, channel, line: line_num });
+                        self.status = ExecutionStatus::WaitingForInput;
+                    }
                     NextAction::Sleep(ms) => {
                         self.current_idx = Some(idx + 1);
+                        self.steps_since_yield = 0;
                         self.status = ExecutionStatus::Sleeping(ms);
                     }
                     NextAction::End => {
                         self.running = false;
                         self.status = ExecutionStatus::Finished;
                     }
+                    NextAction::Stop => {
+                        self.current_idx = Some(idx + 1);
+                        self.running = false;
+                        if idx + 1 >= self.line_order.len() {
+                            self.status = ExecutionStatus::Finished;
+                        } else {
+                            self.stopped = true;
+                            self.status = ExecutionStatus::Stopped;
+                        }
+                    }
                 }
             }
             Err(e) => {
                 self.running = false;
+                self.error_line = Some(line_num);
                 self.status = ExecutionStatus::Error(e);
             }
         }
@@ -223,101 +536,559 @@ impl Interpreter {
         self.status.clone()
     }
 
+    /// Store a line read for the waiting INPUT or INPUT# and let the
+    /// program continue
+    ///
+    /// A `$` variable gets the line as is. A numeric one gets it parsed the
+    /// way VAL does for INPUT, while INPUT# stops the program with an error
+    /// unless the line is a whole number. Returns false if no input is
+    /// waiting or the line was rejected.
+    pub fn provide_input(&mut self, line: &str) -> bool {
+        let Some(pending) = self.input.take() else {
+            return false;
+        };
+        let value = if pending.var.ends_with('$') {
+            Value::String(String::from(line))
+        } else if pending.channel.is_none() {
+            parse_val(line)
+        } else {
+            match line.trim().parse::<i64>() {
+                Ok(n) => Value::Integer(n),
+                Err(_) => {
+                    let e = alloc::format!("INPUT# expected a number, got \"{}\"", line);
+                    self.fail_input(pending.line, e);
+                    return false;
+                }
+            }
+        };
+        self.variables.insert(pending.var, value);
+        self.status = ExecutionStatus::Ready;
+        true
+    }
+
+    /// Whether the program waits for INPUT# rather than console INPUT
+    pub fn waiting_on_channel(&self) -> bool {
+        matches!(&self.input, Some(PendingInput { channel: Some(_), .. }))
+    }
+
+    /// Read the line the waiting INPUT or INPUT# needs and let the program
+    /// continue
+    ///
+    /// Console INPUT and console channels read from `term` with echo; a
+    /// socket channel reads from its socket. The read yields to other tasks
+    /// while no data is available. Returns false if the input ended first:
+    /// on the console the program stays waiting, while a socket channel
+    /// stops it with an error.
+    pub fn read_input(&mut self, term: &mut dyn Terminal) -> bool {
+        let Some(pending) = &self.input else {
+            return false;
+        };
+        let (channel, line_num) = (pending.channel, pending.line);
+        let Some(ch) = channel else {
+            return match terminal::read_line(term, true) {
+                Some(line) => self.provide_input(&line),
+                None => false,
+            };
+        };
+        let mut channels = self.channels.borrow_mut();
+        let echo = matches!(channels.get(&ch), Some(Channel::Console));
+        let status = channel_terminal(term, &mut channels, ch)
+            .map(|target| terminal::read_line_until(target, echo, u64::MAX));
+        drop(channels);
+        match status {
+            Ok(LineStatus::Line(line)) => self.provide_input(&line),
+            Ok(_) if echo => false,
+            Ok(_) => {
+                self.fail_input(line_num, alloc::format!("End of input on channel {}", ch));
+                false
+            }
+            Err(e) => {
+                self.fail_input(line_num, e);
+                false
+            }
+        }
+    }
+
+    /// Stop the program with an input error reported at `line_num`
+    fn fail_input(&mut self, line_num: u32, e: String) {
+        self.input = None;
+        self.running = false;
+        self.error_line = Some(line_num);
+        self.status = ExecutionStatus::Error(e);
+    }
+
+    /// Index of the WEND matching the WHILE at `while_idx`, skipping nested loops
+    fn find_wend(&self, while_idx: usize) -> Option<usize> {
+        let mut depth = 0;
+        for (i, line_num) in self.line_order.iter().enumerate().skip(while_idx + 1) {
+            match self.program.get(line_num) {
+                Some(Statement::While { .. }) => depth += 1,
+                Some(Statement::Wend) if depth == 0 => return Some(i),
+                Some(Statement::Wend) => depth -= 1,
+                _ => {}
+            }
+        }
+        None
+    }
+
     /// List the program
+    ///
+    /// When the program is stopped, the line that will execute next is
+    /// marked with '>'.
     pub fn list(&self, out: &mut dyn fmt::Write) {
+        let marked = if self.stopped { self.current_line() } else { None };
         for &line_num in &self.line_order {
             if let Some(stmt) = self.program.get(&line_num) {
-                let _ = writeln!(out, "{} {}", line_num, format_statement(stmt));
+                let prefix = match marked {
+                    Some(n) if n == line_num => "> ",
+                    Some(_) => "  ",
+                    None => "",
+                };
+                let _ = writeln!(out, "{}{} {}", prefix, line_num, format_statement(stmt));
             }
         }
     }
 
     /// Execute an immediate command (for REPL)
-    pub fn execute_immediate(&mut self, out: &mut dyn fmt::Write, stmt: &Statement) -> ExecutionStatus {
+    pub fn execute_immediate(&mut self, out: &mut dyn Terminal, stmt: &Statement) -> ExecutionStatus {
+        let env = Env {
+            line: 0,
+            idx: 0,
+            line_order: &self.line_order,
+            channels: &self.channels,
+            cursor: out.cursor(),
+            rnd: &self.rnd,
+            err: &self.err,
+            debug: &self.debug,
+            deadline: u64::MAX,
+            max_string_len: self.max_string_len,
+        };
         match execute_statement(
             out,
+            &env,
             &mut self.variables,
             &mut self.for_stack,
+            &mut self.while_stack,
             &mut self.return_stack,
-            &self.line_order,
             stmt,
-            0,
-            0,
         ) {
-            Ok(NextAction::Continue) | Ok(NextAction::End) => ExecutionStatus::Ready,
+            Ok(NextAction::Continue) | Ok(NextAction::End) | Ok(NextAction::Stop) => {
+                ExecutionStatus::Ready
+            }
             Ok(NextAction::Jump(_)) | Ok(NextAction::JumpToIndex(_)) => {
                 ExecutionStatus::Error("Cannot GOTO/GOSUB in immediate mode".into())
             }
+            Ok(NextAction::SkipWhile) => {
+                ExecutionStatus::Error("Cannot use WHILE in immediate mode".into())
+            }
+            Ok(NextAction::AwaitInput { .. }) => {
+                ExecutionStatus::Error("Cannot use INPUT in immediate mode".into())
+            }
             Ok(NextAction::Sleep(ms)) => ExecutionStatus::Sleeping(ms),
             Err(e) => ExecutionStatus::Error(e),
         }
     }
 }
 
+/// Interpreter state a statement runs against, besides the variables and
+/// the FOR/WHILE/GOSUB stacks
+///
+/// Everything here is shared or `Copy`, so IF and TIMEOUT can hand a
+/// (modified) copy to the statement they run.
+struct Env<'a> {
+    /// Line number of the statement (0 in immediate mode)
+    line: u32,
+    /// Index of the statement's line in `line_order`
+    idx: usize,
+    /// Program line numbers in execution order
+    line_order: &'a [u32],
+    /// Open I/O channels; borrow only for the duration of one channel access
+    channels: &'a RefCell<BTreeMap<i64, Channel>>,
+    /// Terminal cursor (column, row) when the statement started
+    cursor: (usize, usize),
+    /// RND generator
+    rnd: &'a RefCell<Rnd>,
+    /// ERR code, set by the file functions and TIMEOUT
+    err: &'a Cell<i64>,
+    /// DEBUG tracing on?
+    debug: &'a Cell<bool>,
+    /// `timer::ticks()` at which waiting statements give up (set by TIMEOUT)
+    deadline: u64,
+    /// Longest string a statement may build, in bytes
+    max_string_len: usize,
+}
+
+/// Print a DEBUG trace line for `line` (0 in immediate mode)
+fn trace(out: &mut dyn Terminal, line: u32, what: fmt::Arguments) {
+    let _ = writeln!(out, "[{}] {}", line, what);
+}
+
+/// A value as DEBUG shows it: strings quoted, arrays by length
+struct DebugValue<'a>(&'a Value);
+
+impl fmt::Display for DebugValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Value::String(s) => write!(f, "\"{}\"", s),
+            Value::Bytes(b) => write!(f, "<{} bytes>", b.len()),
+            Value::IntArray(a) => write!(f, "<{} integers>", a.len()),
+            Value::StringArray(a) => write!(f, "<{} strings>", a.len()),
+            v => write!(f, "{}", v),
+        }
+    }
+}
+
+/// Evaluate PRINT items separated by spaces and end the line
+///
+/// The outer Result is an evaluation error; the inner one is the write
+/// result, which callers may choose to ignore.
+fn print_items(
+    out: &mut dyn Terminal,
+    variables: &BTreeMap<String, Value>,
+    env: &Env,
+    exprs: &[Expr],
+) -> Result<fmt::Result, String> {
+    let mut result = Ok(());
+    for (i, expr) in exprs.iter().enumerate() {
+        let value = eval_expr(variables, env, expr)?;
+        if i > 0 {
+            result = result.and_then(|_| write!(out, " "));
+        }
+        result = result.and_then(|_| write!(out, "{}", value));
+    }
+    Ok(result.and_then(|_| writeln!(out)))
+}
+
+/// Evaluate the two string arguments of a string predicate
+fn eval_string_pair(
+    variables: &BTreeMap<String, Value>,
+    env: &Env,
+    a: &Expr,
+    b: &Expr,
+    name: &str,
+) -> Result<(String, String), String> {
+    let err = || alloc::format!("{} requires string arguments", name);
+    let a = eval_expr(variables, env, a)?.as_string().ok_or_else(err)?;
+    let b = eval_expr(variables, env, b)?.as_string().ok_or_else(err)?;
+    Ok((a, b))
+}
+
+/// Run `f` on the array an expression names, without copying a named one
+fn with_array<R>(
+    variables: &BTreeMap<String, Value>,
+    env: &Env,
+    expr: &Expr,
+    f: impl FnOnce(&Value) -> Result<R, String>,
+) -> Result<R, String> {
+    match expr {
+        Expr::Variable(name) => f(variables.get(name).ok_or_else(|| alloc::format!("Array {} not found", name))?),
+        other => f(&eval_expr(variables, env, other)?),
+    }
+}
+
+/// Evaluate a channel number
+fn eval_channel(variables: &BTreeMap<String, Value>, env: &Env, expr: &Expr) -> Result<i64, String> {
+    eval_expr(variables, env, expr)?
+        .as_integer()
+        .ok_or_else(|| "Channel must be numeric".into())
+}
+
+/// Evaluate the port of an FWALLOW / FWDENY rule
+fn firewall_port(
+    variables: &BTreeMap<String, Value>,
+    env: &Env,
+    expr: &Expr,
+    keyword: &str,
+) -> Result<u16, String> {
+    let port = eval_expr(variables, env, expr)?
+        .as_integer()
+        .ok_or_else(|| alloc::format!("{} port must be numeric", keyword))?;
+    u16::try_from(port).map_err(|_| alloc::format!("{} port out of range", keyword))
+}
+
+/// Resolve an open channel to the terminal it reads from and writes to
+fn channel_terminal<'a>(
+    out: &'a mut dyn Terminal,
+    channels: &'a mut BTreeMap<i64, Channel>,
+    ch: i64,
+) -> Result<&'a mut dyn Terminal, String> {
+    match channels.get_mut(&ch) {
+        Some(Channel::Console) => Ok(out),
+        Some(Channel::Socket(sock)) => Ok(sock),
+        None => Err(alloc::format!("Channel {} not open", ch)),
+    }
+}
+
+/// Parse a number the way VAL does
+///
+/// Leading whitespace is skipped and parsing stops at the first character
+/// that can't continue the number, so "  42abc" is 42; a string that
+/// doesn't start with a number is 0. A decimal point makes it a float.
+fn parse_val(s: &str) -> Value {
+    let s = s.trim_start();
+    let bytes = s.as_bytes();
+    let digits_from = |start: usize| {
+        start + bytes[start..].iter().take_while(|b| b.is_ascii_digit()).count()
+    };
+
+    let sign_len = usize::from(matches!(bytes.first(), Some(b'+' | b'-')));
+    let int_end = digits_from(sign_len);
+    let mut end = int_end;
+    if bytes.get(end) == Some(&b'.') {
+        end = digits_from(end + 1);
+    }
+    let has_digits = int_end > sign_len || end > int_end + 1;
+    if !has_digits {
+        return Value::Integer(0);
+    }
+
+    let number = &s[..end];
+    if end == int_end {
+        if let Ok(n) = number.parse::<i64>() {
+            return Value::Integer(n);
+        }
+    }
+    number.parse::<f64>().map(Value::Float).unwrap_or(Value::Integer(0))
+}
+
+/// An INPUT or INPUT# statement waiting for its line
+struct PendingInput {
+    /// Variable to fill
+    var: String,
+    /// Channel for INPUT#, None for console INPUT
+    channel: Option<i64>,
+    /// Line number of the statement, for errors
+    line: u32,
+}
+
 /// What to do after executing a statement
 enum NextAction {
     Continue,
     Jump(u32),
     JumpToIndex(usize),  // For RETURN - jump to specific index
+    SkipWhile,           // WHILE condition false - continue after matching WEND
+    AwaitInput { var: String, channel: Option<i64> },  // INPUT/INPUT# - wait for a line
     Sleep(u64),
     End,
+    Stop,
+}
+
+/// Pick the ON GOTO/GOSUB target for the (1-based) selector value
+///
+/// Returns None when the selector is out of range, so execution falls
+/// through to the next line.
+fn select_target(
+    variables: &BTreeMap<String, Value>,
+    env: &Env,
+    selector: &Expr,
+    targets: &[u32],
+) -> Result<Option<u32>, String> {
+    let n = eval_expr(variables, env, selector)?
+        .as_integer()
+        .ok_or("ON requires numeric value")?;
+    if n < 1 {
+        return Ok(None);
+    }
+    Ok(targets.get(n as usize - 1).copied())
 }
 
 /// Execute a BASIC statement
 ///
 /// Takes split borrows to avoid cloning the statement:
-/// - variables, for_stack, return_stack are mutable state
-/// - line_order is needed for FOR loop body lookup
+/// - env is the shared interpreter state, including where the statement
+///   is in the program (needed for FOR loop body lookup)
+/// - variables, for_stack, while_stack, return_stack are mutable state
 /// - stmt is borrowed from the program BTreeMap
 fn execute_statement(
-    out: &mut dyn fmt::Write,
+    out: &mut dyn Terminal,
+    env: &Env,
     variables: &mut BTreeMap<String, Value>,
     for_stack: &mut Vec<ForState>,
+    while_stack: &mut Vec<usize>,
     return_stack: &mut Vec<usize>,
-    line_order: &[u32],
     stmt: &Statement,
-    current_line: u32,
-    current_idx: usize,
 ) -> Result<NextAction, String> {
+    let (current_line, current_idx, line_order) = (env.line, env.idx, env.line_order);
     match stmt {
         Statement::Print(exprs) => {
-            for (i, expr) in exprs.iter().enumerate() {
-                let value = eval_expr(variables, expr)?;
-                if i > 0 {
-                    let _ = write!(out, " ");
+            // Console output errors are ignored, as they always have been
+            let _ = print_items(out, variables, env, exprs)?;
+            Ok(NextAction::Continue)
+        }
+
+        Statement::PrintTo { channel, exprs } => {
+            let ch = eval_channel(variables, env, channel)?;
+            let mut channels = env.channels.borrow_mut();
+            let target = channel_terminal(out, &mut channels, ch)?;
+            print_items(target, variables, env, exprs)?
+                .map_err(|_| alloc::format!("Write to channel {} failed", ch))?;
+            Ok(NextAction::Continue)
+        }
+
+        Statement::InputFrom { channel, var } => {
+            let ch = eval_channel(variables, env, channel)?;
+            let mut channels = env.channels.borrow_mut();
+            let echo = matches!(channels.get(&ch), Some(Channel::Console));
+            let target = channel_terminal(out, &mut channels, ch)?;
+            if env.deadline == u64::MAX {
+                return Ok(NextAction::AwaitInput { var: var.clone(), channel: Some(ch) });
+            }
+            // Under TIMEOUT the line is read here, so the wait can be cut short
+            let line = match terminal::read_line_until(target, echo, env.deadline) {
+                LineStatus::Line(line) => line,
+                LineStatus::Eof => return Err(alloc::format!("End of input on channel {}", ch)),
+                LineStatus::TimedOut => {
+                    env.err.set(ERR_TIMEOUT);
+                    return Ok(NextAction::Continue);
+                }
+            };
+            let value = if var.ends_with('$') {
+                Value::String(line)
+            } else {
+                let n = line
+                    .trim()
+                    .parse::<i64>()
+                    .map_err(|_| alloc::format!("INPUT# expected a number, got \"{}\"", line))?;
+                Value::Integer(n)
+            };
+            variables.insert(var.clone(), value);
+            Ok(NextAction::Continue)
+        }
+
+        Statement::Input { prompt, var } => {
+            let _ = write!(out, "{}? ", prompt.as_deref().unwrap_or(""));
+            if env.deadline == u64::MAX {
+                return Ok(NextAction::AwaitInput { var: var.clone(), channel: None });
+            }
+            // Under TIMEOUT the line is read here, so the wait can be cut short
+            match terminal::read_line_until(out, true, env.deadline) {
+                LineStatus::Line(line) => {
+                    let value = if var.ends_with('$') { Value::String(line) } else { parse_val(&line) };
+                    variables.insert(var.clone(), value);
                 }
-                let _ = write!(out, "{}", value);
+                LineStatus::Eof => return Err("End of input".into()),
+                LineStatus::TimedOut => {
+                    let _ = writeln!(out);
+                    env.err.set(ERR_TIMEOUT);
+                }
+            }
+            Ok(NextAction::Continue)
+        }
+
+        Statement::Timeout { ms, stmt } => {
+            let ms = eval_expr(variables, env, ms)?
+                .as_integer()
+                .ok_or("TIMEOUT time must be numeric")?;
+            if ms < 0 {
+                return Err("TIMEOUT time must not be negative".into());
+            }
+            let deadline = crate::timer::ticks().saturating_add(crate::timer::ms_to_ticks(ms as u64));
+            let inner = Env { deadline: env.deadline.min(deadline), ..*env };
+            env.err.set(0);
+            execute_statement(out, &inner, variables, for_stack, while_stack, return_stack, stmt)
+        }
+
+        Statement::Open { channel, target } => {
+            let ch = eval_channel(variables, env, channel)?;
+            let binding = match target {
+                OpenTarget::Console => Channel::Console,
+                OpenTarget::Socket(sock) => {
+                    let sock = eval_expr(variables, env, sock)?
+                        .as_integer()
+                        .ok_or("OPEN socket must be numeric")?;
+                    if sock < 0 {
+                        return Err("OPEN socket must not be negative".into());
+                    }
+                    Channel::Socket(SocketTerminal::new(sock as usize))
+                }
+            };
+            env.channels.borrow_mut().insert(ch, binding);
+            Ok(NextAction::Continue)
+        }
+
+        Statement::CloseChannel(channel) => {
+            // Only unbinds the channel; a socket stays open until CLOSE sock
+            let ch = eval_channel(variables, env, channel)?;
+            if env.channels.borrow_mut().remove(&ch).is_none() {
+                return Err(alloc::format!("Channel {} not open", ch));
             }
-            let _ = writeln!(out);
             Ok(NextAction::Continue)
         }
 
         Statement::Let { var, value } => {
-            let val = eval_expr(variables, value)?;
+            let val = eval_expr(variables, env, value)?;
+            if env.debug.get() {
+                trace(out, current_line, format_args!("{} = {}", var, DebugValue(&val)));
+            }
             variables.insert(var.clone(), val);
             Ok(NextAction::Continue)
         }
 
         Statement::If {
             condition,
-            then_line,
+            then_stmt,
+            else_stmt,
         } => {
-            let cond_val = eval_expr(variables, condition)?;
-            if cond_val.is_truthy() {
-                Ok(NextAction::Jump(*then_line))
+            let cond_val = eval_expr(variables, env, condition)?;
+            if env.debug.get() {
+                let taken = if cond_val.is_truthy() { "THEN" } else if else_stmt.is_some() { "ELSE" } else { "skip" };
+                trace(out, current_line, format_args!(
+                    "IF {} is {} -> {}", format_expr(condition), DebugValue(&cond_val), taken));
+            }
+            let branch = if cond_val.is_truthy() {
+                Some(then_stmt)
             } else {
-                Ok(NextAction::Continue)
+                else_stmt.as_ref()
+            };
+            match branch {
+                Some(branch) => {
+                    execute_statement(out, env, variables, for_stack, while_stack, return_stack, branch)
+                }
+                None => Ok(NextAction::Continue),
             }
         }
 
-        Statement::Goto(target) => Ok(NextAction::Jump(*target)),
+        Statement::Goto(target) => {
+            if env.debug.get() {
+                trace(out, current_line, format_args!("GOTO {}", target));
+            }
+            Ok(NextAction::Jump(*target))
+        }
 
         Statement::Gosub(target) => {
+            if env.debug.get() {
+                trace(out, current_line, format_args!("GOSUB {}", target));
+            }
             // Push return address (next line index) onto stack
             return_stack.push(current_idx + 1);
             Ok(NextAction::Jump(*target))
         }
 
+        Statement::OnGoto { selector, targets } => {
+            match select_target(variables, env, selector, targets)? {
+                Some(target) => {
+                    if env.debug.get() {
+                        trace(out, current_line, format_args!("ON GOTO {}", target));
+                    }
+                    Ok(NextAction::Jump(target))
+                }
+                None => Ok(NextAction::Continue),
+            }
+        }
+
+        Statement::OnGosub { selector, targets } => {
+            match select_target(variables, env, selector, targets)? {
+                Some(target) => {
+                    if env.debug.get() {
+                        trace(out, current_line, format_args!("ON GOSUB {}", target));
+                    }
+                    return_stack.push(current_idx + 1);
+                    Ok(NextAction::Jump(target))
+                }
+                None => Ok(NextAction::Continue),
+            }
+        }
+
         Statement::Return => {
             match return_stack.pop() {
                 Some(idx) => Ok(NextAction::JumpToIndex(idx)),
@@ -331,13 +1102,13 @@ fn execute_statement(
             end,
             step,
         } => {
-            let start_val = eval_expr(variables, start)?
+            let start_val = eval_expr(variables, env, start)?
                 .as_integer()
                 .ok_or("FOR start must be numeric")?;
-            let end_val = eval_expr(variables, end)?
+            let end_val = eval_expr(variables, env, end)?
                 .as_integer()
                 .ok_or("FOR end must be numeric")?;
-            let step_val = eval_expr(variables, step)?
+            let step_val = eval_expr(variables, env, step)?
                 .as_integer()
                 .ok_or("FOR step must be numeric")?;
 
@@ -395,22 +1166,115 @@ fn execute_statement(
             }
         }
 
+        Statement::While { condition } => {
+            if current_line == 0 {
+                return Err("Cannot use WHILE in immediate mode".into());
+            }
+            if eval_expr(variables, env, condition)?.is_truthy() {
+                while_stack.push(current_idx);
+                Ok(NextAction::Continue)
+            } else {
+                Ok(NextAction::SkipWhile)
+            }
+        }
+
+        Statement::Wend => match while_stack.pop() {
+            // Jump back to re-evaluate the condition
+            Some(idx) => Ok(NextAction::JumpToIndex(idx)),
+            None => Err("WEND without WHILE".into()),
+        },
+
         Statement::Sleep(expr) => {
-            let val = eval_expr(variables, expr)?;
-            let ms = val.as_integer().ok_or("SLEEP requires numeric value")? as u64;
-            Ok(NextAction::Sleep(ms))
+            let ms = match eval_expr(variables, env, expr)? {
+                // A float is in seconds: SLEEP 0.5 waits 500 ms
+                Value::Float(secs) if secs.is_nan() => return Err("SLEEP requires numeric value".into()),
+                Value::Float(secs) => floor(secs * 1000.0 + 0.5),
+                val => val.as_integer().ok_or("SLEEP requires numeric value")?,
+            };
+            if ms < 0 {
+                return Err("SLEEP time must not be negative".into());
+            }
+            Ok(NextAction::Sleep(ms as u64))
+        }
+
+        Statement::Randomize(seed) => {
+            let seed = match seed {
+                Some(expr) => eval_expr(variables, env, expr)?
+                    .as_integer()
+                    .ok_or("RANDOMIZE requires numeric value")? as u32,
+                None => rand::clock_seed(),
+            };
+            *env.rnd.borrow_mut() = Rnd::new(seed);
+            Ok(NextAction::Continue)
+        }
+
+        Statement::SleepUs(expr) => {
+            let us = eval_expr(variables, env, expr)?
+                .as_integer()
+                .ok_or("SLEEPUS requires numeric value")?;
+            if !(0..=MAX_SLEEPUS).contains(&us) {
+                return Err(alloc::format!("SLEEPUS must be 0-{} (use SLEEP for longer delays)", MAX_SLEEPUS));
+            }
+            timer::delay_us(us as u64);
+            Ok(NextAction::Continue)
         }
 
         Statement::Rem => Ok(NextAction::Continue),
 
         Statement::End => Ok(NextAction::End),
 
-        Statement::Spawn(name, args) => {
+        Statement::Stop => Ok(NextAction::Stop),
+
+        Statement::Assert { condition, message } => {
+            if eval_expr(variables, env, condition)?.is_truthy() {
+                return Ok(NextAction::Continue);
+            }
+            let mut err = String::from("Assertion failed");
+            if let Some(message) = message {
+                let text = eval_expr(variables, env, message)?;
+                err.push_str(&alloc::format!(": {}", text));
+            }
+            Err(err)
+        }
+
+        Statement::Abort(message) => {
+            let text = eval_expr(variables, env, message)?;
+            Err(alloc::format!("Aborted: {}", text))
+        }
+
+        Statement::Spawn { name, args, restricted } => {
             // Convert Vec<String> to Vec<&str> for the API
             let arg_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
             match api::spawn_program_dynamic(name, &arg_refs) {
                 Ok(task_id) => {
-                    let _ = writeln!(out, "Spawned '{}' as task {}", name, task_id);
+                    // The task hasn't run yet, so this takes effect from its first call
+                    if *restricted {
+                        crate::caps::drop_caps(task_id, crate::caps::CAP_NET);
+                    }
+                    let job = super::jobs::register(name, task_id);
+                    let _ = writeln!(out, "Spawned '{}' as task {} (job {})", name, task_id, job);
+                    Ok(NextAction::Continue)
+                }
+                Err(e) => Err(alloc::format!("SPAWN failed: {:?}", e)),
+            }
+        }
+
+        Statement::SpawnPipe { writer, reader, restricted } => {
+            let writer_args: Vec<&str> = writer.1.iter().map(|s| s.as_str()).collect();
+            let reader_args: Vec<&str> = reader.1.iter().map(|s| s.as_str()).collect();
+            match api::spawn_pipeline((&writer.0, &writer_args), (&reader.0, &reader_args)) {
+                Ok((writer_id, reader_id)) => {
+                    if *restricted {
+                        crate::caps::drop_caps(writer_id, crate::caps::CAP_NET);
+                        crate::caps::drop_caps(reader_id, crate::caps::CAP_NET);
+                    }
+                    let name = alloc::format!("{} | {}", writer.0, reader.0);
+                    let writer_job = super::jobs::register(&name, writer_id);
+                    let reader_job = super::jobs::register(&name, reader_id);
+                    let _ = writeln!(
+                        out,
+                        "Spawned '{}' as tasks {} and {} (jobs {} and {})",
+                        name, writer_id, reader_id, writer_job, reader_job);
                     Ok(NextAction::Continue)
                 }
                 Err(e) => Err(alloc::format!("SPAWN failed: {:?}", e)),
@@ -418,7 +1282,7 @@ fn execute_statement(
         }
 
         Statement::Dim { name, size } => {
-            let size = eval_expr(variables, size)?
+            let size = eval_expr(variables, env, size)?
                 .as_integer()
                 .ok_or("DIM size must be numeric")? as usize;
             // Create array based on name suffix ($ = string, otherwise integer)
@@ -430,22 +1294,74 @@ fn execute_statement(
             Ok(NextAction::Continue)
         }
 
-        Statement::ArrayAssign { name, index, value } => {
-            let idx = eval_expr(variables, index)?
-                .as_integer()
-                .ok_or("Array index must be numeric")? as usize;
-            let val = eval_expr(variables, value)?;
-
-            match variables.get_mut(name) {
-                Some(Value::StringArray(arr)) => {
-                    if idx < arr.len() {
-                        arr[idx] = val.as_string().unwrap_or_default();
-                    } else {
-                        return Err(alloc::format!("Array index {} out of bounds", idx));
-                    }
+        Statement::Push { name, value } => {
+            let val = eval_expr(variables, env, value)?;
+            let array = variables.entry(name.clone()).or_insert_with(|| {
+                if name.ends_with('$') {
+                    Value::StringArray(Vec::new())
+                } else {
+                    Value::IntArray(Vec::new())
                 }
-                Some(Value::IntArray(arr)) => {
-                    if idx < arr.len() {
+            });
+            let len = match array {
+                Value::StringArray(arr) => arr.len(),
+                Value::IntArray(arr) => arr.len(),
+                _ => return Err(alloc::format!("PUSH requires an array, {} is not one", name)),
+            };
+            if len >= MAX_ARRAY_LEN {
+                return Err(alloc::format!("Array {} full ({} elements)", name, MAX_ARRAY_LEN));
+            }
+            match array {
+                Value::StringArray(arr) => arr.push(val.as_string().ok_or("PUSH value must be string")?),
+                Value::IntArray(arr) => arr.push(val.as_integer().ok_or("PUSH value must be numeric")?),
+                _ => unreachable!(),
+            }
+            Ok(NextAction::Continue)
+        }
+
+        Statement::Split { name, source, delim } => {
+            if !name.ends_with('$') {
+                return Err(alloc::format!("SPLIT needs a string array name, not {}", name));
+            }
+            let source = eval_expr(variables, env, source)?
+                .as_string()
+                .ok_or("SPLIT source must be string")?;
+            let delim = eval_expr(variables, env, delim)?
+                .as_string()
+                .ok_or("SPLIT delimiter must be string")?;
+            if delim.is_empty() {
+                return Err("SPLIT delimiter must not be empty".into());
+            }
+            // An empty source has no tokens (rather than one empty one)
+            let mut tokens = Vec::new();
+            if !source.is_empty() {
+                for token in source.split(delim.as_str()) {
+                    if tokens.len() >= MAX_ARRAY_LEN {
+                        return Err(alloc::format!("SPLIT gives more than {} tokens", MAX_ARRAY_LEN));
+                    }
+                    tokens.push(String::from(token));
+                }
+            }
+            variables.insert(name.clone(), Value::StringArray(tokens));
+            Ok(NextAction::Continue)
+        }
+
+        Statement::ArrayAssign { name, index, value } => {
+            let idx = eval_expr(variables, env, index)?
+                .as_integer()
+                .ok_or("Array index must be numeric")? as usize;
+            let val = eval_expr(variables, env, value)?;
+
+            match variables.get_mut(name) {
+                Some(Value::StringArray(arr)) => {
+                    if idx < arr.len() {
+                        arr[idx] = val.as_string().unwrap_or_default();
+                    } else {
+                        return Err(alloc::format!("Array index {} out of bounds", idx));
+                    }
+                }
+                Some(Value::IntArray(arr)) => {
+                    if idx < arr.len() {
                         arr[idx] = val.as_integer().unwrap_or(0);
                     } else {
                         return Err(alloc::format!("Array index {} out of bounds", idx));
@@ -457,134 +1373,671 @@ fn execute_statement(
         }
 
         Statement::Send { sock, data } => {
-            let sock_val = eval_expr(variables, sock)?
+            let sock_val = eval_expr(variables, env, sock)?
                 .as_integer()
                 .ok_or("SEND socket must be numeric")? as usize;
-            let data_val = eval_expr(variables, data)?
+            let data_val = eval_expr(variables, env, data)?;
+            let data_val = data_val.as_bytes().ok_or("SEND data must be string")?;
+            crate::net::tcp::send(sock_val, data_val);
+            Ok(NextAction::Continue)
+        }
+
+        Statement::SetEnv { name, value } => {
+            let name = eval_expr(variables, env, name)?
+                .as_string()
+                .ok_or("SETENV name must be string")?;
+            let value = eval_expr(variables, env, value)?
                 .as_string()
-                .ok_or("SEND data must be string")?;
-            crate::net::tcp::send(sock_val, data_val.as_bytes());
+                .ok_or("SETENV value must be string")?;
+            crate::env::set(&name, &value)?;
             Ok(NextAction::Continue)
         }
 
         Statement::NetClose(sock) => {
-            let sock_val = eval_expr(variables, sock)?
+            let sock_val = eval_expr(variables, env, sock)?
                 .as_integer()
                 .ok_or("CLOSE socket must be numeric")? as usize;
             crate::net::tcp::close(sock_val);
             Ok(NextAction::Continue)
         }
+
+        Statement::SendTo { sock, ip, port, data } => {
+            let sock_val = eval_expr(variables, env, sock)?
+                .as_integer()
+                .ok_or("SENDTO socket must be numeric")? as usize;
+            let ip_val = eval_expr(variables, env, ip)?
+                .as_string()
+                .ok_or("SENDTO address must be string")?;
+            let port_val = eval_expr(variables, env, port)?
+                .as_integer()
+                .ok_or("SENDTO port must be numeric")?;
+            let data_val = eval_expr(variables, env, data)?;
+            let data_val = data_val.as_bytes().ok_or("SENDTO data must be string")?;
+            let dst = crate::net::ipv4::parse_addr(&ip_val)
+                .ok_or_else(|| alloc::format!("Invalid IP address: {}", ip_val))?;
+            let dst_port = u16::try_from(port_val).map_err(|_| "SENDTO port out of range")?;
+            let src_port = crate::net::udp::local_port(sock_val).ok_or("SENDTO socket is not open")?;
+            crate::net::udp::send_to(&dst, dst_port, src_port, data_val);
+            Ok(NextAction::Continue)
+        }
+
+        Statement::Keepalive { sock, on, idle } => {
+            let sock_val = eval_expr(variables, env, sock)?
+                .as_integer()
+                .ok_or("KEEPALIVE socket must be numeric")? as usize;
+            let on_val = eval_expr(variables, env, on)?.is_truthy();
+            let idle_secs = match idle {
+                Some(idle) => {
+                    let secs = eval_expr(variables, env, idle)?
+                        .as_integer()
+                        .ok_or("KEEPALIVE idle time must be numeric")?;
+                    if !(1..=MAX_KEEPALIVE_IDLE).contains(&secs) {
+                        return Err(alloc::format!(
+                            "KEEPALIVE idle time must be between 1 and {} seconds",
+                            MAX_KEEPALIVE_IDLE
+                        ));
+                    }
+                    Some(secs as u64)
+                }
+                None => None,
+            };
+            if !crate::net::tcp::set_keepalive(sock_val, on_val) {
+                return Err("KEEPALIVE socket is not connected".into());
+            }
+            if let Some(secs) = idle_secs {
+                crate::net::tcp::set_keepalive_idle(sock_val, secs * 1000);
+            }
+            Ok(NextAction::Continue)
+        }
+
+        Statement::FwAllow(rule) => {
+            match rule {
+                Some((protocol, port)) => {
+                    let port = firewall_port(variables, env, port, "FWALLOW")?;
+                    crate::net::firewall::allow(*protocol, port)?;
+                }
+                None => crate::net::firewall::disable(),
+            }
+            Ok(NextAction::Continue)
+        }
+
+        Statement::FwDeny(rule) => {
+            match rule {
+                Some((protocol, port)) => {
+                    let port = firewall_port(variables, env, port, "FWDENY")?;
+                    crate::net::firewall::deny(*protocol, port);
+                }
+                None => crate::net::firewall::enable(),
+            }
+            Ok(NextAction::Continue)
+        }
+
+        Statement::Debug(on) => {
+            env.debug.set(*on);
+            Ok(NextAction::Continue)
+        }
+
+        Statement::TabWidth(expr) => {
+            let width = eval_expr(variables, env, expr)?
+                .as_integer()
+                .ok_or("TABWIDTH requires numeric argument")?;
+            if !(1..=MAX_TAB_WIDTH).contains(&width) {
+                return Err(alloc::format!("TABWIDTH must be between 1 and {}", MAX_TAB_WIDTH));
+            }
+            out.set_tab_width(width as usize);
+            Ok(NextAction::Continue)
+        }
+
+        Statement::NetDump(on) => {
+            crate::net::set_capture(eval_expr(variables, env, on)?.is_truthy());
+            Ok(NextAction::Continue)
+        }
+
+        Statement::NetLog(level) => {
+            use crate::net::LogLevel;
+            match level {
+                Some(level) => {
+                    let level = eval_expr(variables, env, level)?
+                        .as_integer()
+                        .ok_or("NETLOG level must be numeric")?;
+                    let level = u8::try_from(level)
+                        .ok()
+                        .and_then(LogLevel::from_u8)
+                        .ok_or("NETLOG level must be 0, 1 or 2")?;
+                    crate::net::set_log_level(level);
+                }
+                None => {
+                    let level = crate::net::log_level();
+                    let _ = writeln!(out, "NETLOG {} ({:?})", level as u8, level);
+                }
+            }
+            Ok(NextAction::Continue)
+        }
+
+        Statement::UdpClose(sock) => {
+            let sock_val = eval_expr(variables, env, sock)?
+                .as_integer()
+                .ok_or("UDPCLOSE socket must be numeric")? as usize;
+            crate::net::udp::close(sock_val);
+            Ok(NextAction::Continue)
+        }
+
+        Statement::Pset { x, y, color } => {
+            let x = eval_expr(variables, env, x)?
+                .as_integer()
+                .ok_or("PSET x must be numeric")?;
+            let y = eval_expr(variables, env, y)?
+                .as_integer()
+                .ok_or("PSET y must be numeric")?;
+            let color = eval_expr(variables, env, color)?
+                .as_integer()
+                .ok_or("PSET color must be numeric")?;
+            // Off-screen pixels, colors outside the palette (and PSET
+            // without VGA) are silently ignored
+            if x >= 0 && y >= 0 && (0..=255).contains(&color) {
+                vga::set_pixel(x as usize, y as usize, color as u8);
+            }
+            Ok(NextAction::Continue)
+        }
+
+        Statement::WaitVbl => {
+            vga::wait_vblank();
+            vga::present();
+            Ok(NextAction::Continue)
+        }
+
+        Statement::Palette { index, r, g, b } => {
+            let mut args = [0u8; 4];
+            for (slot, (expr, name)) in args
+                .iter_mut()
+                .zip([(index, "index"), (r, "red"), (g, "green"), (b, "blue")])
+            {
+                let v = eval_expr(variables, env, expr)?
+                    .as_integer()
+                    .ok_or_else(|| alloc::format!("PALETTE {} must be numeric", name))?;
+                if !(0..=255).contains(&v) {
+                    return Err(alloc::format!("PALETTE {} must be 0-255", name));
+                }
+                *slot = v as u8;
+            }
+            vga::set_palette(args[0], args[1], args[2], args[3]);
+            Ok(NextAction::Continue)
+        }
+
+        Statement::PaletteReset => {
+            vga::reset_palette();
+            Ok(NextAction::Continue)
+        }
+
+        Statement::HttpSend { sock, status, content_type, body } => {
+            let sock_val = eval_expr(variables, env, sock)?
+                .as_integer()
+                .ok_or("HTTPSEND socket must be numeric")? as usize;
+            let status_val = eval_expr(variables, env, status)?
+                .as_integer()
+                .ok_or("HTTPSEND status must be numeric")?;
+            let type_val = eval_expr(variables, env, content_type)?
+                .as_string()
+                .ok_or("HTTPSEND content type must be string")?;
+            let body_val = eval_expr(variables, env, body)?;
+            let body_val = body_val.as_bytes().ok_or("HTTPSEND body must be string")?;
+            send_http_response(env, sock_val, status_val, &type_val, body_val)?;
+            Ok(NextAction::Continue)
+        }
+
+        Statement::ServeFile { sock, name } => {
+            let sock_val = eval_expr(variables, env, sock)?
+                .as_integer()
+                .ok_or("SERVEFILE socket must be numeric")? as usize;
+            let name_val = eval_expr(variables, env, name)?
+                .as_string()
+                .ok_or("SERVEFILE name must be string")?;
+            match crate::executable::read(&name_val) {
+                Ok(bytes) => {
+                    env.err.set(0);
+                    send_http_response(env, sock_val, 200, content_type(&name_val), &bytes)?;
+                }
+                Err(_) => {
+                    env.err.set(ERR_FILE_NOT_FOUND);
+                    send_http_response(env, sock_val, 404, "text/plain", b"404 Not Found\n")?;
+                }
+            }
+            Ok(NextAction::Continue)
+        }
+    }
+}
+
+/// Reason phrase for common HTTP status codes
+fn http_reason(status: i64) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
+
+/// Build an HTTP/1.0 status line and headers, ending with the blank line
+fn http_header(status: i64, content_type: &str, content_length: Option<usize>) -> String {
+    let mut s = alloc::format!("HTTP/1.0 {} {}\r\n", status, http_reason(status));
+    s.push_str(&alloc::format!("Content-Type: {}\r\n", content_type));
+    if let Some(len) = content_length {
+        s.push_str(&alloc::format!("Content-Length: {}\r\n", len));
+    }
+    s.push_str("Connection: close\r\n\r\n");
+    s
+}
+
+/// Content-Type for a file name, from its extension
+fn content_type(name: &str) -> &'static str {
+    let ext = name.rsplit_once('.').map_or("", |(_, ext)| ext);
+    match ext.to_ascii_lowercase().as_str() {
+        "html" | "htm" => "text/html",
+        "txt" | "bas" => "text/plain",
+        "css" => "text/css",
+        "js" => "text/javascript",
+        "json" => "application/json",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "ico" => "image/x-icon",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Send a complete HTTP response, waiting while the send buffer is full
+///
+/// Fails if the connection stops being open for sending, or if the peer
+/// hasn't taken the response within `HTTP_SEND_TIMEOUT_MS`. When a TIMEOUT
+/// deadline passes first, ERR is set to `ERR_TIMEOUT` instead and the peer
+/// gets a truncated response.
+fn send_http_response(env: &Env, sock: usize, status: i64, content_type: &str, body: &[u8]) -> Result<(), String> {
+    use crate::net::tcp::{self, TcpState};
+
+    let cap = crate::timer::ticks().saturating_add(crate::timer::ms_to_ticks(HTTP_SEND_TIMEOUT_MS));
+    let header = http_header(status, content_type, Some(body.len()));
+    for mut bytes in [header.as_bytes(), body] {
+        while !bytes.is_empty() {
+            // A peer that half-closed (CloseWait) can still read the response
+            if !matches!(tcp::get_state(sock), TcpState::Established | TcpState::CloseWait) {
+                return Err("Connection closed while sending HTTP response".into());
+            }
+            match tcp::send(sock, bytes) {
+                n if n < 0 => return Err("Connection closed while sending HTTP response".into()),
+                0 if crate::timer::ticks() >= env.deadline => {
+                    env.err.set(ERR_TIMEOUT);
+                    return Ok(());
+                }
+                0 if crate::timer::ticks() >= cap => {
+                    return Err("HTTP response timed out (peer not reading)".into());
+                }
+                // Send buffer full; let the network task drain it
+                0 => crate::scheduler::yield_now(),
+                n => bytes = &bytes[n as usize..],
+            }
+        }
     }
+    Ok(())
 }
 
 /// Evaluate a BASIC expression
-fn eval_expr(variables: &BTreeMap<String, Value>, expr: &Expr) -> Result<Value, String> {
-    use crate::net::tcp;
+fn eval_expr(variables: &BTreeMap<String, Value>, env: &Env, expr: &Expr) -> Result<Value, String> {
+    use crate::net::{tcp, udp};
 
     match expr {
         Expr::Integer(n) => Ok(Value::Integer(*n)),
+        Expr::Float(x) => Ok(Value::Float(*x)),
         Expr::StringLit(s) => Ok(Value::String(s.clone())),
         Expr::Variable(name) => variables
             .get(name)
             .cloned()
             .ok_or_else(|| alloc::format!("Undefined variable: {}", name)),
         Expr::Negate(inner) => {
-            let val = eval_expr(variables, inner)?;
+            let val = eval_expr(variables, env, inner)?;
             match val {
                 Value::Integer(n) => Ok(Value::Integer(-n)),
-                _ => Err("Cannot negate non-integer".into()),
+                Value::Float(x) => Ok(Value::Float(-x)),
+                _ => Err("Cannot negate non-number".into()),
             }
         }
+        Expr::Not(inner) => {
+            let val = eval_expr(variables, env, inner)?;
+            Ok(Value::Integer(if val.is_truthy() { 0 } else { 1 }))
+        }
         Expr::BinaryOp { left, op, right } => {
-            let l = eval_expr(variables, left)?;
-            let r = eval_expr(variables, right)?;
-            eval_binary_op(&l, op, &r)
+            let l = eval_expr(variables, env, left)?;
+            let r = eval_expr(variables, env, right)?;
+            eval_binary_op(&l, op, &r, env.max_string_len)
         }
         Expr::Mem(arg) => {
-            let idx = eval_expr(variables, arg)?
+            let idx = eval_expr(variables, env, arg)?
                 .as_integer()
                 .ok_or("MEM requires numeric argument")?;
-            let (used, free) = allocator::get_heap_stats();
             match idx {
-                0 => Ok(Value::Integer(used as i64)),
-                1 => Ok(Value::Integer(free as i64)),
-                _ => Err("MEM: invalid argument (use 0 for used, 1 for free)".into()),
+                0 | 1 => {
+                    let (used, free) = allocator::get_heap_stats();
+                    Ok(Value::Integer(if idx == 0 { used } else { free } as i64))
+                }
+                2 | 3 => {
+                    let (largest, blocks, _) = allocator::get_fragmentation_stats();
+                    Ok(Value::Integer(if idx == 2 { largest } else { blocks } as i64))
+                }
+                _ => Err("MEM: invalid argument (0 used, 1 free, 2 largest free block, 3 free blocks)".into()),
             }
         }
 
         // String functions
+        Expr::Rnd(arg) => {
+            let n = eval_expr(variables, env, arg)?
+                .as_integer()
+                .ok_or("RND requires numeric argument")?;
+            env.rnd.borrow_mut().draw(n)
+        }
+        Expr::Abs(arg) => match eval_expr(variables, env, arg)? {
+            Value::Integer(n) => Ok(Value::Integer(n.wrapping_abs())),
+            Value::Float(x) => Ok(Value::Float(if x < 0.0 { -x } else { x })),
+            _ => Err("ABS requires numeric argument".into()),
+        },
+        Expr::Sgn(arg) => {
+            let x = eval_expr(variables, env, arg)?
+                .as_float()
+                .ok_or("SGN requires numeric argument")?;
+            let sign = if x > 0.0 { 1 } else if x < 0.0 { -1 } else { 0 };
+            Ok(Value::Integer(sign))
+        }
+        Expr::Int(arg) => match eval_expr(variables, env, arg)? {
+            Value::Integer(n) => Ok(Value::Integer(n)),
+            Value::Float(x) => Ok(Value::Integer(floor(x))),
+            _ => Err("INT requires numeric argument".into()),
+        },
+        Expr::Min(a, b) | Expr::Max(a, b) => {
+            let (name, op) = match expr {
+                Expr::Min(..) => ("MIN", BinaryOp::Le),
+                _ => ("MAX", BinaryOp::Ge),
+            };
+            let a = eval_expr(variables, env, a)?;
+            let b = eval_expr(variables, env, b)?;
+            if a.as_float().is_none() || b.as_float().is_none() {
+                return Err(alloc::format!("{} requires numeric arguments", name));
+            }
+            // Compared like `<=`/`>=`, so two integers never go through floats
+            Ok(if eval_binary_op(&a, &op, &b, env.max_string_len)?.is_truthy() { a } else { b })
+        }
         Expr::Chr(arg) => {
-            let n = eval_expr(variables, arg)?
+            let n = eval_expr(variables, env, arg)?
                 .as_integer()
                 .ok_or("CHR$ requires numeric argument")?;
             let ch = (n as u8) as char;
             Ok(Value::String(alloc::format!("{}", ch)))
         }
         Expr::Asc(arg) => {
-            let s = eval_expr(variables, arg)?
+            let s = eval_expr(variables, env, arg)?
                 .as_string()
                 .ok_or("ASC requires string argument")?;
             let n = s.bytes().next().unwrap_or(0) as i64;
             Ok(Value::Integer(n))
         }
         Expr::Len(arg) => {
-            let s = eval_expr(variables, arg)?
-                .as_string()
-                .ok_or("LEN requires string argument")?;
+            let s = eval_expr(variables, env, arg)?;
+            let s = s.as_bytes().ok_or("LEN requires string argument")?;
             Ok(Value::Integer(s.len() as i64))
         }
         Expr::Mid(s_expr, start_expr, len_expr) => {
-            let s = eval_expr(variables, s_expr)?
+            let s = eval_expr(variables, env, s_expr)?
                 .as_string()
                 .ok_or("MID$ requires string argument")?;
-            let start = eval_expr(variables, start_expr)?
+            let start = eval_expr(variables, env, start_expr)?
                 .as_integer()
-                .ok_or("MID$ start must be numeric")? as usize;
-            let len = eval_expr(variables, len_expr)?
+                .ok_or("MID$ start must be numeric")?;
+            let len = eval_expr(variables, env, len_expr)?
                 .as_integer()
-                .ok_or("MID$ length must be numeric")? as usize;
-            // BASIC uses 1-based indexing
-            let result: String = s.chars().skip(start.saturating_sub(1)).take(len).collect();
+                .ok_or("MID$ length must be numeric")?;
+            // BASIC uses 1-based indexing, so there is no position 0
+            if start < 1 {
+                return Err("MID$ start must be 1 or greater".into());
+            }
+            // A negative length selects nothing
+            let len = len.max(0) as usize;
+            let result: String = s.chars().skip(start as usize - 1).take(len).collect();
             Ok(Value::String(result))
         }
         Expr::Left(s_expr, n_expr) => {
-            let s = eval_expr(variables, s_expr)?
+            let s = eval_expr(variables, env, s_expr)?
                 .as_string()
                 .ok_or("LEFT$ requires string argument")?;
-            let n = eval_expr(variables, n_expr)?
+            let n = eval_expr(variables, env, n_expr)?
                 .as_integer()
-                .ok_or("LEFT$ count must be numeric")? as usize;
-            let result: String = s.chars().take(n).collect();
+                .ok_or("LEFT$ count must be numeric")?;
+            // A negative count selects nothing
+            let result: String = s.chars().take(n.max(0) as usize).collect();
+            Ok(Value::String(result))
+        }
+        Expr::Right(s_expr, n_expr) => {
+            let s = eval_expr(variables, env, s_expr)?
+                .as_string()
+                .ok_or("RIGHT$ requires string argument")?;
+            let n = eval_expr(variables, env, n_expr)?
+                .as_integer()
+                .ok_or("RIGHT$ count must be numeric")?;
+            // A negative count selects nothing, a large one the whole string
+            let skip = s.chars().count().saturating_sub(n.max(0) as usize);
+            let result: String = s.chars().skip(skip).collect();
             Ok(Value::String(result))
         }
         Expr::Instr(haystack_expr, needle_expr) => {
-            let haystack = eval_expr(variables, haystack_expr)?
+            let haystack = eval_expr(variables, env, haystack_expr)?
                 .as_string()
                 .ok_or("INSTR requires string arguments")?;
-            let needle = eval_expr(variables, needle_expr)?
+            let needle = eval_expr(variables, env, needle_expr)?
                 .as_string()
                 .ok_or("INSTR requires string arguments")?;
             // Return 1-based position, or 0 if not found
             let pos = haystack.find(&needle).map(|p| p + 1).unwrap_or(0);
             Ok(Value::Integer(pos as i64))
         }
+        Expr::StartsWith(s, prefix) => {
+            let (s, prefix) = eval_string_pair(variables, env, s, prefix, "STARTSWITH")?;
+            Ok(Value::Integer(s.starts_with(prefix.as_str()) as i64))
+        }
+        Expr::EndsWith(s, suffix) => {
+            let (s, suffix) = eval_string_pair(variables, env, s, suffix, "ENDSWITH")?;
+            Ok(Value::Integer(s.ends_with(suffix.as_str()) as i64))
+        }
+        Expr::Contains(s, part) => {
+            let (s, part) = eval_string_pair(variables, env, s, part, "CONTAINS")?;
+            Ok(Value::Integer(s.contains(part.as_str()) as i64))
+        }
         Expr::Str(arg) => {
-            let n = eval_expr(variables, arg)?
-                .as_integer()
-                .ok_or("STR$ requires numeric argument")?;
-            Ok(Value::String(alloc::format!("{}", n)))
+            let v = eval_expr(variables, env, arg)?;
+            if v.as_float().is_none() {
+                return Err("STR$ requires numeric argument".into());
+            }
+            Ok(Value::String(alloc::format!("{}", v)))
         }
         Expr::Val(arg) => {
-            let s = eval_expr(variables, arg)?
+            let s = eval_expr(variables, env, arg)?
                 .as_string()
                 .ok_or("VAL requires string argument")?;
-            let n = s.trim().parse::<i64>().unwrap_or(0);
-            Ok(Value::Integer(n))
+            Ok(parse_val(&s))
+        }
+        Expr::UrlDecode(arg) => {
+            let s = eval_expr(variables, env, arg)?
+                .as_string()
+                .ok_or("URLDECODE$ requires string argument")?;
+            Ok(Value::String(url_decode(&s)))
+        }
+        Expr::UrlEncode(arg) => {
+            let s = eval_expr(variables, env, arg)?
+                .as_string()
+                .ok_or("URLENCODE$ requires string argument")?;
+            // Each byte expands to at most three characters
+            if s.len() * 3 > env.max_string_len {
+                return Err("String too long".into());
+            }
+            Ok(Value::String(url_encode(&s)))
+        }
+        Expr::GetField(body_expr, key_expr) => {
+            let body = eval_expr(variables, env, body_expr)?
+                .as_string()
+                .ok_or("GETFIELD$ requires string arguments")?;
+            let key = eval_expr(variables, env, key_expr)?
+                .as_string()
+                .ok_or("GETFIELD$ requires string arguments")?;
+            Ok(Value::String(get_field(&body, &key)))
+        }
+        Expr::Crc32(arg) => {
+            let value = eval_expr(variables, env, arg)?;
+            let data = value.as_bytes().ok_or("CRC32 requires string argument")?;
+            Ok(Value::Integer(checksum::crc32(data) as i64))
+        }
+        Expr::Checksum(arg) => {
+            let value = eval_expr(variables, env, arg)?;
+            let data = value.as_bytes().ok_or("CHECKSUM requires string argument")?;
+            Ok(Value::Integer(checksum::internet_checksum(data) as i64))
+        }
+        Expr::Base64(arg) => {
+            let value = eval_expr(variables, env, arg)?;
+            let data = value.as_bytes().ok_or("BASE64$ requires string argument")?;
+            if base64::encoded_len(data.len()) > env.max_string_len {
+                return Err("String too long".into());
+            }
+            Ok(Value::String(base64::encode(data)))
+        }
+        Expr::Unbase64(arg) => {
+            let value = eval_expr(variables, env, arg)?;
+            let text = value.as_bytes().ok_or("UNBASE64$ requires string argument")?;
+            // Anything that isn't ASCII can't be valid base64 anyway
+            let text = core::str::from_utf8(text).map_err(|_| "UNBASE64$: invalid base64")?;
+            let bytes = base64::decode(text).ok_or("UNBASE64$: invalid base64")?;
+            Ok(Value::Bytes(bytes))
+        }
+
+        // Graphics
+        Expr::FbAddr => Ok(Value::Integer(vga::back_buffer_address().unwrap_or(0) as i64)),
+        Expr::FbWidth => Ok(Value::Integer(vga::WIDTH as i64)),
+        Expr::FbHeight => Ok(Value::Integer(vga::HEIGHT as i64)),
+
+        // Terminal position (1-based, as in classic BASIC)
+        Expr::Pos => Ok(Value::Integer(env.cursor.0 as i64 + 1)),
+        Expr::Csrlin => Ok(Value::Integer(env.cursor.1 as i64 + 1)),
+
+        // Time
+        Expr::Uptime => Ok(Value::Integer((timer::uptime_ms() / 1000) as i64)),
+        Expr::Ticks => Ok(Value::Integer(timer::ticks() as i64)),
+
+        // Introspection
+        Expr::TypeOf(arg) => {
+            // Look arrays up by name rather than cloning them
+            if let Expr::Variable(name) = &**arg {
+                if let Some(v) = variables.get(name) {
+                    return Ok(Value::String(String::from(v.type_name())));
+                }
+            }
+            let v = eval_expr(variables, env, arg)?;
+            Ok(Value::String(String::from(v.type_name())))
+        }
+        Expr::IsDef(arg) => {
+            let name = eval_expr(variables, env, arg)?
+                .as_string()
+                .ok_or("ISDEF requires string argument")?;
+            Ok(Value::Integer(if variables.contains_key(&name) { 1 } else { 0 }))
+        }
+        Expr::Environ(arg) => {
+            let name = eval_expr(variables, env, arg)?
+                .as_string()
+                .ok_or("ENVIRON$ requires string argument")?;
+            Ok(Value::String(crate::env::get(&name).unwrap_or_default()))
+        }
+
+        // Files: a missing file gives an empty result and sets ERR
+        Expr::ReadFile(arg) => {
+            let name = eval_expr(variables, env, arg)?
+                .as_string()
+                .ok_or("READFILE$ requires string argument")?;
+            let Ok(bytes) = crate::executable::read(&name) else {
+                env.err.set(ERR_FILE_NOT_FOUND);
+                return Ok(Value::Bytes(Vec::new()));
+            };
+            if bytes.len() > env.max_string_len {
+                return Err("String too long".into());
+            }
+            env.err.set(0);
+            Ok(Value::Bytes(bytes.into_owned()))
+        }
+        Expr::FileSize(arg) => {
+            let name = eval_expr(variables, env, arg)?
+                .as_string()
+                .ok_or("FILESIZE requires string argument")?;
+            match crate::executable::read(&name) {
+                Ok(bytes) => {
+                    env.err.set(0);
+                    Ok(Value::Integer(bytes.len() as i64))
+                }
+                Err(_) => {
+                    env.err.set(ERR_FILE_NOT_FOUND);
+                    Ok(Value::Integer(0))
+                }
+            }
+        }
+        Expr::Err => Ok(Value::Integer(env.err.get())),
+
+        // Array literal: all integers or all strings, indexed from 0
+        Expr::ArrayLit(items) => {
+            let values = items
+                .iter()
+                .map(|item| eval_expr(variables, env, item))
+                .collect::<Result<Vec<_>, _>>()?;
+            if values.iter().all(|v| matches!(v, Value::Integer(_))) {
+                Ok(Value::IntArray(values.iter().filter_map(|v| v.as_integer()).collect()))
+            } else if values.iter().all(|v| matches!(v, Value::String(_))) {
+                Ok(Value::StringArray(values.iter().filter_map(|v| v.as_string()).collect()))
+            } else {
+                Err("Array literal elements must all be integers or all be strings".into())
+            }
+        }
+
+        Expr::Count(array) => with_array(variables, env, array, |array| match array {
+            Value::StringArray(arr) => Ok(Value::Integer(arr.len() as i64)),
+            Value::IntArray(arr) => Ok(Value::Integer(arr.len() as i64)),
+            _ => Err("COUNT requires an array".into()),
+        }),
+        Expr::Join(array, delim) => {
+            let delim = eval_expr(variables, env, delim)?
+                .as_string()
+                .ok_or("JOIN$ delimiter must be string")?;
+            with_array(variables, env, array, |array| {
+                let mut joined = String::new();
+                let mut append = |i: usize, item: &dyn fmt::Display| -> Result<(), String> {
+                    if i > 0 {
+                        joined.push_str(&delim);
+                    }
+                    let _ = fmt::Write::write_fmt(&mut joined, format_args!("{}", item));
+                    if joined.len() > env.max_string_len {
+                        return Err("String too long".into());
+                    }
+                    Ok(())
+                };
+                match array {
+                    Value::StringArray(arr) => arr.iter().enumerate().try_for_each(|(i, s)| append(i, s))?,
+                    Value::IntArray(arr) => arr.iter().enumerate().try_for_each(|(i, n)| append(i, n))?,
+                    _ => return Err("JOIN$ requires an array".into()),
+                }
+                Ok(Value::String(joined))
+            })
         }
 
         // Array access
         Expr::ArrayAccess { name, index } => {
-            let idx = eval_expr(variables, index)?
+            let idx = eval_expr(variables, env, index)?
                 .as_integer()
                 .ok_or("Array index must be numeric")? as usize;
             match variables.get(name) {
@@ -606,17 +2059,17 @@ fn eval_expr(variables: &BTreeMap<String, Value>, expr: &Expr) -> Result<Value,
             }
         }
         Expr::Listen(sock_expr, port_expr) => {
-            let sock = eval_expr(variables, sock_expr)?
+            let sock = eval_expr(variables, env, sock_expr)?
                 .as_integer()
                 .ok_or("LISTEN socket must be numeric")? as usize;
-            let port = eval_expr(variables, port_expr)?
+            let port = eval_expr(variables, env, port_expr)?
                 .as_integer()
                 .ok_or("LISTEN port must be numeric")? as u16;
             let ok = tcp::listen(sock, port);
             Ok(Value::Integer(if ok { 1 } else { 0 }))
         }
         Expr::Accept(sock_expr) => {
-            let sock = eval_expr(variables, sock_expr)?
+            let sock = eval_expr(variables, env, sock_expr)?
                 .as_integer()
                 .ok_or("ACCEPT socket must be numeric")? as usize;
             match tcp::accept(sock) {
@@ -625,7 +2078,7 @@ fn eval_expr(variables: &BTreeMap<String, Value>, expr: &Expr) -> Result<Value,
             }
         }
         Expr::Recv(sock_expr) => {
-            let sock = eval_expr(variables, sock_expr)?
+            let sock = eval_expr(variables, env, sock_expr)?
                 .as_integer()
                 .ok_or("RECV$ socket must be numeric")? as usize;
             let mut buf = [0u8; 1024];
@@ -637,8 +2090,121 @@ fn eval_expr(variables: &BTreeMap<String, Value>, expr: &Expr) -> Result<Value,
                 _ => Ok(Value::String(String::new())),
             }
         }
+        Expr::UdpSocket(port_expr) => {
+            let port = match port_expr {
+                Some(port_expr) => eval_expr(variables, env, port_expr)?
+                    .as_integer()
+                    .ok_or("UDPSOCKET port must be numeric")?,
+                None => 0,
+            };
+            let port = u16::try_from(port).map_err(|_| "UDPSOCKET port out of range")?;
+            match udp::bind(port) {
+                Some(h) => Ok(Value::Integer(h as i64)),
+                None => Ok(Value::Integer(-1)),
+            }
+        }
+        Expr::Dns(host_expr) => {
+            let host = eval_expr(variables, env, host_expr)?
+                .as_string()
+                .ok_or("RESOLVE$ requires a string")?;
+            Ok(Value::String(match crate::net::dns::resolve(&host) {
+                Some(ip) => alloc::format!("{}.{}.{}.{}", ip[0], ip[1], ip[2], ip[3]),
+                None => String::new(),
+            }))
+        }
+        Expr::NsLookup(host_expr) => {
+            let host = eval_expr(variables, env, host_expr)?
+                .as_string()
+                .ok_or("NSLOOKUP requires a string")?;
+            let packed = crate::net::dns::resolve(&host).map_or(0, u32::from_be_bytes);
+            Ok(Value::Integer(packed as i64))
+        }
+        Expr::IpConfig(which_expr) => {
+            let which = eval_expr(variables, env, which_expr)?
+                .as_integer()
+                .ok_or("IPCONFIG$ requires numeric argument")?;
+            let cfg = crate::net::config();
+            let ip = match which {
+                0 => cfg.ip,
+                1 => cfg.netmask,
+                2 => cfg.gateway,
+                3 => cfg.dns,
+                4 => return Ok(Value::String(String::from(crate::net::dhcp::status().name()))),
+                _ => return Err("IPCONFIG$ argument must be 0-4".into()),
+            };
+            Ok(Value::String(alloc::format!("{}.{}.{}.{}", ip[0], ip[1], ip[2], ip[3])))
+        }
+        Expr::Netstat(which_expr) => {
+            let which = eval_expr(variables, env, which_expr)?
+                .as_integer()
+                .ok_or("NETSTAT requires numeric argument")?;
+            let (rx, tx, dropped) = crate::net::packet::stats();
+            let n = match which {
+                0 => rx,
+                1 => tx,
+                2 => dropped,
+                3 => tcp::active_connection_count(),
+                4 => crate::net::arp::entry_count(),
+                _ => return Err("NETSTAT argument must be 0-4".into()),
+            };
+            Ok(Value::Integer(n as i64))
+        }
+        Expr::RecvFrom(sock_expr) => {
+            let sock = eval_expr(variables, env, sock_expr)?
+                .as_integer()
+                .ok_or("RECVFROM$ socket must be numeric")? as usize;
+            match udp::recv_from(sock) {
+                Some(datagram) => {
+                    let s = String::from_utf8_lossy(&datagram.data).into_owned();
+                    Ok(Value::String(s))
+                }
+                None => Ok(Value::String(String::new())),
+            }
+        }
+        Expr::Recvline(sock_expr) => {
+            let sock = eval_expr(variables, env, sock_expr)?
+                .as_integer()
+                .ok_or("RECVLINE$ socket must be numeric")? as usize;
+            // Lines stay in the socket's receive buffer until complete, so
+            // no per-socket state is needed here. Lines longer than this
+            // buffer or the socket's receive buffer (which would stall with
+            // its window closed) are returned in pieces.
+            let mut buf = [0u8; 1024];
+            let n = tcp::peek(sock, &mut buf);
+            if n <= 0 {
+                return Ok(Value::String(String::new()));
+            }
+            let n = n as usize;
+            let full = n == buf.len() || n >= tcp::rx_capacity(sock);
+            let take = match buf[..n].iter().position(|&b| b == b'\n') {
+                Some(pos) => pos + 1,
+                None if full || tcp::get_state(sock) != tcp::TcpState::Established => n,
+                None => return Ok(Value::String(String::new())),
+            };
+            let n = tcp::recv(sock, &mut buf[..take]);
+            if n <= 0 {
+                return Ok(Value::String(String::new()));
+            }
+            let mut line = &buf[..n as usize];
+            if let Some(rest) = line.strip_suffix(b"\n") {
+                line = rest;
+            }
+            if let Some(rest) = line.strip_suffix(b"\r") {
+                line = rest;
+            }
+            Ok(Value::String(String::from_utf8_lossy(line).into_owned()))
+        }
+        Expr::HttpHeader(status_expr, type_expr) => {
+            let status = eval_expr(variables, env, status_expr)?
+                .as_integer()
+                .ok_or("HTTPHEADER$ status must be numeric")?;
+            let content_type = eval_expr(variables, env, type_expr)?
+                .as_string()
+                .ok_or("HTTPHEADER$ content type must be string")?;
+            Ok(Value::String(http_header(status, &content_type, None)))
+        }
         Expr::Sockstate(sock_expr) => {
-            let sock = eval_expr(variables, sock_expr)?
+            let sock = eval_expr(variables, env, sock_expr)?
                 .as_integer()
                 .ok_or("SOCKSTATE socket must be numeric")? as usize;
             let code = match tcp::get_state(sock) {
@@ -656,18 +2222,221 @@ fn eval_expr(variables: &BTreeMap<String, Value>, expr: &Expr) -> Result<Value,
             };
             Ok(Value::Integer(code))
         }
+        Expr::Avail(sock_expr) => {
+            let sock = eval_expr(variables, env, sock_expr)?
+                .as_integer()
+                .ok_or("AVAIL socket must be numeric")? as usize;
+            Ok(Value::Integer(tcp::available(sock) as i64))
+        }
+        Expr::Peek(sock_expr, n_expr) => {
+            let sock = eval_expr(variables, env, sock_expr)?
+                .as_integer()
+                .ok_or("PEEK$ socket must be numeric")? as usize;
+            let n = eval_expr(variables, env, n_expr)?
+                .as_integer()
+                .ok_or("PEEK$ count must be numeric")?;
+            let mut buf = [0u8; 1024];
+            let n = n.clamp(0, buf.len() as i64) as usize;
+            match tcp::peek(sock, &mut buf[..n]) {
+                n if n > 0 => {
+                    let s = String::from_utf8_lossy(&buf[..n as usize]).into_owned();
+                    Ok(Value::String(s))
+                }
+                _ => Ok(Value::String(String::new())),
+            }
+        }
     }
 }
 
+/// Value of a single hex digit
+fn hex_value(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Decode a URL/form-encoded string ('+' is a space, %XX is a byte).
+/// Malformed % sequences are passed through unchanged.
+fn url_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                match (hex_value(bytes[i + 1]), hex_value(bytes[i + 2])) {
+                    (Some(hi), Some(lo)) => {
+                        out.push(hi << 4 | lo);
+                        i += 2;
+                    }
+                    _ => out.push(b'%'),
+                }
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Percent-encode everything except RFC 3986 unreserved characters
+fn url_encode(s: &str) -> String {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+    let mut out = String::with_capacity(s.len());
+    for &b in s.as_bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'~') {
+            out.push(b as char);
+        } else {
+            out.push('%');
+            out.push(HEX[(b >> 4) as usize] as char);
+            out.push(HEX[(b & 0xF) as usize] as char);
+        }
+    }
+    out
+}
+
+/// Look up `key` in a request body, returning "" if it is absent.
+///
+/// Two formats are understood:
+/// - form bodies/query strings: `key=value&key2=value2` (values are returned
+///   as-is, use URLDECODE$ to decode them; empty pairs are skipped)
+/// - flat JSON objects: `{"key": "value", "n": 12}` (string escapes are
+///   decoded, other values are returned as their literal text; nested
+///   objects and arrays are not supported)
+fn get_field(body: &str, key: &str) -> String {
+    let body = body.trim();
+    if body.starts_with('{') {
+        return get_json_field(body, key).unwrap_or_default();
+    }
+    for pair in body.split('&') {
+        let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+        if k == key {
+            return String::from(v);
+        }
+    }
+    String::new()
+}
+
+/// Find `key` in a flat JSON object. Returns None if absent or malformed.
+fn get_json_field(body: &str, key: &str) -> Option<String> {
+    let bytes = body.as_bytes();
+    let mut i = 1; // skip '{'
+
+    let skip_ws = |i: &mut usize| {
+        while *i < bytes.len() && bytes[*i].is_ascii_whitespace() {
+            *i += 1;
+        }
+    };
+
+    loop {
+        skip_ws(&mut i);
+        if i >= bytes.len() || bytes[i] == b'}' {
+            return None;
+        }
+        if bytes[i] != b'"' {
+            return None;
+        }
+        let (k, next) = read_json_string(bytes, i)?;
+        i = next;
+        skip_ws(&mut i);
+        if i >= bytes.len() || bytes[i] != b':' {
+            return None;
+        }
+        i += 1;
+        skip_ws(&mut i);
+        if i >= bytes.len() {
+            return None;
+        }
+
+        let value = if bytes[i] == b'"' {
+            let (v, next) = read_json_string(bytes, i)?;
+            i = next;
+            v
+        } else {
+            let start = i;
+            while i < bytes.len() && bytes[i] != b',' && bytes[i] != b'}' {
+                i += 1;
+            }
+            String::from(body[start..i].trim())
+        };
+
+        if k == key {
+            return Some(value);
+        }
+
+        skip_ws(&mut i);
+        if i < bytes.len() && bytes[i] == b',' {
+            i += 1;
+        } else {
+            return None;
+        }
+    }
+}
+
+/// Read a JSON string starting at the opening quote.
+/// Returns the decoded string and the index just past the closing quote.
+fn read_json_string(bytes: &[u8], start: usize) -> Option<(String, usize)> {
+    let mut out = Vec::new();
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => return Some((String::from_utf8_lossy(&out).into_owned(), i + 1)),
+            b'\\' => {
+                i += 1;
+                match *bytes.get(i)? {
+                    b'n' => out.push(b'\n'),
+                    b'r' => out.push(b'\r'),
+                    b't' => out.push(b'\t'),
+                    b => out.push(b),
+                }
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    None
+}
+
 /// Evaluate a binary operation
-fn eval_binary_op(l: &Value, op: &BinaryOp, r: &Value) -> Result<Value, String> {
+fn eval_binary_op(l: &Value, op: &BinaryOp, r: &Value, max_len: usize) -> Result<Value, String> {
+    // Logical operators test truthiness, so they accept any values
+    match op {
+        BinaryOp::And => {
+            return Ok(Value::Integer(if l.is_truthy() && r.is_truthy() { 1 } else { 0 }));
+        }
+        BinaryOp::Or => {
+            return Ok(Value::Integer(if l.is_truthy() || r.is_truthy() { 1 } else { 0 }));
+        }
+        _ => {}
+    }
+
     // Handle string concatenation
     if let (Value::String(ls), BinaryOp::Add, Value::String(rs)) = (l, op, r) {
+        if ls.len() + rs.len() > max_len {
+            return Err("String too long".into());
+        }
         let mut result = ls.clone();
         result.push_str(rs);
         return Ok(Value::String(result));
     }
 
+    // Bytes mixed with strings stay raw, so binary file contents survive
+    if matches!(l, Value::Bytes(_)) || matches!(r, Value::Bytes(_)) {
+        if let (Some(lb), Some(rb)) = (l.as_bytes(), r.as_bytes()) {
+            return match op {
+                BinaryOp::Add if lb.len() + rb.len() > max_len => Err("String too long".into()),
+                BinaryOp::Add => Ok(Value::Bytes([lb, rb].concat())),
+                BinaryOp::Eq => Ok(Value::Integer(if lb == rb { 1 } else { 0 })),
+                BinaryOp::Ne => Ok(Value::Integer(if lb != rb { 1 } else { 0 })),
+                _ => Err("Invalid string operation".into()),
+            };
+        }
+    }
+
     // Handle string comparison
     if let (Value::String(ls), Value::String(rs)) = (l, r) {
         return match op {
@@ -677,6 +2446,13 @@ fn eval_binary_op(l: &Value, op: &BinaryOp, r: &Value) -> Result<Value, String>
         };
     }
 
+    // Any float operand makes the operation floating point
+    if matches!(l, Value::Float(_)) || matches!(r, Value::Float(_)) {
+        let lv = l.as_float().ok_or("Type error in left operand")?;
+        let rv = r.as_float().ok_or("Type error in right operand")?;
+        return eval_float_op(lv, op, rv);
+    }
+
     // Numeric operations
     let lv = l.as_integer().ok_or("Type error in left operand")?;
     let rv = r.as_integer().ok_or("Type error in right operand")?;
@@ -689,7 +2465,12 @@ fn eval_binary_op(l: &Value, op: &BinaryOp, r: &Value) -> Result<Value, String>
             if rv == 0 {
                 return Err("Division by zero".into());
             }
-            Value::Integer(lv / rv)
+            // Exact quotients stay integers; 3 / 2 is 1.5
+            if lv.checked_rem(rv) == Some(0) {
+                Value::Integer(lv / rv)
+            } else {
+                Value::Float(lv as f64 / rv as f64)
+            }
         }
         // Comparisons return 1 (true) or 0 (false)
         BinaryOp::Eq => Value::Integer(if lv == rv { 1 } else { 0 }),
@@ -698,11 +2479,92 @@ fn eval_binary_op(l: &Value, op: &BinaryOp, r: &Value) -> Result<Value, String>
         BinaryOp::Gt => Value::Integer(if lv > rv { 1 } else { 0 }),
         BinaryOp::Le => Value::Integer(if lv <= rv { 1 } else { 0 }),
         BinaryOp::Ge => Value::Integer(if lv >= rv { 1 } else { 0 }),
+        BinaryOp::And | BinaryOp::Or => unreachable!("logical operators handled above"),
     };
 
     Ok(result)
 }
 
+/// Round toward negative infinity (core has no f64::floor)
+fn floor(x: f64) -> i64 {
+    let t = x as i64;
+    if (t as f64) > x { t - 1 } else { t }
+}
+
+/// Evaluate a binary operation on floats
+fn eval_float_op(lv: f64, op: &BinaryOp, rv: f64) -> Result<Value, String> {
+    let result = match op {
+        BinaryOp::Add => Value::Float(lv + rv),
+        BinaryOp::Sub => Value::Float(lv - rv),
+        BinaryOp::Mul => Value::Float(lv * rv),
+        BinaryOp::Div => {
+            if rv == 0.0 {
+                return Err("Division by zero".into());
+            }
+            Value::Float(lv / rv)
+        }
+        BinaryOp::Eq => Value::Integer(if lv == rv { 1 } else { 0 }),
+        BinaryOp::Ne => Value::Integer(if lv != rv { 1 } else { 0 }),
+        BinaryOp::Lt => Value::Integer(if lv < rv { 1 } else { 0 }),
+        BinaryOp::Gt => Value::Integer(if lv > rv { 1 } else { 0 }),
+        BinaryOp::Le => Value::Integer(if lv <= rv { 1 } else { 0 }),
+        BinaryOp::Ge => Value::Integer(if lv >= rv { 1 } else { 0 }),
+        BinaryOp::And | BinaryOp::Or => unreachable!("logical operators handled above"),
+    };
+
+    Ok(result)
+}
+
+/// Format a float literal so it lexes back as a float (always with a '.')
+fn format_float(x: f64) -> String {
+    let s = alloc::format!("{}", x);
+    if s.contains('.') || !x.is_finite() {
+        s
+    } else {
+        alloc::format!("{}.0", s)
+    }
+}
+
+/// Binding strength of NOT, between AND and the comparisons
+const NOT_PRECEDENCE: u8 = 3;
+
+/// Format an operand, parenthesized if it binds looser than `min_prec`
+fn format_operand(expr: &Expr, min_prec: u8) -> String {
+    let prec = match expr {
+        Expr::BinaryOp { op, .. } => op.precedence(),
+        Expr::Not(_) => NOT_PRECEDENCE,
+        _ => return format_expr(expr),
+    };
+    if prec < min_prec {
+        alloc::format!("({})", format_expr(expr))
+    } else {
+        format_expr(expr)
+    }
+}
+
+/// Format `"name", "arg", ...` as in SPAWN
+fn format_spawn_command(name: &str, args: &[String]) -> String {
+    let mut s = alloc::format!("\"{}\"", name);
+    for arg in args {
+        s.push_str(&alloc::format!(", \"{}\"", arg));
+    }
+    s
+}
+
+/// Format the targets of ON GOTO/GOSUB
+fn format_line_list(lines: &[u32]) -> String {
+    let lines: Vec<String> = lines.iter().map(|n| alloc::format!("{}", n)).collect();
+    lines.join(", ")
+}
+
+/// Format the branch of an IF, writing a plain GOTO as just the line number
+fn format_if_branch(stmt: &Statement) -> String {
+    match stmt {
+        Statement::Goto(line) => alloc::format!("{}", line),
+        other => format_statement(other),
+    }
+}
+
 /// Format a statement for LIST output
 fn format_statement(stmt: &Statement) -> String {
     match stmt {
@@ -719,29 +2581,66 @@ fn format_statement(stmt: &Statement) -> String {
         Statement::Let { var, value } => {
             alloc::format!("LET {} = {}", var, format_expr(value))
         }
-        Statement::If { condition, then_line } => {
-            alloc::format!("IF {} THEN {}", format_expr(condition), then_line)
+        Statement::If { condition, then_stmt, else_stmt } => {
+            let mut s = alloc::format!(
+                "IF {} THEN {}",
+                format_expr(condition),
+                format_if_branch(then_stmt)
+            );
+            if let Some(else_stmt) = else_stmt {
+                s.push_str(" ELSE ");
+                s.push_str(&format_if_branch(else_stmt));
+            }
+            s
         }
         Statement::Goto(line) => alloc::format!("GOTO {}", line),
         Statement::Gosub(line) => alloc::format!("GOSUB {}", line),
+        Statement::OnGoto { selector, targets } => {
+            alloc::format!("ON {} GOTO {}", format_expr(selector), format_line_list(targets))
+        }
+        Statement::OnGosub { selector, targets } => {
+            alloc::format!("ON {} GOSUB {}", format_expr(selector), format_line_list(targets))
+        }
         Statement::Return => String::from("RETURN"),
         Statement::For { var, start, end, step } => {
             alloc::format!("FOR {} = {} TO {} STEP {}", var, format_expr(start), format_expr(end), format_expr(step))
         }
         Statement::Next(var) => alloc::format!("NEXT {}", var),
+        Statement::While { condition } => alloc::format!("WHILE {}", format_expr(condition)),
+        Statement::Wend => "WEND".into(),
         Statement::Sleep(expr) => alloc::format!("SLEEP {}", format_expr(expr)),
+        Statement::SleepUs(expr) => alloc::format!("SLEEPUS {}", format_expr(expr)),
+        Statement::TabWidth(expr) => alloc::format!("TABWIDTH {}", format_expr(expr)),
+        Statement::Randomize(None) => String::from("RANDOMIZE"),
+        Statement::Randomize(Some(seed)) => alloc::format!("RANDOMIZE {}", format_expr(seed)),
         Statement::Rem => String::from("REM"),
         Statement::End => String::from("END"),
-        Statement::Spawn(name, args) => {
-            let mut s = alloc::format!("SPAWN \"{}\"", name);
-            for arg in args {
-                s.push_str(&alloc::format!(", \"{}\"", arg));
+        Statement::Stop => String::from("STOP"),
+        Statement::Assert { condition, message } => match message {
+            Some(message) => {
+                alloc::format!("ASSERT {}, {}", format_expr(condition), format_expr(message))
             }
-            s
-        }
+            None => alloc::format!("ASSERT {}", format_expr(condition)),
+        },
+        Statement::Abort(message) => alloc::format!("ABORT {}", format_expr(message)),
+        Statement::Spawn { name, args, restricted } => alloc::format!(
+            "SPAWN {}{}",
+            format_spawn_command(name, args),
+            if *restricted { " RESTRICTED" } else { "" }
+        ),
+        Statement::SpawnPipe { writer, reader, restricted } => alloc::format!(
+            "SPAWN {} | {}{}",
+            format_spawn_command(&writer.0, &writer.1),
+            format_spawn_command(&reader.0, &reader.1),
+            if *restricted { " RESTRICTED" } else { "" }
+        ),
         Statement::Dim { name, size } => {
             alloc::format!("DIM {}({})", name, format_expr(size))
         }
+        Statement::Push { name, value } => alloc::format!("PUSH {}, {}", name, format_expr(value)),
+        Statement::Split { name, source, delim } => {
+            alloc::format!("SPLIT {}, {}, {}", name, format_expr(source), format_expr(delim))
+        }
         Statement::ArrayAssign { name, index, value } => {
             alloc::format!("{}({}) = {}", name, format_expr(index), format_expr(value))
         }
@@ -751,12 +2650,96 @@ fn format_statement(stmt: &Statement) -> String {
         Statement::NetClose(sock) => {
             alloc::format!("CLOSE {}", format_expr(sock))
         }
+        Statement::SendTo { sock, ip, port, data } => alloc::format!(
+            "SENDTO {}, {}, {}, {}",
+            format_expr(sock),
+            format_expr(ip),
+            format_expr(port),
+            format_expr(data)
+        ),
+        Statement::UdpClose(sock) => alloc::format!("UDPCLOSE {}", format_expr(sock)),
+        Statement::Timeout { ms, stmt } => {
+            alloc::format!("TIMEOUT {}, {}", format_expr(ms), format_statement(stmt))
+        }
+        Statement::Keepalive { sock, on, idle } => match idle {
+            Some(idle) => alloc::format!(
+                "KEEPALIVE {}, {}, {}",
+                format_expr(sock),
+                format_expr(on),
+                format_expr(idle)
+            ),
+            None => alloc::format!("KEEPALIVE {}, {}", format_expr(sock), format_expr(on)),
+        },
+        Statement::FwAllow(rule) => format_firewall_rule("FWALLOW", rule),
+        Statement::FwDeny(rule) => format_firewall_rule("FWDENY", rule),
+        Statement::NetDump(on) => alloc::format!("NETDUMP {}", format_expr(on)),
+        Statement::Debug(on) => String::from(if *on { "DEBUG ON" } else { "DEBUG OFF" }),
+        Statement::NetLog(None) => String::from("NETLOG"),
+        Statement::NetLog(Some(level)) => alloc::format!("NETLOG {}", format_expr(level)),
+        Statement::Pset { x, y, color } => {
+            alloc::format!("PSET {}, {}, {}", format_expr(x), format_expr(y), format_expr(color))
+        }
+        Statement::WaitVbl => String::from("WAITVBL"),
+        Statement::Palette { index, r, g, b } => {
+            alloc::format!(
+                "PALETTE {}, {}, {}, {}",
+                format_expr(index),
+                format_expr(r),
+                format_expr(g),
+                format_expr(b)
+            )
+        }
+        Statement::PaletteReset => String::from("PALETTE"),
+        Statement::HttpSend { sock, status, content_type, body } => {
+            alloc::format!(
+                "HTTPSEND {}, {}, {}, {}",
+                format_expr(sock), format_expr(status), format_expr(content_type), format_expr(body))
+        }
+        Statement::ServeFile { sock, name } => {
+            alloc::format!("SERVEFILE {}, {}", format_expr(sock), format_expr(name))
+        }
+        Statement::Open { channel, target } => match target {
+            OpenTarget::Console => alloc::format!("OPEN {} AS CONSOLE", format_expr(channel)),
+            OpenTarget::Socket(sock) => {
+                alloc::format!("OPEN {} AS SOCKET {}", format_expr(channel), format_expr(sock))
+            }
+        },
+        Statement::CloseChannel(channel) => alloc::format!("CLOSE #{}", format_expr(channel)),
+        Statement::PrintTo { channel, exprs } => {
+            let mut s = alloc::format!("PRINT #{}", format_expr(channel));
+            for (i, expr) in exprs.iter().enumerate() {
+                s.push_str(if i > 0 { "; " } else { ", " });
+                s.push_str(&format_expr(expr));
+            }
+            s
+        }
+        Statement::InputFrom { channel, var } => {
+            alloc::format!("INPUT #{}, {}", format_expr(channel), var)
+        }
+        Statement::Input { prompt: Some(prompt), var } => {
+            alloc::format!("INPUT \"{}\"; {}", prompt, var)
+        }
+        Statement::Input { prompt: None, var } => alloc::format!("INPUT {}", var),
+        Statement::SetEnv { name, value } => {
+            alloc::format!("SETENV {}, {}", format_expr(name), format_expr(value))
+        }
+    }
+}
+
+fn format_firewall_rule(keyword: &str, rule: &Option<(u8, Expr)>) -> String {
+    match rule {
+        Some((protocol, port)) => {
+            let name = if *protocol == crate::net::ipv4::PROTO_TCP { "TCP" } else { "UDP" };
+            alloc::format!("{} {}, {}", keyword, name, format_expr(port))
+        }
+        None => String::from(keyword),
     }
 }
 
 fn format_expr(expr: &Expr) -> String {
     match expr {
         Expr::Integer(n) => alloc::format!("{}", n),
+        Expr::Float(x) => format_float(*x),
         Expr::StringLit(s) => alloc::format!("\"{}\"", s),
         Expr::Variable(name) => name.clone(),
         Expr::BinaryOp { left, op, right } => {
@@ -771,12 +2754,28 @@ fn format_expr(expr: &Expr) -> String {
                 BinaryOp::Gt => ">",
                 BinaryOp::Le => "<=",
                 BinaryOp::Ge => ">=",
+                BinaryOp::And => "AND",
+                BinaryOp::Or => "OR",
             };
-            alloc::format!("{} {} {}", format_expr(left), op_str, format_expr(right))
+            // Operators are left-associative, so an equal-precedence
+            // operation on the right needs parentheses too
+            let prec = op.precedence();
+            alloc::format!(
+                "{} {} {}",
+                format_operand(left, prec),
+                op_str,
+                format_operand(right, prec + 1))
         }
-        Expr::Negate(inner) => alloc::format!("-{}", format_expr(inner)),
+        Expr::Negate(inner) => alloc::format!("-{}", format_operand(inner, u8::MAX)),
+        Expr::Not(inner) => alloc::format!("NOT {}", format_operand(inner, NOT_PRECEDENCE)),
         Expr::Mem(arg) => alloc::format!("MEM({})", format_expr(arg)),
         // String functions
+        Expr::Rnd(arg) => alloc::format!("RND({})", format_expr(arg)),
+        Expr::Abs(arg) => alloc::format!("ABS({})", format_expr(arg)),
+        Expr::Sgn(arg) => alloc::format!("SGN({})", format_expr(arg)),
+        Expr::Int(arg) => alloc::format!("INT({})", format_expr(arg)),
+        Expr::Min(a, b) => alloc::format!("MIN({}, {})", format_expr(a), format_expr(b)),
+        Expr::Max(a, b) => alloc::format!("MAX({}, {})", format_expr(a), format_expr(b)),
         Expr::Chr(arg) => alloc::format!("CHR$({})", format_expr(arg)),
         Expr::Asc(arg) => alloc::format!("ASC({})", format_expr(arg)),
         Expr::Len(arg) => alloc::format!("LEN({})", format_expr(arg)),
@@ -784,11 +2783,52 @@ fn format_expr(expr: &Expr) -> String {
             alloc::format!("MID$({}, {}, {})", format_expr(s), format_expr(start), format_expr(len))
         }
         Expr::Left(s, n) => alloc::format!("LEFT$({}, {})", format_expr(s), format_expr(n)),
+        Expr::Right(s, n) => alloc::format!("RIGHT$({}, {})", format_expr(s), format_expr(n)),
         Expr::Instr(h, n) => alloc::format!("INSTR({}, {})", format_expr(h), format_expr(n)),
+        Expr::StartsWith(s, p) => alloc::format!("STARTSWITH({}, {})", format_expr(s), format_expr(p)),
+        Expr::EndsWith(s, p) => alloc::format!("ENDSWITH({}, {})", format_expr(s), format_expr(p)),
+        Expr::Contains(s, p) => alloc::format!("CONTAINS({}, {})", format_expr(s), format_expr(p)),
         Expr::Str(arg) => alloc::format!("STR$({})", format_expr(arg)),
         Expr::Val(arg) => alloc::format!("VAL({})", format_expr(arg)),
+        Expr::UrlDecode(arg) => alloc::format!("URLDECODE$({})", format_expr(arg)),
+        Expr::UrlEncode(arg) => alloc::format!("URLENCODE$({})", format_expr(arg)),
+        Expr::Crc32(arg) => alloc::format!("CRC32({})", format_expr(arg)),
+        Expr::Checksum(arg) => alloc::format!("CHECKSUM({})", format_expr(arg)),
+        Expr::Base64(arg) => alloc::format!("BASE64$({})", format_expr(arg)),
+        Expr::Unbase64(arg) => alloc::format!("UNBASE64$({})", format_expr(arg)),
+        Expr::GetField(body, key) => {
+            alloc::format!("GETFIELD$({}, {})", format_expr(body), format_expr(key))
+        }
+        Expr::FbAddr => String::from("FBADDR"),
+        Expr::FbWidth => String::from("FBWIDTH"),
+        Expr::FbHeight => String::from("FBHEIGHT"),
+        Expr::Pos => String::from("POS(0)"),
+        Expr::Csrlin => String::from("CSRLIN"),
+        Expr::Uptime => String::from("UPTIME"),
+        Expr::Ticks => String::from("TICKS"),
+        // Introspection
+        Expr::TypeOf(arg) => alloc::format!("TYPEOF$({})", format_expr(arg)),
+        Expr::IsDef(arg) => alloc::format!("ISDEF({})", format_expr(arg)),
+        Expr::Environ(arg) => alloc::format!("ENVIRON$({})", format_expr(arg)),
+        Expr::ReadFile(arg) => alloc::format!("READFILE$({})", format_expr(arg)),
+        Expr::FileSize(arg) => alloc::format!("FILESIZE({})", format_expr(arg)),
+        Expr::Err => String::from("ERR"),
+        // Array literal
+        Expr::ArrayLit(items) => {
+            let mut s = String::from("{");
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    s.push_str(", ");
+                }
+                s.push_str(&format_expr(item));
+            }
+            s.push('}');
+            s
+        }
         // Array access
         Expr::ArrayAccess { name, index } => alloc::format!("{}({})", name, format_expr(index)),
+        Expr::Count(array) => alloc::format!("COUNT({})", format_expr(array)),
+        Expr::Join(array, delim) => alloc::format!("JOIN$({}, {})", format_expr(array), format_expr(delim)),
         // Network functions
         Expr::Socket => String::from("SOCKET()"),
         Expr::Listen(sock, port) => {
@@ -797,5 +2837,517 @@ fn format_expr(expr: &Expr) -> String {
         Expr::Accept(sock) => alloc::format!("ACCEPT({})", format_expr(sock)),
         Expr::Recv(sock) => alloc::format!("RECV$({})", format_expr(sock)),
         Expr::Sockstate(sock) => alloc::format!("SOCKSTATE({})", format_expr(sock)),
+        Expr::Avail(sock) => alloc::format!("AVAIL({})", format_expr(sock)),
+        Expr::HttpHeader(status, t) => {
+            alloc::format!("HTTPHEADER$({}, {})", format_expr(status), format_expr(t))
+        }
+        Expr::Recvline(sock) => alloc::format!("RECVLINE$({})", format_expr(sock)),
+        Expr::Peek(sock, n) => alloc::format!("PEEK$({}, {})", format_expr(sock), format_expr(n)),
+        Expr::UdpSocket(None) => String::from("UDPSOCKET()"),
+        Expr::UdpSocket(Some(port)) => alloc::format!("UDPSOCKET({})", format_expr(port)),
+        Expr::RecvFrom(sock) => alloc::format!("RECVFROM$({})", format_expr(sock)),
+        Expr::Dns(host) => alloc::format!("RESOLVE$({})", format_expr(host)),
+        Expr::NsLookup(host) => alloc::format!("NSLOOKUP({})", format_expr(host)),
+        Expr::IpConfig(which) => alloc::format!("IPCONFIG$({})", format_expr(which)),
+        Expr::Netstat(which) => alloc::format!("NETSTAT({})", format_expr(which)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::terminal::OutputCursor;
+
+    /// Terminal that captures output for tests, expanding tabs like the
+    /// real terminals
+    struct Capture(String, OutputCursor);
+
+    impl Capture {
+        fn new() -> Self {
+            Capture(String::new(), OutputCursor::new())
+        }
+    }
+
+    impl fmt::Write for Capture {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let Capture(text, cursor) = self;
+            cursor.write(s, &mut |chunk| {
+                text.push_str(chunk);
+                Ok(())
+            })
+        }
+    }
+
+    impl Terminal for Capture {
+        fn poll_byte(&mut self) -> super::super::terminal::ReadStatus {
+            super::super::terminal::ReadStatus::Eof
+        }
+
+        fn cursor(&self) -> (usize, usize) {
+            self.1.position()
+        }
+
+        fn set_tab_width(&mut self, width: usize) {
+            self.1.set_tab_width(width);
+        }
+    }
+
+    #[test]
+    fn test_lowercase_program_runs() {
+        let mut interp = Interpreter::new();
+        interp.load_program("10 let x = 2\n20 for i = 1 to 3\n30 let x = x * 2\n40 next i\n50 print x\n60 end\n").unwrap();
+        interp.run();
+        let mut out = Capture::new();
+        while interp.is_running() {
+            interp.step(&mut out);
+        }
+        assert_eq!(*interp.status(), ExecutionStatus::Finished);
+        assert_eq!(out.0, "16\n");
+    }
+
+    /// Run a program to completion, returning its final status and output
+    fn run_source(src: &str) -> (ExecutionStatus, String) {
+        let mut interp = Interpreter::new();
+        interp.load_program(src).unwrap();
+        interp.run();
+        let mut out = Capture::new();
+        while interp.is_running() {
+            interp.step(&mut out);
+        }
+        (interp.status().clone(), out.0)
+    }
+
+    #[test]
+    fn test_sleep_float_and_negative() {
+        let mut interp = Interpreter::new();
+        interp.load_program("10 SLEEP 0.5\n20 SLEEP 0.25\n30 SLEEP 2\n").unwrap();
+        interp.run();
+        let mut out = Capture::new();
+        let mut sleeps = Vec::new();
+        while interp.is_running() {
+            interp.step(&mut out);
+            if let ExecutionStatus::Sleeping(ms) = interp.status() {
+                sleeps.push(*ms);
+            }
+        }
+        assert_eq!(sleeps, [500, 250, 2]);
+
+        for src in ["10 SLEEP -1\n", "10 SLEEP -0.5\n"] {
+            let (status, _) = run_source(src);
+            assert_eq!(status, ExecutionStatus::Error("SLEEP time must not be negative".into()));
+        }
+    }
+
+    #[test]
+    fn test_tabwidth() {
+        let (status, out) = run_source(
+            "10 PRINT \"A\" + CHR$(9) + \"B\"\n20 TABWIDTH 4\n30 PRINT \"A\" + CHR$(9) + \"B\"\n\
+             40 PRINT \"ABCDE\" + CHR$(9) + \"X\"\n",
+        );
+        assert_eq!(status, ExecutionStatus::Finished);
+        assert_eq!(out, "A       B\nA   B\nABCDE   X\n");
+
+        let (status, _) = run_source("10 TABWIDTH 0\n");
+        assert_eq!(status, ExecutionStatus::Error("TABWIDTH must be between 1 and 80".into()));
+    }
+
+    #[test]
+    fn test_nested_while_in_for() {
+        let (status, out) = run_source(
+            "10 FOR I = 1 TO 2\n20 J = 0\n30 WHILE J < 2\n40 K = 0\n50 WHILE K < I\n\
+             60 K = K + 1\n70 WEND\n80 PRINT I; J; K\n90 J = J + 1\n100 WEND\n110 NEXT I\n",
+        );
+        assert_eq!(status, ExecutionStatus::Finished);
+        assert_eq!(out, "1 0 1\n1 1 1\n2 0 2\n2 1 2\n");
+    }
+
+    #[test]
+    fn test_while_false_skips_body() {
+        let (status, out) = run_source("10 WHILE 0\n20 WHILE 1\n30 PRINT 1\n40 WEND\n50 WEND\n60 PRINT 2\n");
+        assert_eq!(status, ExecutionStatus::Finished);
+        assert_eq!(out, "2\n");
+    }
+
+    #[test]
+    fn test_input_resumes_with_value() {
+        let mut interp = Interpreter::new();
+        interp.load_program("10 INPUT \"N\"; N\n20 INPUT A$\n30 PRINT N * 2; A$\n").unwrap();
+        interp.run();
+        let mut out = Capture::new();
+        let mut answers = ["21", "hi there"].into_iter();
+        while interp.is_running() {
+            if interp.step(&mut out) == ExecutionStatus::WaitingForInput {
+                assert!(interp.provide_input(answers.next().unwrap()));
+            }
+        }
+        assert_eq!(*interp.status(), ExecutionStatus::Finished);
+        assert_eq!(out.0, "N? ? 42 hi there\n");
+    }
+
+    #[test]
+    fn test_input_from_channel_waits_like_input() {
+        let mut interp = Interpreter::new();
+        interp
+            .load_program(
+                "10 OPEN 1 AS CONSOLE\n20 INPUT# 1, A$\n30 INPUT# 1, N\n\
+                 40 PRINT A$; N + 1\n50 INPUT# 1, N\n",
+            )
+            .unwrap();
+        interp.run();
+        let mut out = Capture::new();
+        let mut answers = ["hi", " 41 ", "4x"].into_iter();
+        while interp.is_running() {
+            if interp.step(&mut out) == ExecutionStatus::WaitingForInput {
+                assert!(interp.waiting_on_channel());
+                interp.provide_input(answers.next().unwrap());
+            }
+        }
+        assert_eq!(out.0, "hi 42\n");
+        let e = ExecutionStatus::Error("INPUT# expected a number, got \"4x\"".into());
+        assert_eq!(*interp.status(), e);
+        assert_eq!(interp.error_line(), Some(50));
+    }
+
+    #[test]
+    fn test_logical_operators() {
+        assert_eq!(eval("1 > 0 AND 2 < 10").unwrap().as_integer(), Some(1));
+        assert_eq!(eval("1 > 0 AND 20 < 10").unwrap().as_integer(), Some(0));
+        assert_eq!(eval("0 OR 3").unwrap().as_integer(), Some(1));
+        assert_eq!(eval("NOT 1 = 2").unwrap().as_integer(), Some(1));
+        // AND binds tighter than OR
+        assert_eq!(eval("1 OR 1 AND 0").unwrap().as_integer(), Some(1));
+        assert_eq!(eval(r#"NOT "" AND "x""#).unwrap().as_integer(), Some(1));
+    }
+
+    #[test]
+    fn test_logical_list_round_trip() {
+        let mut interp = Interpreter::new();
+        interp
+            .load_program("10 IF A > 0 AND B < 10 THEN 100\n20 X = NOT (A = 1 OR B = 2) AND (1 - (2 - 3)) * 4\n")
+            .unwrap();
+        let mut out = Capture::new();
+        interp.list(&mut out);
+        assert_eq!(
+            out.0,
+            "10 IF A > 0 AND B < 10 THEN 100\n20 LET X = NOT (A = 1 OR B = 2) AND (1 - (2 - 3)) * 4\n");
+    }
+
+    #[test]
+    fn test_inline_if() {
+        let (status, out) = run_source(
+            "10 X = 3\n20 IF X > 2 THEN PRINT \"big\" ELSE PRINT \"small\"\n\
+             30 IF X > 5 THEN PRINT \"huge\"\n40 IF X = 3 THEN X = 7\n\
+             50 IF X = 1 THEN 90 ELSE 70\n60 PRINT \"skipped\"\n70 PRINT X\n90 END\n");
+        assert_eq!(status, ExecutionStatus::Finished);
+        assert_eq!(out, "big\n7\n");
+    }
+
+    #[test]
+    fn test_inline_if_list_round_trip() {
+        let mut interp = Interpreter::new();
+        interp
+            .load_program("10 IF A THEN PRINT \"A\" ELSE 50\n20 IF B THEN GOTO 30\n")
+            .unwrap();
+        let mut out = Capture::new();
+        interp.list(&mut out);
+        assert_eq!(out.0, "10 IF A THEN PRINT \"A\" ELSE 50\n20 IF B THEN 30\n");
+    }
+
+    #[test]
+    fn test_firewall_list_round_trip() {
+        let mut interp = Interpreter::new();
+        interp
+            .load_program("10 fwallow tcp, 23\n20 FWDENY Udp, P + 1\n30 FWALLOW\n40 FWDENY\n")
+            .unwrap();
+        let mut out = Capture::new();
+        interp.list(&mut out);
+        assert_eq!(out.0, "10 FWALLOW TCP, 23\n20 FWDENY UDP, P + 1\n30 FWALLOW\n40 FWDENY\n");
+        assert!(interp.load_program("10 FWALLOW ICMP, 1\n").is_err());
+    }
+
+    #[test]
+    fn test_on_goto_gosub() {
+        let (status, out) = run_source(
+            "10 FOR I = 0 TO 3\n20 ON I GOSUB 100, 200\n30 NEXT I\n\
+             40 ON 2 GOTO 300, 60\n50 PRINT \"skipped\"\n60 END\n\
+             100 PRINT \"one\"\n110 RETURN\n200 PRINT \"two\"\n210 RETURN\n300 PRINT \"wrong\"\n");
+        assert_eq!(status, ExecutionStatus::Finished);
+        assert_eq!(out, "one\ntwo\n");
+
+        let (status, _) = run_source("10 ON \"A\" GOTO 10\n");
+        assert_eq!(status, ExecutionStatus::Error("ON requires numeric value".into()));
+    }
+
+    #[test]
+    fn test_rnd_is_reproducible_after_randomize() {
+        let src = "10 RANDOMIZE 42\n20 FOR I = 1 TO 20\n30 R = RND(6)\n\
+                   40 IF R < 0 OR R > 5 THEN PRINT \"out of range\"\n50 PRINT R; RND(0)\n60 NEXT I\n";
+        let (status, first) = run_source(src);
+        assert_eq!(status, ExecutionStatus::Finished);
+        let (_, second) = run_source(src);
+        assert_eq!(first, second);
+        for line in first.lines() {
+            let (r, again) = line.split_once(' ').unwrap();
+            assert_eq!(r, again);
+        }
+
+        let (_, out) = run_source("10 X = RND(1)\n20 PRINT X >= 0 AND X < 1\n");
+        assert_eq!(out, "1\n");
+    }
+
+    #[test]
+    fn test_source_reloads_identically() {
+        let mut interp = Interpreter::new();
+        interp
+            .load_program("10 FOR I = 1 TO 3\n20 PRINT \"I=\"; I * 2\n30 NEXT I\n40 IF I > 2 THEN 10 ELSE END\n")
+            .unwrap();
+        let source = interp.source();
+        let mut copy = Interpreter::new();
+        assert_eq!(copy.load_program(&source), Ok(4));
+        assert_eq!(copy.source(), source);
+    }
+
+    #[test]
+    fn test_wend_without_while() {
+        let (status, _) = run_source("10 WEND\n");
+        assert_eq!(status, ExecutionStatus::Error("WEND without WHILE".into()));
+    }
+
+    #[test]
+    fn test_error_line() {
+        let mut interp = Interpreter::new();
+        interp.load_program("10 X = 1\n20 GOSUB 40\n30 END\n40 PRINT 1 / 0\n").unwrap();
+        interp.run();
+        while interp.is_running() {
+            interp.step(&mut Capture::new());
+        }
+        assert_eq!(interp.status(), &ExecutionStatus::Error("Division by zero".into()));
+        assert_eq!(interp.error_line(), Some(40));
+
+        interp.run();
+        assert_eq!(interp.error_line(), None);
+    }
+
+    /// Evaluate an expression in a fresh interpreter
+    fn eval(src: &str) -> Result<Value, String> {
+        let mut interp = Interpreter::new();
+        let line = alloc::format!("R = {}", src);
+        let (_, stmt) = Parser::new(&line).parse_line().unwrap().unwrap();
+        match interp.execute_immediate(&mut Capture::new(), &stmt) {
+            ExecutionStatus::Error(e) => Err(e),
+            _ => Ok(interp.variables.get("R").cloned().unwrap()),
+        }
+    }
+
+    fn eval_string(src: &str) -> String {
+        eval(src).unwrap().as_string().unwrap()
+    }
+
+    #[test]
+    fn test_left_bounds() {
+        assert_eq!(eval_string(r#"LEFT$("HELLO", 2)"#), "HE");
+        assert_eq!(eval_string(r#"LEFT$("HELLO", 0)"#), "");
+        assert_eq!(eval_string(r#"LEFT$("HELLO", -1)"#), "");
+        assert_eq!(eval_string(r#"LEFT$("HELLO", 99)"#), "HELLO");
+    }
+
+    #[test]
+    fn test_right_bounds() {
+        assert_eq!(eval_string(r#"RIGHT$("hello", 2)"#), "lo");
+        assert_eq!(eval_string(r#"RIGHT$("hello", 0)"#), "");
+        assert_eq!(eval_string(r#"RIGHT$("hello", -1)"#), "");
+        assert_eq!(eval_string(r#"RIGHT$("hello", 99)"#), "hello");
+    }
+
+    #[test]
+    fn test_val_prefix() {
+        assert_eq!(eval(r#"VAL("  42abc")"#).unwrap().as_integer(), Some(42));
+        assert_eq!(eval(r#"VAL("-7")"#).unwrap().as_integer(), Some(-7));
+        assert_eq!(eval(r#"VAL("2.5kg")"#).unwrap().to_string(), "2.5");
+        assert_eq!(eval(r#"VAL("abc")"#).unwrap().as_integer(), Some(0));
+        assert_eq!(eval(r#"VAL("-.")"#).unwrap().type_name(), "INT");
+        assert_eq!(eval_string("STR$(-7)"), "-7");
+    }
+
+    #[test]
+    fn test_math_functions() {
+        assert_eq!(eval("ABS(-5)").unwrap().as_integer(), Some(5));
+        assert_eq!(eval("ABS(-2.5)").unwrap().to_string(), "2.5");
+        assert_eq!(eval("SGN(-0.5)").unwrap().as_integer(), Some(-1));
+        assert_eq!(eval("SGN(0)").unwrap().as_integer(), Some(0));
+        assert_eq!(eval("SGN(7)").unwrap().as_integer(), Some(1));
+        assert_eq!(eval("INT(2.7)").unwrap().as_integer(), Some(2));
+        assert_eq!(eval("INT(-2.5)").unwrap().as_integer(), Some(-3));
+        assert_eq!(eval("INT(-3)").unwrap().as_integer(), Some(-3));
+        assert_eq!(eval("MIN(3, -4)").unwrap().as_integer(), Some(-4));
+        assert_eq!(eval("MAX(3, 4.5)").unwrap().to_string(), "4.5");
+        assert!(eval(r#"MAX("A", 1)"#).is_err());
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let (status, out) = run_source(
+            "10 PRINT ERR\n20 F$ = READFILE$(\"nope.txt\")\n30 PRINT LEN(F$); ERR; TYPEOF$(F$)\n\
+             40 PRINT FILESIZE(\"nope.txt\") + ERR\n");
+        assert_eq!(status, ExecutionStatus::Finished);
+        assert_eq!(out, "0\n0 53 BYTES\n53\n");
+    }
+
+    #[test]
+    fn test_push_and_count() {
+        let (status, out) = run_source(
+            "10 PUSH L$, \"a\"\n20 PUSH L$, \"b\"\n30 PRINT COUNT(L$); L$(1)\n\
+             40 DIM N(2)\n50 PUSH N, 7\n60 PRINT COUNT(N); N(3)\n");
+        assert_eq!(status, ExecutionStatus::Finished);
+        assert_eq!(out, "2 b\n4 7\n");
+
+        let (status, _) = run_source("10 FOR I = 0 TO 4096\n20 PUSH A, I\n30 NEXT I\n");
+        assert_eq!(status, ExecutionStatus::Error("Array A full (4096 elements)".into()));
+    }
+
+    #[test]
+    fn test_split() {
+        let (status, out) = run_source(
+            "10 SPLIT P$, \"a=1&b=&c=3\", \"&\"\n20 PRINT COUNT(P$); P$(1); P$(2)\n\
+             30 SPLIT P$, \"\", \",\"\n40 PRINT COUNT(P$)\n");
+        assert_eq!(status, ExecutionStatus::Finished);
+        assert_eq!(out, "3 b= c=3\n0\n");
+
+        let (_, out) = run_source(
+            "10 SPLIT P$, \"x,y,z\", \",\"\n20 PRINT JOIN$(P$, \"; \")\n\
+             30 N = {1, -2, 3}\n40 PRINT JOIN$(N, \",\")\n");
+        assert_eq!(out, "x; y; z\n1,-2,3\n");
+    }
+
+    #[test]
+    fn test_timeout() {
+        let (status, out) = run_source("10 TIMEOUT 100, PRINT 1\n20 PRINT ERR\n");
+        assert_eq!(status, ExecutionStatus::Finished);
+        assert_eq!(out, "1\n0\n");
+
+        // INPUT reads the line itself under TIMEOUT (the capture is at EOF)
+        let (status, _) = run_source("10 TIMEOUT 100, INPUT A$\n");
+        assert_eq!(status, ExecutionStatus::Error("End of input".into()));
+    }
+
+    #[test]
+    fn test_debug_trace() {
+        let (_, out) = run_source(
+            "10 DEBUG ON\n20 X = 2\n30 IF X > 1 THEN GOTO 50\n40 PRINT \"no\"\n\
+             50 DEBUG OFF\n60 X = 3\n");
+        assert_eq!(out, "[20] X = 2\n[30] IF X > 1 is 1 -> THEN\n[30] GOTO 50\n");
+
+        let (_, out) = run_source("10 DEBUG ON\n20 ON 2 GOSUB 40, 50\n30 END\n40 RETURN\n50 RETURN\n");
+        assert_eq!(out, "[20] ON GOSUB 50\n");
+    }
+
+    #[test]
+    fn test_keepalive_idle_bounds() {
+        let e = ExecutionStatus::Error("KEEPALIVE idle time must be between 1 and 32767 seconds".into());
+        assert_eq!(run_source("10 KEEPALIVE 0, 1, -5\n").0, e);
+        assert_eq!(run_source("10 KEEPALIVE 0, 1, 99999999999\n").0, e);
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(content_type("index.html"), "text/html");
+        assert_eq!(content_type("LOGO.PNG"), "image/png");
+        assert_eq!(content_type("site.min.css"), "text/css");
+        assert_eq!(content_type("README"), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_bytes_behave_as_strings() {
+        let max = DEFAULT_MAX_STRING_LEN;
+        let bytes = Value::Bytes(vec![0xFF, b'A']);
+        let joined = eval_binary_op(&bytes, &BinaryOp::Add, &Value::String("B".into()), max).unwrap();
+        assert_eq!(joined.as_bytes(), Some(&b"\xFFAB"[..]));
+        let eq = eval_binary_op(&Value::String("A".into()), &BinaryOp::Eq, &Value::Bytes(vec![b'A']), max);
+        assert_eq!(eq.unwrap().as_integer(), Some(1));
+        assert_eq!(bytes.as_string().unwrap(), "\u{FFFD}A");
+    }
+
+    #[test]
+    fn test_checksums_hash_raw_bytes() {
+        let crc = eval(r#"CRC32(UNBASE64$("/wCA"))"#).unwrap();
+        assert_eq!(crc.as_integer(), Some(2892066527));
+        let sum = eval(r#"CHECKSUM(UNBASE64$("/wCA"))"#).unwrap();
+        assert_eq!(sum.as_integer(), Some(0x80FE));
+    }
+
+    #[test]
+    fn test_base64_round_trips_binary() {
+        let decoded = eval(r#"UNBASE64$("/wCA")"#).unwrap();
+        assert_eq!(decoded.as_bytes(), Some(&b"\xFF\x00\x80"[..]));
+        assert_eq!(eval_string(r#"BASE64$(UNBASE64$("/wCA"))"#), "/wCA");
+        assert_eq!(eval_string(r#"BASE64$("hi")"#), "aGk=");
+    }
+
+    #[test]
+    fn test_max_string_len() {
+        let mut interp = Interpreter::new();
+        interp.set_max_string_len(4);
+        interp.load_program("10 A$ = \"ab\"\n20 A$ = A$ + A$\n30 A$ = A$ + \"c\"\n").unwrap();
+        interp.run();
+        let mut out = Capture::new();
+        while interp.is_running() {
+            interp.step(&mut out);
+        }
+        assert_eq!(*interp.status(), ExecutionStatus::Error("String too long".into()));
+        assert_eq!(interp.error_line(), Some(30));
+    }
+
+    #[test]
+    fn test_mid_bounds() {
+        assert_eq!(eval_string(r#"MID$("HELLO", 2, 3)"#), "ELL");
+        assert_eq!(eval_string(r#"MID$("HELLO", 1, 99)"#), "HELLO");
+        assert_eq!(eval_string(r#"MID$("HELLO", 9, 2)"#), "");
+        assert_eq!(eval_string(r#"MID$("HELLO", 2, -1)"#), "");
+        assert!(eval(r#"MID$("HELLO", 0, 2)"#).is_err());
+        assert!(eval(r#"MID$("HELLO", -3, 2)"#).is_err());
+    }
+
+    #[test]
+    fn test_float_arithmetic() {
+        assert_eq!(eval("3 / 2").unwrap().to_string(), "1.5");
+        assert_eq!(eval("4 / 2").unwrap().type_name(), "INT");
+        assert_eq!(eval("1 + 0.5").unwrap().to_string(), "1.5");
+        assert_eq!(eval("2.5 * 2").unwrap().to_string(), "5");
+        assert_eq!(eval("2.5 * 2").unwrap().type_name(), "FLOAT");
+        assert_eq!(eval("-0.25").unwrap().to_string(), "-0.25");
+        assert_eq!(eval("1.5 > 1").unwrap().as_integer(), Some(1));
+        assert_eq!(eval("7 / 2").unwrap().as_integer(), Some(3));
+        assert!(eval("1.5 / 0").is_err());
+        let e = "Array literal elements must all be integers or all be strings";
+        assert_eq!(eval("{1.5, 2}").unwrap_err(), e);
+    }
+
+    #[test]
+    fn test_float_list_round_trip() {
+        let mut interp = Interpreter::new();
+        interp.load_program("10 X = 2.0 + 0.1\n").unwrap();
+        assert_eq!(interp.format_line(10).unwrap(), "10 LET X = 2.0 + 0.1");
+    }
+
+    #[test]
+    fn test_get_field_form() {
+        assert_eq!(get_field("item=milk&id=3", "id"), "3");
+        assert_eq!(get_field("item=milk&id=3", "item"), "milk");
+        assert_eq!(get_field("item=milk&id=3", "missing"), "");
+        assert_eq!(get_field("item=&id=3", "item"), "");
+        assert_eq!(get_field("item=milk&id=3&", "id"), "3");
+        assert_eq!(get_field("&&item=milk", "item"), "milk");
+    }
+
+    #[test]
+    fn test_get_field_json() {
+        let body = r#"{"item": "milk \"2%\"", "id": 3, "done": false}"#;
+        assert_eq!(get_field(body, "item"), "milk \"2%\"");
+        assert_eq!(get_field(body, "id"), "3");
+        assert_eq!(get_field(body, "done"), "false");
+        assert_eq!(get_field(body, "missing"), "");
+        assert_eq!(get_field(r#"{"item": ""}"#, "item"), "");
+        assert_eq!(get_field(r#"{"id": 3,}"#, "id"), "3");
+        assert_eq!(get_field("{}", "id"), "");
     }
 }
@@ -0,0 +1,115 @@
+//! Registry of background jobs started from BASIC
+//!
+//! Programs launched with SPAWN (and headless BASIC services) are recorded
+//! here under a name so the REPL can list them with JOBS, cap their memory
+//! with QUOTA and terminate them with STOP. Entries whose task has exited
+//! are dropped lazily.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::allocator::Spinlock;
+use crate::executable;
+use crate::scheduler;
+use crate::task::{TaskId, TaskState};
+
+struct Job {
+    number: u32,
+    task_id: TaskId,
+    name: String,
+}
+
+struct Registry {
+    jobs: Vec<Job>,
+    next_number: u32,
+}
+
+static JOBS: Spinlock<Registry> = Spinlock::new(Registry {
+    jobs: Vec::new(),
+    next_number: 1,
+});
+
+/// A background job as shown by JOBS
+pub struct JobInfo {
+    /// Job number (what STOP takes)
+    pub number: u32,
+    pub task_id: TaskId,
+    pub name: String,
+    pub state: TaskState,
+}
+
+/// Record `task_id` as a background job; returns its job number
+pub fn register(name: &str, task_id: TaskId) -> u32 {
+    let mut reg = JOBS.lock();
+    let number = reg.next_number;
+    reg.next_number += 1;
+    reg.jobs.push(Job {
+        number,
+        task_id,
+        name: String::from(name),
+    });
+    number
+}
+
+/// Record the calling task as a background job
+pub fn register_current(name: &str) -> Option<u32> {
+    scheduler::current_task_id().map(|id| register(name, id))
+}
+
+/// List the jobs whose task is still alive, forgetting the rest
+pub fn list() -> Vec<JobInfo> {
+    let tasks = scheduler::get_all_tasks();
+    let state_of = |id: TaskId| {
+        tasks
+            .iter()
+            .find(|t| t.id == id && t.state != TaskState::Finished)
+            .map(|t| t.state)
+    };
+
+    let mut reg = JOBS.lock();
+    reg.jobs.retain(|job| state_of(job.task_id).is_some());
+    reg.jobs
+        .iter()
+        .filter_map(|job| {
+            Some(JobInfo {
+                number: job.number,
+                task_id: job.task_id,
+                name: job.name.clone(),
+                state: state_of(job.task_id)?,
+            })
+        })
+        .collect()
+}
+
+/// Find a live job given its number or name
+fn find(spec: &str) -> Result<JobInfo, String> {
+    let number = spec.parse::<u32>().ok();
+    list()
+        .into_iter()
+        .find(|job| Some(job.number) == number || job.name.eq_ignore_ascii_case(spec))
+        .ok_or_else(|| alloc::format!("No such job: {}", spec))
+}
+
+/// Terminate a job given its number or name
+///
+/// Returns the name of the stopped job, or an error if no live job matches.
+pub fn stop(spec: &str) -> Result<String, String> {
+    let job = find(spec)?;
+
+    if !scheduler::kill(job.task_id) {
+        return Err(alloc::format!("Can't stop job {}", job.number));
+    }
+    JOBS.lock().jobs.retain(|j| j.number != job.number);
+    Ok(job.name)
+}
+
+/// Set the program-region memory quota of a job given its number or name
+///
+/// `bytes` of None removes the limit. Returns the job's name, or an error
+/// if no live job matches or the job has no program memory to limit.
+pub fn set_quota(spec: &str, bytes: Option<usize>) -> Result<String, String> {
+    let job = find(spec)?;
+    if !executable::set_task_quota(job.task_id, bytes) {
+        return Err(alloc::format!("Job {} has no program memory", job.number));
+    }
+    Ok(job.name)
+}
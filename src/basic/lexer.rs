@@ -10,35 +10,97 @@ pub enum Token {
     Let,
     If,
     Then,
+    Else,
     Goto,
     For,
     To,
     Step,
     Next,
+    While,
+    Wend,
     Sleep,
+    Sleepus,
+    Tabwidth,
     Rem,
     End,
+    Stop,
+    Assert,
+    Abort,
+    Cont,
+    Break,
+    Unbreak,
     Run,
     List,
     New,
     Load,
+    Save,
     Mem,
     Spawn,
+    Restricted,
     Memstats,
+    Sysinfo,
+    Jobs,
+    Quota,
+    Sockets,
+    Slice,
+    Strmax,
+    Memwatch,
+    Paste,
+    Screenshot,
     // String functions
     Chr,
+    Rnd,
+    Abs,
+    Sgn,
+    Int,
+    Min,
+    Max,
+    Randomize,
     Asc,
     Len,
     Mid,
     Left,
+    Right,
     Instr,
+    StartsWith,
+    EndsWith,
+    Contains,
     Str,
     Val,
+    Urldecode,
+    Urlencode,
+    Getfield,
+    Crc32,
+    Base64,
+    Unbase64,
+    Checksum,
+    // Graphics
+    Pset,
+    Waitvbl,
+    Palette,
+    Fbaddr,
+    Fbwidth,
+    Fbheight,
+    // Terminal
+    Pos,
+    Csrlin,
+    // Time
+    Uptime,
+    Ticks,
+    // Introspection
+    Typeof,
+    Isdef,
     // Control flow
     Gosub,
+    On,
+    Debug,
     Return,
     // Arrays
     Dim,
+    Push,
+    Count,
+    Split,
+    Join,
     // Network
     Socket,
     NetListen,
@@ -47,6 +109,38 @@ pub enum Token {
     Send,
     Close,
     Sockstate,
+    Avail,
+    Peek,
+    Recvline,
+    Httpheader,
+    Httpsend,
+    Servefile,
+    Udpsocket,
+    Sendto,
+    Recvfrom,
+    Udpclose,
+    Keepalive,
+    Timeout,
+    Fwallow,
+    Fwdeny,
+    Netlog,
+    Netdump,
+    Resolve,
+    Nslookup,
+    Ipconfig,
+    Netstat,
+    // Channels
+    Open,
+    As,
+    Console,
+    Input,
+    // Environment
+    Setenv,
+    Environ,
+    // Files
+    Readfile,
+    Filesize,
+    Err,
 
     // Operators
     Plus,
@@ -59,13 +153,21 @@ pub enum Token {
     Gt,
     Le,
     Ge,
+    And,
+    Or,
+    Not,
     LParen,
     RParen,
+    LBrace,
+    RBrace,
     Semicolon,
     Comma,
+    Hash,
+    Pipe,
 
     // Literals and identifiers
     Integer(i64),
+    Float(f64),
     StringLit(String),
     Identifier(String),
 
@@ -91,6 +193,11 @@ impl<'a> Lexer<'a> {
         self.input[self.pos..].chars().next()
     }
 
+    /// Look at the character after the current one
+    fn peek_next(&self) -> Option<char> {
+        self.input[self.pos..].chars().nth(1)
+    }
+
     /// Consume and return the current character
     fn advance(&mut self) -> Option<char> {
         let ch = self.peek()?;
@@ -127,6 +234,11 @@ impl<'a> Lexer<'a> {
                 Token::Newline
             }
             Some('"') => self.read_string(),
+            Some('\'') => {
+                // Apostrophe comment: behaves like REM, running to end of line
+                self.skip_to_eol();
+                Token::Rem
+            }
             Some(ch) if ch.is_ascii_digit() => self.read_number(),
             Some(ch) if ch.is_ascii_alphabetic() => self.read_identifier_or_keyword(),
             Some('+') => {
@@ -153,6 +265,14 @@ impl<'a> Lexer<'a> {
                 self.advance();
                 Token::RParen
             }
+            Some('{') => {
+                self.advance();
+                Token::LBrace
+            }
+            Some('}') => {
+                self.advance();
+                Token::RBrace
+            }
             Some(';') => {
                 self.advance();
                 Token::Semicolon
@@ -161,6 +281,14 @@ impl<'a> Lexer<'a> {
                 self.advance();
                 Token::Comma
             }
+            Some('#') => {
+                self.advance();
+                Token::Hash
+            }
+            Some('|') => {
+                self.advance();
+                Token::Pipe
+            }
             Some('<') => {
                 self.advance();
                 if self.peek() == Some('>') {
@@ -178,13 +306,26 @@ impl<'a> Lexer<'a> {
                 if self.peek() == Some('=') {
                     self.advance();
                     Token::Ge
+                } else if self.peek() == Some('<') {
+                    // "><" is accepted as an alias for "<>"
+                    self.advance();
+                    Token::Ne
                 } else {
                     Token::Gt
                 }
             }
             Some('=') => {
                 self.advance();
-                Token::Eq
+                // "=<" and "=>" are accepted as aliases for "<=" and ">="
+                if self.peek() == Some('<') {
+                    self.advance();
+                    Token::Le
+                } else if self.peek() == Some('>') {
+                    self.advance();
+                    Token::Ge
+                } else {
+                    Token::Eq
+                }
             }
             _ => {
                 // Skip unknown character
@@ -226,6 +367,22 @@ impl<'a> Lexer<'a> {
             }
         }
 
+        // A '.' followed by a digit makes it a decimal literal (3.14)
+        if self.peek() == Some('.') && self.peek_next().is_some_and(|c| c.is_ascii_digit()) {
+            s.push('.');
+            self.advance();
+            while let Some(ch) = self.peek() {
+                if ch.is_ascii_digit() {
+                    s.push(ch);
+                    self.advance();
+                } else {
+                    break;
+                }
+            }
+            let x: f64 = s.parse().unwrap_or(0.0);
+            return Token::Float(if negative { -x } else { x });
+        }
+
         let n: i64 = s.parse().unwrap_or(0);
         Token::Integer(if negative { -n } else { n })
     }
@@ -242,42 +399,118 @@ impl<'a> Lexer<'a> {
             }
         }
 
-        // Check for keywords (case-insensitive)
-        match s.to_ascii_uppercase().as_str() {
+        // Check for keywords (case-insensitive). The identifier is uppercased
+        // into a stack buffer so classification doesn't allocate; anything
+        // longer than the buffer can't be a keyword.
+        let mut upper = [0u8; 16];
+        let keyword = if s.len() <= upper.len() {
+            upper[..s.len()].copy_from_slice(s.as_bytes());
+            upper[..s.len()].make_ascii_uppercase();
+            core::str::from_utf8(&upper[..s.len()]).unwrap_or("")
+        } else {
+            ""
+        };
+
+        match keyword {
             "PRINT" => Token::Print,
             "LET" => Token::Let,
             "IF" => Token::If,
             "THEN" => Token::Then,
+            "ELSE" => Token::Else,
             "GOTO" => Token::Goto,
             "FOR" => Token::For,
             "TO" => Token::To,
             "STEP" => Token::Step,
             "NEXT" => Token::Next,
+            "WHILE" => Token::While,
+            "WEND" => Token::Wend,
             "SLEEP" => Token::Sleep,
+            "SLEEPUS" => Token::Sleepus,
+            "TABWIDTH" => Token::Tabwidth,
             "REM" => Token::Rem,
             "END" => Token::End,
+            "STOP" => Token::Stop,
+            "ASSERT" => Token::Assert,
+            "ABORT" => Token::Abort,
+            "CONT" => Token::Cont,
+            "BREAK" => Token::Break,
+            "UNBREAK" => Token::Unbreak,
             "RUN" => Token::Run,
             "LIST" => Token::List,
             "NEW" => Token::New,
+            "AND" => Token::And,
+            "OR" => Token::Or,
+            "NOT" => Token::Not,
             "LOAD" => Token::Load,
+            "SAVE" => Token::Save,
             "MEM" => Token::Mem,
             "SPAWN" => Token::Spawn,
+            "RESTRICTED" => Token::Restricted,
             "MEMSTATS" => Token::Memstats,
+            "SYSINFO" => Token::Sysinfo,
+            "JOBS" => Token::Jobs,
+            "QUOTA" => Token::Quota,
+            "SOCKETS" => Token::Sockets,
+            "SLICE" => Token::Slice,
+            "STRMAX" => Token::Strmax,
+            "MEMWATCH" => Token::Memwatch,
+            "PASTE" => Token::Paste,
+            "SCREENSHOT" => Token::Screenshot,
             // String functions (with and without $ suffix)
             "CHR" | "CHR$" => Token::Chr,
+            "RND" => Token::Rnd,
+            "ABS" => Token::Abs,
+            "SGN" => Token::Sgn,
+            "INT" => Token::Int,
+            "MIN" => Token::Min,
+            "MAX" => Token::Max,
+            "RANDOMIZE" => Token::Randomize,
             "ASC" => Token::Asc,
             "LEN" => Token::Len,
             "MID" | "MID$" => Token::Mid,
             "LEFT" | "LEFT$" => Token::Left,
+            "RIGHT" | "RIGHT$" => Token::Right,
             "INSTR" => Token::Instr,
+            "STARTSWITH" => Token::StartsWith,
+            "ENDSWITH" => Token::EndsWith,
+            "CONTAINS" => Token::Contains,
             "STR" | "STR$" => Token::Str,
             "VAL" => Token::Val,
+            "URLDECODE" | "URLDECODE$" => Token::Urldecode,
+            "URLENCODE" | "URLENCODE$" => Token::Urlencode,
+            "GETFIELD" | "GETFIELD$" => Token::Getfield,
+            "CRC32" => Token::Crc32,
+            "BASE64" | "BASE64$" => Token::Base64,
+            "UNBASE64" | "UNBASE64$" => Token::Unbase64,
+            "CHECKSUM" => Token::Checksum,
+            // Graphics
+            "PSET" => Token::Pset,
+            "WAITVBL" => Token::Waitvbl,
+            "PALETTE" => Token::Palette,
+            "FBADDR" => Token::Fbaddr,
+            "FBWIDTH" => Token::Fbwidth,
+            "FBHEIGHT" => Token::Fbheight,
+            // Terminal
+            "POS" => Token::Pos,
+            "CSRLIN" => Token::Csrlin,
+            // Time
+            "UPTIME" => Token::Uptime,
+            "TICKS" => Token::Ticks,
+            // Introspection
+            "TYPEOF" | "TYPEOF$" => Token::Typeof,
+            "ISDEF" => Token::Isdef,
             "RECV" | "RECV$" => Token::Recv,
             // Control flow
             "GOSUB" => Token::Gosub,
+            "ON" => Token::On,
+            "DEBUG" => Token::Debug,
             "RETURN" => Token::Return,
             // Arrays
             "DIM" => Token::Dim,
+            "PUSH" => Token::Push,
+            "COUNT" => Token::Count,
+            "SPLIT" | "SPLIT$" => Token::Split,
+            "JOIN" | "JOIN$" => Token::Join,
             // Network
             "SOCKET" => Token::Socket,
             "LISTEN" => Token::NetListen,
@@ -285,11 +518,44 @@ impl<'a> Lexer<'a> {
             "SEND" => Token::Send,
             "CLOSE" => Token::Close,
             "SOCKSTATE" => Token::Sockstate,
-            _ => Token::Identifier(s.to_ascii_uppercase()),
+            "AVAIL" => Token::Avail,
+            "PEEK$" => Token::Peek,
+            "RECVLINE" | "RECVLINE$" => Token::Recvline,
+            "HTTPHEADER" | "HTTPHEADER$" => Token::Httpheader,
+            "HTTPSEND" => Token::Httpsend,
+            "SERVEFILE" => Token::Servefile,
+            "UDPSOCKET" => Token::Udpsocket,
+            "SENDTO" => Token::Sendto,
+            "RECVFROM" | "RECVFROM$" => Token::Recvfrom,
+            "UDPCLOSE" => Token::Udpclose,
+            "KEEPALIVE" => Token::Keepalive,
+            "TIMEOUT" => Token::Timeout,
+            "FWALLOW" => Token::Fwallow,
+            "FWDENY" => Token::Fwdeny,
+            "NETLOG" => Token::Netlog,
+            "NETDUMP" => Token::Netdump,
+            "RESOLVE" | "RESOLVE$" => Token::Resolve,
+            "NSLOOKUP" => Token::Nslookup,
+            "IPCONFIG" | "IPCONFIG$" => Token::Ipconfig,
+            "NETSTAT" => Token::Netstat,
+            // Channels
+            "OPEN" => Token::Open,
+            "AS" => Token::As,
+            "CONSOLE" => Token::Console,
+            "INPUT" => Token::Input,
+            // Environment
+            "SETENV" => Token::Setenv,
+            "ENVIRON" | "ENVIRON$" => Token::Environ,
+            // Files
+            "READFILE" | "READFILE$" => Token::Readfile,
+            "FILESIZE" => Token::Filesize,
+            "ERR" => Token::Err,
+            // Variable names keep their case (A and a are different variables)
+            _ => Token::Identifier(s),
         }
     }
 
-    /// Skip rest of line (for REM comments)
+    /// Skip rest of line (for REM and ' comments)
     pub fn skip_to_eol(&mut self) {
         while let Some(ch) = self.peek() {
             if ch == '\n' || ch == '\r' {
@@ -299,3 +565,63 @@ impl<'a> Lexer<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(input: &str) -> alloc::vec::Vec<Token> {
+        let mut lexer = Lexer::new(input);
+        let mut out = alloc::vec::Vec::new();
+        loop {
+            let tok = lexer.next_token();
+            if tok == Token::Eof {
+                return out;
+            }
+            out.push(tok);
+        }
+    }
+
+    #[test]
+    fn test_comparison_aliases() {
+        assert_eq!(tokens("A >< B")[1], Token::Ne);
+        assert_eq!(tokens("A =< B")[1], Token::Le);
+        assert_eq!(tokens("A => B")[1], Token::Ge);
+    }
+
+    #[test]
+    fn test_keywords_case_insensitive() {
+        assert_eq!(tokens("print PRINT Print")[..3], [Token::Print, Token::Print, Token::Print]);
+        assert_eq!(tokens("mid$ Left$")[..2], [Token::Mid, Token::Left]);
+    }
+
+    #[test]
+    fn test_variables_keep_case() {
+        assert_eq!(tokens("score Score")[..2], [
+            Token::Identifier("score".into()),
+            Token::Identifier("Score".into()),
+        ]);
+    }
+
+    #[test]
+    fn test_peek_needs_dollar() {
+        assert_eq!(tokens("PEEK$")[0], Token::Peek);
+        assert_eq!(tokens("PEEK")[0], Token::Identifier("PEEK".into()));
+    }
+
+    #[test]
+    fn test_decimal_literals() {
+        assert_eq!(tokens("2.75")[0], Token::Float(2.75));
+        assert_eq!(tokens("10")[0], Token::Integer(10));
+        // A '.' not followed by a digit is not part of the number
+        assert_eq!(tokens("3.")[0], Token::Integer(3));
+    }
+
+    #[test]
+    fn test_canonical_comparisons() {
+        assert_eq!(tokens("A <> B")[1], Token::Ne);
+        assert_eq!(tokens("A <= B")[1], Token::Le);
+        assert_eq!(tokens("A >= B")[1], Token::Ge);
+        assert_eq!(tokens("A = B")[1], Token::Eq);
+    }
+}
@@ -0,0 +1,76 @@
+//! MEMWATCH: background alert when the kernel heap runs low
+//!
+//! A lightweight task samples `allocator::get_heap_stats()` once a second
+//! and prints a warning to serial when free memory first drops below the
+//! limit (and again each time it recovers and drops again). Optionally the
+//! full MEMSTATS report is printed with the warning, to show which task is
+//! holding the memory.
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use crate::{allocator, scheduler};
+
+/// How often the heap is sampled
+const CHECK_INTERVAL_MS: u64 = 1000;
+
+/// Free-byte threshold; 0 means MEMWATCH is off
+static LIMIT: AtomicUsize = AtomicUsize::new(0);
+/// Print the MEMSTATS report along with each alert
+static REPORT: AtomicBool = AtomicBool::new(false);
+/// Whether the watcher task is alive
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Start watching (or change the limit of the running watcher)
+///
+/// Returns false if the watcher task could not be spawned.
+pub fn start(limit: usize, report: bool) -> bool {
+    LIMIT.store(limit.max(1), Ordering::Relaxed);
+    REPORT.store(report, Ordering::Relaxed);
+    if RUNNING.swap(true, Ordering::Relaxed) {
+        return true;
+    }
+    if scheduler::spawn("memwatch", watch_task).is_none() {
+        RUNNING.store(false, Ordering::Relaxed);
+        LIMIT.store(0, Ordering::Relaxed);
+        return false;
+    }
+    true
+}
+
+/// Stop watching; the task exits at its next check
+pub fn stop() {
+    LIMIT.store(0, Ordering::Relaxed);
+}
+
+/// Current limit and report setting, if watching
+pub fn status() -> Option<(usize, bool)> {
+    match LIMIT.load(Ordering::Relaxed) {
+        0 => None,
+        limit => Some((limit, REPORT.load(Ordering::Relaxed))),
+    }
+}
+
+fn watch_task() {
+    let mut alerted = false;
+    loop {
+        scheduler::sleep_ms(CHECK_INTERVAL_MS);
+
+        let limit = LIMIT.load(Ordering::Relaxed);
+        if limit == 0 {
+            RUNNING.store(false, Ordering::Relaxed);
+            return;
+        }
+
+        let (used, free) = allocator::get_heap_stats();
+        if free >= limit {
+            alerted = false;
+        } else if !alerted {
+            alerted = true;
+            crate::println!(
+                "MEMWATCH: heap free {} bytes is below {} ({} used)",
+                free, limit, used);
+            if REPORT.load(Ordering::Relaxed) {
+                super::print_memstats(&mut super::terminal::SerialTerminal::new());
+            }
+        }
+    }
+}
@@ -8,6 +8,8 @@ pub mod lexer;
 pub mod parser;
 pub mod interpreter;
 pub mod terminal;
+pub mod jobs;
+pub mod memwatch;
 
 #[allow(unused_imports)]
 pub use value::Value;
@@ -22,6 +24,97 @@ use crate::meminfo;
 use core::fmt::Write;
 use terminal::{ReadStatus, Terminal};
 
+fn task_state_name(state: crate::task::TaskState) -> &'static str {
+    match state {
+        crate::task::TaskState::Ready => "ready",
+        crate::task::TaskState::Running => "running",
+        crate::task::TaskState::Sleeping => "sleeping",
+        crate::task::TaskState::Finished => "finished",
+    }
+}
+
+/// Print a one-screen summary of the whole system
+fn print_sysinfo(out: &mut dyn core::fmt::Write) {
+    use crate::net;
+
+    let _ = writeln!(out, "=== SYSTEM ===");
+    let uptime = crate::timer::uptime_ms() / 1000;
+    let _ = writeln!(
+        out,
+        "Uptime: {}:{:02}:{:02}   Kernel API: v{}",
+        uptime / 3600, uptime / 60 % 60, uptime % 60, crate::api::API_VERSION);
+
+    let (heap_used, heap_free) = crate::allocator::get_heap_stats();
+    let (prog_used, prog_free) = crate::program_alloc::stats();
+    let _ = writeln!(out, "Heap:    {} KB used, {} KB free", heap_used / 1024, heap_free / 1024);
+    let _ = writeln!(out, "Program: {} KB used, {} KB free", prog_used / 1024, prog_free / 1024);
+
+    let tasks = scheduler::get_all_tasks();
+    let count = |state| tasks.iter().filter(|t| t.state == state).count();
+    let _ = writeln!(
+        out,
+        "Tasks:   {} ({} ready, {} sleeping)",
+        tasks.len(),
+        count(crate::task::TaskState::Ready) + count(crate::task::TaskState::Running),
+        count(crate::task::TaskState::Sleeping));
+
+    if !net::ne2000::is_initialized() {
+        let _ = writeln!(out, "Network: no NIC");
+        return;
+    }
+    let ip = net::config().ip;
+    let mac = net::ne2000::mac_address();
+    let _ = writeln!(
+        out,
+        "Network: {}.{}.{}.{}  MAC {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+        ip[0], ip[1], ip[2], ip[3], mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]);
+    let (connections, established) = net::tcp::connection_counts();
+    let (segments, acks) = net::tcp::ack_stats();
+    let _ = writeln!(
+        out,
+        "TCP:     {} connections, {} established, {} ACKs for {} segments",
+        connections, established, acks, segments);
+    let (rx, tx, dropped) = net::packet::stats();
+    let _ = writeln!(
+        out,
+        "Packets: {} rx, {} tx, {} dropped, {} ring overflows",
+        rx, tx, dropped, net::ne2000::overflow_recoveries());
+}
+
+/// Print the TCP listeners and connection table
+fn print_sockets(out: &mut dyn core::fmt::Write) {
+    let table = crate::net::tcp::connection_table_snapshot();
+    if table.is_empty() {
+        let _ = writeln!(out, "No sockets");
+        return;
+    }
+
+    let addr = |ip: [u8; 4], port: u16| {
+        alloc::format!("{}.{}.{}.{}:{}", ip[0], ip[1], ip[2], ip[3], port)
+    };
+    let _ = writeln!(
+        out,
+        "{:<21} {:<21} {:<11} {:>5} {:>5} {:>6} {:>5}",
+        "Local", "Remote", "State", "Recv", "Send", "Cwnd", "RTO");
+    for c in table {
+        let remote = if c.remote_port == 0 {
+            String::from("*")
+        } else {
+            addr(c.remote_ip, c.remote_port)
+        };
+        let _ = writeln!(
+            out,
+            "{:<21} {:<21} {:<11} {:>5} {:>5} {:>6} {:>5}",
+            addr(c.local_ip, c.local_port),
+            remote,
+            alloc::format!("{:?}", c.state),
+            c.rx_available,
+            c.tx_pending,
+            c.cwnd,
+            c.rto);
+    }
+}
+
 /// Print detailed memory statistics using the unified meminfo API
 fn print_memstats(out: &mut dyn core::fmt::Write) {
     let _ = writeln!(out, "=== MEMORY MAP ===");
@@ -39,6 +132,10 @@ fn print_memstats(out: &mut dyn core::fmt::Write) {
         let _ = writeln!(out);
     }
 
+    let (largest, blocks, smallest) = crate::allocator::get_fragmentation_stats();
+    let _ = writeln!(out, "Heap free blocks: {} (largest {} bytes, smallest {} bytes)", blocks, largest, smallest);
+    let _ = writeln!(out);
+
     // Per-task breakdown
     let tasks = meminfo::get_task_memory_info();
 
@@ -47,12 +144,7 @@ fn print_memstats(out: &mut dyn core::fmt::Write) {
     } else {
         let _ = writeln!(out, "TASKS ({}):", tasks.len());
         for task in &tasks {
-            let state_str = match task.state {
-                crate::task::TaskState::Ready => "ready",
-                crate::task::TaskState::Running => "running",
-                crate::task::TaskState::Sleeping => "sleeping",
-                crate::task::TaskState::Finished => "finished",
-            };
+            let state_str = task_state_name(task.state);
             let _ = writeln!(out);
             let _ = writeln!(out, "  [{}] {} ({})", task.id, task.name, state_str);
 
@@ -106,92 +198,149 @@ fn print_memstats(out: &mut dyn core::fmt::Write) {
     let _ = writeln!(out);
 }
 
+/// First delay before restarting a headless program after an error
+const RESTART_BACKOFF_MIN_MS: u64 = 1000;
+
+/// Longest delay between restarts; a program that ran at least this long
+/// before failing starts over at the minimum delay
+const RESTART_BACKOFF_MAX_MS: u64 = 60_000;
+
 /// Run a BASIC program headlessly (for background tasks)
-pub fn run_headless(source: &str) {
-    let mut term = terminal::SerialTerminal;
+///
+/// With `restart_on_error`, a runtime error is logged and the program is
+/// restarted from the top after a delay that doubles with each quick
+/// successive failure, so long-running services survive transient errors
+/// without spinning in a crash loop.
+pub fn run_headless(source: &str, restart_on_error: bool) {
+    let mut term = terminal::SerialTerminal::new();
     let mut interp = Interpreter::new();
     if let Err(e) = interp.load_program(source) {
         let _ = writeln!(&mut term, "BASIC load error: {}", e);
         return;
     }
+
+    let mut backoff_ms = RESTART_BACKOFF_MIN_MS;
+    loop {
+        let started_ms = crate::timer::uptime_ms();
+        run_to_completion(&mut interp, &mut term);
+
+        let ExecutionStatus::Error(ref e) = *interp.status() else {
+            return;
+        };
+        match interp.error_line() {
+            Some(n) => {
+                let _ = writeln!(&mut term, "BASIC Error on line {}: {}", n, e);
+            }
+            None => {
+                let _ = writeln!(&mut term, "BASIC Error: {}", e);
+            }
+        }
+        if !restart_on_error {
+            return;
+        }
+
+        if crate::timer::uptime_ms() - started_ms >= RESTART_BACKOFF_MAX_MS {
+            backoff_ms = RESTART_BACKOFF_MIN_MS;
+        }
+        let _ = writeln!(&mut term, "Restarting in {} ms", backoff_ms);
+        scheduler::sleep_ms(backoff_ms);
+        backoff_ms = (backoff_ms * 2).min(RESTART_BACKOFF_MAX_MS);
+    }
+}
+
+/// Run a headless program from the top until it finishes, stops or fails
+fn run_to_completion(interp: &mut Interpreter, term: &mut dyn Terminal) {
     interp.run();
 
     while interp.is_running() {
-        let status = interp.step(&mut term);
+        let status = interp.step(term);
         match status {
             ExecutionStatus::Sleeping(ms) => {
                 scheduler::sleep_ms(ms);
             }
             ExecutionStatus::Ready => {
-                scheduler::yield_now();
+                if interp.yield_due() {
+                    scheduler::yield_now();
+                }
             }
             ExecutionStatus::Finished | ExecutionStatus::Error(_) => {
                 break;
             }
+            ExecutionStatus::Stopped => {
+                // Nobody can CONT a headless program
+                break;
+            }
+            ExecutionStatus::WaitingForInput if interp.waiting_on_channel() => {
+                if !interp.read_input(term) {
+                    break;
+                }
+            }
             ExecutionStatus::WaitingForInput => {
-                // Headless mode can't handle input
+                // Headless mode can't handle console input
                 break;
             }
         }
     }
-
-    if let ExecutionStatus::Error(ref e) = *interp.status() {
-        let _ = writeln!(&mut term, "BASIC Error: {}", e);
-    }
 }
 
-/// Read a line from a terminal (with echo and editing).
-///
-/// Returns None on EOF (e.g., telnet disconnect).
-fn read_line(term: &mut dyn Terminal) -> Option<String> {
-    let mut line = String::new();
+/// Startup programs looked up in the exec table, in order of preference
+const AUTOEXEC_FILES: [&str; 2] = ["autoexec.bas", "startup.bas"];
 
-    loop {
-        let byte = match term.poll_byte() {
-            ReadStatus::Byte(b) => b,
-            ReadStatus::NoData => {
-                scheduler::yield_now();
-                continue;
-            }
-            ReadStatus::Eof => return None,
-        };
+/// Load and run the first startup program found, if any
+///
+/// The program runs in the REPL's interpreter, so once it ends (or is
+/// stopped) it can be listed, edited and re-run like any other.
+fn run_autoexec(interp: &mut Interpreter, term: &mut dyn Terminal) {
+    let Some((name, bytes)) = AUTOEXEC_FILES
+        .iter()
+        .find_map(|&name| crate::executable::read(name).ok().map(|b| (name, b)))
+    else {
+        return;
+    };
+    let Ok(src) = core::str::from_utf8(&bytes) else {
+        let _ = writeln!(term, "{}: not valid UTF-8", name);
+        return;
+    };
 
-        match byte {
-            b'\r' | b'\n' => {
-                let _ = writeln!(term); // Echo newline
-                break;
-            }
-            8 | 127 => {
-                // Backspace or DEL
-                if !line.is_empty() {
-                    line.pop();
-                    let _ = write!(term, "\x08 \x08"); // Erase character
-                }
-            }
-            b if b >= 32 && b < 127 => {
-                // Printable ASCII
-                line.push(b as char);
-                let _ = write!(term, "{}", b as char); // Echo
-            }
-            _ => {}
+    match interp.load_program(src) {
+        Ok(0) => {}
+        Ok(_) => {
+            let _ = writeln!(term, "Running {}", name);
+            interp.run();
+            run_program(interp, term);
+        }
+        Err(e) => {
+            let _ = writeln!(term, "{}: {}", name, e);
+            interp.clear();
         }
     }
-
-    Some(line)
 }
 
 /// Run the interactive BASIC REPL
-pub fn run_repl_on_terminal(term: &mut dyn Terminal) {
+///
+/// With `autoexec`, a startup program from the exec table (see
+/// `AUTOEXEC_FILES`) is run before the first prompt.
+pub fn run_repl_on_terminal(term: &mut dyn Terminal, autoexec: bool) {
     let _ = writeln!(term, "Ralph BASIC v1.0");
     let _ = writeln!(term, "Type RUN to execute, LIST to show program, NEW to clear");
-    let _ = writeln!(term, "Type LOAD \"name\" to load name.bas");
+    let _ = writeln!(term, "Type LOAD \"name\" to load name.bas, SAVE \"name\" to save it");
     let _ = writeln!(term);
 
     let mut interp = Interpreter::new();
+    if autoexec {
+        run_autoexec(&mut interp, term);
+    }
 
     loop {
-        let _ = write!(term, "> ");
-        let Some(line) = read_line(term) else {
+        match interp.current_line() {
+            Some(n) if interp.is_stopped() => {
+                let _ = write!(term, "[{}]> ", n);
+            }
+            _ => {
+                let _ = write!(term, "> ");
+            }
+        }
+        let Some(line) = terminal::read_line(term, true) else {
             return;
         };
         let line = line.trim();
@@ -207,20 +356,65 @@ pub fn run_repl_on_terminal(term: &mut dyn Terminal) {
         match parser.current_token() {
             Token::Run => {
                 interp.run();
-                while interp.is_running() {
-                    let status = interp.step(term);
-                    match status {
-                        ExecutionStatus::Sleeping(ms) => {
-                            scheduler::sleep_ms(ms);
+                run_program(&mut interp, term);
+                continue;
+            }
+            Token::Cont => {
+                if interp.cont() {
+                    run_program(&mut interp, term);
+                } else {
+                    let _ = writeln!(term, "Can't continue");
+                }
+                continue;
+            }
+            Token::Step => {
+                if let Some(line) = interp.current_line().and_then(|n| interp.format_line(n)) {
+                    let _ = writeln!(term, "{}", line);
+                }
+                match interp.single_step(term) {
+                    ExecutionStatus::Sleeping(ms) => scheduler::sleep_ms(ms),
+                    ExecutionStatus::WaitingForInput => {
+                        if !interp.read_input(term) && interp.is_running() {
+                            return;
                         }
-                        ExecutionStatus::Ready => {
-                            scheduler::yield_now();
+                    }
+                    ExecutionStatus::Finished => {
+                        let _ = writeln!(term, "Program finished");
+                    }
+                    _ => {}
+                }
+                report_stop(&interp, term);
+                continue;
+            }
+            Token::Break => {
+                match parse_line_arg(line) {
+                    Ok(Some(n)) => interp.add_breakpoint(n),
+                    Ok(None) => {
+                        let _ = write!(term, "Breakpoints:");
+                        for n in interp.breakpoints() {
+                            let _ = write!(term, " {}", n);
                         }
-                        _ => break,
+                        let _ = writeln!(term);
+                    }
+                    Err(e) => {
+                        let _ = writeln!(term, "Error: {}", e);
                     }
                 }
-                if let ExecutionStatus::Error(ref e) = *interp.status() {
-                    let _ = writeln!(term, "Error: {}", e);
+                continue;
+            }
+            Token::Unbreak => {
+                match parse_line_arg(line) {
+                    Ok(Some(n)) => {
+                        if !interp.remove_breakpoint(n) {
+                            let _ = writeln!(term, "No breakpoint on line {}", n);
+                        }
+                    }
+                    Ok(None) => {
+                        let _ = writeln!(term, "Usage: UNBREAK <line>");
+                    }
+                    Err(e) => {
+                        let _ = writeln!(term, "Error: {}", e);
+                    }
                 }
                 continue;
             }
@@ -244,10 +438,131 @@ pub fn run_repl_on_terminal(term: &mut dyn Terminal) {
                 }
                 continue;
             }
+            Token::Save => {
+                match save_bas_program(&interp, line) {
+                    Ok(filename) => {
+                        let _ = writeln!(term, "Saved {} (until reboot)", filename);
+                    }
+                    Err(e) => {
+                        let _ = writeln!(term, "Error: {}", e);
+                    }
+                }
+                continue;
+            }
             Token::Memstats => {
                 print_memstats(term);
                 continue;
             }
+            Token::Sysinfo => {
+                print_sysinfo(term);
+                continue;
+            }
+            Token::Sockets => {
+                print_sockets(term);
+                continue;
+            }
+            Token::Jobs => {
+                let jobs = jobs::list();
+                if jobs.is_empty() {
+                    let _ = writeln!(term, "No jobs");
+                }
+                for job in jobs {
+                    let _ = writeln!(
+                        term,
+                        "[{}] {} (task {}, {})",
+                        job.number, job.name, job.task_id, task_state_name(job.state));
+                }
+                continue;
+            }
+            Token::Stop if line.split_whitespace().nth(1).is_some() => {
+                // STOP <job>; a bare STOP is the statement
+                let spec = line.split_whitespace().nth(1).unwrap_or("").trim_matches('"');
+                match jobs::stop(spec) {
+                    Ok(name) => {
+                        let _ = writeln!(term, "Stopped {}", name);
+                    }
+                    Err(e) => {
+                        let _ = writeln!(term, "Error: {}", e);
+                    }
+                }
+                continue;
+            }
+            Token::Quota => {
+                let mut args = line.split_whitespace().skip(1);
+                let spec = args.next().map(|s| s.trim_matches('"'));
+                let bytes = match args.next() {
+                    Some(arg) if arg.eq_ignore_ascii_case("OFF") => Some(None),
+                    Some(arg) => arg.parse::<usize>().ok().map(Some),
+                    None => None,
+                };
+                match (spec, bytes) {
+                    (Some(spec), Some(bytes)) => match jobs::set_quota(spec, bytes) {
+                        Ok(name) => {
+                            let _ = writeln!(term, "Quota of {} set", name);
+                        }
+                        Err(e) => {
+                            let _ = writeln!(term, "Error: {}", e);
+                        }
+                    },
+                    _ => {
+                        let _ = writeln!(term, "Usage: QUOTA <job> <bytes>|OFF");
+                    }
+                }
+                continue;
+            }
+            Token::Slice => {
+                match line.split_whitespace().nth(1).map(str::parse::<u32>) {
+                    None => {
+                        let _ = writeln!(term, "Yielding every {} statements", interp.yield_interval());
+                    }
+                    Some(Ok(n)) if n > 0 => interp.set_yield_interval(n),
+                    Some(_) => {
+                        let _ = writeln!(term, "Usage: SLICE <statements>");
+                    }
+                }
+                continue;
+            }
+            Token::Strmax => {
+                match line.split_whitespace().nth(1).map(str::parse::<usize>) {
+                    None => {
+                        let _ = writeln!(term, "Strings are limited to {} bytes", interp.max_string_len());
+                    }
+                    Some(Ok(n)) if n > 0 => interp.set_max_string_len(n),
+                    Some(_) => {
+                        let _ = writeln!(term, "Usage: STRMAX <bytes>");
+                    }
+                }
+                continue;
+            }
+            Token::Memwatch => {
+                if let Err(e) = memwatch_command(line, term) {
+                    let _ = writeln!(term, "Error: {}", e);
+                }
+                continue;
+            }
+            Token::Paste => {
+                let _ = writeln!(term, "Paste program, end with a line containing only '.'");
+                let Some(source) = read_paste(term) else {
+                    return;
+                };
+                match interp.load_program(&source) {
+                    Ok(n) => {
+                        let _ = writeln!(term, "Pasted {} lines", n);
+                    }
+                    Err(e) => {
+                        let _ = writeln!(term, "Syntax error: {} (nothing loaded)", e);
+                    }
+                }
+                continue;
+            }
+            Token::Screenshot => {
+                if crate::screenshot::dump_to_serial() {
+                    let _ = writeln!(term, "Screenshot written to serial");
+                } else {
+                    let _ = writeln!(term, "Error: VGA is not enabled");
+                }
+                continue;
+            }
             _ => {}
         }
 
@@ -279,13 +594,139 @@ pub fn run_repl_on_terminal(term: &mut dyn Terminal) {
     }
 }
 
-fn load_bas_program(interp: &mut Interpreter, input: &str) -> Result<String, String> {
-    // Expect: LOAD <name>  OR  LOAD "name"
+/// Read raw program lines for PASTE until a line containing only '.'
+///
+/// Nothing is echoed and no prompts are printed, so a pasted block arrives
+/// exactly as sent. CR, LF and CRLF all end a line; blank lines are dropped.
+/// Returns None on EOF.
+fn read_paste(term: &mut dyn Terminal) -> Option<String> {
+    let mut source = String::new();
+    let mut line = String::new();
+
+    loop {
+        match term.read_byte_timeout(u64::MAX) {
+            ReadStatus::Byte(b'\r') | ReadStatus::Byte(b'\n') => {
+                let text = line.trim();
+                if text == "." {
+                    return Some(source);
+                }
+                if !text.is_empty() {
+                    source.push_str(text);
+                    source.push('\n');
+                }
+                line.clear();
+            }
+            ReadStatus::Byte(b) if b == b'\t' || (32..127).contains(&b) => line.push(b as char),
+            ReadStatus::Byte(_) | ReadStatus::NoData => {}
+            ReadStatus::Eof => return None,
+        }
+    }
+}
+
+/// MEMWATCH [OFF | limit [REPORT]]
+fn memwatch_command(input: &str, term: &mut dyn Terminal) -> Result<(), String> {
+    let mut args = input.split_whitespace().skip(1);
+    match args.next() {
+        None => match memwatch::status() {
+            Some((limit, report)) => {
+                let _ = writeln!(
+                    term,
+                    "Watching for free heap below {} bytes{}",
+                    limit, if report { " (with report)" } else { "" });
+            }
+            None => {
+                let _ = writeln!(term, "MEMWATCH is off");
+            }
+        },
+        Some(arg) if arg.eq_ignore_ascii_case("OFF") => memwatch::stop(),
+        Some(arg) => {
+            let limit = arg
+                .parse::<usize>()
+                .map_err(|_| String::from("Usage: MEMWATCH [OFF | limit [REPORT]]"))?;
+            let report = match args.next() {
+                None => false,
+                Some(a) if a.eq_ignore_ascii_case("REPORT") => true,
+                Some(_) => return Err("Usage: MEMWATCH [OFF | limit [REPORT]]".into()),
+            };
+            if limit == 0 {
+                memwatch::stop();
+            } else if !memwatch::start(limit, report) {
+                return Err("Can't start memwatch task".into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parse the optional line-number argument of a REPL command (e.g. BREAK 100)
+fn parse_line_arg(input: &str) -> Result<Option<u32>, String> {
+    match input.split_whitespace().nth(1) {
+        None => Ok(None),
+        Some(arg) => arg
+            .parse::<u32>()
+            .map(Some)
+            .map_err(|_| alloc::format!("Invalid line number: {}", arg)),
+    }
+}
+
+/// Run the program until it finishes, errors or stops
+fn run_program(interp: &mut Interpreter, term: &mut dyn Terminal) {
+    while interp.is_running() {
+        let status = interp.step(term);
+        match status {
+            ExecutionStatus::Sleeping(ms) => {
+                scheduler::sleep_ms(ms);
+            }
+            ExecutionStatus::Ready => {
+                if interp.yield_due() {
+                    scheduler::yield_now();
+                }
+            }
+            ExecutionStatus::WaitingForInput => {
+                // On EOF the program stays paused; the REPL sees EOF next
+                if !interp.read_input(term) {
+                    break;
+                }
+            }
+            _ => break,
+        }
+    }
+    report_stop(interp, term);
+}
+
+/// Report why a program is no longer running (error or stop location)
+fn report_stop(interp: &Interpreter, term: &mut dyn Terminal) {
+    match *interp.status() {
+        ExecutionStatus::Error(ref e) => match interp.error_line() {
+            Some(n) => {
+                let _ = writeln!(term, "Error on line {}: {}", n, e);
+            }
+            None => {
+                let _ = writeln!(term, "Error: {}", e);
+            }
+        },
+        _ if interp.is_stopped() => {
+            if let Some(n) = interp.current_line() {
+                if interp.breakpoints().any(|&b| b == n) {
+                    let _ = writeln!(term, "Breakpoint at line {}", n);
+                } else {
+                    let _ = writeln!(term, "Stopped at line {}", n);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// File name argument of LOAD/SAVE, with ".bas" appended if missing
+fn bas_filename(input: &str) -> Result<String, String> {
+    // Expect: CMD <name>  OR  CMD "name"
     let mut parts = input.trim().splitn(2, char::is_whitespace);
-    let _cmd = parts.next().unwrap_or("");
+    let cmd = parts.next().unwrap_or("").to_ascii_uppercase();
+    let usage = || alloc::format!("Usage: {} \"name\"", cmd);
     let arg = parts.next().unwrap_or("").trim();
     if arg.is_empty() {
-        return Err("Usage: LOAD \"name\"".into());
+        return Err(usage());
     }
 
     let name = if let Some(stripped) = arg.strip_prefix('"') {
@@ -298,17 +739,20 @@ fn load_bas_program(interp: &mut Interpreter, input: &str) -> Result<String, Str
     };
 
     if name.is_empty() {
-        return Err("Usage: LOAD \"name\"".into());
+        return Err(usage());
     }
 
-    let filename = if name.to_ascii_lowercase().ends_with(".bas") {
+    Ok(if name.to_ascii_lowercase().ends_with(".bas") {
         String::from(name)
     } else {
         alloc::format!("{}.bas", name)
-    };
+    })
+}
 
+fn load_bas_program(interp: &mut Interpreter, input: &str) -> Result<String, String> {
+    let filename = bas_filename(input)?;
     let bytes = crate::executable::read(&filename).map_err(|e| alloc::format!("{:?}", e))?;
-    let src = core::str::from_utf8(bytes).map_err(|_| String::from("File is not valid UTF-8"))?;
+    let src = core::str::from_utf8(&bytes).map_err(|_| String::from("File is not valid UTF-8"))?;
 
     interp.clear();
     let loaded = interp.load_program(src)?;
@@ -318,21 +762,36 @@ fn load_bas_program(interp: &mut Interpreter, input: &str) -> Result<String, Str
     Ok(filename)
 }
 
+/// Write the current program to the file overlay (lost on reboot)
+fn save_bas_program(interp: &Interpreter, input: &str) -> Result<String, String> {
+    let filename = bas_filename(input)?;
+    let source = interp.source();
+    if source.is_empty() {
+        return Err("No program to save".into());
+    }
+    crate::executable::write(&filename, source.as_bytes())
+        .map_err(|e| alloc::format!("{:?}", e))?;
+    Ok(filename)
+}
+
 /// Memory monitor task (headless BASIC program)
 pub fn memstats_task() {
     let program = r#"
 10 REM Memory monitor - runs in background
 20 LET U = MEM(0)
 30 LET F = MEM(1)
+35 LET L = MEM(2)
 40 PRINT "Heap: "; U; " used, "; F; " free"
+45 IF L < 16384 THEN PRINT "Heap fragmented: largest free block "; L; " bytes in "; MEM(3); " blocks"
 50 SLEEP 10000
 60 GOTO 20
 "#;
-    run_headless(program);
+    jobs::register_current("memstats");
+    run_headless(program, true);
 }
 
 /// Interactive BASIC REPL task
 pub fn repl_task() {
-    let mut term = terminal::SerialTerminal;
-    run_repl_on_terminal(&mut term);
+    let mut term = terminal::SerialTerminal::new();
+    run_repl_on_terminal(&mut term, true);
 }
@@ -7,11 +7,16 @@ use alloc::vec::Vec;
 use alloc::boxed::Box;
 use super::lexer::{Lexer, Token};
 
+/// Maximum number of elements in an array literal
+pub const MAX_ARRAY_LITERAL: usize = 256;
+
 /// A BASIC expression
 #[derive(Clone, Debug)]
 pub enum Expr {
     /// Integer literal
     Integer(i64),
+    /// Floating-point literal
+    Float(f64),
     /// String literal
     StringLit(String),
     /// Variable reference
@@ -24,8 +29,24 @@ pub enum Expr {
     },
     /// Unary negation
     Negate(Box<Expr>),
+    /// NOT expr - 1 if expr is false (zero or empty), else 0
+    Not(Box<Expr>),
     /// MEM(n) function call
     Mem(Box<Expr>),
+    /// RND(n) - random integer in [0, n); RND(1) is a float in [0, 1),
+    /// RND(0) repeats the last value
+    Rnd(Box<Expr>),
+    // Math functions
+    /// ABS(x) - absolute value
+    Abs(Box<Expr>),
+    /// SGN(x) - sign: -1, 0 or 1
+    Sgn(Box<Expr>),
+    /// INT(x) - largest integer not greater than x
+    Int(Box<Expr>),
+    /// MIN(a, b) - smaller of two numbers
+    Min(Box<Expr>, Box<Expr>),
+    /// MAX(a, b) - larger of two numbers
+    Max(Box<Expr>, Box<Expr>),
     // String functions
     /// CHR$(n) - character from ASCII code
     Chr(Box<Expr>),
@@ -37,12 +58,65 @@ pub enum Expr {
     Mid(Box<Expr>, Box<Expr>, Box<Expr>),
     /// LEFT$(s$, n) - first n characters
     Left(Box<Expr>, Box<Expr>),
+    /// RIGHT$(s$, n) - last n characters
+    Right(Box<Expr>, Box<Expr>),
     /// INSTR(haystack$, needle$) - find substring
     Instr(Box<Expr>, Box<Expr>),
+    /// STARTSWITH(s$, prefix$) - 1 if s$ begins with prefix$, else 0
+    StartsWith(Box<Expr>, Box<Expr>),
+    /// ENDSWITH(s$, suffix$) - 1 if s$ ends with suffix$, else 0
+    EndsWith(Box<Expr>, Box<Expr>),
+    /// CONTAINS(s$, part$) - 1 if part$ occurs in s$, else 0
+    Contains(Box<Expr>, Box<Expr>),
     /// STR$(n) - convert number to string
     Str(Box<Expr>),
     /// VAL(s$) - convert string to number
     Val(Box<Expr>),
+    /// URLDECODE$(s$) - decode '+' and %XX escapes
+    UrlDecode(Box<Expr>),
+    /// URLENCODE$(s$) - percent-encode reserved characters
+    UrlEncode(Box<Expr>),
+    /// GETFIELD$(body$, key$) - value of key in a form body or flat JSON object
+    GetField(Box<Expr>, Box<Expr>),
+    /// CRC32(s$) - CRC-32 of the string's bytes
+    Crc32(Box<Expr>),
+    /// CHECKSUM(s$) - 16-bit internet checksum of the string's bytes
+    Checksum(Box<Expr>),
+    /// BASE64$(s$) - base64-encode the string's bytes
+    Base64(Box<Expr>),
+    /// UNBASE64$(s$) - decode base64 (whitespace ignored)
+    Unbase64(Box<Expr>),
+    // Graphics
+    /// FBADDR - VGA back buffer address (0 if VGA is off)
+    FbAddr,
+    /// FBWIDTH - framebuffer width in pixels (also the stride in bytes)
+    FbWidth,
+    /// FBHEIGHT - framebuffer height in pixels
+    FbHeight,
+    // Terminal
+    /// POS(n) - current output column (argument ignored)
+    Pos,
+    /// CSRLIN - current output row
+    Csrlin,
+    // Time
+    /// UPTIME - whole seconds since boot
+    Uptime,
+    /// TICKS - timer ticks since boot
+    Ticks,
+    // Introspection
+    /// TYPEOF$(x) - name of the value's type
+    TypeOf(Box<Expr>),
+    /// ISDEF(name$) - 1 if the named variable is defined
+    IsDef(Box<Expr>),
+    /// ENVIRON$(name$) - value of a shared environment variable ("" if unset)
+    Environ(Box<Expr>),
+    // Files
+    /// READFILE$(name$) - contents of an embedded file as bytes
+    ReadFile(Box<Expr>),
+    /// FILESIZE(name$) - size of an embedded file in bytes
+    FileSize(Box<Expr>),
+    /// ERR - error code of the last file function (0 if it succeeded)
+    Err,
     // Network functions
     /// SOCKET() - create socket
     Socket,
@@ -54,6 +128,33 @@ pub enum Expr {
     Recv(Box<Expr>),
     /// SOCKSTATE(sock) - get socket state
     Sockstate(Box<Expr>),
+    /// AVAIL(sock) - bytes waiting in receive buffer
+    Avail(Box<Expr>),
+    /// PEEK$(sock, n) - look at buffered data without consuming it
+    Peek(Box<Expr>, Box<Expr>),
+    /// RECVLINE$(sock) - receive one complete line
+    Recvline(Box<Expr>),
+    /// HTTPHEADER$(status, type$) - HTTP status line and headers
+    HttpHeader(Box<Expr>, Box<Expr>),
+    /// UDPSOCKET([port]) - bind a UDP socket (ephemeral port if omitted)
+    UdpSocket(Option<Box<Expr>>),
+    /// RECVFROM$(sock) - next queued datagram ("" if none)
+    RecvFrom(Box<Expr>),
+    /// RESOLVE$(host$) - dotted-quad address of a host name ("" if unknown)
+    Dns(Box<Expr>),
+    /// NSLOOKUP(host$) - address of a host name packed into an integer
+    NsLookup(Box<Expr>),
+    /// IPCONFIG$(n) - address (0), netmask (1), gateway (2), DNS server (3)
+    /// or how they were set (4)
+    IpConfig(Box<Expr>),
+    /// NETSTAT(n) - network counter n
+    Netstat(Box<Expr>),
+    /// COUNT(array) - number of elements
+    Count(Box<Expr>),
+    /// JOIN$(array, delim$) - elements joined into one string
+    Join(Box<Expr>, Box<Expr>),
+    /// Array literal: {expr, expr, ...}
+    ArrayLit(Vec<Expr>),
     // Array access
     /// Array element access: ARR(index)
     ArrayAccess { name: String, index: Box<Expr> },
@@ -72,6 +173,38 @@ pub enum BinaryOp {
     Gt,
     Le,
     Ge,
+    /// 1 if both operands are true (nonzero / non-empty), else 0
+    And,
+    /// 1 if either operand is true, else 0
+    Or,
+}
+
+impl BinaryOp {
+    /// Binding strength; higher binds tighter (NOT sits between AND and
+    /// the comparisons)
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::Eq
+            | BinaryOp::Ne
+            | BinaryOp::Lt
+            | BinaryOp::Gt
+            | BinaryOp::Le
+            | BinaryOp::Ge => 4,
+            BinaryOp::Add | BinaryOp::Sub => 5,
+            BinaryOp::Mul | BinaryOp::Div => 6,
+        }
+    }
+}
+
+/// What an OPEN statement binds a channel to
+#[derive(Clone, Debug)]
+pub enum OpenTarget {
+    /// The interpreter's terminal
+    Console,
+    /// A TCP socket handle
+    Socket(Expr),
 }
 
 /// FOR loop state
@@ -90,8 +223,14 @@ pub enum Statement {
     Print(Vec<Expr>),
     /// LET var = expr
     Let { var: String, value: Expr },
-    /// IF cond THEN linenum
-    If { condition: Expr, then_line: u32 },
+    /// IF cond THEN linenum|statement [ELSE linenum|statement]
+    ///
+    /// A bare line number after THEN or ELSE is stored as a GOTO.
+    If {
+        condition: Expr,
+        then_stmt: Box<Statement>,
+        else_stmt: Option<Box<Statement>>,
+    },
     /// GOTO linenum
     Goto(u32),
     /// FOR var = start TO end [STEP step]
@@ -103,26 +242,99 @@ pub enum Statement {
     },
     /// NEXT var
     Next(String),
+    /// WHILE cond (loop body runs up to the matching WEND)
+    While { condition: Expr },
+    /// WEND (end of WHILE loop)
+    Wend,
     /// SLEEP milliseconds
     Sleep(Expr),
+    /// SLEEPUS microseconds (busy-wait, does not yield)
+    SleepUs(Expr),
+    /// TABWIDTH n - distance between the tab stops '\t' expands to
+    TabWidth(Expr),
+    /// RANDOMIZE [seed] - restart RND (from the clock without a seed)
+    Randomize(Option<Expr>),
     /// REM (comment - no-op)
     Rem,
     /// END
     End,
-    /// SPAWN "program_name" [, "arg1", "arg2", ...]
-    Spawn(String, Vec<String>),
+    /// STOP (pause program; resume with CONT or STEP)
+    Stop,
+    /// ASSERT cond [, msg$] (error if cond is false)
+    Assert { condition: Expr, message: Option<Expr> },
+    /// ABORT msg$ (unconditional error)
+    Abort(Expr),
+    /// SPAWN "program_name" [, "arg1", "arg2", ...] [RESTRICTED]
+    Spawn { name: String, args: Vec<String>, restricted: bool },
+    /// SPAWN "a" [, args...] | "b" [, args...] [RESTRICTED]
+    /// (a's output feeds b's input)
+    SpawnPipe {
+        writer: (String, Vec<String>),
+        reader: (String, Vec<String>),
+        restricted: bool,
+    },
     /// GOSUB linenum
     Gosub(u32),
+    /// ON expr GOTO linenum [, linenum]*
+    OnGoto { selector: Expr, targets: Vec<u32> },
+    /// ON expr GOSUB linenum [, linenum]*
+    OnGosub { selector: Expr, targets: Vec<u32> },
     /// RETURN
     Return,
     /// DIM name(size)
     Dim { name: String, size: Expr },
+    /// PUSH name, value (append to an array, creating it if needed)
+    Push { name: String, value: Expr },
+    /// SPLIT name$, source$, delim$ (replace a string array with the pieces)
+    Split { name: String, source: Expr, delim: Expr },
     /// Array assignment: ARR(index) = value
     ArrayAssign { name: String, index: Expr, value: Expr },
     /// SEND sock, data$
     Send { sock: Expr, data: Expr },
     /// CLOSE sock
     NetClose(Expr),
+    /// SENDTO sock, ip$, port, data$
+    SendTo { sock: Expr, ip: Expr, port: Expr, data: Expr },
+    /// UDPCLOSE sock
+    UdpClose(Expr),
+    /// TIMEOUT ms, statement - abandon the statement's waiting after ms
+    Timeout { ms: Expr, stmt: Box<Statement> },
+    /// KEEPALIVE sock, on [, idle_seconds]
+    Keepalive { sock: Expr, on: Expr, idle: Option<Expr> },
+    /// FWALLOW [TCP|UDP, port] (protocol as an IPv4 protocol number)
+    FwAllow(Option<(u8, Expr)>),
+    /// FWDENY [TCP|UDP, port]
+    FwDeny(Option<(u8, Expr)>),
+    /// NETLOG [level] - set (or show) the network log level
+    NetLog(Option<Expr>),
+    /// DEBUG ON / DEBUG OFF - trace LET values, IF conditions and jumps
+    Debug(bool),
+    /// NETDUMP on - log every frame to serial
+    NetDump(Expr),
+    /// PSET x, y, color
+    Pset { x: Expr, y: Expr, color: Expr },
+    /// WAITVBL (wait for vertical retrace)
+    WaitVbl,
+    /// PALETTE index, r, g, b
+    Palette { index: Expr, r: Expr, g: Expr, b: Expr },
+    /// PALETTE (restore the default palette)
+    PaletteReset,
+    /// HTTPSEND sock, status, type$, body$
+    HttpSend { sock: Expr, status: Expr, content_type: Expr, body: Expr },
+    /// SERVEFILE sock, name$ (send a file as an HTTP response)
+    ServeFile { sock: Expr, name: Expr },
+    /// OPEN ch AS SOCKET sock / OPEN ch AS CONSOLE
+    Open { channel: Expr, target: OpenTarget },
+    /// CLOSE #ch
+    CloseChannel(Expr),
+    /// PRINT# ch, expr [; expr]*
+    PrintTo { channel: Expr, exprs: Vec<Expr> },
+    /// INPUT# ch, var
+    InputFrom { channel: Expr, var: String },
+    /// INPUT ["prompt";] var (read a line from the terminal)
+    Input { prompt: Option<String>, var: String },
+    /// SETENV name$, value$
+    SetEnv { name: Expr, value: Expr },
 }
 
 /// Parse error
@@ -172,6 +384,12 @@ impl<'a> Parser<'a> {
         // Parse statement
         let stmt = self.parse_statement()?;
 
+        // Skip a trailing comment (e.g. `LET X = 1 ' initialize`)
+        if self.current == Token::Rem {
+            self.lexer.skip_to_eol();
+            self.current = self.lexer.next_token();
+        }
+
         // Consume newline if present
         if self.current == Token::Newline {
             self.advance();
@@ -189,18 +407,96 @@ impl<'a> Parser<'a> {
             Token::Goto => self.parse_goto(),
             Token::For => self.parse_for(),
             Token::Next => self.parse_next(),
+            Token::While => {
+                self.advance();
+                Ok(Statement::While {
+                    condition: self.parse_expression()?,
+                })
+            }
+            Token::Wend => {
+                self.advance();
+                Ok(Statement::Wend)
+            }
             Token::Sleep => self.parse_sleep(),
+            Token::Randomize => {
+                self.advance();
+                if self.at_statement_end() {
+                    Ok(Statement::Randomize(None))
+                } else {
+                    Ok(Statement::Randomize(Some(self.parse_expression()?)))
+                }
+            }
+            Token::Sleepus => {
+                self.advance();
+                Ok(Statement::SleepUs(self.parse_expression()?))
+            }
+            Token::Tabwidth => {
+                self.advance();
+                Ok(Statement::TabWidth(self.parse_expression()?))
+            }
             Token::Spawn => self.parse_spawn(),
             Token::Gosub => self.parse_gosub(),
+            Token::On => self.parse_on(),
+            Token::Debug => {
+                self.advance();
+                let on = match &self.current {
+                    Token::On => true,
+                    Token::Identifier(s) if s == "OFF" => false,
+                    _ => return Err(ParseError("Expected ON or OFF after DEBUG".into())),
+                };
+                self.advance();
+                Ok(Statement::Debug(on))
+            }
             Token::Return => {
                 self.advance();
                 Ok(Statement::Return)
             }
             Token::Dim => self.parse_dim(),
+            Token::Push => self.parse_push(),
+            Token::Split => self.parse_split(),
             Token::Send => self.parse_send(),
             Token::Close => self.parse_close(),
-            Token::Rem => {
+            Token::Open => self.parse_open(),
+            Token::Input => self.parse_input(),
+            Token::Httpsend => self.parse_httpsend(),
+            Token::Servefile => self.parse_servefile(),
+            Token::Sendto => self.parse_sendto(),
+            Token::Keepalive => self.parse_keepalive(),
+            Token::Timeout => self.parse_timeout(),
+            Token::Fwallow => {
+                self.advance();
+                Ok(Statement::FwAllow(self.parse_firewall_rule("FWALLOW")?))
+            }
+            Token::Fwdeny => {
+                self.advance();
+                Ok(Statement::FwDeny(self.parse_firewall_rule("FWDENY")?))
+            }
+            Token::Netlog => {
                 self.advance();
+                if self.at_statement_end() {
+                    Ok(Statement::NetLog(None))
+                } else {
+                    Ok(Statement::NetLog(Some(self.parse_expression()?)))
+                }
+            }
+            Token::Netdump => {
+                self.advance();
+                Ok(Statement::NetDump(self.parse_expression()?))
+            }
+            Token::Udpclose => {
+                self.advance();
+                Ok(Statement::UdpClose(self.parse_expression()?))
+            }
+            Token::Pset => self.parse_pset(),
+            Token::Palette => self.parse_palette(),
+            Token::Setenv => self.parse_setenv(),
+            Token::Waitvbl => {
+                self.advance();
+                Ok(Statement::WaitVbl)
+            }
+            Token::Rem => {
+                // Skip the comment text without tokenizing it (advancing
+                // first would swallow the newline of an empty comment)
                 self.lexer.skip_to_eol();
                 // Fetch the next token (newline or EOF) after skipping to EOL
                 self.current = self.lexer.next_token();
@@ -210,6 +506,25 @@ impl<'a> Parser<'a> {
                 self.advance();
                 Ok(Statement::End)
             }
+            Token::Stop => {
+                self.advance();
+                Ok(Statement::Stop)
+            }
+            Token::Assert => {
+                self.advance();
+                let condition = self.parse_expression()?;
+                let message = if self.current == Token::Comma {
+                    self.advance();
+                    Some(self.parse_expression()?)
+                } else {
+                    None
+                };
+                Ok(Statement::Assert { condition, message })
+            }
+            Token::Abort => {
+                self.advance();
+                Ok(Statement::Abort(self.parse_expression()?))
+            }
             Token::Identifier(name) => {
                 // Could be implicit LET (X = 5) or array assignment (ARR(I) = 5)
                 let var = name.clone();
@@ -249,11 +564,26 @@ impl<'a> Parser<'a> {
 
     fn parse_print(&mut self) -> Result<Statement, ParseError> {
         self.advance(); // consume PRINT
+
+        // PRINT# ch, items
+        let channel = if self.current == Token::Hash {
+            self.advance();
+            let channel = self.parse_expression()?;
+            if self.current == Token::Comma {
+                self.advance();
+            } else if !self.at_statement_end() {
+                return Err(ParseError("Expected ',' after channel in PRINT#".into()));
+            }
+            Some(channel)
+        } else {
+            None
+        };
+
         let mut exprs = Vec::new();
 
         loop {
             // Check for end of statement
-            if matches!(self.current, Token::Newline | Token::Eof) {
+            if self.at_statement_end() {
                 break;
             }
 
@@ -268,7 +598,85 @@ impl<'a> Parser<'a> {
             }
         }
 
-        Ok(Statement::Print(exprs))
+        match channel {
+            Some(channel) => Ok(Statement::PrintTo { channel, exprs }),
+            None => Ok(Statement::Print(exprs)),
+        }
+    }
+
+    fn parse_input(&mut self) -> Result<Statement, ParseError> {
+        self.advance(); // consume INPUT
+
+        if self.current != Token::Hash {
+            return self.parse_terminal_input();
+        }
+        self.advance();
+
+        let channel = self.parse_expression()?;
+        if self.current != Token::Comma {
+            return Err(ParseError("Expected ',' after channel in INPUT#".into()));
+        }
+        self.advance();
+
+        let var = match &self.current {
+            Token::Identifier(name) => name.clone(),
+            _ => return Err(ParseError("Expected variable name in INPUT#".into())),
+        };
+        self.advance();
+
+        Ok(Statement::InputFrom { channel, var })
+    }
+
+    /// INPUT ["prompt";] var, after the INPUT keyword
+    fn parse_terminal_input(&mut self) -> Result<Statement, ParseError> {
+        let prompt = if let Token::StringLit(s) = &self.current {
+            let prompt = s.clone();
+            self.advance();
+            if !matches!(self.current, Token::Semicolon | Token::Comma) {
+                return Err(ParseError("Expected ';' after INPUT prompt".into()));
+            }
+            self.advance();
+            Some(prompt)
+        } else {
+            None
+        };
+
+        let var = match &self.current {
+            Token::Identifier(name) => name.clone(),
+            _ => return Err(ParseError("Expected variable name in INPUT".into())),
+        };
+        self.advance();
+
+        Ok(Statement::Input { prompt, var })
+    }
+
+    fn parse_open(&mut self) -> Result<Statement, ParseError> {
+        self.advance(); // consume OPEN
+
+        // The '#' before the channel number is optional
+        if self.current == Token::Hash {
+            self.advance();
+        }
+        let channel = self.parse_expression()?;
+
+        if self.current != Token::As {
+            return Err(ParseError("Expected AS in OPEN".into()));
+        }
+        self.advance();
+
+        let target = match self.current {
+            Token::Socket => {
+                self.advance();
+                OpenTarget::Socket(self.parse_expression()?)
+            }
+            Token::Console => {
+                self.advance();
+                OpenTarget::Console
+            }
+            _ => return Err(ParseError("Expected SOCKET or CONSOLE after AS".into())),
+        };
+
+        Ok(Statement::Open { channel, target })
     }
 
     fn parse_let(&mut self) -> Result<Statement, ParseError> {
@@ -299,18 +707,35 @@ impl<'a> Parser<'a> {
         }
         self.advance();
 
-        let then_line = match &self.current {
-            Token::Integer(n) => *n as u32,
-            _ => return Err(ParseError("Expected line number after THEN".into())),
+        let then_stmt = Box::new(self.parse_if_branch("THEN")?);
+
+        let else_stmt = if self.current == Token::Else {
+            self.advance();
+            Some(Box::new(self.parse_if_branch("ELSE")?))
+        } else {
+            None
         };
-        self.advance();
 
         Ok(Statement::If {
             condition,
-            then_line,
+            then_stmt,
+            else_stmt,
         })
     }
 
+    /// Parse what follows THEN or ELSE: a line number (GOTO) or a statement
+    fn parse_if_branch(&mut self, keyword: &str) -> Result<Statement, ParseError> {
+        if let Token::Integer(n) = &self.current {
+            let line = *n as u32;
+            self.advance();
+            return Ok(Statement::Goto(line));
+        }
+        if self.at_statement_end() {
+            return Err(ParseError(alloc::format!("Expected statement after {}", keyword)));
+        }
+        self.parse_statement()
+    }
+
     fn parse_goto(&mut self) -> Result<Statement, ParseError> {
         self.advance(); // consume GOTO
 
@@ -386,6 +811,32 @@ impl<'a> Parser<'a> {
     fn parse_spawn(&mut self) -> Result<Statement, ParseError> {
         self.advance(); // consume SPAWN
 
+        let (name, args) = self.parse_spawn_command()?;
+        let reader = if self.current == Token::Pipe {
+            self.advance(); // consume |
+            Some(self.parse_spawn_command()?)
+        } else {
+            None
+        };
+
+        // RESTRICTED: no network access for the spawned program(s)
+        let restricted = self.current == Token::Restricted;
+        if restricted {
+            self.advance();
+        }
+
+        Ok(match reader {
+            Some(reader) => Statement::SpawnPipe {
+                writer: (name, args),
+                reader,
+                restricted,
+            },
+            None => Statement::Spawn { name, args, restricted },
+        })
+    }
+
+    /// "program_name" [, "arg1", "arg2", ...]
+    fn parse_spawn_command(&mut self) -> Result<(String, Vec<String>), ParseError> {
         // Expect a string literal for the program name
         let name = match &self.current {
             Token::StringLit(s) => s.clone(),
@@ -406,7 +857,7 @@ impl<'a> Parser<'a> {
             args.push(arg);
         }
 
-        Ok(Statement::Spawn(name, args))
+        Ok((name, args))
     }
 
     fn parse_gosub(&mut self) -> Result<Statement, ParseError> {
@@ -421,6 +872,40 @@ impl<'a> Parser<'a> {
         Ok(Statement::Gosub(line))
     }
 
+    fn parse_on(&mut self) -> Result<Statement, ParseError> {
+        self.advance(); // consume ON
+
+        let selector = self.parse_expression()?;
+
+        let gosub = match self.current {
+            Token::Goto => false,
+            Token::Gosub => true,
+            _ => return Err(ParseError("Expected GOTO or GOSUB after ON expression".into())),
+        };
+        self.advance();
+
+        let mut targets = Vec::new();
+        loop {
+            match &self.current {
+                Token::Integer(n) => targets.push(*n as u32),
+                _ => return Err(ParseError("Expected line number in ON list".into())),
+            }
+            self.advance();
+
+            if self.current == Token::Comma {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+
+        if gosub {
+            Ok(Statement::OnGosub { selector, targets })
+        } else {
+            Ok(Statement::OnGoto { selector, targets })
+        }
+    }
+
     fn parse_dim(&mut self) -> Result<Statement, ParseError> {
         self.advance(); // consume DIM
 
@@ -445,6 +930,50 @@ impl<'a> Parser<'a> {
         Ok(Statement::Dim { name, size })
     }
 
+    fn parse_push(&mut self) -> Result<Statement, ParseError> {
+        self.advance(); // consume PUSH
+
+        let name = match &self.current {
+            Token::Identifier(n) => n.clone(),
+            _ => return Err(ParseError("Expected array name after PUSH".into())),
+        };
+        self.advance();
+
+        if self.current != Token::Comma {
+            return Err(ParseError("Expected ',' after array name in PUSH".into()));
+        }
+        self.advance();
+
+        let value = self.parse_expression()?;
+
+        Ok(Statement::Push { name, value })
+    }
+
+    fn parse_split(&mut self) -> Result<Statement, ParseError> {
+        self.advance(); // consume SPLIT
+
+        let name = match &self.current {
+            Token::Identifier(n) => n.clone(),
+            _ => return Err(ParseError("Expected array name after SPLIT".into())),
+        };
+        self.advance();
+
+        if self.current != Token::Comma {
+            return Err(ParseError("Expected ',' after array name in SPLIT".into()));
+        }
+        self.advance();
+
+        let source = self.parse_expression()?;
+        if self.current != Token::Comma {
+            return Err(ParseError("Expected ',' after source in SPLIT".into()));
+        }
+        self.advance();
+
+        let delim = self.parse_expression()?;
+
+        Ok(Statement::Split { name, source, delim })
+    }
+
     fn parse_send(&mut self) -> Result<Statement, ParseError> {
         self.advance(); // consume SEND
 
@@ -460,78 +989,333 @@ impl<'a> Parser<'a> {
         Ok(Statement::Send { sock, data })
     }
 
+    fn parse_setenv(&mut self) -> Result<Statement, ParseError> {
+        self.advance(); // consume SETENV
+
+        let name = self.parse_expression()?;
+
+        if self.current != Token::Comma {
+            return Err(ParseError("Expected ',' after name in SETENV".into()));
+        }
+        self.advance();
+
+        let value = self.parse_expression()?;
+
+        Ok(Statement::SetEnv { name, value })
+    }
+
     fn parse_close(&mut self) -> Result<Statement, ParseError> {
         self.advance(); // consume CLOSE
+        if self.current == Token::Hash {
+            self.advance();
+            return Ok(Statement::CloseChannel(self.parse_expression()?));
+        }
         let sock = self.parse_expression()?;
         Ok(Statement::NetClose(sock))
     }
 
-    /// Parse expression with operator precedence
-    fn parse_expression(&mut self) -> Result<Expr, ParseError> {
-        self.parse_comparison()
-    }
+    fn parse_httpsend(&mut self) -> Result<Statement, ParseError> {
+        self.advance(); // consume HTTPSEND
 
-    fn parse_comparison(&mut self) -> Result<Expr, ParseError> {
-        let mut left = self.parse_additive()?;
+        let sock = self.parse_expression()?;
+        if self.current != Token::Comma {
+            return Err(ParseError("Expected ',' after socket in HTTPSEND".into()));
+        }
+        self.advance();
 
-        loop {
-            let op = match &self.current {
-                Token::Eq => BinaryOp::Eq,
-                Token::Ne => BinaryOp::Ne,
-                Token::Lt => BinaryOp::Lt,
-                Token::Gt => BinaryOp::Gt,
-                Token::Le => BinaryOp::Le,
-                Token::Ge => BinaryOp::Ge,
-                _ => break,
-            };
-            self.advance();
-            let right = self.parse_additive()?;
-            left = Expr::BinaryOp {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
-            };
+        let status = self.parse_expression()?;
+        if self.current != Token::Comma {
+            return Err(ParseError("Expected ',' after status in HTTPSEND".into()));
+        }
+        self.advance();
+
+        let content_type = self.parse_expression()?;
+        if self.current != Token::Comma {
+            return Err(ParseError("Expected ',' after content type in HTTPSEND".into()));
         }
+        self.advance();
 
-        Ok(left)
+        let body = self.parse_expression()?;
+
+        Ok(Statement::HttpSend { sock, status, content_type, body })
     }
 
-    fn parse_additive(&mut self) -> Result<Expr, ParseError> {
-        let mut left = self.parse_multiplicative()?;
+    fn parse_servefile(&mut self) -> Result<Statement, ParseError> {
+        self.advance(); // consume SERVEFILE
 
-        loop {
-            let op = match &self.current {
-                Token::Plus => BinaryOp::Add,
-                Token::Minus => BinaryOp::Sub,
-                _ => break,
-            };
-            self.advance();
-            let right = self.parse_multiplicative()?;
-            left = Expr::BinaryOp {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
-            };
+        let sock = self.parse_expression()?;
+        if self.current != Token::Comma {
+            return Err(ParseError("Expected ',' after socket in SERVEFILE".into()));
         }
+        self.advance();
 
-        Ok(left)
+        let name = self.parse_expression()?;
+
+        Ok(Statement::ServeFile { sock, name })
     }
 
-    fn parse_multiplicative(&mut self) -> Result<Expr, ParseError> {
-        let mut left = self.parse_unary()?;
+    fn parse_sendto(&mut self) -> Result<Statement, ParseError> {
+        self.advance(); // consume SENDTO
 
-        loop {
-            let op = match &self.current {
-                Token::Star => BinaryOp::Mul,
-                Token::Slash => BinaryOp::Div,
-                _ => break,
-            };
-            self.advance();
-            let right = self.parse_unary()?;
-            left = Expr::BinaryOp {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
+        let sock = self.parse_expression()?;
+        if self.current != Token::Comma {
+            return Err(ParseError("Expected ',' after socket in SENDTO".into()));
+        }
+        self.advance();
+
+        let ip = self.parse_expression()?;
+        if self.current != Token::Comma {
+            return Err(ParseError("Expected ',' after address in SENDTO".into()));
+        }
+        self.advance();
+
+        let port = self.parse_expression()?;
+        if self.current != Token::Comma {
+            return Err(ParseError("Expected ',' after port in SENDTO".into()));
+        }
+        self.advance();
+
+        let data = self.parse_expression()?;
+
+        Ok(Statement::SendTo { sock, ip, port, data })
+    }
+
+    fn parse_timeout(&mut self) -> Result<Statement, ParseError> {
+        self.advance(); // consume TIMEOUT
+
+        let ms = self.parse_expression()?;
+        if self.current != Token::Comma {
+            return Err(ParseError("Expected ',' after time in TIMEOUT".into()));
+        }
+        self.advance();
+
+        if self.at_statement_end() {
+            return Err(ParseError("Expected statement after TIMEOUT".into()));
+        }
+        let stmt = Box::new(self.parse_statement()?);
+
+        Ok(Statement::Timeout { ms, stmt })
+    }
+
+    fn parse_keepalive(&mut self) -> Result<Statement, ParseError> {
+        self.advance(); // consume KEEPALIVE
+
+        let sock = self.parse_expression()?;
+        if self.current != Token::Comma {
+            return Err(ParseError("Expected ',' after socket in KEEPALIVE".into()));
+        }
+        self.advance();
+
+        let on = self.parse_expression()?;
+        let idle = if self.current == Token::Comma {
+            self.advance();
+            Some(self.parse_expression()?)
+        } else {
+            None
+        };
+
+        Ok(Statement::Keepalive { sock, on, idle })
+    }
+
+    /// Optional `TCP|UDP, port` after FWALLOW / FWDENY
+    fn parse_firewall_rule(&mut self, keyword: &str) -> Result<Option<(u8, Expr)>, ParseError> {
+        if self.at_statement_end() {
+            return Ok(None);
+        }
+
+        let protocol = match &self.current {
+            Token::Identifier(name) if name.eq_ignore_ascii_case("TCP") => crate::net::ipv4::PROTO_TCP,
+            Token::Identifier(name) if name.eq_ignore_ascii_case("UDP") => crate::net::ipv4::PROTO_UDP,
+            _ => return Err(ParseError(alloc::format!("Expected TCP or UDP after {}", keyword))),
+        };
+        self.advance();
+
+        if self.current != Token::Comma {
+            return Err(ParseError(alloc::format!("Expected ',' after protocol in {}", keyword)));
+        }
+        self.advance();
+
+        Ok(Some((protocol, self.parse_expression()?)))
+    }
+
+    fn parse_pset(&mut self) -> Result<Statement, ParseError> {
+        self.advance(); // consume PSET
+
+        let x = self.parse_expression()?;
+        if self.current != Token::Comma {
+            return Err(ParseError("Expected ',' after x in PSET".into()));
+        }
+        self.advance();
+
+        let y = self.parse_expression()?;
+        if self.current != Token::Comma {
+            return Err(ParseError("Expected ',' after y in PSET".into()));
+        }
+        self.advance();
+
+        let color = self.parse_expression()?;
+
+        Ok(Statement::Pset { x, y, color })
+    }
+
+    fn parse_palette(&mut self) -> Result<Statement, ParseError> {
+        self.advance(); // consume PALETTE
+
+        if self.at_statement_end() {
+            return Ok(Statement::PaletteReset);
+        }
+
+        let index = self.parse_expression()?;
+        if self.current != Token::Comma {
+            return Err(ParseError("Expected ',' after index in PALETTE".into()));
+        }
+        self.advance();
+
+        let r = self.parse_expression()?;
+        if self.current != Token::Comma {
+            return Err(ParseError("Expected ',' after red in PALETTE".into()));
+        }
+        self.advance();
+
+        let g = self.parse_expression()?;
+        if self.current != Token::Comma {
+            return Err(ParseError("Expected ',' after green in PALETTE".into()));
+        }
+        self.advance();
+
+        let b = self.parse_expression()?;
+
+        Ok(Statement::Palette { index, r, g, b })
+    }
+
+    /// Parse expression with operator precedence
+    /// Parse `NAME(a, b)` after a two-argument function name
+    fn parse_two_args(&mut self, name: &str) -> Result<(Expr, Expr), ParseError> {
+        self.advance();
+        if self.current != Token::LParen {
+            return Err(ParseError(alloc::format!("Expected '(' after {}", name)));
+        }
+        self.advance();
+        let a = self.parse_expression()?;
+        if self.current != Token::Comma {
+            return Err(ParseError(alloc::format!("Expected ',' in {}", name)));
+        }
+        self.advance();
+        let b = self.parse_expression()?;
+        if self.current != Token::RParen {
+            return Err(ParseError(alloc::format!("Expected ')' after {}", name)));
+        }
+        self.advance();
+        Ok((a, b))
+    }
+
+    fn parse_expression(&mut self) -> Result<Expr, ParseError> {
+        self.parse_or()
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.parse_and()?;
+
+        while self.current == Token::Or {
+            self.advance();
+            let right = self.parse_and()?;
+            left = Expr::BinaryOp {
+                left: Box::new(left),
+                op: BinaryOp::Or,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.parse_not()?;
+
+        while self.current == Token::And {
+            self.advance();
+            let right = self.parse_not()?;
+            left = Expr::BinaryOp {
+                left: Box::new(left),
+                op: BinaryOp::And,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(left)
+    }
+
+    /// NOT applies to a whole comparison: NOT A = 1 is NOT (A = 1)
+    fn parse_not(&mut self) -> Result<Expr, ParseError> {
+        if self.current == Token::Not {
+            self.advance();
+            let expr = self.parse_not()?;
+            return Ok(Expr::Not(Box::new(expr)));
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.parse_additive()?;
+
+        loop {
+            let op = match &self.current {
+                Token::Eq => BinaryOp::Eq,
+                Token::Ne => BinaryOp::Ne,
+                Token::Lt => BinaryOp::Lt,
+                Token::Gt => BinaryOp::Gt,
+                Token::Le => BinaryOp::Le,
+                Token::Ge => BinaryOp::Ge,
+                _ => break,
+            };
+            self.advance();
+            let right = self.parse_additive()?;
+            left = Expr::BinaryOp {
+                left: Box::new(left),
+                op,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(left)
+    }
+
+    fn parse_additive(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.parse_multiplicative()?;
+
+        loop {
+            let op = match &self.current {
+                Token::Plus => BinaryOp::Add,
+                Token::Minus => BinaryOp::Sub,
+                _ => break,
+            };
+            self.advance();
+            let right = self.parse_multiplicative()?;
+            left = Expr::BinaryOp {
+                left: Box::new(left),
+                op,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(left)
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.parse_unary()?;
+
+        loop {
+            let op = match &self.current {
+                Token::Star => BinaryOp::Mul,
+                Token::Slash => BinaryOp::Div,
+                _ => break,
+            };
+            self.advance();
+            let right = self.parse_unary()?;
+            left = Expr::BinaryOp {
+                left: Box::new(left),
+                op,
+                right: Box::new(right),
             };
         }
 
@@ -552,165 +1336,495 @@ impl<'a> Parser<'a> {
             Token::Integer(n) => {
                 let n = *n;
                 self.advance();
-                Ok(Expr::Integer(n))
+                Ok(Expr::Integer(n))
+            }
+            Token::Float(x) => {
+                let x = *x;
+                self.advance();
+                Ok(Expr::Float(x))
+            }
+            Token::StringLit(s) => {
+                let s = s.clone();
+                self.advance();
+                Ok(Expr::StringLit(s))
+            }
+            Token::Identifier(name) => {
+                let name = name.clone();
+                self.advance();
+                // Check for array access: name(index)
+                if self.current == Token::LParen {
+                    self.advance();
+                    let index = self.parse_expression()?;
+                    if self.current != Token::RParen {
+                        return Err(ParseError("Expected ')' after array index".into()));
+                    }
+                    self.advance();
+                    return Ok(Expr::ArrayAccess { name, index: Box::new(index) });
+                }
+                Ok(Expr::Variable(name))
+            }
+            Token::Mem => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after MEM".into()));
+                }
+                self.advance();
+                let arg = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')'".into()));
+                }
+                self.advance();
+                Ok(Expr::Mem(Box::new(arg)))
+            }
+            // String functions
+            Token::Rnd => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after RND".into()));
+                }
+                self.advance();
+                let arg = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')'".into()));
+                }
+                self.advance();
+                Ok(Expr::Rnd(Box::new(arg)))
+            }
+            Token::Abs => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after ABS".into()));
+                }
+                self.advance();
+                let arg = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')'".into()));
+                }
+                self.advance();
+                Ok(Expr::Abs(Box::new(arg)))
+            }
+            Token::Sgn => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after SGN".into()));
+                }
+                self.advance();
+                let arg = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')'".into()));
+                }
+                self.advance();
+                Ok(Expr::Sgn(Box::new(arg)))
+            }
+            Token::Int => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after INT".into()));
+                }
+                self.advance();
+                let arg = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')'".into()));
+                }
+                self.advance();
+                Ok(Expr::Int(Box::new(arg)))
+            }
+            Token::Min => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after MIN".into()));
+                }
+                self.advance();
+                let a = self.parse_expression()?;
+                if self.current != Token::Comma {
+                    return Err(ParseError("Expected ',' in MIN".into()));
+                }
+                self.advance();
+                let b = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after MIN".into()));
+                }
+                self.advance();
+                Ok(Expr::Min(Box::new(a), Box::new(b)))
+            }
+            Token::Max => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after MAX".into()));
+                }
+                self.advance();
+                let a = self.parse_expression()?;
+                if self.current != Token::Comma {
+                    return Err(ParseError("Expected ',' in MAX".into()));
+                }
+                self.advance();
+                let b = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after MAX".into()));
+                }
+                self.advance();
+                Ok(Expr::Max(Box::new(a), Box::new(b)))
+            }
+            Token::Chr => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after CHR$".into()));
+                }
+                self.advance();
+                let arg = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')'".into()));
+                }
+                self.advance();
+                Ok(Expr::Chr(Box::new(arg)))
+            }
+            Token::Asc => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after ASC".into()));
+                }
+                self.advance();
+                let arg = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')'".into()));
+                }
+                self.advance();
+                Ok(Expr::Asc(Box::new(arg)))
+            }
+            Token::Len => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after LEN".into()));
+                }
+                self.advance();
+                let arg = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')'".into()));
+                }
+                self.advance();
+                Ok(Expr::Len(Box::new(arg)))
+            }
+            Token::Mid => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after MID$".into()));
+                }
+                self.advance();
+                let s = self.parse_expression()?;
+                if self.current != Token::Comma {
+                    return Err(ParseError("Expected ',' in MID$".into()));
+                }
+                self.advance();
+                let start = self.parse_expression()?;
+                if self.current != Token::Comma {
+                    return Err(ParseError("Expected ',' in MID$".into()));
+                }
+                self.advance();
+                let len = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after MID$".into()));
+                }
+                self.advance();
+                Ok(Expr::Mid(Box::new(s), Box::new(start), Box::new(len)))
+            }
+            Token::Left => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after LEFT$".into()));
+                }
+                self.advance();
+                let s = self.parse_expression()?;
+                if self.current != Token::Comma {
+                    return Err(ParseError("Expected ',' in LEFT$".into()));
+                }
+                self.advance();
+                let n = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after LEFT$".into()));
+                }
+                self.advance();
+                Ok(Expr::Left(Box::new(s), Box::new(n)))
+            }
+            Token::Right => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after RIGHT$".into()));
+                }
+                self.advance();
+                let s = self.parse_expression()?;
+                if self.current != Token::Comma {
+                    return Err(ParseError("Expected ',' in RIGHT$".into()));
+                }
+                self.advance();
+                let n = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after RIGHT$".into()));
+                }
+                self.advance();
+                Ok(Expr::Right(Box::new(s), Box::new(n)))
+            }
+            Token::Instr => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after INSTR".into()));
+                }
+                self.advance();
+                let haystack = self.parse_expression()?;
+                if self.current != Token::Comma {
+                    return Err(ParseError("Expected ',' in INSTR".into()));
+                }
+                self.advance();
+                let needle = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after INSTR".into()));
+                }
+                self.advance();
+                Ok(Expr::Instr(Box::new(haystack), Box::new(needle)))
             }
-            Token::StringLit(s) => {
-                let s = s.clone();
+            Token::StartsWith => {
+                let (s, prefix) = self.parse_two_args("STARTSWITH")?;
+                Ok(Expr::StartsWith(Box::new(s), Box::new(prefix)))
+            }
+            Token::EndsWith => {
+                let (s, suffix) = self.parse_two_args("ENDSWITH")?;
+                Ok(Expr::EndsWith(Box::new(s), Box::new(suffix)))
+            }
+            Token::Contains => {
+                let (s, part) = self.parse_two_args("CONTAINS")?;
+                Ok(Expr::Contains(Box::new(s), Box::new(part)))
+            }
+            Token::Str => {
                 self.advance();
-                Ok(Expr::StringLit(s))
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after STR$".into()));
+                }
+                self.advance();
+                let arg = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after STR$".into()));
+                }
+                self.advance();
+                Ok(Expr::Str(Box::new(arg)))
             }
-            Token::Identifier(name) => {
-                let name = name.clone();
+            Token::Val => {
                 self.advance();
-                // Check for array access: name(index)
-                if self.current == Token::LParen {
-                    self.advance();
-                    let index = self.parse_expression()?;
-                    if self.current != Token::RParen {
-                        return Err(ParseError("Expected ')' after array index".into()));
-                    }
-                    self.advance();
-                    return Ok(Expr::ArrayAccess { name, index: Box::new(index) });
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after VAL".into()));
                 }
-                Ok(Expr::Variable(name))
+                self.advance();
+                let arg = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after VAL".into()));
+                }
+                self.advance();
+                Ok(Expr::Val(Box::new(arg)))
             }
-            Token::Mem => {
+            Token::Urldecode => {
                 self.advance();
                 if self.current != Token::LParen {
-                    return Err(ParseError("Expected '(' after MEM".into()));
+                    return Err(ParseError("Expected '(' after URLDECODE$".into()));
                 }
                 self.advance();
                 let arg = self.parse_expression()?;
                 if self.current != Token::RParen {
-                    return Err(ParseError("Expected ')'".into()));
+                    return Err(ParseError("Expected ')' after URLDECODE$".into()));
                 }
                 self.advance();
-                Ok(Expr::Mem(Box::new(arg)))
+                Ok(Expr::UrlDecode(Box::new(arg)))
             }
-            // String functions
-            Token::Chr => {
+            Token::Urlencode => {
                 self.advance();
                 if self.current != Token::LParen {
-                    return Err(ParseError("Expected '(' after CHR$".into()));
+                    return Err(ParseError("Expected '(' after URLENCODE$".into()));
                 }
                 self.advance();
                 let arg = self.parse_expression()?;
                 if self.current != Token::RParen {
-                    return Err(ParseError("Expected ')'".into()));
+                    return Err(ParseError("Expected ')' after URLENCODE$".into()));
                 }
                 self.advance();
-                Ok(Expr::Chr(Box::new(arg)))
+                Ok(Expr::UrlEncode(Box::new(arg)))
             }
-            Token::Asc => {
+            Token::Crc32 => {
                 self.advance();
                 if self.current != Token::LParen {
-                    return Err(ParseError("Expected '(' after ASC".into()));
+                    return Err(ParseError("Expected '(' after CRC32".into()));
                 }
                 self.advance();
                 let arg = self.parse_expression()?;
                 if self.current != Token::RParen {
-                    return Err(ParseError("Expected ')'".into()));
+                    return Err(ParseError("Expected ')' after CRC32".into()));
                 }
                 self.advance();
-                Ok(Expr::Asc(Box::new(arg)))
+                Ok(Expr::Crc32(Box::new(arg)))
             }
-            Token::Len => {
+            Token::Checksum => {
                 self.advance();
                 if self.current != Token::LParen {
-                    return Err(ParseError("Expected '(' after LEN".into()));
+                    return Err(ParseError("Expected '(' after CHECKSUM".into()));
                 }
                 self.advance();
                 let arg = self.parse_expression()?;
                 if self.current != Token::RParen {
-                    return Err(ParseError("Expected ')'".into()));
+                    return Err(ParseError("Expected ')' after CHECKSUM".into()));
                 }
                 self.advance();
-                Ok(Expr::Len(Box::new(arg)))
+                Ok(Expr::Checksum(Box::new(arg)))
             }
-            Token::Mid => {
+            Token::Base64 => {
                 self.advance();
                 if self.current != Token::LParen {
-                    return Err(ParseError("Expected '(' after MID$".into()));
+                    return Err(ParseError("Expected '(' after BASE64$".into()));
                 }
                 self.advance();
-                let s = self.parse_expression()?;
-                if self.current != Token::Comma {
-                    return Err(ParseError("Expected ',' in MID$".into()));
+                let arg = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after BASE64$".into()));
                 }
                 self.advance();
-                let start = self.parse_expression()?;
-                if self.current != Token::Comma {
-                    return Err(ParseError("Expected ',' in MID$".into()));
+                Ok(Expr::Base64(Box::new(arg)))
+            }
+            Token::Unbase64 => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after UNBASE64$".into()));
                 }
                 self.advance();
-                let len = self.parse_expression()?;
+                let arg = self.parse_expression()?;
                 if self.current != Token::RParen {
-                    return Err(ParseError("Expected ')' after MID$".into()));
+                    return Err(ParseError("Expected ')' after UNBASE64$".into()));
                 }
                 self.advance();
-                Ok(Expr::Mid(Box::new(s), Box::new(start), Box::new(len)))
+                Ok(Expr::Unbase64(Box::new(arg)))
             }
-            Token::Left => {
+            Token::Getfield => {
                 self.advance();
                 if self.current != Token::LParen {
-                    return Err(ParseError("Expected '(' after LEFT$".into()));
+                    return Err(ParseError("Expected '(' after GETFIELD$".into()));
                 }
                 self.advance();
-                let s = self.parse_expression()?;
+                let body = self.parse_expression()?;
                 if self.current != Token::Comma {
-                    return Err(ParseError("Expected ',' in LEFT$".into()));
+                    return Err(ParseError("Expected ',' in GETFIELD$".into()));
                 }
                 self.advance();
-                let n = self.parse_expression()?;
+                let key = self.parse_expression()?;
                 if self.current != Token::RParen {
-                    return Err(ParseError("Expected ')' after LEFT$".into()));
+                    return Err(ParseError("Expected ')' after GETFIELD$".into()));
                 }
                 self.advance();
-                Ok(Expr::Left(Box::new(s), Box::new(n)))
+                Ok(Expr::GetField(Box::new(body), Box::new(key)))
             }
-            Token::Instr => {
+            // Graphics
+            Token::Fbaddr => {
                 self.advance();
-                if self.current != Token::LParen {
-                    return Err(ParseError("Expected '(' after INSTR".into()));
+                Ok(Expr::FbAddr)
+            }
+            Token::Fbwidth => {
+                self.advance();
+                Ok(Expr::FbWidth)
+            }
+            Token::Fbheight => {
+                self.advance();
+                Ok(Expr::FbHeight)
+            }
+            // Terminal
+            Token::Pos => {
+                self.advance();
+                // Classic BASIC takes a dummy argument: POS(0)
+                if self.current == Token::LParen {
+                    self.advance();
+                    self.parse_expression()?;
+                    if self.current != Token::RParen {
+                        return Err(ParseError("Expected ')' after POS".into()));
+                    }
+                    self.advance();
                 }
+                Ok(Expr::Pos)
+            }
+            Token::Csrlin => {
                 self.advance();
-                let haystack = self.parse_expression()?;
-                if self.current != Token::Comma {
-                    return Err(ParseError("Expected ',' in INSTR".into()));
+                Ok(Expr::Csrlin)
+            }
+            // Time
+            Token::Uptime => {
+                self.advance();
+                Ok(Expr::Uptime)
+            }
+            Token::Ticks => {
+                self.advance();
+                Ok(Expr::Ticks)
+            }
+            // Introspection
+            Token::Typeof => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after TYPEOF$".into()));
                 }
                 self.advance();
-                let needle = self.parse_expression()?;
+                let arg = self.parse_expression()?;
                 if self.current != Token::RParen {
-                    return Err(ParseError("Expected ')' after INSTR".into()));
+                    return Err(ParseError("Expected ')' after TYPEOF$".into()));
                 }
                 self.advance();
-                Ok(Expr::Instr(Box::new(haystack), Box::new(needle)))
+                Ok(Expr::TypeOf(Box::new(arg)))
             }
-            Token::Str => {
+            Token::Isdef => {
                 self.advance();
                 if self.current != Token::LParen {
-                    return Err(ParseError("Expected '(' after STR$".into()));
+                    return Err(ParseError("Expected '(' after ISDEF".into()));
                 }
                 self.advance();
                 let arg = self.parse_expression()?;
                 if self.current != Token::RParen {
-                    return Err(ParseError("Expected ')' after STR$".into()));
+                    return Err(ParseError("Expected ')' after ISDEF".into()));
                 }
                 self.advance();
-                Ok(Expr::Str(Box::new(arg)))
+                Ok(Expr::IsDef(Box::new(arg)))
             }
-            Token::Val => {
+            Token::Environ => {
                 self.advance();
                 if self.current != Token::LParen {
-                    return Err(ParseError("Expected '(' after VAL".into()));
+                    return Err(ParseError("Expected '(' after ENVIRON$".into()));
                 }
                 self.advance();
                 let arg = self.parse_expression()?;
                 if self.current != Token::RParen {
-                    return Err(ParseError("Expected ')' after VAL".into()));
+                    return Err(ParseError("Expected ')' after ENVIRON$".into()));
                 }
                 self.advance();
-                Ok(Expr::Val(Box::new(arg)))
+                Ok(Expr::Environ(Box::new(arg)))
+            }
+            // Files
+            Token::Readfile => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after READFILE$".into()));
+                }
+                self.advance();
+                let name = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after READFILE$".into()));
+                }
+                self.advance();
+                Ok(Expr::ReadFile(Box::new(name)))
+            }
+            Token::Filesize => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after FILESIZE".into()));
+                }
+                self.advance();
+                let name = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after FILESIZE".into()));
+                }
+                self.advance();
+                Ok(Expr::FileSize(Box::new(name)))
+            }
+            Token::Err => {
+                self.advance();
+                Ok(Expr::Err)
             }
             // Network functions
             Token::Socket => {
@@ -782,6 +1896,206 @@ impl<'a> Parser<'a> {
                 self.advance();
                 Ok(Expr::Sockstate(Box::new(sock)))
             }
+            Token::Avail => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after AVAIL".into()));
+                }
+                self.advance();
+                let sock = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after AVAIL".into()));
+                }
+                self.advance();
+                Ok(Expr::Avail(Box::new(sock)))
+            }
+            Token::Peek => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after PEEK$".into()));
+                }
+                self.advance();
+                let sock = self.parse_expression()?;
+                if self.current != Token::Comma {
+                    return Err(ParseError("Expected ',' in PEEK$".into()));
+                }
+                self.advance();
+                let n = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after PEEK$".into()));
+                }
+                self.advance();
+                Ok(Expr::Peek(Box::new(sock), Box::new(n)))
+            }
+            Token::Recvline => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after RECVLINE$".into()));
+                }
+                self.advance();
+                let sock = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after RECVLINE$".into()));
+                }
+                self.advance();
+                Ok(Expr::Recvline(Box::new(sock)))
+            }
+            Token::Udpsocket => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after UDPSOCKET".into()));
+                }
+                self.advance();
+                let port = if self.current == Token::RParen {
+                    None
+                } else {
+                    Some(Box::new(self.parse_expression()?))
+                };
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after UDPSOCKET".into()));
+                }
+                self.advance();
+                Ok(Expr::UdpSocket(port))
+            }
+            Token::Resolve => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after RESOLVE$".into()));
+                }
+                self.advance();
+                let host = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after RESOLVE$".into()));
+                }
+                self.advance();
+                Ok(Expr::Dns(Box::new(host)))
+            }
+            Token::Nslookup => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after NSLOOKUP".into()));
+                }
+                self.advance();
+                let host = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after NSLOOKUP".into()));
+                }
+                self.advance();
+                Ok(Expr::NsLookup(Box::new(host)))
+            }
+            Token::Ipconfig => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after IPCONFIG$".into()));
+                }
+                self.advance();
+                let which = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after IPCONFIG$".into()));
+                }
+                self.advance();
+                Ok(Expr::IpConfig(Box::new(which)))
+            }
+            Token::Netstat => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after NETSTAT".into()));
+                }
+                self.advance();
+                let which = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after NETSTAT".into()));
+                }
+                self.advance();
+                Ok(Expr::Netstat(Box::new(which)))
+            }
+            Token::Recvfrom => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after RECVFROM$".into()));
+                }
+                self.advance();
+                let sock = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after RECVFROM$".into()));
+                }
+                self.advance();
+                Ok(Expr::RecvFrom(Box::new(sock)))
+            }
+            Token::Httpheader => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after HTTPHEADER$".into()));
+                }
+                self.advance();
+                let status = self.parse_expression()?;
+                if self.current != Token::Comma {
+                    return Err(ParseError("Expected ',' in HTTPHEADER$".into()));
+                }
+                self.advance();
+                let content_type = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after HTTPHEADER$".into()));
+                }
+                self.advance();
+                Ok(Expr::HttpHeader(Box::new(status), Box::new(content_type)))
+            }
+            Token::Count => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after COUNT".into()));
+                }
+                self.advance();
+                let array = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after COUNT".into()));
+                }
+                self.advance();
+                Ok(Expr::Count(Box::new(array)))
+            }
+            Token::Join => {
+                self.advance();
+                if self.current != Token::LParen {
+                    return Err(ParseError("Expected '(' after JOIN$".into()));
+                }
+                self.advance();
+                let array = self.parse_expression()?;
+                if self.current != Token::Comma {
+                    return Err(ParseError("Expected ',' in JOIN$".into()));
+                }
+                self.advance();
+                let delim = self.parse_expression()?;
+                if self.current != Token::RParen {
+                    return Err(ParseError("Expected ')' after JOIN$".into()));
+                }
+                self.advance();
+                Ok(Expr::Join(Box::new(array), Box::new(delim)))
+            }
+            Token::LBrace => {
+                self.advance();
+                let mut items = Vec::new();
+                if self.current != Token::RBrace {
+                    loop {
+                        if items.len() >= MAX_ARRAY_LITERAL {
+                            return Err(ParseError(alloc::format!(
+                                "Array literal longer than {} elements",
+                                MAX_ARRAY_LITERAL
+                            )));
+                        }
+                        items.push(self.parse_expression()?);
+                        if self.current == Token::Comma {
+                            self.advance();
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                if self.current != Token::RBrace {
+                    return Err(ParseError("Expected '}' after array literal".into()));
+                }
+                self.advance();
+                Ok(Expr::ArrayLit(items))
+            }
             Token::LParen => {
                 self.advance();
                 let expr = self.parse_expression()?;
@@ -798,6 +2112,11 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Check if the current token ends a statement (newline, EOF or comment)
+    fn at_statement_end(&self) -> bool {
+        matches!(self.current, Token::Newline | Token::Eof | Token::Rem | Token::Else)
+    }
+
     /// Check if current token is end of input
     pub fn is_eof(&self) -> bool {
         self.current == Token::Eof
@@ -1,4 +1,7 @@
+use alloc::string::String;
 use core::fmt;
+use crate::net::tcp;
+use crate::scheduler;
 
 /// Result of polling for a single input byte.
 pub enum ReadStatus {
@@ -10,18 +13,137 @@ pub enum ReadStatus {
     Eof,
 }
 
+/// Result of reading a line with a deadline.
+pub enum LineStatus {
+    /// A complete line (without the terminator).
+    Line(String),
+    /// The stream closed before a line was read.
+    Eof,
+    /// The deadline passed first; the partial line is discarded.
+    TimedOut,
+}
+
+/// Default distance between tab stops.
+pub const DEFAULT_TAB_WIDTH: usize = 8;
+
 /// A terminal for the BASIC REPL: non-blocking input + formatted output.
 pub trait Terminal: fmt::Write {
     fn poll_byte(&mut self) -> ReadStatus;
+
+    /// Wait for a byte, yielding to other tasks, until `deadline_tick`
+    ///
+    /// Returns `NoData` once `timer::ticks()` reaches the deadline; pass
+    /// `u64::MAX` to wait indefinitely.
+    fn read_byte_timeout(&mut self, deadline_tick: u64) -> ReadStatus {
+        loop {
+            match self.poll_byte() {
+                ReadStatus::NoData => {
+                    if crate::timer::ticks() >= deadline_tick {
+                        return ReadStatus::NoData;
+                    }
+                    scheduler::yield_now();
+                }
+                status => return status,
+            }
+        }
+    }
+
+    /// Current output position as zero-based (column, row).
+    fn cursor(&self) -> (usize, usize);
+
+    /// Set the distance between tab stops used to expand '\t'.
+    fn set_tab_width(&mut self, width: usize);
+}
+
+/// Tracks the output position of a terminal and expands tabs.
+///
+/// Rows only ever count up (there is no knowledge of the screen height);
+/// columns reset on CR/LF and step back on backspace.
+pub struct OutputCursor {
+    column: usize,
+    row: usize,
+    tab_width: usize,
+}
+
+impl OutputCursor {
+    pub const fn new() -> Self {
+        OutputCursor {
+            column: 0,
+            row: 0,
+            tab_width: DEFAULT_TAB_WIDTH,
+        }
+    }
+
+    pub fn position(&self) -> (usize, usize) {
+        (self.column, self.row)
+    }
+
+    pub fn set_tab_width(&mut self, width: usize) {
+        self.tab_width = width.max(1);
+    }
+
+    /// Pass `s` to `sink` with tabs expanded to spaces, updating the position.
+    pub fn write(&mut self, s: &str, sink: &mut dyn FnMut(&str) -> fmt::Result) -> fmt::Result {
+        const SPACES: &str = "                ";
+        let mut start = 0;
+        for (i, ch) in s.char_indices() {
+            match ch {
+                '\t' => {
+                    sink(&s[start..i])?;
+                    let mut pad = self.tab_width - self.column % self.tab_width;
+                    self.column += pad;
+                    while pad > 0 {
+                        let n = pad.min(SPACES.len());
+                        sink(&SPACES[..n])?;
+                        pad -= n;
+                    }
+                    start = i + 1;
+                }
+                '\n' => {
+                    self.column = 0;
+                    self.row += 1;
+                }
+                '\r' => self.column = 0,
+                '\x08' => self.column = self.column.saturating_sub(1),
+                c if !c.is_control() => self.column += 1,
+                _ => {}
+            }
+        }
+        sink(&s[start..])
+    }
+}
+
+impl Default for OutputCursor {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// Serial-backed terminal (COM1).
-pub struct SerialTerminal;
+pub struct SerialTerminal {
+    cursor: OutputCursor,
+}
+
+impl SerialTerminal {
+    pub const fn new() -> Self {
+        SerialTerminal {
+            cursor: OutputCursor::new(),
+        }
+    }
+}
+
+impl Default for SerialTerminal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl fmt::Write for SerialTerminal {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        crate::serial::SERIAL.write_str(s);
-        Ok(())
+        self.cursor.write(s, &mut |chunk| {
+            crate::serial::SERIAL.write_str(chunk);
+            Ok(())
+        })
     }
 }
 
@@ -33,5 +155,132 @@ impl Terminal for SerialTerminal {
             ReadStatus::NoData
         }
     }
+
+    fn cursor(&self) -> (usize, usize) {
+        self.cursor.position()
+    }
+
+    fn set_tab_width(&mut self, width: usize) {
+        self.cursor.set_tab_width(width);
+    }
 }
 
+
+/// Terminal on a TCP socket, used for BASIC channels (OPEN ch AS SOCKET)
+///
+/// Unlike the telnet terminal there is no protocol handling: bytes pass
+/// through unchanged.
+pub struct SocketTerminal {
+    sock: usize,
+    cursor: OutputCursor,
+}
+
+impl SocketTerminal {
+    pub const fn new(sock: usize) -> Self {
+        SocketTerminal {
+            sock,
+            cursor: OutputCursor::new(),
+        }
+    }
+}
+
+impl fmt::Write for SocketTerminal {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let sock = self.sock;
+        self.cursor.write(s, &mut |chunk| {
+            let mut bytes = chunk.as_bytes();
+            while !bytes.is_empty() {
+                let n = tcp::send(sock, bytes);
+                if n < 0 {
+                    return Err(fmt::Error);
+                }
+                if n == 0 {
+                    // Send buffer full; let the network task drain it
+                    scheduler::yield_now();
+                    continue;
+                }
+                bytes = &bytes[n as usize..];
+            }
+            Ok(())
+        })
+    }
+}
+
+impl Terminal for SocketTerminal {
+    fn poll_byte(&mut self) -> ReadStatus {
+        let mut byte = [0u8; 1];
+        match tcp::recv(self.sock, &mut byte) {
+            n if n < 0 => ReadStatus::Eof,
+            0 => {
+                // About to wait for input: get any prompt out now
+                tcp::flush(self.sock);
+                ReadStatus::NoData
+            }
+            _ => ReadStatus::Byte(byte[0]),
+        }
+    }
+
+    fn cursor(&self) -> (usize, usize) {
+        self.cursor.position()
+    }
+
+    fn set_tab_width(&mut self, width: usize) {
+        self.cursor.set_tab_width(width);
+    }
+}
+
+/// Read a line from a terminal, yielding while waiting for input.
+///
+/// With `echo`, typed characters are echoed and backspace edits the line
+/// (for interactive terminals), and either CR or LF ends the line.
+/// Without it, the line ends at LF and CRs are dropped, so CRLF-terminated
+/// network input reads as one line.
+///
+/// Returns None on EOF (e.g., telnet disconnect) before any input.
+pub fn read_line(term: &mut dyn Terminal, echo: bool) -> Option<String> {
+    match read_line_until(term, echo, u64::MAX) {
+        LineStatus::Line(line) => Some(line),
+        LineStatus::Eof | LineStatus::TimedOut => None,
+    }
+}
+
+/// Like `read_line`, but give up once `timer::ticks()` reaches `deadline_tick`
+pub fn read_line_until(term: &mut dyn Terminal, echo: bool, deadline_tick: u64) -> LineStatus {
+    let mut line = String::new();
+
+    loop {
+        let byte = match term.read_byte_timeout(deadline_tick) {
+            ReadStatus::Byte(b) => b,
+            ReadStatus::NoData => return LineStatus::TimedOut,
+            ReadStatus::Eof if echo || line.is_empty() => return LineStatus::Eof,
+            ReadStatus::Eof => break,
+        };
+
+        match byte {
+            b'\r' if !echo => {}
+            b'\r' | b'\n' => {
+                if echo {
+                    let _ = writeln!(term); // Echo newline
+                }
+                break;
+            }
+            8 | 127 if echo => {
+                // Backspace or DEL
+                if !line.is_empty() {
+                    line.pop();
+                    let _ = write!(term, "\x08 \x08"); // Erase character
+                }
+            }
+            b if (32..127).contains(&b) => {
+                // Printable ASCII
+                line.push(b as char);
+                if echo {
+                    let _ = write!(term, "{}", b as char); // Echo
+                }
+            }
+            _ => {}
+        }
+    }
+
+    LineStatus::Line(line)
+}
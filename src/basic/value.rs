@@ -4,13 +4,18 @@ use alloc::string::String;
 use alloc::vec::Vec;
 use core::fmt;
 
-/// A BASIC value (integer, string, or array)
+/// A BASIC value (integer, float, string, bytes, or array)
 #[derive(Clone, Debug)]
 pub enum Value {
     /// Integer value
     Integer(i64),
+    /// Floating-point value
+    Float(f64),
     /// String value
     String(String),
+    /// Raw bytes (file contents or decoded base64 that need not be UTF-8);
+    /// behaves as a string
+    Bytes(Vec<u8>),
     /// Integer array
     IntArray(Vec<i64>),
     /// String array
@@ -18,31 +23,69 @@ pub enum Value {
 }
 
 impl Value {
-    /// Get integer value, or None if not an integer
+    /// Get integer value (floats are truncated toward zero), or None if not numeric
     pub fn as_integer(&self) -> Option<i64> {
         match self {
             Value::Integer(n) => Some(*n),
+            Value::Float(x) => Some(*x as i64),
             Value::String(_) => None,
+            Value::Bytes(_) => None,
             Value::IntArray(_) => None,
             Value::StringArray(_) => None,
         }
     }
 
+    /// Get numeric value as a float, or None if not numeric
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Integer(n) => Some(*n as f64),
+            Value::Float(x) => Some(*x),
+            _ => None,
+        }
+    }
+
     /// Get string value, or None if not a string
+    ///
+    /// Bytes are converted, with invalid UTF-8 replaced by U+FFFD.
     pub fn as_string(&self) -> Option<String> {
         match self {
             Value::Integer(_) => None,
+            Value::Float(_) => None,
             Value::String(s) => Some(s.clone()),
+            Value::Bytes(b) => Some(String::from_utf8_lossy(b).into_owned()),
             Value::IntArray(_) => None,
             Value::StringArray(_) => None,
         }
     }
 
+    /// Get the raw bytes of a string or bytes value
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::String(s) => Some(s.as_bytes()),
+            Value::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Name of the value's type, as returned by TYPEOF$
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Integer(_) => "INT",
+            Value::Float(_) => "FLOAT",
+            Value::String(_) => "STRING",
+            Value::Bytes(_) => "BYTES",
+            Value::IntArray(_) => "INTARRAY",
+            Value::StringArray(_) => "STRINGARRAY",
+        }
+    }
+
     /// Check if value is truthy (non-zero or non-empty)
     pub fn is_truthy(&self) -> bool {
         match self {
             Value::Integer(n) => *n != 0,
+            Value::Float(x) => *x != 0.0,
             Value::String(s) => !s.is_empty(),
+            Value::Bytes(b) => !b.is_empty(),
             Value::IntArray(arr) => !arr.is_empty(),
             Value::StringArray(arr) => !arr.is_empty(),
         }
@@ -53,9 +96,30 @@ impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Value::Integer(n) => write!(f, "{}", n),
+            // Shortest form that reads back exactly: 1.5, 0.1, 2
+            Value::Float(x) => write!(f, "{}", x),
             Value::String(s) => write!(f, "{}", s),
-            Value::IntArray(_) => write!(f, "[Array]"),
-            Value::StringArray(_) => write!(f, "[Array]"),
+            Value::Bytes(b) => write!(f, "{}", String::from_utf8_lossy(b)),
+            Value::IntArray(arr) => {
+                write!(f, "[")?;
+                for (i, n) in arr.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", n)?;
+                }
+                write!(f, "]")
+            }
+            Value::StringArray(arr) => {
+                write!(f, "[")?;
+                for (i, s) in arr.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "\"{}\"", s)?;
+                }
+                write!(f, "]")
+            }
         }
     }
 }
@@ -0,0 +1,43 @@
+//! Per-task capabilities
+//!
+//! Each task holds a bitmask of the kernel API services it may use. Tasks
+//! have every capability unless they were restricted at spawn time (e.g.
+//! BASIC `SPAWN ... RESTRICTED`), so only the exceptions are stored.
+
+use alloc::collections::BTreeMap;
+use crate::allocator::Spinlock;
+use crate::scheduler;
+use crate::task::TaskId;
+
+/// May use the net_* API
+pub const CAP_NET: u32 = 1 << 0;
+/// May allocate memory with the alloc API
+pub const CAP_ALLOC: u32 = 1 << 1;
+/// Every capability (the default)
+pub const CAP_ALL: u32 = CAP_NET | CAP_ALLOC;
+
+/// Capabilities of tasks that don't have all of them
+static RESTRICTED: Spinlock<BTreeMap<TaskId, u32>> = Spinlock::new(BTreeMap::new());
+
+/// Capabilities of `task`
+pub fn get(task: TaskId) -> u32 {
+    RESTRICTED.lock().get(&task).copied().unwrap_or(CAP_ALL)
+}
+
+/// Take `caps` away from `task`
+pub fn drop_caps(task: TaskId, caps: u32) {
+    let remaining = get(task) & !caps;
+    if remaining != CAP_ALL {
+        RESTRICTED.lock().insert(task, remaining);
+    }
+}
+
+/// Whether the current task holds `cap` (kernel context holds everything)
+pub fn current_has(cap: u32) -> bool {
+    scheduler::current_task_id().is_none_or(|task| get(task) & cap == cap)
+}
+
+/// Forget a task's restrictions (called when the task is reaped)
+pub fn task_exited(task: TaskId) {
+    RESTRICTED.lock().remove(&task);
+}
@@ -2,25 +2,27 @@
 //!
 //! Switches execution between tasks by saving/restoring callee-saved registers.
 //!
-//! # SIMD State Warning
+//! # SIMD State
 //!
 //! This context switch implementation only saves/restores general-purpose
 //! callee-saved registers (r15, r14, r13, r12, rbx, rbp, rsp). It does NOT
 //! save SSE/AVX state (XMM0-15, YMM0-15).
 //!
-//! **Implications:**
-//! - SIMD registers are NOT preserved across context switches
-//! - Tasks using SIMD/floating-point may see corrupted values after yielding
-//! - The Rust compiler may use SSE for memcpy/floating-point operations
+//! **Why floating point still survives a switch:**
+//! - Tasks are never preempted; they only switch inside an ordinary call
+//!   to `context_switch`
+//! - The System V ABI makes every XMM register caller-saved, so the compiler
+//!   has already spilled any live float (e.g. a BASIC `Value::Float`) to the
+//!   task's stack before the call
+//! - MXCSR and the x87 control word are callee-saved, but nothing changes
+//!   them from their defaults, so every task sees the same values
 //!
-//! **Current mitigations:**
-//! - Target spec disables advanced SSE extensions (-sse3, -sse4, -avx, etc.)
-//! - Most BASIC interpreter code uses only integer operations
-//!
-//! **Future solutions (if SIMD support needed):**
+//! **If that changes (preemption, or code that sets rounding modes):**
 //! - Use FXSAVE/FXRSTOR to save 512 bytes of FPU/SSE state per task
 //! - Or add `+soft-float` to target spec to disable hardware FP entirely
 
+use core::sync::atomic::{AtomicUsize, Ordering};
+use crate::scheduler;
 use crate::task::Context;
 
 /// Switch from the current task's context to the next task's context
@@ -76,3 +78,116 @@ pub unsafe extern "C" fn switch_context(
         "ret",
     )
 }
+
+/// Yields performed by each self-test task
+const SELF_TEST_ROUNDS: usize = 100;
+
+/// Self-test tasks still running
+static SELF_TEST_PENDING: AtomicUsize = AtomicUsize::new(0);
+/// Rounds in which a callee-saved register came back changed
+static SELF_TEST_FAILURES: AtomicUsize = AtomicUsize::new(0);
+
+/// Spawn the boot-time context switch self-test
+///
+/// Two tasks load distinct sentinel values into every callee-saved
+/// register (rbx, rbp, r12-r15), yield to each other, and check the values
+/// survived the round trip. The last one to finish prints PASS or FAIL.
+pub fn spawn_self_test() -> bool {
+    SELF_TEST_PENDING.store(2, Ordering::SeqCst);
+    SELF_TEST_FAILURES.store(0, Ordering::SeqCst);
+    let a = scheduler::spawn_with_arg("ctxtest-a", self_test_task, 1);
+    let b = scheduler::spawn_with_arg("ctxtest-b", self_test_task, 2);
+    a.is_some() && b.is_some()
+}
+
+fn self_test_task(which: usize) {
+    for round in 0..SELF_TEST_ROUNDS {
+        let mut sentinels = [0u64; 6];
+        for (reg, s) in sentinels.iter_mut().enumerate() {
+            *s = 0x5A5A_0000_0000_0000
+                | (which as u64) << 32
+                | (round as u64) << 8
+                | reg as u64;
+        }
+        // Safety: yield_with_sentinels only reads the six sentinels
+        if unsafe { yield_with_sentinels(&sentinels) } != 0 {
+            SELF_TEST_FAILURES.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    if SELF_TEST_PENDING.fetch_sub(1, Ordering::SeqCst) == 1 {
+        let failures = SELF_TEST_FAILURES.load(Ordering::SeqCst);
+        if failures == 0 {
+            crate::println!(
+                "Context switch self-test: PASS ({} yields)", 2 * SELF_TEST_ROUNDS);
+        } else {
+            crate::println!(
+                "Context switch self-test: FAIL ({} of {} yields corrupted registers)",
+                failures, 2 * SELF_TEST_ROUNDS);
+        }
+    }
+}
+
+extern "C" fn self_test_yield() {
+    scheduler::yield_now();
+}
+
+/// Load `sentinels` into rbx, rbp, r12-r15, yield, and compare
+///
+/// Returns zero if every register still holds its sentinel. The original
+/// register values are restored either way.
+///
+/// # Safety
+/// Runs in a scheduler task; rbp is not a valid frame pointer during the
+/// yield, so nothing may unwind or backtrace through it.
+unsafe fn yield_with_sentinels(sentinels: &[u64; 6]) -> u64 {
+    let diff: u64;
+    core::arch::asm!(
+        // Align the stack for the call, keeping the old rsp for the end
+        "mov rax, rsp",
+        "and rsp, -16",
+        "push rax",
+        "push rax",
+        "push rbx",
+        "push rbp",
+        "push r12",
+        "push r13",
+        "push r14",
+        "push r15",
+        "push rdi",
+        "push rdi",
+        "mov rbx, [rdi + 0]",
+        "mov rbp, [rdi + 8]",
+        "mov r12, [rdi + 16]",
+        "mov r13, [rdi + 24]",
+        "mov r14, [rdi + 32]",
+        "mov r15, [rdi + 40]",
+        "call {yield_fn}",
+        "pop rdi",
+        "pop rdi",
+        "xor rbx, [rdi + 0]",
+        "xor rbp, [rdi + 8]",
+        "xor r12, [rdi + 16]",
+        "xor r13, [rdi + 24]",
+        "xor r14, [rdi + 32]",
+        "xor r15, [rdi + 40]",
+        "mov rax, rbx",
+        "or rax, rbp",
+        "or rax, r12",
+        "or rax, r13",
+        "or rax, r14",
+        "or rax, r15",
+        "pop r15",
+        "pop r14",
+        "pop r13",
+        "pop r12",
+        "pop rbp",
+        "pop rbx",
+        "pop rsp",
+        yield_fn = sym self_test_yield,
+        inout("rdi") sentinels.as_ptr() => _,
+        out("rax") diff,
+        clobber_abi("C"),
+    );
+    diff
+}
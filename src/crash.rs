@@ -0,0 +1,163 @@
+//! Kernel panic dump
+//!
+//! Prints what is needed to debug a crash after the fact: the panic
+//! message, the task that was running, the general-purpose registers and a
+//! frame-pointer backtrace. Everything here avoids the heap and the
+//! scheduler's panicking accessors, since either may be what broke.
+
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::{println, scheduler};
+
+/// Maximum number of frames printed in the backtrace
+const MAX_FRAMES: usize = 16;
+
+/// Frame pointers outside this range can't be on a stack (identity-mapped
+/// RAM ends at 16MB; page zero is never mapped in use)
+const STACK_MIN: u64 = 0x1000;
+const STACK_MAX: u64 = 0x0100_0000;
+
+/// Largest plausible distance between two consecutive frames
+const MAX_FRAME_SIZE: u64 = 64 * 1024;
+
+/// Set on the first panic so a panic inside the dump doesn't recurse
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+/// Registers as seen on entry to the panic handler
+#[derive(Default)]
+struct Registers {
+    gpr: [u64; 16],
+    rip: u64,
+    rflags: u64,
+    cr2: u64,
+    cr3: u64,
+}
+
+const GPR_NAMES: [&str; 16] = [
+    "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp",
+    "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15",
+];
+
+impl Registers {
+    /// Capture the current register state
+    ///
+    /// Must be inlined into the panic handler so rsp/rbp describe the
+    /// panicking stack. The register holding the buffer pointer reads back
+    /// as the pointer itself.
+    #[inline(always)]
+    fn capture() -> Self {
+        let mut regs = Registers::default();
+        // Safety: only stores into `regs` and reads control registers
+        unsafe {
+            core::arch::asm!(
+                "mov [{p} + 0x00], rax",
+                "mov [{p} + 0x08], rbx",
+                "mov [{p} + 0x10], rcx",
+                "mov [{p} + 0x18], rdx",
+                "mov [{p} + 0x20], rsi",
+                "mov [{p} + 0x28], rdi",
+                "mov [{p} + 0x30], rbp",
+                "mov [{p} + 0x38], rsp",
+                "mov [{p} + 0x40], r8",
+                "mov [{p} + 0x48], r9",
+                "mov [{p} + 0x50], r10",
+                "mov [{p} + 0x58], r11",
+                "mov [{p} + 0x60], r12",
+                "mov [{p} + 0x68], r13",
+                "mov [{p} + 0x70], r14",
+                "mov [{p} + 0x78], r15",
+                p = in(reg) regs.gpr.as_mut_ptr(),
+                options(nostack, preserves_flags),
+            );
+            core::arch::asm!(
+                "lea {rip}, [rip]",
+                "pushfq",
+                "pop {rflags}",
+                "mov {cr2}, cr2",
+                "mov {cr3}, cr3",
+                rip = out(reg) regs.rip,
+                rflags = out(reg) regs.rflags,
+                cr2 = out(reg) regs.cr2,
+                cr3 = out(reg) regs.cr3,
+            );
+        }
+        regs
+    }
+
+    fn rbp(&self) -> u64 {
+        self.gpr[6]
+    }
+
+    fn print(&self) {
+        for (pair, names) in self.gpr.chunks(2).zip(GPR_NAMES.chunks(2)) {
+            println!("  {:>3}={:016x}  {:>3}={:016x}", names[0], pair[0], names[1], pair[1]);
+        }
+        println!("  rip={:016x}  rfl={:016x}", self.rip, self.rflags);
+        println!("  cr2={:016x}  cr3={:016x}", self.cr2, self.cr3);
+    }
+}
+
+/// Walk the rbp chain, printing return addresses
+///
+/// Stops at the first frame pointer that doesn't look like it points into
+/// a stack, so a corrupted chain ends the trace instead of faulting.
+fn print_backtrace(mut rbp: u64) {
+    for depth in 0..MAX_FRAMES {
+        if rbp & 7 != 0 || !(STACK_MIN..STACK_MAX - 16).contains(&rbp) {
+            return;
+        }
+        // Safety: rbp is aligned and inside identity-mapped RAM
+        let (next, ret) = unsafe {
+            let frame = rbp as *const u64;
+            (frame.read(), frame.add(1).read())
+        };
+        if ret == 0 {
+            return;
+        }
+        println!("  #{:<2} {:016x}", depth, ret);
+        if next <= rbp || next - rbp > MAX_FRAME_SIZE {
+            return;
+        }
+        rbp = next;
+    }
+    println!("  ...");
+}
+
+/// Print the panic dump and halt
+pub fn panic(info: &PanicInfo) -> ! {
+    let regs = Registers::capture();
+    // Safety: nothing after this may be interrupted by task switches
+    unsafe {
+        core::arch::asm!("cli", options(nomem, nostack));
+    }
+
+    if PANICKING.swap(true, Ordering::SeqCst) {
+        println!("\n!!! NESTED PANIC: {} !!!", info);
+        halt();
+    }
+
+    println!("\n!!! KERNEL PANIC !!!");
+    println!("{}", info);
+
+    match scheduler::try_current_task() {
+        Some((id, name)) => println!("Task: {} ({})", id, name),
+        None => println!("Task: none (scheduler not running)"),
+    }
+
+    println!("Registers:");
+    regs.print();
+
+    println!("Backtrace:");
+    print_backtrace(regs.rbp());
+
+    halt();
+}
+
+fn halt() -> ! {
+    loop {
+        // Safety: interrupts are off, so this parks the CPU for good
+        unsafe {
+            core::arch::asm!("hlt", options(nomem, nostack, preserves_flags));
+        }
+    }
+}
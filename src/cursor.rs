@@ -151,6 +151,17 @@ fn draw_tooltip(x: i16, y: i16, start_addr: usize, end_addr: usize, region: &str
     }
 }
 
+/// Periodic VGA work, registered as a timer callback when VGA is enabled
+///
+/// Runs in interrupt context (see `timer::register_callback`).
+pub fn tick() {
+    // Poll mouse since IRQ12 may not be working in QEMU
+    mouse::poll();
+    update();
+    // Show anything drawn outside a cursor update (e.g. memvis changes)
+    vga::present_if_dirty();
+}
+
 /// Update cursor and tooltip (called from timer tick)
 pub fn update() {
     if !vga::is_enabled() || !mouse::is_initialized() {
@@ -185,6 +196,9 @@ pub fn update() {
         region_info.is_allocated,
     );
 
+    // Show the composed frame
+    vga::present();
+
     mouse::clear_dirty();
 }
 
@@ -214,5 +228,7 @@ pub fn init() {
         region_info.is_allocated,
     );
 
+    vga::present();
+
     mouse::clear_dirty();
 }
@@ -0,0 +1,47 @@
+//! Shared environment variables
+//!
+//! A small global key/value store for configuration shared between the
+//! BASIC REPL (SETENV / ENVIRON$) and loaded programs (getenv / setenv in
+//! the kernel API). Names are case-sensitive; the store is bounded so a
+//! runaway script can't fill the kernel heap.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use crate::allocator::Spinlock;
+
+/// Maximum number of variables
+pub const MAX_ENV_VARS: usize = 32;
+/// Maximum length of a name in bytes
+pub const MAX_ENV_NAME_LEN: usize = 32;
+/// Maximum length of a value in bytes
+pub const MAX_ENV_VALUE_LEN: usize = 256;
+
+static ENV: Spinlock<BTreeMap<String, String>> = Spinlock::new(BTreeMap::new());
+
+/// Look up a variable
+pub fn get(name: &str) -> Option<String> {
+    ENV.lock().get(name).cloned()
+}
+
+/// Set a variable; an empty value removes it
+pub fn set(name: &str, value: &str) -> Result<(), &'static str> {
+    if name.is_empty() || name.len() > MAX_ENV_NAME_LEN || name.contains('=') {
+        return Err("Invalid environment variable name");
+    }
+    if value.len() > MAX_ENV_VALUE_LEN {
+        return Err("Environment value too long");
+    }
+
+    let mut env = ENV.lock();
+    if value.is_empty() {
+        env.remove(name);
+    } else if let Some(existing) = env.get_mut(name) {
+        existing.clear();
+        existing.push_str(value);
+    } else if env.len() >= MAX_ENV_VARS {
+        return Err("Environment full");
+    } else {
+        env.insert(String::from(name), String::from(value));
+    }
+    Ok(())
+}
@@ -2,13 +2,20 @@
 //!
 //! Manages embedded executables: discovering them in the disk image,
 //! loading them into program memory, and cleaning up when they exit.
+//!
+//! The table doubles as a read-only faux filesystem for assets such as
+//! BASIC sources. Files written at runtime (`write`) go to an in-memory
+//! overlay that `read` and `list` consult first; the overlay is lost on
+//! reboot, as nothing flushes it back to the disk image.
 
+use alloc::borrow::Cow;
 use alloc::collections::BTreeMap;
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::cell::UnsafeCell;
 use core::sync::atomic::{AtomicBool, Ordering};
 
+use crate::allocator::Spinlock;
 use crate::elf;
 use crate::program_alloc;
 use crate::task::TaskId;
@@ -19,6 +26,15 @@ const EXEC_TABLE_MAGIC: [u8; 4] = *b"REXE";
 /// Maximum number of executables in the table
 const MAX_EXECUTABLES: usize = 15;
 
+/// Total bytes the writable overlay may hold
+const MAX_OVERLAY_BYTES: usize = 256 * 1024;
+
+/// Default cap on a task's program-region bytes (stack + program + heap)
+pub const DEFAULT_TASK_QUOTA: usize = 2 * 1024 * 1024;
+
+/// Files written at runtime, shadowing table entries of the same name
+static OVERLAY: Spinlock<BTreeMap<String, Vec<u8>>> = Spinlock::new(BTreeMap::new());
+
 /// Executable table header (matches disk format)
 #[repr(C)]
 struct ExecTableHeader {
@@ -76,6 +92,10 @@ pub enum ExecError {
     AllocationFailed,
     /// Invalid executable table
     InvalidTable,
+    /// Name belongs to an ELF executable, which can't be overwritten
+    ReadOnly,
+    /// Writable overlay is full
+    NoSpace,
 }
 
 impl From<elf::ElfError> for ExecError {
@@ -93,6 +113,8 @@ struct TaskAllocations {
     program: Option<(usize, usize, String)>,
     /// User heap allocations via alloc() API - list of (addr, size)
     heap_blocks: Vec<(usize, usize)>,
+    /// Maximum bytes this task may hold in total, None for no limit
+    quota: Option<usize>,
 }
 
 impl TaskAllocations {
@@ -101,8 +123,21 @@ impl TaskAllocations {
             stack: (stack_base, stack_size),
             program: None,
             heap_blocks: Vec::new(),
+            quota: Some(DEFAULT_TASK_QUOTA),
         }
     }
+
+    /// Bytes charged against the quota: stack, program and heap blocks
+    fn used_bytes(&self) -> usize {
+        let program = self.program.as_ref().map_or(0, |(_, size, _)| *size);
+        let heap: usize = self.heap_blocks.iter().map(|(_, size)| size).sum();
+        self.stack.1 + program + heap
+    }
+
+    /// Would allocating `size` more bytes stay within the quota?
+    fn fits(&self, size: usize) -> bool {
+        self.quota.is_none_or(|quota| self.used_bytes() + size <= quota)
+    }
 }
 
 /// Executable registry state
@@ -264,28 +299,38 @@ fn validate_table(addr: usize) -> bool {
     true
 }
 
-/// List all available executables
+/// List all available executables and files
+///
+/// Table entries come first, followed by files that exist only in the
+/// writable overlay.
 pub fn list() -> Vec<String> {
-    if !REGISTRY.is_initialized() {
-        return Vec::new();
-    }
+    let mut names = if REGISTRY.is_initialized() {
+        REGISTRY.with(|reg| {
+            if reg.table_addr == 0 {
+                return Vec::new();
+            }
 
-    REGISTRY.with(|reg| {
-        if reg.table_addr == 0 {
-            return Vec::new();
-        }
+            let header = unsafe { &*(reg.table_addr as *const ExecTableHeader) };
+            let mut names = Vec::new();
 
-        let header = unsafe { &*(reg.table_addr as *const ExecTableHeader) };
-        let mut names = Vec::new();
+            for i in 0..reg.exec_count {
+                let entry = &header.entries[i];
+                let name = entry_name(entry);
+                names.push(name);
+            }
 
-        for i in 0..reg.exec_count {
-            let entry = &header.entries[i];
-            let name = entry_name(entry);
-            names.push(name);
-        }
+            names
+        })
+    } else {
+        Vec::new()
+    };
 
-        names
-    })
+    for name in OVERLAY.lock().keys() {
+        if !names.contains(name) {
+            names.push(name.clone());
+        }
+    }
+    names
 }
 
 /// Get the name from an executable entry
@@ -366,15 +411,47 @@ pub fn load(name: &str) -> Result<LoadedProgram, ExecError> {
 /// Read an embedded file's raw bytes by name.
 ///
 /// The executable table is also used as a simple "faux filesystem" for
-/// non-ELF assets (e.g. BASIC `.bas` source). The caller is responsible for
-/// interpreting the returned bytes.
-pub fn read(name: &str) -> Result<&'static [u8], ExecError> {
+/// non-ELF assets (e.g. BASIC `.bas` source). Files in the writable overlay
+/// take precedence and are returned as a copy. The caller is responsible
+/// for interpreting the returned bytes.
+pub fn read(name: &str) -> Result<Cow<'static, [u8]>, ExecError> {
+    if let Some(bytes) = OVERLAY.lock().get(name) {
+        return Ok(Cow::Owned(bytes.clone()));
+    }
+
     if !REGISTRY.is_initialized() {
         return Err(ExecError::NotInitialized);
     }
 
     let (addr, size) = find_executable(name)?;
-    Ok(unsafe { core::slice::from_raw_parts(addr as *const u8, size) })
+    Ok(Cow::Borrowed(unsafe { core::slice::from_raw_parts(addr as *const u8, size) }))
+}
+
+/// Write a file to the in-memory overlay, replacing any earlier write
+///
+/// The file shadows a non-ELF table entry of the same name until reboot;
+/// nothing is written back to disk. Executables can't be replaced.
+pub fn write(name: &str, bytes: &[u8]) -> Result<(), ExecError> {
+    if REGISTRY.is_initialized() {
+        if let Ok((addr, size)) = find_executable(name) {
+            let data = unsafe { core::slice::from_raw_parts(addr as *const u8, size) };
+            if data.starts_with(b"\x7fELF") {
+                return Err(ExecError::ReadOnly);
+            }
+        }
+    }
+
+    let mut overlay = OVERLAY.lock();
+    let others: usize = overlay
+        .iter()
+        .filter(|(n, _)| n.as_str() != name)
+        .map(|(_, data)| data.len())
+        .sum();
+    if others + bytes.len() > MAX_OVERLAY_BYTES {
+        return Err(ExecError::NoSpace);
+    }
+    overlay.insert(String::from(name), Vec::from(bytes));
+    Ok(())
 }
 
 /// Register a task's stack allocation
@@ -398,10 +475,27 @@ pub fn register_task_program(task_id: TaskId, base_addr: usize, size: usize, nam
     });
 }
 
+/// Set a task's memory quota
+///
+/// `bytes` caps the task's stack, program and heap blocks together; None
+/// removes the limit. Tasks start with `DEFAULT_TASK_QUOTA`. Lowering the
+/// quota below current usage frees nothing, it only makes further
+/// allocations fail. Returns false if the task is unknown.
+pub fn set_task_quota(task_id: TaskId, bytes: Option<usize>) -> bool {
+    REGISTRY.with(|reg| match reg.task_allocations.get_mut(&task_id) {
+        Some(allocs) => {
+            allocs.quota = bytes;
+            true
+        }
+        None => false,
+    })
+}
+
 /// Allocate heap memory for a task
 ///
 /// Allocations are rounded up to 4KB multiples.
-/// Returns the allocation address, or None if allocation fails.
+/// Returns the allocation address, or None if allocation fails or would
+/// take the task over its quota.
 pub fn task_alloc(task_id: TaskId, size: usize) -> Option<usize> {
     if size == 0 {
         return None;
@@ -410,17 +504,21 @@ pub fn task_alloc(task_id: TaskId, size: usize) -> Option<usize> {
     // Round up to 4KB multiple
     let aligned_size = (size + 0xFFF) & !0xFFF;
 
-    // Allocate from program region
-    let addr = program_alloc::allocate(aligned_size)?;
-
-    // Track the allocation
     REGISTRY.with(|reg| {
-        if let Some(allocs) = reg.task_allocations.get_mut(&task_id) {
-            allocs.heap_blocks.push((addr, aligned_size));
+        let allocs = reg.task_allocations.get_mut(&task_id);
+        if allocs.as_ref().is_some_and(|a| !a.fits(aligned_size)) {
+            return None;
         }
-    });
 
-    Some(addr)
+        // Allocate from program region
+        let addr = program_alloc::allocate(aligned_size)?;
+
+        // Track the allocation
+        if let Some(allocs) = allocs {
+            allocs.heap_blocks.push((addr, aligned_size));
+        }
+        Some(addr)
+    })
 }
 
 /// Free heap memory for a task
@@ -583,3 +681,26 @@ pub fn find_task_by_program_addr(addr: usize) -> Option<TaskId> {
         None
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quota_counts_stack_program_and_heap() {
+        let mut allocs = TaskAllocations::new(0x400000, 64 * 1024);
+        allocs.program = Some((0x410000, 32 * 1024, String::from("hello")));
+        allocs.quota = Some(128 * 1024);
+        assert!(allocs.fits(32 * 1024));
+        assert!(!allocs.fits(32 * 1024 + 4096));
+
+        allocs.heap_blocks.push((0x500000, 32 * 1024));
+        assert!(!allocs.fits(4096));
+        // Freeing a block gives its headroom back
+        allocs.heap_blocks.pop();
+        assert!(allocs.fits(32 * 1024));
+
+        allocs.quota = None;
+        assert!(allocs.fits(16 * 1024 * 1024));
+    }
+}
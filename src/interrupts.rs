@@ -128,7 +128,7 @@ extern "C" fn ne2000_handler() {
     net::ne2000::handle_interrupt();
 
     // Send End-Of-Interrupt to both PICs (IRQ10 is on slave PIC)
-    pic::send_eoi(10);
+    pic::send_eoi(net::ne2000::NE2000_IRQ);
 }
 
 /// NE2000 ISR stub - saves state, calls handler, restores state
@@ -8,10 +8,14 @@ extern crate alloc;
 
 mod allocator;
 mod api;
+mod base64;
 mod basic;
+mod caps;
 mod context_switch;
+mod crash;
 mod cursor;
 mod elf;
+mod env;
 mod executable;
 mod font;
 mod gilbert;
@@ -22,8 +26,11 @@ mod meminfo;
 mod mouse;
 mod net;
 mod pic;
+mod pipe;
 mod program_alloc;
+mod rand;
 mod scheduler;
+mod screenshot;
 mod serial;
 mod task;
 mod timer;
@@ -202,6 +209,7 @@ pub extern "C" fn kernel_main() -> ! {
     // Enable CPU interrupts
     idt::enable_interrupts();
     println!("Interrupts enabled (STI)");
+    println!("TSC: {} MHz", timer::calibrate_tsc());
 
     // Initialize scheduler
     println!("\nInitializing scheduler...");
@@ -210,13 +218,15 @@ pub extern "C" fn kernel_main() -> ! {
     // Initialize network subsystem
     println!("\nInitializing network...");
     net::init();
-    if net::ne2000::init() {
-        pic::enable_irq(10);  // Enable NE2000 IRQ
-        println!("IRQ10 enabled (NE2000)");
+    if let Some(irq) = net::ne2000::init(net::ne2000::NE2000_IOBASE) {
+        pic::enable_irq(irq);  // Enable NE2000 IRQ
+        println!("IRQ{} enabled (NE2000)", irq);
     }
 
     // Initialize mouse (only useful in VGA mode)
     if vga::is_enabled() {
+        timer::register_callback(1, cursor::tick);
+
         println!("\nInitializing mouse...");
         if mouse::init() {
             pic::enable_irq(12);  // Enable PS/2 mouse IRQ
@@ -253,6 +263,11 @@ pub extern "C" fn kernel_main() -> ! {
             None => println!("  - telnetd: FAILED (out of memory)"),
         }
     }
+    if context_switch::spawn_self_test() {
+        println!("  - ctxtest-a/b: Context switch self-test");
+    } else {
+        println!("  - ctxtest-a/b: FAILED (out of memory)");
+    }
     match scheduler::spawn("basic-repl", basic::repl_task) {
         Some(_) => println!("  - basic-repl: Interactive BASIC interpreter"),
         None => println!("  - basic-repl: FAILED (out of memory)"),
@@ -267,22 +282,10 @@ pub extern "C" fn kernel_main() -> ! {
     scheduler::run()
 }
 
-/// Halt the CPU until the next interrupt
-#[inline]
-fn hlt() {
-    unsafe {
-        core::arch::asm!("hlt", options(nomem, nostack, preserves_flags));
-    }
-}
-
 /// Panic handler
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    println!("\n!!! KERNEL PANIC !!!");
-    println!("{}", info);
-    loop {
-        hlt();
-    }
+    crash::panic(info)
 }
 
 /// Allocation error handler (called when allocation fails)
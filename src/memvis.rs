@@ -146,17 +146,16 @@ pub fn get_pixel(x: usize, y: usize) -> u8 {
 }
 
 /// Redraw the entire memory visualization from shadow buffer
+///
+/// Draws into the VGA back buffer; call `vga::present()` to show it.
 pub fn redraw() {
     if !vga::is_enabled() {
         return;
     }
 
-    // Copy shadow buffer to VGA framebuffer
+    // Copy shadow buffer to the VGA back buffer
     unsafe {
-        let fb = 0xA0000 as *mut u8;
-        for i in 0..SHADOW_BUFFER.len() {
-            fb.add(i).write_volatile(SHADOW_BUFFER[i]);
-        }
+        vga::blit(&*core::ptr::addr_of!(SHADOW_BUFFER));
     }
 }
 
@@ -184,6 +183,21 @@ pub fn on_dealloc(addr: usize, size: usize) {
     draw_region(addr, size, color);
 }
 
+/// Called when an allocation is resized in place
+///
+/// Colors only the part of the block that changed state.
+pub fn on_resize(addr: usize, old_size: usize, new_size: usize) {
+    if !vga::is_enabled() {
+        return;
+    }
+
+    if new_size > old_size {
+        draw_region(addr + old_size, new_size - old_size, alloc_color_for_addr(addr));
+    } else if new_size < old_size {
+        draw_region(addr + new_size, old_size - new_size, free_color_for_addr(addr));
+    }
+}
+
 /// Draw a memory region with the specified color using Gilbert curve layout
 fn draw_region(addr: usize, size: usize, color: u8) {
     let start_d = match addr_to_gilbert_index(addr) {
@@ -2,8 +2,7 @@
 //!
 //! Handles ARP requests and replies for IPv4 over Ethernet.
 
-use crate::net::{ethernet, ne2000, CONFIG};
-use crate::println;
+use crate::net::{config, ethernet, ipv4, ne2000};
 
 /// ARP header size
 pub const HEADER_SIZE: usize = 28;
@@ -51,8 +50,8 @@ impl ArpEntry {
 /// ARP cache size
 const ARP_CACHE_SIZE: usize = 16;
 
-/// ARP cache entry timeout (5 minutes in ticks at 100 Hz)
-const ARP_TIMEOUT_TICKS: u64 = 5 * 60 * 100;
+/// ARP cache entry timeout in milliseconds (5 minutes)
+const ARP_TIMEOUT_MS: u64 = 5 * 60 * 1000;
 
 /// ARP cache
 static mut ARP_CACHE: [ArpEntry; ARP_CACHE_SIZE] = [ArpEntry::empty(); ARP_CACHE_SIZE];
@@ -124,9 +123,10 @@ impl ArpPacket {
         })
     }
 
-    /// Check if this ARP request is for our IP
+    /// Check if this ARP request is for our IP (never, while we have none)
     pub fn is_for_our_ip(&self) -> bool {
-        self.tpa == CONFIG.ip
+        let ip = config().ip;
+        self.tpa == ip && ip != ipv4::UNSPECIFIED
     }
 }
 
@@ -158,7 +158,7 @@ pub fn build_packet(
     // Sender hardware address (our MAC)
     buffer[8..14].copy_from_slice(&our_mac);
     // Sender protocol address (our IP)
-    buffer[14..18].copy_from_slice(&CONFIG.ip);
+    buffer[14..18].copy_from_slice(&config().ip);
     // Target hardware address
     buffer[18..24].copy_from_slice(target_mac);
     // Target protocol address
@@ -182,7 +182,7 @@ pub fn process_packet(data: &[u8]) {
         ARP_REQUEST => {
             // Is this request for our IP?
             if arp.is_for_our_ip() {
-                println!(
+                net_log!(Events,
                     "[arp] Request for {}.{}.{}.{} from {}.{}.{}.{}",
                     arp.tpa[0], arp.tpa[1], arp.tpa[2], arp.tpa[3],
                     arp.spa[0], arp.spa[1], arp.spa[2], arp.spa[3]
@@ -191,7 +191,7 @@ pub fn process_packet(data: &[u8]) {
             }
         }
         ARP_REPLY => {
-            println!(
+            net_log!(Events,
                 "[arp] Reply: {}.{}.{}.{} is {:02X}:{:02X}:{:02X}:{:02X}:{:02X}:{:02X}",
                 arp.spa[0], arp.spa[1], arp.spa[2], arp.spa[3],
                 arp.sha[0], arp.sha[1], arp.sha[2], arp.sha[3], arp.sha[4], arp.sha[5]
@@ -207,7 +207,7 @@ fn send_reply(dst_mac: &[u8; 6], dst_ip: &[u8; 4]) {
     build_packet(&mut arp_data, ARP_REPLY, dst_mac, dst_ip);
 
     if ethernet::send_frame(dst_mac, ethernet::ETHERTYPE_ARP, &arp_data) {
-        println!(
+        net_log!(Events,
             "[arp] Sent reply to {}.{}.{}.{}",
             dst_ip[0], dst_ip[1], dst_ip[2], dst_ip[3]
         );
@@ -221,7 +221,7 @@ pub fn send_request(target_ip: &[u8; 4]) {
     build_packet(&mut arp_data, ARP_REQUEST, &zero_mac, target_ip);
 
     if ethernet::send_frame(&ethernet::BROADCAST_MAC, ethernet::ETHERTYPE_ARP, &arp_data) {
-        println!(
+        net_log!(Events,
             "[arp] Sent request for {}.{}.{}.{}",
             target_ip[0], target_ip[1], target_ip[2], target_ip[3]
         );
@@ -236,7 +236,7 @@ pub fn lookup(ip: &[u8; 4]) -> Option<[u8; 6]> {
         for entry in ARP_CACHE.iter() {
             if entry.valid && entry.ip == *ip {
                 // Check if entry hasn't expired
-                if now.wrapping_sub(entry.timestamp) < ARP_TIMEOUT_TICKS {
+                if crate::timer::ticks_to_ms(now.wrapping_sub(entry.timestamp)) < ARP_TIMEOUT_MS {
                     return Some(entry.mac);
                 }
             }
@@ -285,13 +285,18 @@ fn update_cache(ip: &[u8; 4], mac: &[u8; 6]) {
     }
 }
 
+/// Number of valid entries in the ARP cache
+pub fn entry_count() -> usize {
+    unsafe { ARP_CACHE.iter().filter(|e| e.valid).count() }
+}
+
 /// Expire old entries from the ARP cache
 pub fn expire_old_entries() {
     let now = crate::timer::ticks();
 
     unsafe {
         for entry in ARP_CACHE.iter_mut() {
-            if entry.valid && now.wrapping_sub(entry.timestamp) >= ARP_TIMEOUT_TICKS {
+            if entry.valid && crate::timer::ticks_to_ms(now.wrapping_sub(entry.timestamp)) >= ARP_TIMEOUT_MS {
                 entry.valid = false;
             }
         }
@@ -304,13 +309,14 @@ pub fn expire_old_entries() {
 /// needs to be sent. The caller should retry after a delay.
 pub fn resolve(ip: &[u8; 4]) -> Option<[u8; 6]> {
     // Check if IP is on our network
-    let on_local_network = (ip[0] & CONFIG.netmask[0]) == (CONFIG.ip[0] & CONFIG.netmask[0])
-        && (ip[1] & CONFIG.netmask[1]) == (CONFIG.ip[1] & CONFIG.netmask[1])
-        && (ip[2] & CONFIG.netmask[2]) == (CONFIG.ip[2] & CONFIG.netmask[2])
-        && (ip[3] & CONFIG.netmask[3]) == (CONFIG.ip[3] & CONFIG.netmask[3]);
+    let cfg = config();
+    let on_local_network = (ip[0] & cfg.netmask[0]) == (cfg.ip[0] & cfg.netmask[0])
+        && (ip[1] & cfg.netmask[1]) == (cfg.ip[1] & cfg.netmask[1])
+        && (ip[2] & cfg.netmask[2]) == (cfg.ip[2] & cfg.netmask[2])
+        && (ip[3] & cfg.netmask[3]) == (cfg.ip[3] & cfg.netmask[3]);
 
     // If not on local network, resolve gateway instead
-    let target_ip = if on_local_network { *ip } else { CONFIG.gateway };
+    let target_ip = if on_local_network { *ip } else { cfg.gateway };
 
     // Check cache first
     if let Some(mac) = lookup(&target_ip) {
@@ -0,0 +1,135 @@
+//! Packet capture
+//!
+//! When enabled, every frame received or sent is summarized on one serial
+//! line, tcpdump style: direction, frame length, addresses, the TCP/UDP
+//! ports and TCP flags, and a hex/ASCII preview of the first payload
+//! bytes. Formatting goes straight to `println!` through `Display`
+//! wrappers, so nothing is allocated on the receive path.
+
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::net::{ethernet, ipv4, tcp, udp};
+use crate::println;
+
+/// Payload bytes shown in the hex/ASCII preview
+const PREVIEW_LEN: usize = 16;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turn capture on or off
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Is capture on?
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+struct Mac<'a>(&'a [u8; 6]);
+
+impl fmt::Display for Mac<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let m = self.0;
+        write!(f, "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}", m[0], m[1], m[2], m[3], m[4], m[5])
+    }
+}
+
+struct Ip<'a>(&'a [u8; 4]);
+
+impl fmt::Display for Ip<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}.{}", self.0[0], self.0[1], self.0[2], self.0[3])
+    }
+}
+
+/// TCP flags as letters (FIN, SYN, RST, PSH, ACK, URG), '.' for unset
+struct TcpFlags(u8);
+
+impl fmt::Display for TcpFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (bit, letter) in b"FSRPAU".iter().enumerate() {
+            let c = if self.0 & (1 << bit) != 0 { *letter } else { b'.' };
+            write!(f, "{}", c as char)?;
+        }
+        Ok(())
+    }
+}
+
+/// Hex and printable-ASCII view of the start of a payload
+struct Preview<'a>(&'a [u8]);
+
+impl fmt::Display for Preview<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bytes = &self.0[..self.0.len().min(PREVIEW_LEN)];
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        write!(f, " |")?;
+        for b in bytes {
+            write!(f, " {:02x}", b)?;
+        }
+        write!(f, " |")?;
+        for &b in bytes {
+            let c = if (32..127).contains(&b) { b as char } else { '.' };
+            write!(f, "{}", c)?;
+        }
+        write!(f, "|")
+    }
+}
+
+/// Print a summary of `frame` if capture is on
+///
+/// `direction` is "RX" or "TX". Frames too short to parse are shown with
+/// their length only.
+pub fn dump(direction: &str, frame: &[u8]) {
+    if !is_enabled() {
+        return;
+    }
+    let len = frame.len();
+    let Some(eth) = ethernet::EthernetHeader::parse(frame) else {
+        println!("[cap] {} {:4} runt", direction, len);
+        return;
+    };
+    let payload = ethernet::EthernetHeader::payload(frame);
+
+    if eth.ethertype != ethernet::ETHERTYPE_IPV4 {
+        let kind = if eth.ethertype == ethernet::ETHERTYPE_ARP { "ARP" } else { "type" };
+        println!(
+            "[cap] {} {:4} {} {:#06x} {} > {}{}",
+            direction, len, kind, eth.ethertype, Mac(&eth.src_mac), Mac(&eth.dst_mac), Preview(payload));
+        return;
+    }
+
+    let Some(ip) = ipv4::Ipv4Header::parse(payload) else {
+        println!("[cap] {} {:4} IPv4 malformed", direction, len);
+        return;
+    };
+    let end = (ip.total_length as usize).clamp(ip.header_length(), payload.len());
+    let data = &payload[ip.header_length()..end];
+    let (src, dst) = (Ip(&ip.src_ip), Ip(&ip.dst_ip));
+
+    match ip.protocol {
+        ipv4::PROTO_TCP => match tcp::TcpHeader::parse(data) {
+            Some(h) => println!(
+                "[cap] {} {:4} TCP {}:{} > {}:{} [{}] seq={} ack={} win={} len={}{}",
+                direction, len, src, h.src_port, dst, h.dst_port, TcpFlags(h.flags),
+                h.seq_num, h.ack_num, h.window, h.payload(data).len(), Preview(h.payload(data))),
+            None => println!("[cap] {} {:4} TCP {} > {} malformed", direction, len, src, dst),
+        },
+        ipv4::PROTO_UDP => match udp::UdpHeader::parse(data) {
+            Some(h) => println!(
+                "[cap] {} {:4} UDP {}:{} > {}:{} len={}{}",
+                direction, len, src, h.src_port, dst, h.dst_port,
+                h.payload(data).len(), Preview(h.payload(data))),
+            None => println!("[cap] {} {:4} UDP {} > {} malformed", direction, len, src, dst),
+        },
+        ipv4::PROTO_ICMP => println!(
+            "[cap] {} {:4} ICMP {} > {} type={} code={}",
+            direction, len, src, dst,
+            data.first().copied().unwrap_or(0), data.get(1).copied().unwrap_or(0)),
+        proto => println!(
+            "[cap] {} {:4} IPv4 {} > {} proto={}{}",
+            direction, len, src, dst, proto, Preview(data)),
+    }
+}
@@ -142,6 +142,22 @@ pub fn tcp_udp_checksum(
     fold_checksum(sum)
 }
 
+/// Calculate the CRC-32 (IEEE 802.3, as used by Ethernet, zlib and PNG)
+///
+/// Computed bitwise without a lookup table; fine for the short buffers
+/// BASIC programs pass in.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xFFFF_FFFF;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -162,4 +178,11 @@ mod tests {
         let data = [0x45, 0x00, 0x00, 0x73, 0x00];
         let _ = internet_checksum(&data); // Should not panic
     }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b"The quick brown fox jumps over the lazy dog"), 0x414F_A339);
+    }
 }
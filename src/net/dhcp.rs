@@ -0,0 +1,387 @@
+//! DHCP client
+//!
+//! Asks for an address once at startup: DISCOVER, wait for an OFFER,
+//! REQUEST it and wait for the ACK, then switch `net::config()` to the
+//! leased address, netmask, gateway and DNS server. While this runs our
+//! address is 0.0.0.0. If no server answers within `DHCP_TIMEOUT_MS` the
+//! static configuration is put back. Leases aren't renewed; QEMU's last a
+//! day.
+//!
+//! Driven from the network task by `poll()`, so nothing here blocks.
+
+use crate::allocator::Spinlock;
+use crate::net::{config, ipv4, ne2000, set_config, udp, NetConfig, STATIC_CONFIG};
+use crate::{rand, timer};
+
+/// Well-known BOOTP/DHCP ports
+pub const SERVER_PORT: u16 = 67;
+pub const CLIENT_PORT: u16 = 68;
+
+/// Give up and use the static configuration after this long
+const DHCP_TIMEOUT_MS: u64 = 5000;
+
+/// Resend DISCOVER or REQUEST if nothing came back within this time
+const RETRY_MS: u64 = 1000;
+
+/// Fixed BOOTP part of a message, up to and including the magic cookie
+const FIXED_SIZE: usize = 240;
+
+/// Room for the options we send
+const MAX_MESSAGE_SIZE: usize = FIXED_SIZE + 64;
+
+const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+
+/// BOOTP opcodes
+const OP_REQUEST: u8 = 1;
+const OP_REPLY: u8 = 2;
+
+/// Ask the server to broadcast its replies, as we can't receive unicast
+/// to an address we don't have yet on every network
+const FLAG_BROADCAST: u16 = 0x8000;
+
+/// DHCP message types (option 53)
+pub const DHCPDISCOVER: u8 = 1;
+pub const DHCPOFFER: u8 = 2;
+pub const DHCPREQUEST: u8 = 3;
+pub const DHCPACK: u8 = 5;
+pub const DHCPNAK: u8 = 6;
+
+/// Option codes
+const OPT_PAD: u8 = 0;
+const OPT_SUBNET_MASK: u8 = 1;
+const OPT_ROUTER: u8 = 3;
+const OPT_DNS: u8 = 6;
+const OPT_REQUESTED_IP: u8 = 50;
+const OPT_LEASE_TIME: u8 = 51;
+const OPT_MESSAGE_TYPE: u8 = 53;
+const OPT_SERVER_ID: u8 = 54;
+const OPT_PARAMETERS: u8 = 55;
+const OPT_END: u8 = 255;
+
+/// Where the current configuration came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// DHCP is off; the static configuration is in use
+    Static,
+    /// Waiting for a server (our address is 0.0.0.0)
+    Pending,
+    /// Using a leased address
+    Bound,
+    /// No server answered, so the static configuration is in use
+    Fallback,
+}
+
+impl Status {
+    pub fn name(self) -> &'static str {
+        match self {
+            Status::Static | Status::Fallback => "STATIC",
+            Status::Pending => "PENDING",
+            Status::Bound => "DHCP",
+        }
+    }
+}
+
+/// The fields of an OFFER or ACK we use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reply {
+    pub msg_type: u8,
+    /// Offered address (yiaddr)
+    pub ip: [u8; 4],
+    pub server: Option<[u8; 4]>,
+    pub netmask: Option<[u8; 4]>,
+    pub router: Option<[u8; 4]>,
+    pub dns: Option<[u8; 4]>,
+    pub lease_s: Option<u32>,
+}
+
+impl Reply {
+    /// Configuration from this reply, filling gaps from `earlier` (the
+    /// OFFER, for an ACK that leaves options out) and then the static one
+    fn config(&self, earlier: Option<&Reply>) -> NetConfig {
+        let pick = |this: Option<[u8; 4]>, that: Option<[u8; 4]>, default: [u8; 4]| {
+            this.or(that).unwrap_or(default)
+        };
+        NetConfig {
+            ip: self.ip,
+            netmask: pick(self.netmask, earlier.and_then(|r| r.netmask), STATIC_CONFIG.netmask),
+            gateway: pick(self.router, earlier.and_then(|r| r.router), STATIC_CONFIG.gateway),
+            dns: pick(self.dns, earlier.and_then(|r| r.dns), STATIC_CONFIG.dns),
+        }
+    }
+}
+
+enum Phase {
+    Idle,
+    /// DISCOVER sent, waiting for an OFFER
+    Selecting,
+    /// REQUEST sent for this OFFER, waiting for the ACK
+    Requesting(Reply),
+    Done,
+}
+
+struct Client {
+    status: Status,
+    phase: Phase,
+    sock: Option<usize>,
+    xid: u32,
+    /// `timer::uptime_ms()` at which to give up
+    deadline_ms: u64,
+    /// `timer::uptime_ms()` at which to (re)send
+    next_send_ms: u64,
+}
+
+static CLIENT: Spinlock<Client> = Spinlock::new(Client {
+    status: Status::Static,
+    phase: Phase::Idle,
+    sock: None,
+    xid: 0,
+    deadline_ms: 0,
+    next_send_ms: 0,
+});
+
+/// Build a DISCOVER, or a REQUEST for `offer`, into `buffer`
+///
+/// Returns the message length, or 0 if the buffer is too small.
+pub fn build_message(buffer: &mut [u8], xid: u32, mac: &[u8; 6], offer: Option<&Reply>) -> usize {
+    if buffer.len() < MAX_MESSAGE_SIZE {
+        return 0;
+    }
+    buffer[..MAX_MESSAGE_SIZE].fill(0);
+    buffer[0] = OP_REQUEST;
+    buffer[1] = 1; // Ethernet
+    buffer[2] = 6; // MAC address length
+    buffer[4..8].copy_from_slice(&xid.to_be_bytes());
+    buffer[10..12].copy_from_slice(&FLAG_BROADCAST.to_be_bytes());
+    buffer[28..34].copy_from_slice(mac);
+    buffer[236..FIXED_SIZE].copy_from_slice(&MAGIC_COOKIE);
+
+    let mut pos = FIXED_SIZE;
+    let mut option = |code: u8, data: &[u8]| {
+        buffer[pos] = code;
+        buffer[pos + 1] = data.len() as u8;
+        buffer[pos + 2..pos + 2 + data.len()].copy_from_slice(data);
+        pos += 2 + data.len();
+    };
+    match offer {
+        None => option(OPT_MESSAGE_TYPE, &[DHCPDISCOVER]),
+        Some(offer) => {
+            option(OPT_MESSAGE_TYPE, &[DHCPREQUEST]);
+            option(OPT_REQUESTED_IP, &offer.ip);
+            if let Some(server) = offer.server {
+                option(OPT_SERVER_ID, &server);
+            }
+        }
+    }
+    option(OPT_PARAMETERS, &[OPT_SUBNET_MASK, OPT_ROUTER, OPT_DNS, OPT_LEASE_TIME]);
+    buffer[pos] = OPT_END;
+    pos + 1
+}
+
+/// First four bytes of an option's data as an address
+fn addr(data: &[u8]) -> Option<[u8; 4]> {
+    Some([*data.first()?, *data.get(1)?, *data.get(2)?, *data.get(3)?])
+}
+
+/// Parse a server reply to transaction `xid` for our `mac`
+pub fn parse_reply(msg: &[u8], xid: u32, mac: &[u8; 6]) -> Option<Reply> {
+    if msg.len() < FIXED_SIZE
+        || msg[0] != OP_REPLY
+        || msg[4..8] != xid.to_be_bytes()
+        || msg[28..34] != mac[..]
+        || msg[236..FIXED_SIZE] != MAGIC_COOKIE
+    {
+        return None;
+    }
+
+    let mut reply = Reply {
+        msg_type: 0,
+        ip: addr(&msg[16..20])?,
+        server: None,
+        netmask: None,
+        router: None,
+        dns: None,
+        lease_s: None,
+    };
+    let mut pos = FIXED_SIZE;
+    while let Some(&code) = msg.get(pos) {
+        match code {
+            OPT_END => break,
+            OPT_PAD => {
+                pos += 1;
+                continue;
+            }
+            _ => {}
+        }
+        let len = *msg.get(pos + 1)? as usize;
+        let data = msg.get(pos + 2..pos + 2 + len)?;
+        match code {
+            OPT_MESSAGE_TYPE => reply.msg_type = *data.first()?,
+            OPT_SERVER_ID => reply.server = addr(data),
+            OPT_SUBNET_MASK => reply.netmask = addr(data),
+            // Routers and DNS servers are lists; the first is preferred
+            OPT_ROUTER => reply.router = addr(data),
+            OPT_DNS => reply.dns = addr(data),
+            OPT_LEASE_TIME => reply.lease_s = addr(data).map(u32::from_be_bytes),
+            _ => {}
+        }
+        pos += 2 + len;
+    }
+    (reply.msg_type != 0).then_some(reply)
+}
+
+/// Broadcast a DISCOVER or REQUEST (never waits for ARP)
+fn send(xid: u32, offer: Option<&Reply>) {
+    let mut msg = [0u8; MAX_MESSAGE_SIZE];
+    let len = build_message(&mut msg, xid, &ne2000::mac_address(), offer);
+    let mut datagram = [0u8; udp::HEADER_SIZE + MAX_MESSAGE_SIZE];
+    let len = udp::build_datagram(
+        &mut datagram,
+        ipv4::UNSPECIFIED,
+        ipv4::BROADCAST,
+        CLIENT_PORT,
+        SERVER_PORT,
+        &msg[..len],
+    );
+    ipv4::send_packet(&ipv4::BROADCAST, ipv4::PROTO_UDP, &datagram[..len]);
+}
+
+/// Run DHCP when the network task starts
+pub fn enable() {
+    CLIENT.lock().status = Status::Pending;
+}
+
+/// Where the current configuration came from
+pub fn status() -> Status {
+    CLIENT.lock().status
+}
+
+/// Start asking for an address, if `enable` was called
+pub fn start() {
+    let mut client = CLIENT.lock();
+    if client.status != Status::Pending {
+        return;
+    }
+    let Some(sock) = udp::bind(CLIENT_PORT) else {
+        net_log!(Errors, "[dhcp] Port {} in use, keeping static address", CLIENT_PORT);
+        client.status = Status::Fallback;
+        return;
+    };
+
+    let now = timer::uptime_ms();
+    client.sock = Some(sock);
+    client.phase = Phase::Selecting;
+    client.xid = rand::clock_seed();
+    client.deadline_ms = now + DHCP_TIMEOUT_MS;
+    client.next_send_ms = now;
+    set_config(NetConfig { ip: ipv4::UNSPECIFIED, ..config() });
+}
+
+/// Stop the exchange, installing `cfg`
+fn finish(client: &mut Client, status: Status, cfg: NetConfig) {
+    if let Some(sock) = client.sock.take() {
+        udp::close(sock);
+    }
+    client.phase = Phase::Done;
+    client.status = status;
+    set_config(cfg);
+}
+
+/// Handle replies and resend or give up when due (called by the network
+/// task every pass)
+pub fn poll() {
+    let mut client = CLIENT.lock();
+    let Some(sock) = client.sock else {
+        return;
+    };
+    let mac = ne2000::mac_address();
+
+    while let Some(datagram) = udp::recv_from(sock) {
+        if datagram.src_port != SERVER_PORT {
+            continue;
+        }
+        let Some(reply) = parse_reply(&datagram.data, client.xid, &mac) else {
+            continue;
+        };
+        match (&client.phase, reply.msg_type) {
+            (Phase::Selecting, DHCPOFFER) => {
+                client.phase = Phase::Requesting(reply);
+                client.next_send_ms = 0;
+            }
+            (Phase::Requesting(offer), DHCPACK) => {
+                let cfg = reply.config(Some(offer));
+                net_log!(Events, "[dhcp] Leased {}.{}.{}.{} for {}s",
+                    cfg.ip[0], cfg.ip[1], cfg.ip[2], cfg.ip[3], reply.lease_s.unwrap_or(0));
+                finish(&mut client, Status::Bound, cfg);
+                return;
+            }
+            (Phase::Requesting(_), DHCPNAK) => {
+                // Offer withdrawn; start over with a new transaction
+                net_log!(Events, "[dhcp] Request refused, retrying");
+                client.phase = Phase::Selecting;
+                client.xid = client.xid.wrapping_add(1);
+                client.next_send_ms = 0;
+            }
+            _ => {}
+        }
+    }
+
+    let now = timer::uptime_ms();
+    if now >= client.deadline_ms {
+        net_log!(Errors, "[dhcp] No answer, using static address {}.{}.{}.{}",
+            STATIC_CONFIG.ip[0], STATIC_CONFIG.ip[1], STATIC_CONFIG.ip[2], STATIC_CONFIG.ip[3]);
+        finish(&mut client, Status::Fallback, STATIC_CONFIG);
+        return;
+    }
+    if now >= client.next_send_ms {
+        client.next_send_ms = now + RETRY_MS;
+        match &client.phase {
+            Phase::Selecting => send(client.xid, None),
+            Phase::Requesting(offer) => send(client.xid, Some(offer)),
+            Phase::Idle | Phase::Done => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offer_round_trip() {
+        let mac = [0x52, 0x54, 0x00, 0x12, 0x34, 0x56];
+        let mut msg = [0u8; MAX_MESSAGE_SIZE];
+        let len = build_message(&mut msg, 0xCAFE, &mac, None);
+        assert_eq!(&msg[FIXED_SIZE..FIXED_SIZE + 3], &[OPT_MESSAGE_TYPE, 1, DHCPDISCOVER]);
+        assert_eq!(msg[len - 1], OPT_END);
+
+        // Turn the DISCOVER into an OFFER for 10.0.2.15
+        let mut offer = msg[..FIXED_SIZE].to_vec();
+        offer[0] = OP_REPLY;
+        offer[16..20].copy_from_slice(&[10, 0, 2, 15]);
+        offer.extend_from_slice(&[OPT_MESSAGE_TYPE, 1, DHCPOFFER, OPT_PAD]);
+        offer.extend_from_slice(&[OPT_SERVER_ID, 4, 10, 0, 2, 2, OPT_SUBNET_MASK, 4, 255, 255, 255, 0]);
+        offer.extend_from_slice(&[OPT_DNS, 8, 10, 0, 2, 3, 8, 8, 8, 8]);
+        offer.extend_from_slice(&[OPT_LEASE_TIME, 4, 0, 1, 0x51, 0x80, OPT_END]);
+        let reply = parse_reply(&offer, 0xCAFE, &mac).unwrap();
+        assert_eq!(reply.msg_type, DHCPOFFER);
+        assert_eq!(reply.server, Some([10, 0, 2, 2]));
+        assert_eq!(reply.lease_s, Some(86400));
+        assert_eq!(reply.router, None);
+
+        let cfg = reply.config(None);
+        assert_eq!(cfg.ip, [10, 0, 2, 15]);
+        assert_eq!(cfg.dns, [10, 0, 2, 3]);
+        assert_eq!(cfg.gateway, STATIC_CONFIG.gateway);
+
+        // The REQUEST names the offered address and server
+        let len = build_message(&mut msg, 0xCAFE, &mac, Some(&reply));
+        let options = &msg[FIXED_SIZE..len];
+        assert!(options.windows(6).any(|w| w == [OPT_REQUESTED_IP, 4, 10, 0, 2, 15]));
+        assert!(options.windows(6).any(|w| w == [OPT_SERVER_ID, 4, 10, 0, 2, 2]));
+
+        // Other transactions, other clients and truncated options are ignored
+        assert_eq!(parse_reply(&offer, 0xBEEF, &mac), None);
+        assert_eq!(parse_reply(&offer, 0xCAFE, &[0; 6]), None);
+        assert_eq!(parse_reply(&offer[..offer.len() - 3], 0xCAFE, &mac), None);
+    }
+}
@@ -0,0 +1,249 @@
+//! DNS resolver
+//!
+//! Looks up IPv4 addresses (type A records) by sending a recursive query
+//! over UDP to the configured DNS server. Answers are kept in a small cache
+//! for the TTL the server gave (capped at `MAX_CACHE_TTL_S`); failures are
+//! not cached.
+
+use alloc::string::String;
+use crate::allocator::Spinlock;
+use crate::net::{config, ipv4, udp};
+use crate::{rand, scheduler, timer};
+
+/// Well-known DNS server port
+pub const DNS_PORT: u16 = 53;
+
+/// Longest host name we look up
+pub const MAX_NAME_LEN: usize = 253;
+
+/// How long to wait for an answer before retrying, and how often
+const QUERY_TIMEOUT_MS: u64 = 1000;
+const QUERY_ATTEMPTS: usize = 2;
+
+/// Number of cached names
+const CACHE_SIZE: usize = 8;
+
+/// Upper bound on how long an answer is cached, whatever its TTL
+const MAX_CACHE_TTL_S: u32 = 3600;
+
+/// DNS message header size
+const HEADER_SIZE: usize = 12;
+
+/// Header flag bits
+const FLAG_RESPONSE: u16 = 0x8000;
+const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+const RCODE_MASK: u16 = 0x000F;
+
+/// Record type A and class IN
+const TYPE_A: u16 = 1;
+const CLASS_IN: u16 = 1;
+
+struct CacheEntry {
+    name: String,
+    addr: [u8; 4],
+    /// `timer::uptime_ms()` after which the entry is stale
+    expires_ms: u64,
+}
+
+static CACHE: Spinlock<[Option<CacheEntry>; CACHE_SIZE]> =
+    Spinlock::new([const { None }; CACHE_SIZE]);
+
+fn cache_lookup(name: &str) -> Option<[u8; 4]> {
+    let now = timer::uptime_ms();
+    CACHE
+        .lock()
+        .iter()
+        .flatten()
+        .find(|e| e.expires_ms > now && e.name.eq_ignore_ascii_case(name))
+        .map(|e| e.addr)
+}
+
+/// Remember an answer, replacing the same name or the entry expiring first
+fn cache_insert(name: &str, addr: [u8; 4], ttl_s: u32) {
+    let expires_ms = timer::uptime_ms() + ttl_s.min(MAX_CACHE_TTL_S) as u64 * 1000;
+    let mut cache = CACHE.lock();
+    let slot = cache
+        .iter()
+        .position(|e| matches!(e, Some(e) if e.name.eq_ignore_ascii_case(name)))
+        .or_else(|| cache.iter().position(Option::is_none))
+        .unwrap_or_else(|| {
+            (0..CACHE_SIZE)
+                .min_by_key(|&i| cache[i].as_ref().map_or(0, |e| e.expires_ms))
+                .unwrap_or(0)
+        });
+    cache[slot] = Some(CacheEntry {
+        name: String::from(name),
+        addr,
+        expires_ms,
+    });
+}
+
+/// Build a recursive type-A query for `name` into `buffer`
+///
+/// Returns the message length, or None if the name is empty, too long or
+/// has an empty or over-long label.
+fn build_query(buffer: &mut [u8], id: u16, name: &str) -> Option<usize> {
+    let name = name.strip_suffix('.').unwrap_or(name);
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return None;
+    }
+    let total = HEADER_SIZE + name.len() + 2 + 4;
+    if buffer.len() < total {
+        return None;
+    }
+
+    buffer[..HEADER_SIZE].fill(0);
+    buffer[0..2].copy_from_slice(&id.to_be_bytes());
+    buffer[2..4].copy_from_slice(&FLAG_RECURSION_DESIRED.to_be_bytes());
+    // One question
+    buffer[4..6].copy_from_slice(&1u16.to_be_bytes());
+
+    let mut pos = HEADER_SIZE;
+    for label in name.split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return None;
+        }
+        buffer[pos] = label.len() as u8;
+        buffer[pos + 1..pos + 1 + label.len()].copy_from_slice(label.as_bytes());
+        pos += 1 + label.len();
+    }
+    buffer[pos] = 0;
+    pos += 1;
+
+    buffer[pos..pos + 2].copy_from_slice(&TYPE_A.to_be_bytes());
+    buffer[pos + 2..pos + 4].copy_from_slice(&CLASS_IN.to_be_bytes());
+    Some(pos + 4)
+}
+
+/// Position just past the (possibly compressed) name starting at `pos`
+fn skip_name(msg: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *msg.get(pos)?;
+        match len {
+            0 => return Some(pos + 1),
+            // A compression pointer ends the name
+            l if l & 0xC0 == 0xC0 => return msg.get(pos + 1).map(|_| pos + 2),
+            l if l & 0xC0 != 0 => return None,
+            l => pos += 1 + l as usize,
+        }
+    }
+}
+
+fn read_u16(msg: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*msg.get(pos)?, *msg.get(pos + 1)?]))
+}
+
+fn read_u32(msg: &[u8], pos: usize) -> Option<u32> {
+    Some(((read_u16(msg, pos)? as u32) << 16) | read_u16(msg, pos + 2)? as u32)
+}
+
+/// Address and TTL of the first A record in a response to query `id`
+fn parse_response(msg: &[u8], id: u16) -> Option<([u8; 4], u32)> {
+    let flags = read_u16(msg, 2)?;
+    if read_u16(msg, 0)? != id || flags & FLAG_RESPONSE == 0 || flags & RCODE_MASK != 0 {
+        return None;
+    }
+    let questions = read_u16(msg, 4)?;
+    let answers = read_u16(msg, 6)?;
+
+    let mut pos = HEADER_SIZE;
+    for _ in 0..questions {
+        pos = skip_name(msg, pos)? + 4;
+    }
+    for _ in 0..answers {
+        pos = skip_name(msg, pos)?;
+        let rtype = read_u16(msg, pos)?;
+        let class = read_u16(msg, pos + 2)?;
+        let ttl = read_u32(msg, pos + 4)?;
+        let rdlength = read_u16(msg, pos + 8)? as usize;
+        pos += 10;
+        let rdata = msg.get(pos..pos + rdlength)?;
+        if rtype == TYPE_A && class == CLASS_IN && rdlength == 4 {
+            return Some(([rdata[0], rdata[1], rdata[2], rdata[3]], ttl));
+        }
+        pos += rdlength;
+    }
+    None
+}
+
+/// Send one query and wait for its answer
+fn query(sock: usize, port: u16, id: u16, name: &str) -> Option<([u8; 4], u32)> {
+    let mut msg = [0u8; HEADER_SIZE + MAX_NAME_LEN + 2 + 4];
+    let len = build_query(&mut msg, id, name)?;
+    let server = config().dns;
+    if !udp::send_to(&server, DNS_PORT, port, &msg[..len]) {
+        return None;
+    }
+
+    let deadline = timer::uptime_ms() + QUERY_TIMEOUT_MS;
+    while timer::uptime_ms() < deadline {
+        while let Some(reply) = udp::recv_from(sock) {
+            if reply.src_ip != server || reply.src_port != DNS_PORT {
+                continue;
+            }
+            if let Some(answer) = parse_response(&reply.data, id) {
+                return Some(answer);
+            }
+        }
+        scheduler::sleep_ms(10);
+    }
+    None
+}
+
+/// Look up the IPv4 address of `hostname`
+///
+/// Dotted-quad strings are returned as-is without a query. Blocks the
+/// calling task (never the network task) for up to two seconds; returns
+/// None on timeout, an error reply or a name without an A record.
+pub fn resolve(hostname: &str) -> Option<[u8; 4]> {
+    if let Some(addr) = ipv4::parse_addr(hostname) {
+        return Some(addr);
+    }
+    if let Some(addr) = cache_lookup(hostname) {
+        return Some(addr);
+    }
+
+    let sock = udp::bind(0)?;
+    let port = udp::local_port(sock)?;
+    let mut result = None;
+    for _ in 0..QUERY_ATTEMPTS {
+        let id = rand::clock_seed() as u16;
+        if let Some((addr, ttl)) = query(sock, port, id, hostname) {
+            cache_insert(hostname, addr, ttl);
+            result = Some(addr);
+            break;
+        }
+    }
+    udp::close(sock);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_and_response() {
+        let mut msg = [0u8; 512];
+        let len = build_query(&mut msg, 0x1234, "example.com.").unwrap();
+        assert_eq!(&msg[HEADER_SIZE..len], b"\x07example\x03com\x00\x00\x01\x00\x01");
+
+        // Turn the query into a response: a CNAME, then an A record whose
+        // name is a compression pointer back to the question
+        let mut reply = msg[..len].to_vec();
+        reply[2..4].copy_from_slice(&0x8180u16.to_be_bytes());
+        reply[6..8].copy_from_slice(&2u16.to_be_bytes());
+        reply.extend_from_slice(&[0xC0, 12, 0, 5, 0, 1, 0, 0, 0, 60, 0, 2, 0xC0, 12]);
+        reply.extend_from_slice(&[0xC0, 12, 0, 1, 0, 1, 0, 0, 1, 0x2C, 0, 4, 93, 184, 216, 34]);
+        assert_eq!(parse_response(&reply, 0x1234), Some(([93, 184, 216, 34], 300)));
+
+        // Wrong ID, error code or a truncated record
+        assert_eq!(parse_response(&reply, 0x4321), None);
+        assert_eq!(parse_response(&reply[..reply.len() - 1], 0x1234), None);
+        reply[3] |= 3;
+        assert_eq!(parse_response(&reply, 0x1234), None);
+
+        assert_eq!(build_query(&mut msg, 1, "bad..name"), None);
+        assert_eq!(build_query(&mut msg, 1, ""), None);
+    }
+}
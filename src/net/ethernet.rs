@@ -2,7 +2,7 @@
 //!
 //! Parses and builds Ethernet II frames.
 
-use crate::net::ne2000;
+use crate::net::{capture, ne2000};
 
 /// Ethernet header size in bytes
 pub const HEADER_SIZE: usize = 14;
@@ -126,6 +126,7 @@ pub fn send_frame(dst_mac: &[u8; 6], ethertype: u16, payload: &[u8]) -> bool {
 
     // Calculate frame length (pad to minimum if needed)
     let send_len = core::cmp::max(frame_len, MIN_FRAME_SIZE);
+    capture::dump("TX", &buffer[..frame_len]);
 
     // Actually transmit via NE2000
     ne2000::send(&buffer[..send_len])
@@ -0,0 +1,99 @@
+//! Inbound port filter
+//!
+//! Off by default, so every port is reachable. Once enabled, only
+//! (protocol, port) pairs on the allow list accept new traffic: a TCP SYN
+//! to any other port is answered with RST and a UDP datagram with ICMP
+//! port unreachable. Only connection-opening SYNs are checked, so
+//! established connections (including ones we opened) are unaffected, and
+//! UDP datagrams to ephemeral ports pass so replies reach sockets bound
+//! with `udp::bind(0)`.
+
+use alloc::vec::Vec;
+use crate::allocator::Spinlock;
+use crate::net::{ipv4, tcp, udp};
+
+/// Maximum number of allow rules
+pub const MAX_RULES: usize = 16;
+
+struct Firewall {
+    enabled: bool,
+    allowed: Vec<(u8, u16)>,
+}
+
+static FIREWALL: Spinlock<Firewall> = Spinlock::new(Firewall {
+    enabled: false,
+    allowed: Vec::new(),
+});
+
+/// Allow new traffic to `port` and turn filtering on
+pub fn allow(protocol: u8, port: u16) -> Result<(), &'static str> {
+    let mut fw = FIREWALL.lock();
+    if !fw.allowed.contains(&(protocol, port)) {
+        if fw.allowed.len() >= MAX_RULES {
+            return Err("Firewall rule table full");
+        }
+        fw.allowed.push((protocol, port));
+    }
+    fw.enabled = true;
+    Ok(())
+}
+
+/// Remove `port` from the allow list and turn filtering on
+pub fn deny(protocol: u8, port: u16) {
+    let mut fw = FIREWALL.lock();
+    fw.allowed.retain(|&rule| rule != (protocol, port));
+    fw.enabled = true;
+}
+
+/// Block everything not on the allow list
+pub fn enable() {
+    FIREWALL.lock().enabled = true;
+}
+
+/// Turn filtering off and forget all rules (the default-open policy)
+pub fn disable() {
+    let mut fw = FIREWALL.lock();
+    fw.enabled = false;
+    fw.allowed.clear();
+}
+
+/// Whether filtering is on, and the allow list
+pub fn rules() -> (bool, Vec<(u8, u16)>) {
+    let fw = FIREWALL.lock();
+    (fw.enabled, fw.allowed.clone())
+}
+
+/// Decide whether an inbound TCP or UDP packet may be delivered
+fn allowed(protocol: u8, payload: &[u8]) -> bool {
+    let fw = FIREWALL.lock();
+    if !fw.enabled {
+        return true;
+    }
+    let dst_port = match protocol {
+        ipv4::PROTO_TCP => match tcp::TcpHeader::parse(payload) {
+            Some(h) if h.is_syn() && !h.is_ack() => h.dst_port,
+            _ => return true,
+        },
+        ipv4::PROTO_UDP => match udp::UdpHeader::parse(payload) {
+            Some(h) if h.dst_port < udp::EPHEMERAL_FIRST => h.dst_port,
+            _ => return true,
+        },
+        _ => return true,
+    };
+    fw.allowed.contains(&(protocol, dst_port))
+}
+
+/// Check an inbound packet, rejecting it if it is filtered
+///
+/// `packet` is the whole IPv4 packet (the ICMP error quotes its start).
+/// Returns true if the packet should be dispatched.
+pub fn check_inbound(header: &ipv4::Ipv4Header, packet: &[u8], payload: &[u8]) -> bool {
+    if allowed(header.protocol, payload) {
+        return true;
+    }
+    match header.protocol {
+        ipv4::PROTO_TCP => tcp::reject(header, payload),
+        _ => super::icmp::send_port_unreachable(header, packet),
+    }
+    false
+}
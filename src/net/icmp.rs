@@ -3,15 +3,21 @@
 //! Handles ICMP echo request/reply (ping).
 
 use crate::net::{checksum, ipv4};
-use crate::println;
 
 /// ICMP header size
 pub const HEADER_SIZE: usize = 8;
 
 /// ICMP types
 pub const TYPE_ECHO_REPLY: u8 = 0;
+pub const TYPE_DEST_UNREACHABLE: u8 = 3;
 pub const TYPE_ECHO_REQUEST: u8 = 8;
 
+/// Destination unreachable code for a closed port
+pub const CODE_PORT_UNREACHABLE: u8 = 3;
+
+/// Bytes of the offending datagram quoted after its IP header
+const QUOTED_DATA: usize = 8;
+
 /// Parsed ICMP header
 #[derive(Debug, Clone, Copy)]
 pub struct IcmpHeader {
@@ -107,20 +113,13 @@ pub fn process_packet(ip_header: &ipv4::Ipv4Header, data: &[u8]) {
 
     // Verify checksum
     if !IcmpHeader::verify_checksum(data) {
-        println!("[icmp] Bad checksum, dropping");
+        net_log!(Errors, "[icmp] Bad checksum, dropping");
         return;
     }
 
     match icmp.icmp_type {
         TYPE_ECHO_REQUEST => {
-            println!(
-                "[icmp] Echo request from {}.{}.{}.{} seq={}",
-                ip_header.src_ip[0], ip_header.src_ip[1],
-                ip_header.src_ip[2], ip_header.src_ip[3],
-                icmp.sequence
-            );
-
-            // Send echo reply
+            // Logged once, when the reply goes out
             send_echo_reply(
                 &ip_header.src_ip,
                 icmp.identifier,
@@ -129,7 +128,7 @@ pub fn process_packet(ip_header: &ipv4::Ipv4Header, data: &[u8]) {
             );
         }
         TYPE_ECHO_REPLY => {
-            println!(
+            net_log!(Events,
                 "[icmp] Echo reply from {}.{}.{}.{} seq={}",
                 ip_header.src_ip[0], ip_header.src_ip[1],
                 ip_header.src_ip[2], ip_header.src_ip[3],
@@ -152,9 +151,51 @@ fn send_echo_reply(dst_ip: &[u8; 4], identifier: u16, sequence: u16, payload: &[
     }
 
     if ipv4::send_packet(dst_ip, ipv4::PROTO_ICMP, &icmp_buffer[..icmp_len]) {
-        println!(
+        net_log!(Events,
             "[icmp] Sent echo reply to {}.{}.{}.{} seq={}",
             dst_ip[0], dst_ip[1], dst_ip[2], dst_ip[3], sequence
         );
     }
 }
+
+/// Send a port unreachable error for a received IPv4 `packet`
+///
+/// The message quotes the packet's IP header and first 8 payload bytes so
+/// the sender can match it to the socket that sent it.
+pub fn send_port_unreachable(ip_header: &ipv4::Ipv4Header, packet: &[u8]) {
+    let quoted = core::cmp::min(packet.len(), ip_header.header_length() + QUOTED_DATA);
+    let mut buffer = [0u8; HEADER_SIZE + 60 + QUOTED_DATA];
+    let total_len = HEADER_SIZE + quoted;
+
+    buffer[0] = TYPE_DEST_UNREACHABLE;
+    buffer[1] = CODE_PORT_UNREACHABLE;
+    // Checksum and the unused word stay zero for now
+    buffer[HEADER_SIZE..total_len].copy_from_slice(&packet[..quoted]);
+
+    let cksum = checksum::internet_checksum(&buffer[..total_len]);
+    buffer[2..4].copy_from_slice(&cksum.to_be_bytes());
+
+    ipv4::send_packet(&ip_header.src_ip, ipv4::PROTO_ICMP, &buffer[..total_len]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_echo_reply_mirrors_request() {
+        let mut buf = [0u8; 64];
+        let len = build_echo_reply(&mut buf, 0x1234, 7, b"ping data");
+        assert_eq!(len, HEADER_SIZE + 9);
+
+        let reply = IcmpHeader::parse(&buf[..len]).unwrap();
+        assert_eq!(reply.icmp_type, TYPE_ECHO_REPLY);
+        assert_eq!(reply.code, 0);
+        assert_eq!(reply.identifier, 0x1234);
+        assert_eq!(reply.sequence, 7);
+        assert_eq!(IcmpHeader::payload(&buf[..len]), b"ping data");
+        assert!(IcmpHeader::verify_checksum(&buf[..len]));
+
+        assert_eq!(build_echo_reply(&mut buf[..10], 1, 1, b"too long"), 0);
+    }
+}
@@ -2,8 +2,8 @@
 //!
 //! Parses and builds IPv4 packets.
 
-use crate::net::{arp, checksum, ethernet, CONFIG};
-use crate::println;
+use core::sync::atomic::{AtomicU16, Ordering};
+use crate::net::{arp, checksum, config, ethernet};
 
 /// IPv4 header minimum size (without options)
 pub const HEADER_SIZE: usize = 20;
@@ -16,6 +16,21 @@ pub const PROTO_ICMP: u8 = 1;
 pub const PROTO_TCP: u8 = 6;
 pub const PROTO_UDP: u8 = 17;
 
+/// Limited broadcast address (this network, never forwarded)
+pub const BROADCAST: [u8; 4] = [255, 255, 255, 255];
+
+/// Source address of a host that doesn't have one yet (during DHCP)
+pub const UNSPECIFIED: [u8; 4] = [0, 0, 0, 0];
+
+/// TTL of outgoing packets
+pub const DEFAULT_TTL: u8 = 64;
+
+/// Don't Fragment flag in the flags/fragment-offset field
+pub const FLAG_DONT_FRAGMENT: u16 = 0x4000;
+
+/// Identification of the next outgoing packet
+static NEXT_ID: AtomicU16 = AtomicU16::new(1);
+
 /// Parsed IPv4 header
 #[derive(Debug, Clone, Copy)]
 pub struct Ipv4Header {
@@ -115,8 +130,12 @@ impl Ipv4Header {
     }
 
     /// Check if this packet is for us
+    ///
+    /// Broadcasts are, and while we have no address yet (DHCP is still
+    /// running) so is anything that reached our MAC.
     pub fn is_for_us(&self) -> bool {
-        self.dst_ip == CONFIG.ip
+        let ip = config().ip;
+        self.dst_ip == ip || self.dst_ip == BROADCAST || ip == UNSPECIFIED
     }
 
     /// Verify the header checksum
@@ -158,19 +177,18 @@ pub fn build_header(
     buffer[1] = 0x00;
     // Total Length
     buffer[2..4].copy_from_slice(&total_length.to_be_bytes());
-    // Identification (use a simple counter or 0)
-    buffer[4..6].copy_from_slice(&identification().to_be_bytes());
+    // Identification, unique per packet so peers can reassemble fragments
+    buffer[4..6].copy_from_slice(&next_identification().to_be_bytes());
     // Flags (Don't Fragment) and Fragment Offset (0)
-    buffer[6..8].copy_from_slice(&0x4000u16.to_be_bytes());
-    // TTL (64 is common)
-    buffer[8] = 64;
+    buffer[6..8].copy_from_slice(&FLAG_DONT_FRAGMENT.to_be_bytes());
+    buffer[8] = DEFAULT_TTL;
     // Protocol
     buffer[9] = protocol;
     // Checksum (0 for now, calculate after)
     buffer[10] = 0;
     buffer[11] = 0;
     // Source IP
-    buffer[12..16].copy_from_slice(&CONFIG.ip);
+    buffer[12..16].copy_from_slice(&config().ip);
     // Destination IP
     buffer[16..20].copy_from_slice(dst_ip);
 
@@ -182,12 +200,21 @@ pub fn build_header(
 }
 
 /// Get next packet identification number
-fn identification() -> u16 {
-    static mut ID: u16 = 0;
-    unsafe {
-        ID = ID.wrapping_add(1);
-        ID
+fn next_identification() -> u16 {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Build a complete IPv4 packet (header and payload) into `buffer`
+///
+/// Returns the packet length, or 0 if it doesn't fit.
+pub fn build_packet(buffer: &mut [u8], protocol: u8, dst_ip: &[u8; 4], payload: &[u8]) -> usize {
+    let total = HEADER_SIZE + payload.len();
+    if total > buffer.len() || total > MAX_PACKET_SIZE {
+        return 0;
     }
+    let header_len = build_header(buffer, protocol, dst_ip, payload.len());
+    buffer[header_len..total].copy_from_slice(payload);
+    total
 }
 
 /// Process a received IPv4 packet
@@ -205,19 +232,23 @@ pub fn process_packet(data: &[u8]) {
 
     // Verify checksum
     if !header.verify_checksum(data) {
-        println!("[ipv4] Bad checksum, dropping");
+        net_log!(Errors, "[ipv4] Bad checksum, dropping");
         return;
     }
 
     // We don't handle fragmented packets
     if header.is_fragmented() {
-        println!("[ipv4] Fragmented packet, dropping");
+        net_log!(Errors, "[ipv4] Fragmented packet, dropping");
         return;
     }
 
     // Get payload
     let payload = header.payload(data);
 
+    if !super::firewall::check_inbound(&header, data, payload) {
+        return;
+    }
+
     // Dispatch based on protocol
     match header.protocol {
         PROTO_ICMP => {
@@ -226,6 +257,9 @@ pub fn process_packet(data: &[u8]) {
         PROTO_TCP => {
             super::tcp::process_packet(&header, payload);
         }
+        PROTO_UDP => {
+            super::udp::process_packet(&header, payload);
+        }
         _ => {
             // Unknown protocol, ignore
         }
@@ -234,11 +268,17 @@ pub fn process_packet(data: &[u8]) {
 
 /// Send an IPv4 packet
 ///
-/// Handles ARP resolution and Ethernet framing.
-/// Returns true if the packet was sent (or queued for ARP).
+/// Handles ARP resolution and Ethernet framing; broadcasts go straight to
+/// the broadcast MAC. Returns true if the packet was sent (or queued for
+/// ARP).
 pub fn send_packet(dst_ip: &[u8; 4], protocol: u8, payload: &[u8]) -> bool {
     // Resolve destination MAC via ARP
-    let dst_mac = match arp::resolve(dst_ip) {
+    let resolved = if *dst_ip == BROADCAST {
+        Some(ethernet::BROADCAST_MAC)
+    } else {
+        arp::resolve(dst_ip)
+    };
+    let dst_mac = match resolved {
         Some(mac) => mac,
         None => {
             // ARP request sent, caller should retry later
@@ -248,15 +288,79 @@ pub fn send_packet(dst_ip: &[u8; 4], protocol: u8, payload: &[u8]) -> bool {
 
     // Build IPv4 packet
     let mut packet = [0u8; MAX_PACKET_SIZE];
-    let header_len = build_header(&mut packet, protocol, dst_ip, payload.len());
-
-    if header_len == 0 || header_len + payload.len() > MAX_PACKET_SIZE {
+    let len = build_packet(&mut packet, protocol, dst_ip, payload);
+    if len == 0 {
         return false;
     }
 
-    // Copy payload
-    packet[header_len..header_len + payload.len()].copy_from_slice(payload);
-
     // Send via Ethernet
-    ethernet::send_frame(&dst_mac, ethernet::ETHERTYPE_IPV4, &packet[..header_len + payload.len()])
+    ethernet::send_frame(&dst_mac, ethernet::ETHERTYPE_IPV4, &packet[..len])
+}
+
+/// Parse a dotted-quad address like "10.0.2.2"
+///
+/// Exactly four decimal octets (0-255) are required; no leading '+',
+/// whitespace or shortened forms.
+pub fn parse_addr(s: &str) -> Option<[u8; 4]> {
+    let mut addr = [0u8; 4];
+    let mut parts = s.split('.');
+    for octet in addr.iter_mut() {
+        let part = parts.next()?;
+        if part.is_empty() || part.len() > 3 || !part.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        *octet = part.parse().ok()?;
+    }
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_addr() {
+        assert_eq!(parse_addr("10.0.2.2"), Some([10, 0, 2, 2]));
+        assert_eq!(parse_addr("255.255.255.255"), Some([255, 255, 255, 255]));
+        assert_eq!(parse_addr("256.0.0.1"), None);
+        assert_eq!(parse_addr("10.0.2"), None);
+        assert_eq!(parse_addr("10.0.2.2.1"), None);
+        assert_eq!(parse_addr("10..2.2"), None);
+        assert_eq!(parse_addr("+1.0.0.1"), None);
+        assert_eq!(parse_addr(" 1.0.0.1"), None);
+    }
+
+    #[test]
+    fn test_outgoing_header_fields() {
+        let dst = [10, 0, 2, 2];
+        let mut first = [0u8; 64];
+        let mut second = [0u8; 64];
+        let len = build_packet(&mut first, PROTO_TCP, &dst, b"hello");
+        assert_eq!(len, HEADER_SIZE + 5);
+        build_packet(&mut second, PROTO_TCP, &dst, b"hello");
+
+        let header = Ipv4Header::parse(&first[..len]).unwrap();
+        assert_eq!(header.version, 4);
+        assert_eq!(header.header_length(), HEADER_SIZE);
+        assert_eq!(header.tos, 0);
+        assert_eq!(header.total_length as usize, len);
+        assert_eq!(header.flags_fragment, FLAG_DONT_FRAGMENT);
+        assert!(!header.is_fragmented());
+        assert_eq!(header.ttl, DEFAULT_TTL);
+        assert_eq!(header.protocol, PROTO_TCP);
+        assert_eq!(header.src_ip, config().ip);
+        assert_eq!(header.dst_ip, dst);
+        assert!(header.verify_checksum(&first[..len]));
+        assert_eq!(header.payload(&first[..len]), b"hello");
+
+        let next = Ipv4Header::parse(&second).unwrap();
+        assert_eq!(next.identification, header.identification.wrapping_add(1));
+
+        // Too big for one unfragmented packet
+        let mut big = [0u8; MAX_PACKET_SIZE + 1];
+        assert_eq!(build_packet(&mut big, PROTO_UDP, &dst, &[0; MAX_PACKET_SIZE]), 0);
+    }
 }
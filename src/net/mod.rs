@@ -1,25 +1,91 @@
 //! Network subsystem for Ralph OS
 //!
-//! Provides TCP/IP networking with an NE2000 NIC driver.
+//! Provides TCP/UDP/IP networking with an NE2000 NIC driver.
 //!
 //! ## Architecture
 //!
 //! - All packet processing happens in `network_task()`
 //! - IRQ handler only copies packets to pre-allocated ring buffer
 //! - User programs use non-blocking socket API
+//! - Protocol messages go through `net_log!` and can be quieted with
+//!   `set_log_level`
+
+/// Print a network message if its level is enabled
+///
+/// `net_log!(Events, "...")` for connection and address-resolution
+/// events, `net_log!(Errors, "...")` for dropped or malformed traffic.
+macro_rules! net_log {
+    ($level:ident, $($arg:tt)*) => {
+        if $crate::net::log_enabled($crate::net::LogLevel::$level) {
+            $crate::println!($($arg)*);
+        }
+    };
+}
 
 pub mod arp;
+pub mod capture;
 pub mod checksum;
+pub mod dhcp;
+pub mod dns;
 pub mod ethernet;
+pub mod firewall;
 pub mod icmp;
 pub mod ipv4;
 pub mod ne2000;
 pub mod packet;
 pub mod tcp;
+pub mod udp;
 
+use core::sync::atomic::{AtomicU8, Ordering};
+use crate::allocator::Spinlock;
 use crate::println;
 
+/// How much the network stack prints to the serial console
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    /// Nothing
+    Off = 0,
+    /// Dropped or malformed packets and NIC errors
+    Errors = 1,
+    /// Errors plus connection, ARP and ping events (the default)
+    Events = 2,
+}
+
+impl LogLevel {
+    pub fn from_u8(level: u8) -> Option<Self> {
+        match level {
+            0 => Some(LogLevel::Off),
+            1 => Some(LogLevel::Errors),
+            2 => Some(LogLevel::Events),
+            _ => None,
+        }
+    }
+}
+
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Events as u8);
+
+/// Set how much the network stack logs
+pub fn set_log_level(level: LogLevel) {
+    LOG_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Current network log level
+pub fn log_level() -> LogLevel {
+    LogLevel::from_u8(LOG_LEVEL.load(Ordering::Relaxed)).unwrap_or(LogLevel::Events)
+}
+
+/// Are messages of `level` printed?
+pub fn log_enabled(level: LogLevel) -> bool {
+    level as u8 <= LOG_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Log a summary of every frame sent or received to serial (see `capture`)
+pub fn set_capture(enabled: bool) {
+    capture::set_enabled(enabled);
+}
+
 /// Network configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct NetConfig {
     /// Our IP address
     pub ip: [u8; 4],
@@ -27,15 +93,35 @@ pub struct NetConfig {
     pub netmask: [u8; 4],
     /// Gateway IP
     pub gateway: [u8; 4],
+    /// DNS server IP
+    pub dns: [u8; 4],
 }
 
-/// Default network configuration (for QEMU user networking)
-pub static CONFIG: NetConfig = NetConfig {
+/// Static network configuration (for QEMU user networking), used until
+/// DHCP is tried and whenever it gets no answer
+pub const STATIC_CONFIG: NetConfig = NetConfig {
     ip: [10, 0, 2, 15],       // QEMU user net default
     netmask: [255, 255, 255, 0],
     gateway: [10, 0, 2, 2],
+    dns: [10, 0, 2, 3],       // QEMU user net DNS proxy
 };
 
+/// Ask a DHCP server for the configuration at startup (false always uses
+/// `STATIC_CONFIG`)
+const USE_DHCP: bool = true;
+
+static CONFIG: Spinlock<NetConfig> = Spinlock::new(STATIC_CONFIG);
+
+/// Current network configuration
+pub fn config() -> NetConfig {
+    *CONFIG.lock()
+}
+
+/// Replace the network configuration (DHCP lease or fallback)
+pub fn set_config(config: NetConfig) {
+    *CONFIG.lock() = config;
+}
+
 /// Initialize the network subsystem
 ///
 /// This must be called before enabling interrupts.
@@ -46,9 +132,16 @@ pub fn init() {
     // Initialize packet buffer pool
     packet::init();
 
-    println!("  IP: {}.{}.{}.{}", CONFIG.ip[0], CONFIG.ip[1], CONFIG.ip[2], CONFIG.ip[3]);
-    println!("  Netmask: {}.{}.{}.{}", CONFIG.netmask[0], CONFIG.netmask[1], CONFIG.netmask[2], CONFIG.netmask[3]);
-    println!("  Gateway: {}.{}.{}.{}", CONFIG.gateway[0], CONFIG.gateway[1], CONFIG.gateway[2], CONFIG.gateway[3]);
+    let cfg = config();
+    println!("  IP: {}.{}.{}.{}", cfg.ip[0], cfg.ip[1], cfg.ip[2], cfg.ip[3]);
+    println!("  Netmask: {}.{}.{}.{}", cfg.netmask[0], cfg.netmask[1], cfg.netmask[2], cfg.netmask[3]);
+    println!("  Gateway: {}.{}.{}.{}", cfg.gateway[0], cfg.gateway[1], cfg.gateway[2], cfg.gateway[3]);
+    println!("  DNS: {}.{}.{}.{}", cfg.dns[0], cfg.dns[1], cfg.dns[2], cfg.dns[3]);
+
+    if USE_DHCP {
+        println!("  DHCP: enabled (static address above is the fallback)");
+        dhcp::enable();
+    }
 }
 
 /// Main network task entry point
@@ -59,8 +152,11 @@ pub fn init() {
 /// - IPv4 routing
 /// - ICMP ping reply
 /// - TCP state machine
+/// - UDP datagram queues
+/// - DHCP address configuration
 pub fn network_task() {
     println!("[net] Network task started");
+    dhcp::start();
 
     loop {
         // Process received packets
@@ -69,12 +165,20 @@ pub fn network_task() {
             packet::release_rx_buffer();
         }
 
+        // Let a reader waiting on pushed data run before the timers
+        if tcp::take_push_hint() {
+            crate::scheduler::yield_now();
+        }
+
         // Process TCP timers
         tcp::process_timers();
 
         // Process ARP cache expiry
         arp::expire_old_entries();
 
+        // Send or retry DHCP requests while configuring
+        dhcp::poll();
+
         // Sleep for 10ms (100 Hz polling)
         crate::scheduler::sleep_ms(10);
     }
@@ -86,7 +190,7 @@ fn process_rx_packet(data: &[u8], len: usize) {
     let Some(eth_header) = ethernet::EthernetHeader::parse(&data[..len]) else {
         return;
     };
-
+    capture::dump("RX", &data[..len]);
 
     // Check if frame is for us
     if !eth_header.is_for_us() {
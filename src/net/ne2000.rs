@@ -14,6 +14,7 @@
 //! The NE2000 uses a paged register model. Page 0 is for normal operation,
 //! Page 1 for physical address and multicast filters.
 
+use core::sync::atomic::{AtomicUsize, Ordering};
 use crate::io::{inb, outb, inw, outw};
 use crate::println;
 use super::packet;
@@ -26,6 +27,19 @@ use super::packet;
 /// Using standard ISA address 0x300 with QEMU's ne2k_isa device
 pub const NE2000_IOBASE: u16 = 0x300;
 
+/// Common ISA bases tried when there is no card at the requested one
+const PROBE_IOBASES: [u16; 3] = [0x300, 0x320, 0x280];
+
+/// IRQ line of the card
+///
+/// An ISA NE2000 has no register that reports its IRQ (it is set by
+/// jumpers, or `irq=` in QEMU), and the IDT routes only this line to
+/// `ne2000_handler`, so the card must be configured for it.
+pub const NE2000_IRQ: u8 = 10;
+
+/// Bytes 14 and 15 of an NE2000's station address PROM
+const PROM_SIGNATURE: u8 = 0x57;
+
 /// NE2000 register offsets (active in all pages)
 const CR: u16 = 0x00;       // Command Register
 
@@ -149,6 +163,9 @@ static mut NE2000: Ne2000 = Ne2000 {
     initialized: false,
 };
 
+/// Number of receive ring overflows recovered from
+static OVERFLOW_RECOVERIES: AtomicUsize = AtomicUsize::new(0);
+
 /// Receive packet header (stored at start of each packet in ring buffer)
 #[repr(C, packed)]
 struct RxHeader {
@@ -162,13 +179,13 @@ struct RxHeader {
 // Driver Implementation
 // ============================================================================
 
-/// Initialize the NE2000 NIC
+/// Reset the card at `base` and read its MAC from the station address PROM
 ///
-/// Returns true if initialization succeeded.
-pub fn init() -> bool {
+/// Returns None if nothing answers the reset or the PROM lacks the NE2000
+/// signature (an empty ISA slot reads as all ones and passes the reset
+/// check, but not this one). Leaves the card stopped in 16-bit mode.
+fn probe(base: u16) -> Option<[u8; 6]> {
     unsafe {
-        let base = NE2000.iobase;
-
         // Reset the NIC
         let reset_val = inb(base + RESET);
         outb(base + RESET, reset_val);
@@ -182,8 +199,7 @@ pub fn init() -> bool {
             timeout -= 1;
         }
         if timeout == 0 {
-            println!("  NE2000: Reset timeout");
-            return false;
+            return None;
         }
 
         // Clear interrupt status
@@ -195,6 +211,47 @@ pub fn init() -> bool {
         // Set data configuration: 16-bit transfers, normal operation
         outb(base + DCR, DCR_WTS | DCR_FT1);
 
+        // Set up remote DMA to read the PROM at address 0
+        outb(base + RSAR0, 0);
+        outb(base + RSAR1, 0);
+        outb(base + RBCR0, 32);
+        outb(base + RBCR1, 0);
+        outb(base + CR, CR_STA | CR_DMA_READ);
+
+        // In 16-bit mode each PROM byte is sent twice, as one word
+        let mut prom = [0u8; 16];
+        for byte in prom.iter_mut() {
+            *byte = inw(base + DATA) as u8;
+        }
+
+        // Abort DMA
+        outb(base + CR, CR_STP | CR_DMA_NONE);
+        outb(base + ISR, ISR_RDC);
+
+        if prom[14] != PROM_SIGNATURE || prom[15] != PROM_SIGNATURE {
+            return None;
+        }
+        let mut mac = [0u8; 6];
+        mac.copy_from_slice(&prom[..6]);
+        Some(mac)
+    }
+}
+
+/// Initialize the NE2000 NIC
+///
+/// Tries `iobase` first, then the other common ISA bases. Returns the IRQ
+/// to enable, or None if no card answers at any of them.
+pub fn init(iobase: u16) -> Option<u8> {
+    let mut candidates = core::iter::once(iobase).chain(PROBE_IOBASES.into_iter().filter(|&b| b != iobase));
+    let Some((base, mac)) = candidates.find_map(|b| probe(b).map(|mac| (b, mac))) else {
+        println!("  NE2000: No card found");
+        return None;
+    };
+
+    unsafe {
+        NE2000.iobase = base;
+        NE2000.mac = mac;
+
         // Clear remote byte count
         outb(base + RBCR0, 0);
         outb(base + RBCR1, 0);
@@ -210,40 +267,16 @@ pub fn init() -> bool {
         outb(base + PSTOP, RX_STOP);
         outb(base + BNRY, RX_START);
 
-        // Switch to page 1 to set CURR and read MAC
+        // Switch to page 1 to set CURR and the physical address
         outb(base + CR, CR_STP | CR_DMA_NONE | CR_PS0);
 
         // Set current page
         outb(base + CURR, RX_START + 1);
         NE2000.next_pkt = RX_START + 1;
 
-        // Read MAC address from PROM (first 6 bytes of on-chip memory)
-        // Switch back to page 0 for DMA
-        outb(base + CR, CR_STP | CR_DMA_NONE);
-
-        // Set up remote DMA to read PROM at address 0
-        outb(base + RSAR0, 0);
-        outb(base + RSAR1, 0);
-        outb(base + RBCR0, 12);  // Read 12 bytes (MAC is duplicated)
-        outb(base + RBCR1, 0);
-
-        // Start remote read
-        outb(base + CR, CR_STA | CR_DMA_READ);
-
-        // Read MAC (each byte is sent twice in 16-bit mode)
-        for i in 0..6 {
-            let word = inw(base + DATA);
-            NE2000.mac[i] = word as u8;
-        }
-
-        // Abort DMA
-        outb(base + CR, CR_STP | CR_DMA_NONE);
-        outb(base + ISR, ISR_RDC);
-
-        // Set physical address (page 1)
-        outb(base + CR, CR_STP | CR_DMA_NONE | CR_PS0);
-        for i in 0..6 {
-            outb(base + PAR0 + i as u16, NE2000.mac[i]);
+        // Set physical address
+        for (i, &byte) in mac.iter().enumerate() {
+            outb(base + PAR0 + i as u16, byte);
         }
 
         // Accept all multicast (set all MAR bits)
@@ -264,13 +297,12 @@ pub fn init() -> bool {
         outb(base + CR, CR_STA | CR_DMA_NONE);
 
         NE2000.initialized = true;
+    }
 
-        println!("  NE2000: MAC {:02X}:{:02X}:{:02X}:{:02X}:{:02X}:{:02X}",
-            NE2000.mac[0], NE2000.mac[1], NE2000.mac[2],
-            NE2000.mac[3], NE2000.mac[4], NE2000.mac[5]);
+    println!("  NE2000: I/O base {:#x}, MAC {:02X}:{:02X}:{:02X}:{:02X}:{:02X}:{:02X}",
+        base, mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]);
 
-        true
-    }
+    Some(NE2000_IRQ)
 }
 
 /// Get the MAC address
@@ -328,27 +360,20 @@ pub fn handle_interrupt() -> usize {
 
             // Handle receive error
             if isr & ISR_RXE != 0 {
-                crate::println!("[ne2000] RX ERROR");
+                net_log!(Errors, "[ne2000] RX ERROR");
                 outb(base + ISR, ISR_RXE);
             }
 
             // Handle transmit error
             if isr & ISR_TXE != 0 {
-                crate::println!("[ne2000] TX ERROR");
+                net_log!(Errors, "[ne2000] TX ERROR");
                 outb(base + ISR, ISR_TXE);
             }
 
             // Handle overwrite warning (ring buffer overflow)
             if isr & ISR_OVW != 0 {
-                crate::println!("[ne2000] OVERFLOW - resetting");
-                // Reset the NIC receive logic
-                outb(base + CR, CR_STP | CR_DMA_NONE);  // Stop
-                outb(base + ISR, ISR_OVW);  // Clear overflow
-                // Reset boundary
-                NE2000.next_pkt = RX_START + 1;
-                outb(base + BNRY, RX_START);
-                // Restart
-                outb(base + CR, CR_STA | CR_DMA_NONE);
+                net_log!(Errors, "[ne2000] RX ring overflow, recovering");
+                packets += recover_overflow();
             }
         }
 
@@ -356,6 +381,65 @@ pub fn handle_interrupt() -> usize {
     }
 }
 
+/// Recover from a receive ring overflow
+///
+/// The DP8390 stops receiving once the ring fills and only resumes after
+/// this sequence from its datasheet: stop the chip, empty the ring in
+/// loopback mode (so nothing new arrives meanwhile) and restart, re-issuing
+/// a transmit that the stop interrupted. Returns the number of packets
+/// salvaged from the ring.
+fn recover_overflow() -> usize {
+    unsafe {
+        let base = NE2000.iobase;
+
+        // Note whether a transmit was in progress, then stop
+        let was_transmitting = inb(base + CR) & CR_TXP != 0;
+        outb(base + CR, CR_STP | CR_DMA_NONE);
+
+        // The chip sets RST once it has come to a halt (at least 1.6ms)
+        let mut timeout = 10000;
+        while timeout > 0 && inb(base + ISR) & ISR_RST == 0 {
+            timeout -= 1;
+        }
+
+        outb(base + RBCR0, 0);
+        outb(base + RBCR1, 0);
+
+        // A transmit cut short by the stop (neither completed nor failed)
+        // has to be started again
+        let resend = was_transmitting && inb(base + ISR) & (ISR_PTX | ISR_TXE) == 0;
+
+        // Internal loopback, so the restarted chip receives nothing from
+        // the wire while the ring is emptied
+        outb(base + TCR, TCR_LB0);
+        outb(base + CR, CR_STA | CR_DMA_NONE);
+
+        let packets = receive_packets();
+
+        // Resync with the chip in case a corrupt header left us lost,
+        // dropping whatever is still in the ring
+        outb(base + CR, CR_STA | CR_DMA_NONE | CR_PS0);
+        let curr = inb(base + CURR);
+        outb(base + CR, CR_STA | CR_DMA_NONE);
+        NE2000.next_pkt = curr;
+        outb(base + BNRY, if curr == RX_START { RX_STOP - 1 } else { curr - 1 });
+
+        outb(base + ISR, ISR_OVW | ISR_RST);
+        outb(base + TCR, 0);
+        if resend {
+            outb(base + CR, CR_STA | CR_TXP | CR_DMA_NONE);
+        }
+
+        OVERFLOW_RECOVERIES.fetch_add(1, Ordering::Relaxed);
+        packets
+    }
+}
+
+/// Number of receive ring overflows recovered from since boot
+pub fn overflow_recoveries() -> usize {
+    OVERFLOW_RECOVERIES.load(Ordering::Relaxed)
+}
+
 /// Receive all pending packets from the NIC
 fn receive_packets() -> usize {
     unsafe {
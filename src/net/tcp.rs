@@ -5,10 +5,19 @@
 //! - Data transfer with acknowledgments
 //! - Connection termination
 //! - Out-of-order segment handling
+//! - Keepalive probes for idle connections (off unless enabled per socket)
+//! - Delayed ACKs (RFC 1122): every second in-order segment, or after
+//!   `DELAYED_ACK_MS`
 //! - Simple congestion control (Reno-like)
+//!
+//! Socket handles are indices into a handle table. A listener is only a
+//! handle entry; connections live in a fixed table of lazily allocated
+//! control blocks.
 
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use crate::net::{checksum, ipv4};
-use crate::println;
 use crate::timer;
 
 /// TCP header size (without options)
@@ -18,28 +27,46 @@ pub const HEADER_SIZE: usize = 20;
 pub const MSS: u16 = 1460;
 
 /// Maximum number of concurrent connections
-const MAX_CONNECTIONS: usize = 8;
+const MAX_CONNECTIONS: usize = 16;
+
+/// Maximum number of socket handles (listeners plus connections in use)
+const MAX_SOCKETS: usize = 24;
+
+/// Connections a listener holds for `accept` before it drops new SYNs
+const LISTEN_BACKLOG: usize = 4;
+
+/// Buffer sizes a socket can be created with (applies to each direction)
+pub const BUFFER_SIZES: [usize; 4] = [512, 1024, 2048, 4096];
+
+/// Receive/send buffer size of sockets created with `socket()`
+pub const DEFAULT_BUFFER_SIZE: usize = 1024;
+
+/// Maximum number of disjoint out-of-order ranges kept per connection
+const OOO_BUFFER_SIZE: usize = 4;
 
-/// Receive buffer size per connection
-const RX_BUFFER_SIZE: usize = 1024;
+/// Initial RTO in milliseconds
+const INITIAL_RTO_MS: u64 = 200;
 
-/// Send buffer size per connection
-const TX_BUFFER_SIZE: usize = 512;
+/// Minimum RTO in milliseconds
+const MIN_RTO_MS: u64 = 200;
 
-/// Out-of-order segment buffer size
-const OOO_BUFFER_SIZE: usize = 2;
+/// Maximum RTO in milliseconds
+const MAX_RTO_MS: u64 = 60_000;
 
-/// Initial RTO (200ms in ticks at 100Hz)
-const INITIAL_RTO: u64 = 20;
+/// Longest an ACK for in-order data is held back, hoping to cover a
+/// second segment or ride along with outgoing data
+const DELAYED_ACK_MS: u64 = 200;
 
-/// Minimum RTO (200ms)
-const MIN_RTO: u64 = 20;
+/// Default idle time before the first keepalive probe
+pub const KEEPALIVE_IDLE_MS: u64 = 120_000;
 
-/// Maximum RTO (60 seconds)
-const MAX_RTO: u64 = 6000;
+/// Time between unanswered keepalive probes, and how many are sent
+/// before the connection is given up
+const KEEPALIVE_INTERVAL_MS: u64 = 10_000;
+const KEEPALIVE_PROBES: u8 = 5;
 
-/// Time-Wait timeout (30 seconds at 100Hz) - simplified from 2*MSL
-const TIME_WAIT_TIMEOUT: u64 = 3000;
+/// Time-Wait timeout in milliseconds - simplified from 2*MSL
+const TIME_WAIT_TIMEOUT_MS: u64 = 30_000;
 
 // TCP flags
 const FLAG_FIN: u8 = 0x01;
@@ -48,6 +75,14 @@ const FLAG_RST: u8 = 0x04;
 const FLAG_PSH: u8 = 0x08;
 const FLAG_ACK: u8 = 0x10;
 
+/// TCP option kinds
+const OPT_END: u8 = 0;
+const OPT_NOP: u8 = 1;
+const OPT_MSS: u8 = 2;
+
+/// Smallest peer MSS we accept; smaller advertisements are raised to this
+const MIN_PEER_MSS: u16 = 64;
+
 /// TCP connection state
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TcpState {
@@ -127,6 +162,39 @@ impl TcpHeader {
         }
     }
 
+    /// Maximum segment size advertised in the options, if any
+    ///
+    /// Walks the options between the fixed header and `header_length()`,
+    /// skipping unknown kinds by their length byte. A malformed length
+    /// ends the walk rather than reading past the header.
+    pub fn mss_option(&self, data: &[u8]) -> Option<u16> {
+        let end = core::cmp::min(self.header_length(), data.len());
+        let options = data.get(HEADER_SIZE..end)?;
+        let mut i = 0;
+        while i < options.len() {
+            match options[i] {
+                OPT_END => break,
+                OPT_NOP => i += 1,
+                kind => {
+                    let len = *options.get(i + 1)? as usize;
+                    if len < 2 || i + len > options.len() {
+                        return None;
+                    }
+                    if kind == OPT_MSS && len == 4 {
+                        return Some(u16::from_be_bytes([options[i + 2], options[i + 3]]));
+                    }
+                    i += len;
+                }
+            }
+        }
+        None
+    }
+
+    /// Sequence space the segment occupies (payload, plus one each for SYN and FIN)
+    pub fn seq_len(&self, data: &[u8]) -> u32 {
+        self.payload(data).len() as u32 + self.is_syn() as u32 + self.is_fin() as u32
+    }
+
     /// Check if SYN flag is set
     pub fn is_syn(&self) -> bool {
         (self.flags & FLAG_SYN) != 0
@@ -146,62 +214,159 @@ impl TcpHeader {
     pub fn is_rst(&self) -> bool {
         (self.flags & FLAG_RST) != 0
     }
-}
 
-/// Maximum OOO segment data size
-const OOO_DATA_SIZE: usize = 512;
+    /// Check if PSH flag is set
+    pub fn is_psh(&self) -> bool {
+        (self.flags & FLAG_PSH) != 0
+    }
+}
 
-/// Out-of-order segment
-#[derive(Clone, Copy)]
-struct OooSegment {
+/// A run of contiguous out-of-order data
+struct OooRange {
     seq: u32,
-    len: u16,
-    data: [u8; OOO_DATA_SIZE],
-    valid: bool,
+    data: Vec<u8>,
 }
 
-impl OooSegment {
-    const fn empty() -> Self {
-        OooSegment {
-            seq: 0,
-            len: 0,
-            data: [0; OOO_DATA_SIZE],
-            valid: false,
+impl OooRange {
+    fn end(&self) -> u32 {
+        self.seq.wrapping_add(self.data.len() as u32)
+    }
+}
+
+/// Out-of-order data, as disjoint ranges sorted by sequence number
+///
+/// Overlapping or adjacent segments are merged into a single range, so a
+/// reordered burst takes one slot. Only data inside the receive window is
+/// kept, which bounds the heap used per connection.
+struct OooQueue {
+    ranges: Vec<OooRange>,
+}
+
+impl OooQueue {
+    const fn new() -> Self {
+        OooQueue { ranges: Vec::new() }
+    }
+
+    /// Buffer `data` received at `seq`
+    ///
+    /// Anything beyond `window` bytes past `rcv_nxt` is cut off. When this
+    /// leaves more than `OOO_BUFFER_SIZE` ranges, the one farthest beyond
+    /// `rcv_nxt` is dropped. Returns whether any of `data` was kept.
+    fn insert(&mut self, rcv_nxt: u32, window: usize, seq: u32, data: &[u8]) -> bool {
+        let offset = |seq: u32| seq.wrapping_sub(rcv_nxt) as usize;
+
+        let start = offset(seq);
+        if start == 0 || start >= window {
+            return false;
+        }
+        let data = &data[..data.len().min(window - start)];
+        if data.is_empty() {
+            return false;
+        }
+        let end = start + data.len();
+
+        // Ranges overlapping or touching [start, end) get merged with it
+        let first = self.ranges.iter().position(|r| offset(r.end()) >= start)
+            .unwrap_or(self.ranges.len());
+        let last = self.ranges.iter().rposition(|r| offset(r.seq) <= end);
+        let merged = match last {
+            Some(last) if last >= first => first..last + 1,
+            _ => first..first,
+        };
+
+        let neighbours = &self.ranges[merged.clone()];
+        let new_start = neighbours.first().map_or(start, |r| start.min(offset(r.seq)));
+        let new_end = neighbours.last().map_or(end, |r| end.max(offset(r.end())));
+
+        let mut buf = Vec::new();
+        if buf.try_reserve_exact(new_end - new_start).is_err() {
+            return false;
+        }
+        buf.resize(new_end - new_start, 0);
+        for range in self.ranges.drain(merged.clone()) {
+            let at = offset(range.seq) - new_start;
+            buf[at..at + range.data.len()].copy_from_slice(&range.data);
+        }
+        buf[start - new_start..end - new_start].copy_from_slice(data);
+
+        self.ranges.insert(merged.start, OooRange {
+            seq: rcv_nxt.wrapping_add(new_start as u32),
+            data: buf,
+        });
+
+        if self.ranges.len() > OOO_BUFFER_SIZE {
+            self.ranges.pop();
+            return merged.start < self.ranges.len();
+        }
+        true
+    }
+
+    /// Take the data that continues the stream at `rcv_nxt`, if buffered
+    ///
+    /// Ranges already covered by in-order data are discarded on the way.
+    fn pop_in_order(&mut self, rcv_nxt: u32) -> Option<Vec<u8>> {
+        while let Some(first) = self.ranges.first() {
+            if seq_after(first.seq, rcv_nxt) {
+                return None;
+            }
+            let mut range = self.ranges.remove(0);
+            let skip = rcv_nxt.wrapping_sub(range.seq) as usize;
+            if skip < range.data.len() {
+                range.data.drain(..skip);
+                return Some(range.data);
+            }
         }
+        None
     }
 }
 
 /// Ring buffer for data
+///
+/// The storage is allocated on the kernel heap when the connection is
+/// opened, so unused slots and listeners hold no buffer memory.
 struct RingBuffer {
-    data: [u8; RX_BUFFER_SIZE],
+    data: Vec<u8>,
     head: usize,
     tail: usize,
     len: usize,
 }
 
 impl RingBuffer {
+    /// An empty buffer with no storage (capacity 0)
     const fn new() -> Self {
         RingBuffer {
-            data: [0; RX_BUFFER_SIZE],
+            data: Vec::new(),
             head: 0,
             tail: 0,
             len: 0,
         }
     }
 
+    /// A buffer holding `size` bytes, or None if the heap is exhausted
+    fn with_capacity(size: usize) -> Option<Self> {
+        let mut data = Vec::new();
+        data.try_reserve_exact(size).ok()?;
+        data.resize(size, 0);
+        Some(RingBuffer { data, ..Self::new() })
+    }
+
+    fn capacity(&self) -> usize {
+        self.data.len()
+    }
+
     fn available(&self) -> usize {
         self.len
     }
 
     fn free_space(&self) -> usize {
-        RX_BUFFER_SIZE - self.len
+        self.capacity() - self.len
     }
 
     fn write(&mut self, data: &[u8]) -> usize {
         let to_write = core::cmp::min(data.len(), self.free_space());
         for &byte in data.iter().take(to_write) {
             self.data[self.head] = byte;
-            self.head = (self.head + 1) % RX_BUFFER_SIZE;
+            self.head = (self.head + 1) % self.capacity();
         }
         self.len += to_write;
         to_write
@@ -211,7 +376,7 @@ impl RingBuffer {
         let to_read = core::cmp::min(buf.len(), self.len);
         for byte in buf.iter_mut().take(to_read) {
             *byte = self.data[self.tail];
-            self.tail = (self.tail + 1) % RX_BUFFER_SIZE;
+            self.tail = (self.tail + 1) % self.capacity();
         }
         self.len -= to_read;
         to_read
@@ -222,7 +387,7 @@ impl RingBuffer {
         let mut pos = self.tail;
         for byte in buf.iter_mut().take(to_read) {
             *byte = self.data[pos];
-            pos = (pos + 1) % RX_BUFFER_SIZE;
+            pos = (pos + 1) % self.capacity();
         }
         to_read
     }
@@ -232,17 +397,20 @@ impl RingBuffer {
             return 0;
         }
         let to_read = core::cmp::min(buf.len(), self.len - offset);
-        let mut pos = (self.tail + offset) % RX_BUFFER_SIZE;
+        let mut pos = (self.tail + offset) % self.capacity();
         for byte in buf.iter_mut().take(to_read) {
             *byte = self.data[pos];
-            pos = (pos + 1) % RX_BUFFER_SIZE;
+            pos = (pos + 1) % self.capacity();
         }
         to_read
     }
 
     fn consume(&mut self, count: usize) {
         let to_consume = core::cmp::min(count, self.len);
-        self.tail = (self.tail + to_consume) % RX_BUFFER_SIZE;
+        if to_consume == 0 {
+            return;
+        }
+        self.tail = (self.tail + to_consume) % self.capacity();
         self.len -= to_consume;
     }
 
@@ -285,13 +453,13 @@ pub struct TcpControlBlock {
     pub irs: u32,
 
     // Retransmission
-    /// Retransmission timeout (in ticks)
+    /// Retransmission timeout (in ms)
     pub rto: u64,
-    /// Smoothed RTT
+    /// Smoothed RTT (in ms)
     pub srtt: u64,
-    /// RTT variance
+    /// RTT variance (in ms)
     pub rttvar: u64,
-    /// Last send time (for RTT measurement)
+    /// Last send time in ticks (for RTT measurement)
     pub last_send_time: u64,
     /// Retransmit timer
     pub retransmit_timer: u64,
@@ -299,25 +467,51 @@ pub struct TcpControlBlock {
     pub retransmit_count: u8,
 
     // Congestion control
+    /// Largest segment the peer accepts (its MSS option, else our MSS)
+    pub peer_mss: u16,
     /// Congestion window
     pub cwnd: u32,
     /// Slow start threshold
     pub ssthresh: u32,
+    /// Bytes acknowledged since cwnd last grew in congestion avoidance
+    pub bytes_acked_ca: u32,
     /// Duplicate ACK count
     pub dup_ack_count: u8,
+    /// In fast recovery (after a fast retransmit, until `recover` is ACKed)
+    pub in_recovery: bool,
+    /// snd_nxt when fast recovery started (NewReno)
+    pub recover: u32,
     /// Last ACK received
     pub last_ack: u32,
 
     // Out-of-order buffer
-    ooo_segments: [OooSegment; OOO_BUFFER_SIZE],
+    ooo: OooQueue,
 
     // Data buffers
+    /// Size of each buffer, allocated when the connection opens
+    buffer_size: usize,
     rx_buffer: RingBuffer,
     tx_buffer: RingBuffer,
 
     // Time-Wait timer
     time_wait_timer: u64,
 
+    // Keepalive
+    /// Send probes when the connection is idle
+    keepalive: bool,
+    /// Idle time before the first probe (in ms)
+    keepalive_idle_ms: u64,
+    /// Tick at which the next probe is due
+    keepalive_timer: u64,
+    /// Probes sent since the peer was last heard from
+    keepalive_probes: u8,
+
+    // Delayed ACK
+    /// Tick by which the held-back ACK must be sent (0 if none is pending)
+    delayed_ack_deadline: u64,
+    /// In-order segments received since we last sent an ACK
+    unacked_segments: u8,
+
     /// Is this slot in use?
     pub in_use: bool,
     /// Has this connection received data?
@@ -326,6 +520,8 @@ pub struct TcpControlBlock {
     pub remote_closed: bool,
     /// Has this connection been accepted by an application?
     pub accepted: bool,
+    /// Bumped each time the slot is reused, so stale handles are detected
+    generation: u32,
 }
 
 impl TcpControlBlock {
@@ -341,31 +537,45 @@ impl TcpControlBlock {
             snd_wnd: 0,
             iss: 0,
             rcv_nxt: 0,
-            rcv_wnd: RX_BUFFER_SIZE as u16,
+            rcv_wnd: 0,
             irs: 0,
-            rto: INITIAL_RTO,
+            rto: INITIAL_RTO_MS,
             srtt: 0,
             rttvar: 0,
             last_send_time: 0,
             retransmit_timer: 0,
             retransmit_count: 0,
+            peer_mss: MSS,
             cwnd: MSS as u32,
             ssthresh: 65535,
+            bytes_acked_ca: 0,
             dup_ack_count: 0,
+            in_recovery: false,
+            recover: 0,
             last_ack: 0,
-            ooo_segments: [OooSegment::empty(); OOO_BUFFER_SIZE],
+            ooo: OooQueue::new(),
+            buffer_size: DEFAULT_BUFFER_SIZE,
             rx_buffer: RingBuffer::new(),
             tx_buffer: RingBuffer::new(),
             time_wait_timer: 0,
+            keepalive: false,
+            keepalive_idle_ms: KEEPALIVE_IDLE_MS,
+            keepalive_timer: 0,
+            keepalive_probes: 0,
+            delayed_ack_deadline: 0,
+            unacked_segments: 0,
             in_use: false,
             has_data: false,
             remote_closed: false,
             accepted: false,
+            generation: 0,
         }
     }
 
     fn reset(&mut self) {
+        let generation = self.generation;
         *self = Self::new();
+        self.generation = generation;
     }
 
     /// Get bytes available to read
@@ -378,6 +588,11 @@ impl TcpControlBlock {
         self.rx_buffer.read(buf)
     }
 
+    /// Copy data from receive buffer without consuming it
+    pub fn peek(&self, buf: &mut [u8]) -> usize {
+        self.rx_buffer.peek(buf)
+    }
+
     /// Write data to send buffer
     pub fn write(&mut self, data: &[u8]) -> usize {
         self.tx_buffer.write(data)
@@ -388,6 +603,113 @@ impl TcpControlBlock {
         self.tx_buffer.available()
     }
 
+    /// Largest payload to put in one segment
+    fn send_mss(&self) -> usize {
+        core::cmp::min(MSS, self.peer_mss) as usize
+    }
+
+    /// Take the peer's MSS from the options of a SYN
+    fn learn_peer_mss(&mut self, tcp: &TcpHeader, data: &[u8]) {
+        if let Some(mss) = tcp.mss_option(data) {
+            self.peer_mss = core::cmp::max(mss, MIN_PEER_MSS);
+        }
+    }
+
+    /// Schedule a retransmission one RTO from now
+    fn arm_retransmit_timer(&mut self) {
+        self.retransmit_timer = timer::ticks() + timer::ms_to_ticks(self.rto);
+    }
+
+    /// Restart the idle countdown (the peer was heard from)
+    fn restart_keepalive_timer(&mut self) {
+        self.keepalive_timer = timer::ticks() + timer::ms_to_ticks(self.keepalive_idle_ms);
+        self.keepalive_probes = 0;
+    }
+
+    /// Schedule the end of the Time-Wait state
+    fn start_time_wait_timer(&mut self) {
+        self.time_wait_timer = timer::ticks() + timer::ms_to_ticks(TIME_WAIT_TIMEOUT_MS);
+    }
+
+    /// Grow the congestion window for newly acknowledged data (RFC 5681)
+    ///
+    /// Slow start adds at most one MSS per ACK. Congestion avoidance counts
+    /// acknowledged bytes and adds one MSS per cwnd's worth, i.e. about one
+    /// MSS per round trip however the ACKs are spread.
+    fn grow_cwnd(&mut self, bytes_acked: u32) {
+        let mss = MSS as u32;
+        if self.cwnd < self.ssthresh {
+            self.cwnd = self.cwnd.saturating_add(bytes_acked.min(mss));
+        } else {
+            self.bytes_acked_ca = self.bytes_acked_ca.saturating_add(bytes_acked);
+            if self.bytes_acked_ca >= self.cwnd {
+                self.bytes_acked_ca -= self.cwnd;
+                self.cwnd = self.cwnd.saturating_add(mss);
+            }
+        }
+    }
+
+    /// Halve ssthresh and restart slow start after a retransmission timeout
+    fn collapse_cwnd(&mut self) {
+        self.ssthresh = core::cmp::max(self.cwnd / 2, 2 * MSS as u32);
+        self.cwnd = MSS as u32;
+        self.bytes_acked_ca = 0;
+        self.in_recovery = false;
+    }
+
+    /// Enter fast recovery on the third duplicate ACK (RFC 6582)
+    ///
+    /// cwnd is inflated by the three segments that have left the network;
+    /// the caller retransmits the missing segment.
+    fn enter_fast_recovery(&mut self) {
+        self.ssthresh = core::cmp::max(self.cwnd / 2, 2 * MSS as u32);
+        self.cwnd = self.ssthresh + 3 * MSS as u32;
+        self.bytes_acked_ca = 0;
+        self.recover = self.snd_nxt;
+        self.in_recovery = true;
+    }
+
+    /// Update cwnd for an ACK of new data
+    ///
+    /// Outside fast recovery cwnd grows normally. An ACK covering `recover`
+    /// ends recovery and deflates cwnd to ssthresh. A partial ACK keeps
+    /// recovery going: cwnd shrinks by the data acknowledged (plus one MSS
+    /// for the retransmission), and true is returned because the next hole
+    /// must be retransmitted.
+    fn on_new_ack(&mut self, ack: u32, bytes_acked: u32) -> bool {
+        if !self.in_recovery {
+            self.grow_cwnd(bytes_acked);
+            return false;
+        }
+        if !seq_after(self.recover, ack) {
+            self.cwnd = self.ssthresh;
+            self.in_recovery = false;
+            return false;
+        }
+        let mss = MSS as u32;
+        self.cwnd = self.cwnd.saturating_sub(bytes_acked).max(mss);
+        if bytes_acked >= mss {
+            self.cwnd = self.cwnd.saturating_add(mss);
+        }
+        true
+    }
+
+    /// Allocate the receive and send buffers (`buffer_size` bytes each)
+    ///
+    /// Returns false if the heap is exhausted.
+    fn alloc_buffers(&mut self) -> bool {
+        let (Some(rx), Some(tx)) = (
+            RingBuffer::with_capacity(self.buffer_size),
+            RingBuffer::with_capacity(self.buffer_size),
+        ) else {
+            return false;
+        };
+        self.rx_buffer = rx;
+        self.tx_buffer = tx;
+        self.update_rcv_wnd();
+        true
+    }
+
     /// Update receive window based on buffer space
     fn update_rcv_wnd(&mut self) {
         self.rcv_wnd = self.rx_buffer.free_space() as u16;
@@ -395,14 +717,63 @@ impl TcpControlBlock {
 }
 
 /// Connection table
-static mut CONNECTIONS: [TcpControlBlock; MAX_CONNECTIONS] = {
-    const EMPTY: TcpControlBlock = TcpControlBlock::new();
-    [EMPTY; MAX_CONNECTIONS]
-};
+///
+/// Control blocks are allocated on the heap the first time a slot is
+/// needed and kept for reuse afterwards (their generation must survive),
+/// so only as many exist as were ever open at once.
+static mut CONNECTIONS: [Option<Box<TcpControlBlock>>; MAX_CONNECTIONS] =
+    [const { None }; MAX_CONNECTIONS];
+
+/// The allocated control blocks, with their slot index
+fn tcbs() -> impl Iterator<Item = (usize, &'static mut TcpControlBlock)> {
+    unsafe {
+        CONNECTIONS
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, slot)| Some((i, slot.as_deref_mut()?)))
+    }
+}
+
+/// The control block in slot `idx`, which must have been handed out by
+/// `alloc_connection`
+fn tcb(idx: usize) -> &'static mut TcpControlBlock {
+    unsafe { CONNECTIONS[idx].as_deref_mut().expect("TCP slot not allocated") }
+}
+
+/// A listening socket: no TCB, just what incoming connections need
+#[derive(Clone, Copy)]
+struct Listener {
+    port: u16,
+    /// Buffer size given to each accepted connection
+    buffer_size: usize,
+    /// Unaccepted connections allowed at once
+    backlog: usize,
+}
+
+/// What a socket handle refers to
+#[derive(Clone, Copy)]
+enum Socket {
+    Free,
+    /// Created, but neither connecting nor listening yet
+    Unbound { buffer_size: usize },
+    Listener(Listener),
+    /// A slot in CONNECTIONS, valid while its generation matches
+    Connection { conn: usize, generation: u32 },
+}
+
+/// Socket handle table (handles are indices)
+static mut SOCKETS: [Socket; MAX_SOCKETS] = [Socket::Free; MAX_SOCKETS];
 
 /// Next ephemeral port
 static mut NEXT_PORT: u16 = 49152;
 
+/// Set when a PSH segment delivers data (see `take_push_hint`)
+static PUSH_RECEIVED: AtomicBool = AtomicBool::new(false);
+
+/// Data segments received and pure ACKs sent, over all connections
+static SEGMENTS_RECEIVED: AtomicUsize = AtomicUsize::new(0);
+static ACKS_SENT: AtomicUsize = AtomicUsize::new(0);
+
 /// Generate initial sequence number
 fn generate_iss() -> u32 {
     // Simple ISN based on timer ticks (in production, use something more random)
@@ -421,64 +792,103 @@ fn alloc_port() -> u16 {
 
 /// Find a connection by local/remote address
 fn find_connection(local_port: u16, remote_ip: &[u8; 4], remote_port: u16) -> Option<usize> {
-    unsafe {
-        for (i, conn) in CONNECTIONS.iter().enumerate() {
-            if conn.in_use
+    tcbs()
+        .find(|(_, conn)| {
+            conn.in_use
                 && conn.local_port == local_port
                 && conn.remote_ip == *remote_ip
                 && conn.remote_port == remote_port
-            {
-                return Some(i);
-            }
-        }
+        })
+        .map(|(i, _)| i)
+}
+
+/// Find the listener on a port
+fn find_listener(local_port: u16) -> Option<Listener> {
+    unsafe {
+        SOCKETS.iter().find_map(|sock| match sock {
+            Socket::Listener(l) if l.port == local_port => Some(*l),
+            _ => None,
+        })
+    }
+}
+
+/// Number of connections to `port` waiting to be accepted
+fn pending_accepts(port: u16) -> usize {
+    tcbs()
+        .filter(|(_, c)| c.in_use && c.local_port == port && c.remote_port != 0 && !c.accepted)
+        .count()
+}
+
+/// Allocate a free socket handle
+fn alloc_socket(socket: Socket) -> Option<usize> {
+    unsafe {
+        let sock = SOCKETS.iter().position(|s| matches!(s, Socket::Free))?;
+        SOCKETS[sock] = socket;
+        Some(sock)
     }
-    None
 }
 
-/// Find a listening connection on a port
-fn find_listener(local_port: u16) -> Option<usize> {
+/// The handle for a connection slot
+fn connection_socket(conn: usize) -> Socket {
+    Socket::Connection { conn, generation: tcb(conn).generation }
+}
+
+/// The live connection a socket handle refers to
+fn connection(sock: usize) -> Option<&'static mut TcpControlBlock> {
     unsafe {
-        for (i, conn) in CONNECTIONS.iter().enumerate() {
-            if conn.in_use && conn.state == TcpState::Listen && conn.local_port == local_port {
-                return Some(i);
+        match SOCKETS.get(sock)? {
+            Socket::Connection { conn, generation } => {
+                let tcb = tcb(*conn);
+                if tcb.in_use && tcb.generation == *generation {
+                    Some(tcb)
+                } else {
+                    None
+                }
             }
+            _ => None,
         }
     }
-    None
 }
 
 /// Allocate a new connection slot
 fn alloc_connection() -> Option<usize> {
+    let claim = |(i, conn): (usize, &mut TcpControlBlock)| {
+        conn.reset();
+        conn.in_use = true;
+        conn.generation = conn.generation.wrapping_add(1);
+        i
+    };
+
+    // First, try to find an allocated block that is free
+    if let Some(found) = tcbs().find(|(_, conn)| !conn.in_use) {
+        return Some(claim(found));
+    }
+
+    // Then allocate a block for an empty slot
     unsafe {
-        // First, try to find a completely free slot
-        for (i, conn) in CONNECTIONS.iter_mut().enumerate() {
-            if !conn.in_use {
-                conn.reset();
-                conn.in_use = true;
-                return Some(i);
-            }
+        if let Some(i) = CONNECTIONS.iter().position(Option::is_none) {
+            let mut block = Box::new(TcpControlBlock::new());
+            block.in_use = true;
+            block.generation = 1;
+            CONNECTIONS[i] = Some(block);
+            return Some(i);
         }
+    }
 
-        // If no free slot, reuse a socket in closing states
-        // These states mean we're done with the connection and just waiting for TCP cleanup
-        for (i, conn) in CONNECTIONS.iter_mut().enumerate() {
-            if conn.in_use {
-                match conn.state {
-                    TcpState::TimeWait
+    // If no free slot, reuse a socket in closing states
+    // These states mean we're done with the connection and just waiting for TCP cleanup
+    tcbs()
+        .find(|(_, conn)| {
+            matches!(
+                conn.state,
+                TcpState::TimeWait
                     | TcpState::FinWait1
                     | TcpState::FinWait2
                     | TcpState::Closing
-                    | TcpState::LastAck => {
-                        conn.reset();
-                        conn.in_use = true;
-                        return Some(i);
-                    }
-                    _ => {}
-                }
-            }
-        }
-    }
-    None
+                    | TcpState::LastAck
+            )
+        })
+        .map(claim)
 }
 
 /// Build TCP segment
@@ -560,14 +970,27 @@ fn send_segment_seq(
     );
     segment[16..18].copy_from_slice(&cksum.to_be_bytes());
 
-    ipv4::send_packet(&conn.remote_ip, ipv4::PROTO_TCP, &segment[..seg_len])
+    if !ipv4::send_packet(&conn.remote_ip, ipv4::PROTO_TCP, &segment[..seg_len]) {
+        return false;
+    }
+    if flags == FLAG_ACK && payload.is_empty() {
+        ACKS_SENT.fetch_add(1, Ordering::Relaxed);
+    }
+    true
 }
 
-/// Send RST segment
-fn send_rst(src_ip: &[u8; 4], dst_ip: &[u8; 4], header: &TcpHeader) {
+/// Acknowledge everything received so far, cancelling a delayed ACK
+fn send_ack(conn: &mut TcpControlBlock) -> bool {
+    conn.delayed_ack_deadline = 0;
+    conn.unacked_segments = 0;
+    send_segment(conn, FLAG_ACK, &[])
+}
+
+/// Send RST segment in reply to `header` (RFC 793 reset generation)
+fn send_rst(src_ip: &[u8; 4], dst_ip: &[u8; 4], header: &TcpHeader, data: &[u8]) {
     let mut segment = [0u8; 20];
     let seq = if header.is_ack() { header.ack_num } else { 0 };
-    let ack = header.seq_num.wrapping_add(1);
+    let ack = header.seq_num.wrapping_add(header.seq_len(data));
     let flags = FLAG_RST | if header.is_ack() { 0 } else { FLAG_ACK };
 
     let seg_len = build_segment(
@@ -588,6 +1011,15 @@ fn send_rst(src_ip: &[u8; 4], dst_ip: &[u8; 4], header: &TcpHeader) {
     ipv4::send_packet(dst_ip, ipv4::PROTO_TCP, &segment[..seg_len]);
 }
 
+/// Refuse an incoming segment with RST (used by the firewall)
+pub fn reject(ip_header: &ipv4::Ipv4Header, data: &[u8]) {
+    if let Some(tcp) = TcpHeader::parse(data) {
+        if !tcp.is_rst() {
+            send_rst(&ip_header.dst_ip, &ip_header.src_ip, &tcp, data);
+        }
+    }
+}
+
 /// Process incoming TCP segment
 pub fn process_packet(ip_header: &ipv4::Ipv4Header, data: &[u8]) {
     let Some(tcp) = TcpHeader::parse(data) else {
@@ -602,33 +1034,39 @@ pub fn process_packet(ip_header: &ipv4::Ipv4Header, data: &[u8]) {
         data,
     );
     if cksum != 0 {
-        println!("[tcp] Bad checksum, dropping");
+        net_log!(Errors, "[tcp] Bad checksum, dropping");
         return;
     }
 
     // Find existing connection
     if let Some(idx) = find_connection(tcp.dst_port, &ip_header.src_ip, tcp.src_port) {
-        unsafe {
-            process_segment(&mut CONNECTIONS[idx], &tcp, data, ip_header);
-        }
+        process_segment(tcb(idx), &tcp, data, ip_header);
         return;
     }
 
     // Check for listener (SYN to listening port)
     if tcp.is_syn() && !tcp.is_ack() {
-        if let Some(_listener_idx) = find_listener(tcp.dst_port) {
+        if let Some(listener) = find_listener(tcp.dst_port) {
+            if pending_accepts(listener.port) >= listener.backlog {
+                // Backlog full - drop the SYN; the client will retry
+                return;
+            }
             // Create new connection for incoming SYN
             if let Some(idx) = alloc_connection() {
-                unsafe {
-                    let conn = &mut CONNECTIONS[idx];
-                    conn.local_ip = ip_header.dst_ip;
-                    conn.local_port = tcp.dst_port;
-                    conn.remote_ip = ip_header.src_ip;
-                    conn.remote_port = tcp.src_port;
-                    conn.state = TcpState::Listen;
-
-                    process_segment(conn, &tcp, data, ip_header);
+                let conn = tcb(idx);
+                conn.buffer_size = listener.buffer_size;
+                if !conn.alloc_buffers() {
+                    // Out of memory - drop the SYN; the client will retry
+                    conn.reset();
+                    return;
                 }
+                conn.local_ip = ip_header.dst_ip;
+                conn.local_port = tcp.dst_port;
+                conn.remote_ip = ip_header.src_ip;
+                conn.remote_port = tcp.src_port;
+                conn.state = TcpState::Listen;
+
+                process_segment(conn, &tcp, data, ip_header);
                 return;
             } else {
                 // No slot available - drop the SYN silently
@@ -640,7 +1078,7 @@ pub fn process_packet(ip_header: &ipv4::Ipv4Header, data: &[u8]) {
 
     // No connection found, send RST
     if !tcp.is_rst() {
-        send_rst(&ip_header.dst_ip, &ip_header.src_ip, &tcp);
+        send_rst(&ip_header.dst_ip, &ip_header.src_ip, &tcp, data);
     }
 }
 
@@ -657,9 +1095,58 @@ fn process_segment(
         return;
     }
 
+    // Anything from the peer, including the answer to a probe, shows the
+    // connection is alive
+    conn.restart_keepalive_timer();
+
+    let reply_rst = || send_rst(&ip_header.dst_ip, &ip_header.src_ip, tcp, data);
+
+    // A new SYN on a synchronized connection means the peer has restarted;
+    // a retransmitted one (same ISN) is handled by the state below
+    let synchronized = !matches!(
+        conn.state,
+        TcpState::Closed | TcpState::Listen | TcpState::SynSent | TcpState::SynReceived
+    );
+    if synchronized && tcp.is_syn() && tcp.seq_num != conn.irs {
+        reply_rst();
+        conn.reset();
+        return;
+    }
+
+    // Drop segments that fall entirely outside the receive window (old
+    // duplicates or far ahead), re-ACKing so the peer can resynchronize. In
+    // Time-Wait this answers a retransmitted FIN, so the wait starts over.
+    let window = conn.rx_buffer.free_space() as u32;
+    if (synchronized || conn.state == TcpState::SynReceived)
+        && !segment_acceptable(conn.rcv_nxt, window, tcp.seq_num, tcp.seq_len(data))
+    {
+        if conn.state == TcpState::TimeWait {
+            conn.start_time_wait_timer();
+        }
+        send_ack(conn);
+        return;
+    }
+
+    // After our close the application can't read any more data, so new
+    // data is lost; tell the peer instead of ACKing it (RFC 1122 4.2.2.13)
+    let closed_by_us = matches!(
+        conn.state,
+        TcpState::FinWait1 | TcpState::FinWait2 | TcpState::Closing
+            | TcpState::LastAck | TcpState::TimeWait
+    );
+    let payload_len = tcp.payload(data).len() as u32;
+    if closed_by_us
+        && payload_len > 0
+        && seq_after(tcp.seq_num.wrapping_add(payload_len), conn.rcv_nxt)
+    {
+        reply_rst();
+        conn.reset();
+        return;
+    }
+
     match conn.state {
         TcpState::Closed => {
-            // Should not happen
+            reply_rst();
         }
 
         TcpState::Listen => {
@@ -673,30 +1160,38 @@ fn process_segment(
                 conn.snd_wnd = tcp.window;
                 conn.remote_ip = ip_header.src_ip;
                 conn.remote_port = tcp.src_port;
+                conn.learn_peer_mss(tcp, data);
 
                 if send_segment(conn, FLAG_SYN | FLAG_ACK, &[]) {
                     conn.snd_nxt = conn.snd_nxt.wrapping_add(1);
                     conn.state = TcpState::SynReceived;
                     conn.last_send_time = timer::ticks();
-                    conn.retransmit_timer = timer::ticks() + conn.rto;
+                    conn.arm_retransmit_timer();
                 }
+            } else if tcp.is_ack() {
+                reply_rst();
+                conn.reset();
             }
         }
 
         TcpState::SynSent => {
-            if tcp.is_syn() && tcp.is_ack() {
+            if tcp.is_ack() && tcp.ack_num != conn.snd_nxt {
+                // ACK for something we never sent (e.g. an old connection)
+                reply_rst();
+            } else if tcp.is_syn() && tcp.is_ack() {
                 // Received SYN-ACK
                 if tcp.ack_num == conn.snd_nxt {
                     conn.irs = tcp.seq_num;
                     conn.rcv_nxt = tcp.seq_num.wrapping_add(1);
                     conn.snd_una = tcp.ack_num;
                     conn.snd_wnd = tcp.window;
+                    conn.learn_peer_mss(tcp, data);
 
                     // Send ACK
-                    if send_segment(conn, FLAG_ACK, &[]) {
+                    if send_ack(conn) {
                         conn.state = TcpState::Established;
                         update_rtt(conn);
-                        println!(
+                        net_log!(Events,
                             "[tcp] Connected to {}.{}.{}.{}:{}",
                             conn.remote_ip[0], conn.remote_ip[1],
                             conn.remote_ip[2], conn.remote_ip[3],
@@ -707,6 +1202,7 @@ fn process_segment(
             } else if tcp.is_syn() {
                 // Simultaneous open
                 conn.irs = tcp.seq_num;
+                conn.learn_peer_mss(tcp, data);
                 conn.rcv_nxt = tcp.seq_num.wrapping_add(1);
 
                 if send_segment(conn, FLAG_SYN | FLAG_ACK, &[]) {
@@ -716,12 +1212,14 @@ fn process_segment(
         }
 
         TcpState::SynReceived => {
-            if tcp.is_ack() && tcp.ack_num == conn.snd_nxt {
+            if tcp.is_ack() && tcp.ack_num != conn.snd_nxt {
+                reply_rst();
+            } else if tcp.is_ack() {
                 conn.snd_una = tcp.ack_num;
                 conn.snd_wnd = tcp.window;
                 conn.state = TcpState::Established;
                 update_rtt(conn);
-                println!(
+                net_log!(Events,
                     "[tcp] Established from {}.{}.{}.{}:{}",
                     conn.remote_ip[0], conn.remote_ip[1],
                     conn.remote_ip[2], conn.remote_ip[3],
@@ -746,9 +1244,9 @@ fn process_segment(
             if tcp.is_fin() {
                 conn.rcv_nxt = conn.rcv_nxt.wrapping_add(1);
                 conn.remote_closed = true;
-                send_segment(conn, FLAG_ACK, &[]);
+                send_ack(conn);
                 conn.state = TcpState::CloseWait;
-                println!("[tcp] Received FIN, entering CloseWait");
+                net_log!(Events, "[tcp] Received FIN, entering CloseWait");
             }
         }
 
@@ -757,18 +1255,18 @@ fn process_segment(
                 conn.snd_una = tcp.ack_num;
                 if tcp.is_fin() {
                     conn.rcv_nxt = conn.rcv_nxt.wrapping_add(1);
-                    let sent = send_segment(conn, FLAG_ACK, &[]);
-                    println!("[tcp] FinWait1->TimeWait ACK sent={}", sent);
+                    let sent = send_ack(conn);
+                    net_log!(Events, "[tcp] FinWait1->TimeWait ACK sent={}", sent);
                     conn.state = TcpState::TimeWait;
-                    conn.time_wait_timer = timer::ticks() + TIME_WAIT_TIMEOUT;
+                    conn.start_time_wait_timer();
                 } else {
-                    println!("[tcp] FinWait1->FinWait2");
+                    net_log!(Events, "[tcp] FinWait1->FinWait2");
                     conn.state = TcpState::FinWait2;
                 }
             } else if tcp.is_fin() {
                 conn.rcv_nxt = conn.rcv_nxt.wrapping_add(1);
-                let sent = send_segment(conn, FLAG_ACK, &[]);
-                println!("[tcp] FinWait1->Closing ACK sent={}", sent);
+                let sent = send_ack(conn);
+                net_log!(Events, "[tcp] FinWait1->Closing ACK sent={}", sent);
                 conn.state = TcpState::Closing;
             }
         }
@@ -776,10 +1274,10 @@ fn process_segment(
         TcpState::FinWait2 => {
             if tcp.is_fin() {
                 conn.rcv_nxt = conn.rcv_nxt.wrapping_add(1);
-                let sent = send_segment(conn, FLAG_ACK, &[]);
-                println!("[tcp] Sending final ACK, success={}", sent);
+                let sent = send_ack(conn);
+                net_log!(Events, "[tcp] Sending final ACK, success={}", sent);
                 conn.state = TcpState::TimeWait;
-                conn.time_wait_timer = timer::ticks() + TIME_WAIT_TIMEOUT;
+                conn.start_time_wait_timer();
             }
         }
 
@@ -793,31 +1291,39 @@ fn process_segment(
         TcpState::Closing => {
             if tcp.is_ack() && tcp.ack_num == conn.snd_nxt {
                 conn.state = TcpState::TimeWait;
-                conn.time_wait_timer = timer::ticks() + TIME_WAIT_TIMEOUT;
+                conn.start_time_wait_timer();
             }
         }
 
         TcpState::LastAck => {
             if tcp.is_ack() && tcp.ack_num == conn.snd_nxt {
                 conn.reset();
-                println!("[tcp] Connection closed");
+                net_log!(Events, "[tcp] Connection closed");
             }
         }
 
         TcpState::TimeWait => {
-            // Should timeout and close
+            // Should timeout and close (a retransmitted FIN is re-ACKed above)
         }
     }
 }
 
 /// Process incoming data
 fn process_data(conn: &mut TcpControlBlock, tcp: &TcpHeader, data: &[u8]) {
-    let payload = tcp.payload(data);
+    let mut payload = tcp.payload(data);
+    let mut seg_seq = tcp.seq_num;
+
+    // Trim bytes we already have, so a retransmission that overlaps new
+    // data is delivered in order
+    if seq_after(conn.rcv_nxt, seg_seq) {
+        let dup = conn.rcv_nxt.wrapping_sub(seg_seq) as usize;
+        payload = payload.get(dup..).unwrap_or(&[]);
+        seg_seq = conn.rcv_nxt;
+    }
     if payload.is_empty() {
         return;
     }
-
-    let seg_seq = tcp.seq_num;
+    SEGMENTS_RECEIVED.fetch_add(1, Ordering::Relaxed);
 
     // Check if segment is in order
     if seg_seq == conn.rcv_nxt {
@@ -825,57 +1331,48 @@ fn process_data(conn: &mut TcpControlBlock, tcp: &TcpHeader, data: &[u8]) {
         let written = conn.rx_buffer.write(payload);
         conn.rcv_nxt = conn.rcv_nxt.wrapping_add(written as u32);
         conn.has_data = true;
-        conn.update_rcv_wnd();
+        if tcp.is_psh() {
+            PUSH_RECEIVED.store(true, Ordering::Relaxed);
+        }
 
         // Check for buffered out-of-order segments that are now in order
         deliver_ooo_segments(conn);
+        conn.update_rcv_wnd();
 
-        // Send ACK
-        send_segment(conn, FLAG_ACK, &[]);
+        // ACK every second segment at once; otherwise wait a little for
+        // another segment or outgoing data to carry the ACK
+        conn.unacked_segments = conn.unacked_segments.saturating_add(1);
+        if ack_now(conn.unacked_segments, conn.rx_buffer.free_space(), conn.rx_buffer.capacity()) {
+            send_ack(conn);
+        } else if conn.delayed_ack_deadline == 0 {
+            conn.delayed_ack_deadline = timer::ticks() + timer::ms_to_ticks(DELAYED_ACK_MS);
+        }
     } else if seq_after(seg_seq, conn.rcv_nxt) {
-        // Out-of-order segment, buffer it
-        buffer_ooo_segment(conn, seg_seq, payload);
-
-        // Send duplicate ACK
-        send_segment(conn, FLAG_ACK, &[]);
-    }
-    // else: old segment, ignore
-}
-
-/// Buffer out-of-order segment
-fn buffer_ooo_segment(conn: &mut TcpControlBlock, seq: u32, data: &[u8]) {
-    // Find empty slot or oldest segment
-    let mut slot = None;
-    for (i, seg) in conn.ooo_segments.iter().enumerate() {
-        if !seg.valid {
-            slot = Some(i);
-            break;
+        // Out-of-order segment: buffer it and send a duplicate ACK at once
+        // so the sender can fast-retransmit the hole
+        let window = conn.rx_buffer.free_space();
+        if conn.ooo.insert(conn.rcv_nxt, window, seg_seq, payload) {
+            send_ack(conn);
         }
     }
+}
 
-    if let Some(i) = slot {
-        let len = core::cmp::min(data.len(), OOO_DATA_SIZE);
-        conn.ooo_segments[i].seq = seq;
-        conn.ooo_segments[i].len = len as u16;
-        conn.ooo_segments[i].data[..len].copy_from_slice(&data[..len]);
-        conn.ooo_segments[i].valid = true;
-    }
+/// Should the ACK for an in-order segment go out immediately?
+///
+/// Yes for the second unacknowledged segment, and once the receive buffer
+/// is more than half full, so a sender filling our window learns about
+/// the shrinking window without waiting for the timer.
+fn ack_now(unacked_segments: u8, free_space: usize, capacity: usize) -> bool {
+    unacked_segments >= 2 || free_space < capacity / 2
 }
 
-/// Deliver buffered out-of-order segments that are now in order
+/// Deliver buffered out-of-order data that is now in order
 fn deliver_ooo_segments(conn: &mut TcpControlBlock) {
-    loop {
-        let mut delivered = false;
-        for seg in conn.ooo_segments.iter_mut() {
-            if seg.valid && seg.seq == conn.rcv_nxt {
-                let written = conn.rx_buffer.write(&seg.data[..seg.len as usize]);
-                conn.rcv_nxt = conn.rcv_nxt.wrapping_add(written as u32);
-                seg.valid = false;
-                delivered = true;
-                break;
-            }
-        }
-        if !delivered {
+    while let Some(data) = conn.ooo.pop_in_order(conn.rcv_nxt) {
+        let written = conn.rx_buffer.write(&data);
+        conn.rcv_nxt = conn.rcv_nxt.wrapping_add(written as u32);
+        if written < data.len() {
+            // Receive buffer full; the sender retransmits the rest
             break;
         }
     }
@@ -894,38 +1391,32 @@ fn process_ack(conn: &mut TcpControlBlock, ack: u32) {
         update_rtt(conn);
 
         // Congestion control: update cwnd
-        if conn.cwnd < conn.ssthresh {
-            // Slow start
-            conn.cwnd = conn.cwnd.saturating_add(bytes_acked as u32);
-        } else {
-            // Congestion avoidance
-            conn.cwnd = conn.cwnd.saturating_add(
-                (MSS as u32 * MSS as u32) / conn.cwnd
-            );
-        }
+        let partial_ack = conn.on_new_ack(ack, bytes_acked as u32);
 
         // Reset duplicate ACK counter
         conn.dup_ack_count = 0;
         conn.last_ack = ack;
 
         // Reset retransmit timer
-        if conn.snd_una != conn.snd_nxt {
-            conn.retransmit_timer = timer::ticks() + conn.rto;
+        if partial_ack {
+            // NewReno: the next segment was lost too; resend it now
+            retransmit(conn);
+        } else if conn.snd_una != conn.snd_nxt {
+            conn.arm_retransmit_timer();
         } else {
             conn.retransmit_timer = 0;
             conn.retransmit_count = 0;
         }
     } else if ack == conn.last_ack {
         // Duplicate ACK
-        conn.dup_ack_count += 1;
-        if conn.dup_ack_count == 3 {
+        conn.dup_ack_count = conn.dup_ack_count.saturating_add(1);
+        if conn.in_recovery {
+            // Another segment left the network; inflate cwnd
+            conn.cwnd = conn.cwnd.saturating_add(MSS as u32);
+        } else if conn.dup_ack_count == 3 {
             // Fast retransmit
-            conn.ssthresh = core::cmp::max(conn.cwnd / 2, 2 * MSS as u32);
-            conn.cwnd = conn.ssthresh + 3 * MSS as u32;
+            conn.enter_fast_recovery();
             retransmit(conn);
-        } else if conn.dup_ack_count > 3 {
-            // Fast recovery
-            conn.cwnd = conn.cwnd.saturating_add(MSS as u32);
         }
     }
 }
@@ -933,7 +1424,7 @@ fn process_ack(conn: &mut TcpControlBlock, ack: u32) {
 /// Update RTT estimates
 fn update_rtt(conn: &mut TcpControlBlock) {
     let now = timer::ticks();
-    let measured = now.saturating_sub(conn.last_send_time);
+    let measured = timer::ticks_to_ms(now.saturating_sub(conn.last_send_time));
 
     if conn.srtt == 0 {
         // First measurement
@@ -951,8 +1442,8 @@ fn update_rtt(conn: &mut TcpControlBlock) {
     }
 
     conn.rto = conn.srtt + 4 * conn.rttvar;
-    conn.rto = core::cmp::max(conn.rto, MIN_RTO);
-    conn.rto = core::cmp::min(conn.rto, MAX_RTO);
+    conn.rto = core::cmp::max(conn.rto, MIN_RTO_MS);
+    conn.rto = core::cmp::min(conn.rto, MAX_RTO_MS);
 }
 
 /// Retransmit unacknowledged data
@@ -968,15 +1459,30 @@ fn retransmit(conn: &mut TcpControlBlock) {
         return;
     }
 
-    let to_send = core::cmp::min(unacked, MSS as usize);
+    let to_send = core::cmp::min(unacked, conn.send_mss());
     let mut data = [0u8; MSS as usize];
     conn.tx_buffer.peek_offset(0, &mut data[..to_send]);
 
     send_segment_seq(conn, conn.snd_una, FLAG_ACK | FLAG_PSH, &data[..to_send]);
-    conn.retransmit_timer = timer::ticks() + conn.rto;
+    conn.arm_retransmit_timer();
     conn.retransmit_count += 1;
 }
 
+/// RFC 793 acceptability test: does a segment of `len` sequence numbers
+/// starting at `seq` overlap the receive window [rcv_nxt, rcv_nxt + wnd)?
+///
+/// With a zero window only an empty segment at `rcv_nxt` (e.g. a pure ACK)
+/// is acceptable.
+fn segment_acceptable(rcv_nxt: u32, wnd: u32, seq: u32, len: u32) -> bool {
+    let in_window = |s: u32| s.wrapping_sub(rcv_nxt) < wnd;
+    match (len, wnd) {
+        (0, 0) => seq == rcv_nxt,
+        (0, _) => in_window(seq),
+        (_, 0) => false,
+        _ => in_window(seq) || in_window(seq.wrapping_add(len - 1)),
+    }
+}
+
 /// Sequence number comparison (handles wrap-around)
 fn seq_after(a: u32, b: u32) -> bool {
     (a.wrapping_sub(b) as i32) > 0
@@ -986,141 +1492,208 @@ fn seq_after(a: u32, b: u32) -> bool {
 pub fn process_timers() {
     let now = timer::ticks();
 
-    unsafe {
-        for conn in CONNECTIONS.iter_mut() {
-            if !conn.in_use {
-                continue;
-            }
+    for (_, conn) in tcbs() {
+        if !conn.in_use {
+            continue;
+        }
 
-            // Time-Wait timeout
-            if conn.state == TcpState::TimeWait {
-                if now >= conn.time_wait_timer {
-                    conn.reset();
-                    println!("[tcp] Time-Wait expired");
-                }
-                continue;
+        // Time-Wait timeout
+        if conn.state == TcpState::TimeWait {
+            if now >= conn.time_wait_timer {
+                conn.reset();
+                net_log!(Events, "[tcp] Time-Wait expired");
             }
+            continue;
+        }
 
-            // Retransmission timeout
-            if conn.retransmit_timer > 0 && now >= conn.retransmit_timer {
-                if conn.retransmit_count >= 5 {
-                    // Too many retries, abort
-                    println!("[tcp] Connection timed out");
-                    conn.reset();
-                } else {
-                    // Exponential backoff
-                    conn.rto = core::cmp::min(conn.rto * 2, MAX_RTO);
-                    conn.ssthresh = core::cmp::max(conn.cwnd / 2, 2 * MSS as u32);
-                    conn.cwnd = MSS as u32;
-                    retransmit(conn);
-                }
+        // Retransmission timeout
+        if conn.retransmit_timer > 0 && now >= conn.retransmit_timer {
+            if conn.retransmit_count >= 5 {
+                // Too many retries, abort
+                net_log!(Errors, "[tcp] Connection timed out");
+                conn.reset();
+            } else {
+                // Exponential backoff
+                conn.rto = core::cmp::min(conn.rto * 2, MAX_RTO_MS);
+                conn.collapse_cwnd();
+                retransmit(conn);
             }
+        }
 
-            // Send pending data
-            if conn.state == TcpState::Established {
-                send_pending_data(conn);
+        // Send pending data
+        if conn.state == TcpState::Established {
+            let _ = send_pending_data(conn);
+        }
+
+        // Keepalive: probe an idle connection with a segment one byte
+        // before snd_nxt, which the peer must answer with an ACK. A
+        // connection with data to send is not idle; the retransmission
+        // timer covers it instead.
+        if conn.keepalive
+            && conn.state == TcpState::Established
+            && conn.bytes_pending() == 0
+            && now >= conn.keepalive_timer
+        {
+            if conn.keepalive_probes >= KEEPALIVE_PROBES {
+                net_log!(Errors, "[tcp] Keepalive timed out, resetting connection");
+                send_segment(conn, FLAG_RST | FLAG_ACK, &[]);
+                conn.reset();
+                continue;
             }
+            send_segment_seq(conn, conn.snd_nxt.wrapping_sub(1), FLAG_ACK, &[]);
+            conn.keepalive_probes += 1;
+            conn.keepalive_timer = now + timer::ms_to_ticks(KEEPALIVE_INTERVAL_MS);
         }
-    }
-}
 
-/// Send pending data from TX buffer
-fn send_pending_data(conn: &mut TcpControlBlock) {
-    let total_buffered = conn.tx_buffer.available();
-    if total_buffered == 0 {
-        return;
+        // Flush a delayed ACK that no data segment picked up
+        if conn.delayed_ack_deadline > 0 && now >= conn.delayed_ack_deadline {
+            send_ack(conn);
+        }
     }
+}
 
-    // Calculate how much we can send
+/// Send the next segment of pending data from the TX buffer
+///
+/// PSH is set only on the segment that empties the unsent part of the
+/// buffer, so the receiver sees one push per write burst. Returns the
+/// number of bytes sent.
+fn send_pending_data(conn: &mut TcpControlBlock) -> usize {
     let flight_size = conn.snd_nxt.wrapping_sub(conn.snd_una) as usize;
     let window = core::cmp::min(conn.snd_wnd as usize, conn.cwnd as usize);
-    let can_send = window.saturating_sub(flight_size);
+    let buffered = conn.tx_buffer.available();
+    let Some((to_send, last)) = next_segment(buffered, flight_size, window, conn.send_mss()) else {
+        return 0;
+    };
 
-    if can_send == 0 {
-        return;
+    let mut data = [0u8; MSS as usize];
+    conn.tx_buffer.peek_offset(flight_size, &mut data[..to_send]);
+
+    let flags = if last { FLAG_ACK | FLAG_PSH } else { FLAG_ACK };
+    if !send_segment(conn, flags, &data[..to_send]) {
+        return 0;
+    }
+    // The segment acknowledges everything received so far
+    conn.delayed_ack_deadline = 0;
+    conn.unacked_segments = 0;
+    conn.snd_nxt = conn.snd_nxt.wrapping_add(to_send as u32);
+    conn.last_send_time = timer::ticks();
+    if conn.retransmit_timer == 0 {
+        conn.arm_retransmit_timer();
     }
+    to_send
+}
 
-    let unsent = total_buffered.saturating_sub(flight_size);
-    if unsent == 0 {
-        return;
+/// Size of the next new-data segment, and whether it empties the unsent
+/// part of the buffer
+///
+/// `buffered` counts all bytes in the TX buffer, of which `flight_size`
+/// are sent but unacknowledged; `window` is the smaller of the send and
+/// congestion windows, and no segment exceeds `mss`. None if nothing may
+/// be sent now.
+fn next_segment(buffered: usize, flight_size: usize, window: usize, mss: usize) -> Option<(usize, bool)> {
+    let can_send = window.saturating_sub(flight_size);
+    let unsent = buffered.saturating_sub(flight_size);
+    if can_send == 0 || unsent == 0 {
+        return None;
     }
 
-    let to_send = core::cmp::min(core::cmp::min(unsent, can_send), MSS as usize);
-    let mut data = [0u8; MSS as usize];
-    conn.tx_buffer.peek_offset(flight_size, &mut data[..to_send]);
+    let to_send = core::cmp::min(core::cmp::min(unsent, can_send), mss);
+    Some((to_send, to_send == unsent))
+}
 
-    if send_segment(conn, FLAG_ACK | FLAG_PSH, &data[..to_send]) {
-        conn.snd_nxt = conn.snd_nxt.wrapping_add(to_send as u32);
-        conn.last_send_time = timer::ticks();
-        if conn.retransmit_timer == 0 {
-            conn.retransmit_timer = timer::ticks() + conn.rto;
-        }
-    }
+/// Did a segment carrying PSH deliver data since the last call?
+///
+/// The network task uses this to yield straight away after a receive
+/// batch, so a task polling for input sees pushed data promptly.
+pub fn take_push_hint() -> bool {
+    PUSH_RECEIVED.swap(false, Ordering::Relaxed)
 }
 
 // ============================================================================
 // Public API
 // ============================================================================
 
-/// Create a new socket
+/// Create a new socket with the default buffer size
 pub fn socket() -> Option<usize> {
-    alloc_connection()
+    socket_with_bufsize(DEFAULT_BUFFER_SIZE)
+}
+
+/// Create a new socket with `size`-byte receive and send buffers
+///
+/// `size` must be one of `BUFFER_SIZES`. The socket holds no connection
+/// slot until it connects; a listener never takes one and gives each
+/// incoming connection buffers of its size.
+pub fn socket_with_bufsize(size: usize) -> Option<usize> {
+    if !BUFFER_SIZES.contains(&size) {
+        return None;
+    }
+    alloc_socket(Socket::Unbound { buffer_size: size })
 }
 
 /// Start a connection (active open)
 pub fn connect(sock: usize, remote_ip: &[u8; 4], remote_port: u16) -> bool {
     unsafe {
-        if sock >= MAX_CONNECTIONS || !CONNECTIONS[sock].in_use {
+        let Some(Socket::Unbound { buffer_size }) = SOCKETS.get(sock).copied() else {
             return false;
-        }
-
-        let conn = &mut CONNECTIONS[sock];
-        if conn.state != TcpState::Closed {
+        };
+        let Some(idx) = alloc_connection() else {
             return false;
-        }
+        };
 
-        conn.local_ip = crate::net::CONFIG.ip;
+        let conn = tcb(idx);
+        conn.buffer_size = buffer_size;
+        conn.accepted = true;
+        conn.local_ip = crate::net::config().ip;
         conn.local_port = alloc_port();
         conn.remote_ip = *remote_ip;
         conn.remote_port = remote_port;
         conn.iss = generate_iss();
         conn.snd_nxt = conn.iss;
         conn.snd_una = conn.iss;
+        if !conn.alloc_buffers() {
+            conn.reset();
+            return false;
+        }
 
         // Send SYN
         if send_segment(conn, FLAG_SYN, &[]) {
             conn.snd_nxt = conn.snd_nxt.wrapping_add(1);
             conn.state = TcpState::SynSent;
             conn.last_send_time = timer::ticks();
-            conn.retransmit_timer = timer::ticks() + conn.rto;
-            println!(
+            conn.arm_retransmit_timer();
+            SOCKETS[sock] = connection_socket(idx);
+            net_log!(Events,
                 "[tcp] Connecting to {}.{}.{}.{}:{}",
                 remote_ip[0], remote_ip[1], remote_ip[2], remote_ip[3],
                 remote_port
             );
             true
         } else {
+            conn.reset();
             false
         }
     }
 }
 
 /// Listen on a port (passive open)
+///
+/// Fails if the socket is already in use or another socket listens on
+/// the port.
 pub fn listen(sock: usize, port: u16) -> bool {
     unsafe {
-        if sock >= MAX_CONNECTIONS || !CONNECTIONS[sock].in_use {
+        let Some(Socket::Unbound { buffer_size }) = SOCKETS.get(sock).copied() else {
             return false;
-        }
-
-        let conn = &mut CONNECTIONS[sock];
-        if conn.state != TcpState::Closed {
+        };
+        if find_listener(port).is_some() {
             return false;
         }
 
-        conn.local_ip = crate::net::CONFIG.ip;
-        conn.local_port = port;
-        conn.state = TcpState::Listen;
-        println!("[tcp] Listening on port {}", port);
+        SOCKETS[sock] = Socket::Listener(Listener {
+            port,
+            buffer_size,
+            backlog: LISTEN_BACKLOG,
+        });
+        net_log!(Events, "[tcp] Listening on port {}", port);
         true
     }
 }
@@ -1128,11 +1701,135 @@ pub fn listen(sock: usize, port: u16) -> bool {
 /// Get socket state
 pub fn get_state(sock: usize) -> TcpState {
     unsafe {
-        if sock >= MAX_CONNECTIONS || !CONNECTIONS[sock].in_use {
-            return TcpState::Closed;
+        match SOCKETS.get(sock) {
+            Some(Socket::Listener(_)) => TcpState::Listen,
+            Some(Socket::Connection { .. }) => {
+                connection(sock).map_or(TcpState::Closed, |conn| conn.state)
+            }
+            _ => TcpState::Closed,
+        }
+    }
+}
+
+/// Remote address and port of a connected (or connecting) socket
+///
+/// None for unused sockets and listeners.
+pub fn peer_addr(sock: usize) -> Option<([u8; 4], u16)> {
+    let conn = connection(sock)?;
+    if conn.remote_port == 0 {
+        return None;
+    }
+    Some((conn.remote_ip, conn.remote_port))
+}
+
+/// Local address and port of a bound socket (listening or connected)
+pub fn local_addr(sock: usize) -> Option<([u8; 4], u16)> {
+    unsafe {
+        if let Some(Socket::Listener(l)) = SOCKETS.get(sock) {
+            return Some((crate::net::config().ip, l.port));
         }
-        CONNECTIONS[sock].state
     }
+    let conn = connection(sock)?;
+    if conn.local_port == 0 {
+        return None;
+    }
+    Some((conn.local_ip, conn.local_port))
+}
+
+/// The fields of one connection shown by the SOCKETS command
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectionInfo {
+    pub local_ip: [u8; 4],
+    pub local_port: u16,
+    pub remote_ip: [u8; 4],
+    pub remote_port: u16,
+    pub state: TcpState,
+    /// Bytes waiting to be read
+    pub rx_available: usize,
+    /// Bytes in the send buffer (unsent plus unacknowledged)
+    pub tx_pending: usize,
+    pub cwnd: u32,
+    /// Retransmission timeout in ms
+    pub rto: u64,
+}
+
+/// Copy out the state of every listener and in-use connection
+///
+/// Listeners come first, with no remote address and zero counters.
+pub fn connection_table_snapshot() -> Vec<ConnectionInfo> {
+    unsafe {
+        let listeners = SOCKETS.iter().filter_map(|s| match s {
+            Socket::Listener(l) => Some(ConnectionInfo {
+                local_ip: crate::net::config().ip,
+                local_port: l.port,
+                remote_ip: [0; 4],
+                remote_port: 0,
+                state: TcpState::Listen,
+                rx_available: 0,
+                tx_pending: 0,
+                cwnd: 0,
+                rto: 0,
+            }),
+            _ => None,
+        });
+        let connections = tcbs().filter(|(_, c)| c.in_use).map(|(_, c)| ConnectionInfo {
+            local_ip: c.local_ip,
+            local_port: c.local_port,
+            remote_ip: c.remote_ip,
+            remote_port: c.remote_port,
+            state: c.state,
+            rx_available: c.bytes_available(),
+            tx_pending: c.bytes_pending(),
+            cwnd: c.cwnd,
+            rto: c.rto,
+        });
+        listeners.chain(connections).collect()
+    }
+}
+
+/// Number of connections in use and how many of them are established
+pub fn connection_counts() -> (usize, usize) {
+    tcbs().filter(|(_, c)| c.in_use).fold((0, 0), |(in_use, established), (_, c)| {
+        (in_use + 1, established + (c.state == TcpState::Established) as usize)
+    })
+}
+
+/// Number of connections in use, in any state (for diagnostics)
+pub fn active_connection_count() -> usize {
+    tcbs().filter(|(_, c)| c.in_use).count()
+}
+
+/// Turn keepalive probes on or off for a connected socket
+///
+/// An idle connection (nothing received, nothing waiting to be sent) is
+/// probed after the idle time and every 10 seconds after that; after 5
+/// unanswered probes it is reset. Returns false if `sock` is not a
+/// connection.
+pub fn set_keepalive(sock: usize, enabled: bool) -> bool {
+    let Some(conn) = connection(sock) else {
+        return false;
+    };
+    conn.keepalive = enabled;
+    conn.restart_keepalive_timer();
+    true
+}
+
+/// Set how long a connection may be idle before the first keepalive
+/// probe (default `KEEPALIVE_IDLE_MS`)
+pub fn set_keepalive_idle(sock: usize, idle_ms: u64) -> bool {
+    let Some(conn) = connection(sock) else {
+        return false;
+    };
+    conn.keepalive_idle_ms = idle_ms;
+    conn.restart_keepalive_timer();
+    true
+}
+
+/// Data segments received and pure ACKs sent since boot
+///
+/// With delayed ACKs a bulk download sends about one ACK per two segments.
+pub fn ack_stats() -> (usize, usize) {
+    (SEGMENTS_RECEIVED.load(Ordering::Relaxed), ACKS_SENT.load(Ordering::Relaxed))
 }
 
 /// Check if connected
@@ -1142,129 +1839,361 @@ pub fn is_connected(sock: usize) -> bool {
 
 /// Get bytes available to read
 pub fn available(sock: usize) -> usize {
-    unsafe {
-        if sock >= MAX_CONNECTIONS || !CONNECTIONS[sock].in_use {
-            return 0;
-        }
-        CONNECTIONS[sock].bytes_available()
-    }
+    connection(sock).map_or(0, |conn| conn.bytes_available())
+}
+
+/// Size of the socket's receive buffer (0 if not a connection)
+pub fn rx_capacity(sock: usize) -> usize {
+    connection(sock).map_or(0, |conn| conn.rx_buffer.capacity())
 }
 
 /// Read data from socket (non-blocking)
 pub fn recv(sock: usize, buf: &mut [u8]) -> isize {
-    unsafe {
-        if sock >= MAX_CONNECTIONS || !CONNECTIONS[sock].in_use {
-            return -1;
-        }
+    let Some(conn) = connection(sock) else {
+        return -1;
+    };
 
-        let conn = &mut CONNECTIONS[sock];
+    // Check for closed connection
+    if conn.state == TcpState::Closed {
+        return -1;
+    }
 
-        // Check for closed connection
-        if conn.state == TcpState::Closed {
-            return -1;
-        }
+    let n = conn.read(buf);
+    conn.update_rcv_wnd();
 
-        let n = conn.read(buf);
-        conn.update_rcv_wnd();
+    if n > 0 {
+        n as isize
+    } else if conn.remote_closed {
+        -1  // EOF
+    } else {
+        0  // No data yet
+    }
+}
 
-        if n > 0 {
-            n as isize
-        } else if conn.remote_closed {
-            -1  // EOF
-        } else {
-            0  // No data yet
-        }
+/// Copy buffered data from socket without consuming it (non-blocking)
+pub fn peek(sock: usize, buf: &mut [u8]) -> isize {
+    let Some(conn) = connection(sock) else {
+        return -1;
+    };
+    if conn.state == TcpState::Closed {
+        return -1;
     }
+
+    conn.peek(buf) as isize
 }
 
 /// Write data to socket (non-blocking)
 pub fn send(sock: usize, data: &[u8]) -> isize {
-    unsafe {
-        if sock >= MAX_CONNECTIONS || !CONNECTIONS[sock].in_use {
-            return -1;
-        }
+    let Some(conn) = connection(sock) else {
+        return -1;
+    };
+
+    if conn.state != TcpState::Established && conn.state != TcpState::CloseWait {
+        return -1;
+    }
 
-        let conn = &mut CONNECTIONS[sock];
+    let n = conn.write(data);
+    n as isize
+}
 
-        if conn.state != TcpState::Established && conn.state != TcpState::CloseWait {
-            return -1;
-        }
+/// Send buffered data now instead of waiting for the network task
+///
+/// Sends as many segments as the send and congestion windows allow; data
+/// beyond the window stays buffered. Returns the number of bytes sent, or
+/// -1 if the socket is not connected.
+pub fn flush(sock: usize) -> isize {
+    let Some(conn) = connection(sock) else {
+        return -1;
+    };
+    if conn.state != TcpState::Established && conn.state != TcpState::CloseWait {
+        return -1;
+    }
 
-        let n = conn.write(data);
-        n as isize
+    let mut sent = 0;
+    loop {
+        match send_pending_data(conn) {
+            0 => break,
+            n => sent += n,
+        }
     }
+    sent as isize
 }
 
-/// Close socket (initiate graceful close)
+/// Close socket (initiate graceful close) and release the handle
+///
+/// A connection finishes closing in the background. Closing a listener
+/// resets the connections it had not yet handed out.
 pub fn close(sock: usize) {
     unsafe {
-        if sock >= MAX_CONNECTIONS || !CONNECTIONS[sock].in_use {
-            return;
-        }
-
-        let conn = &mut CONNECTIONS[sock];
-
-        match conn.state {
-            TcpState::Established => {
-                if send_segment(conn, FLAG_FIN | FLAG_ACK, &[]) {
-                    conn.snd_nxt = conn.snd_nxt.wrapping_add(1);
-                    conn.state = TcpState::FinWait1;
-                    conn.retransmit_timer = timer::ticks() + conn.rto;
-                    println!("[tcp] Closing connection");
+        match SOCKETS.get(sock).copied() {
+            None | Some(Socket::Free) => return,
+            Some(Socket::Unbound { .. }) => {}
+            Some(Socket::Listener(listener)) => {
+                for (_, conn) in tcbs() {
+                    if conn.in_use
+                        && conn.local_port == listener.port
+                        && conn.remote_port != 0
+                        && !conn.accepted
+                    {
+                        send_segment(conn, FLAG_RST | FLAG_ACK, &[]);
+                        conn.reset();
+                    }
                 }
             }
-            TcpState::CloseWait => {
-                if send_segment(conn, FLAG_FIN | FLAG_ACK, &[]) {
-                    conn.snd_nxt = conn.snd_nxt.wrapping_add(1);
-                    conn.state = TcpState::LastAck;
-                    conn.retransmit_timer = timer::ticks() + conn.rto;
+            Some(Socket::Connection { .. }) => {
+                if let Some(conn) = connection(sock) {
+                    close_connection(conn);
                 }
             }
-            TcpState::SynSent | TcpState::Listen => {
-                conn.reset();
+        }
+        SOCKETS[sock] = Socket::Free;
+    }
+}
+
+/// Start the FIN handshake (or drop a connection still being set up)
+fn close_connection(conn: &mut TcpControlBlock) {
+    match conn.state {
+        TcpState::Established => {
+            if send_segment(conn, FLAG_FIN | FLAG_ACK, &[]) {
+                conn.snd_nxt = conn.snd_nxt.wrapping_add(1);
+                conn.state = TcpState::FinWait1;
+                conn.arm_retransmit_timer();
+                net_log!(Events, "[tcp] Closing connection");
+            }
+        }
+        TcpState::CloseWait => {
+            if send_segment(conn, FLAG_FIN | FLAG_ACK, &[]) {
+                conn.snd_nxt = conn.snd_nxt.wrapping_add(1);
+                conn.state = TcpState::LastAck;
+                conn.arm_retransmit_timer();
             }
-            _ => {}
         }
+        TcpState::SynSent | TcpState::Listen => {
+            conn.reset();
+        }
+        _ => {}
     }
 }
 
 /// Accept a new connection on a listening socket
+///
+/// Returns a new socket handle; the listener keeps listening.
 pub fn accept(sock: usize) -> Option<usize> {
     unsafe {
-        if sock >= MAX_CONNECTIONS || !CONNECTIONS[sock].in_use {
+        let Some(Socket::Listener(listener)) = SOCKETS.get(sock).copied() else {
             return None;
+        };
+
+        let ready = |conn: &TcpControlBlock| {
+            conn.in_use
+                && conn.local_port == listener.port
+                && !conn.accepted
+                && (conn.state == TcpState::Established || conn.state == TcpState::CloseWait)
+        };
+
+        // Prefer connections with data waiting, otherwise take any
+        // established connection that hasn't been accepted yet
+        let idx = tcbs()
+            .find(|(_, c)| ready(c) && c.bytes_available() > 0)
+            .or_else(|| tcbs().find(|(_, c)| ready(c)))
+            .map(|(i, _)| i)?;
+
+        let new_sock = alloc_socket(connection_socket(idx))?;
+        tcb(idx).accepted = true;
+        Some(new_sock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMSS: u32 = MSS as u32;
+
+    /// ACK one full window in MSS-sized pieces, as a round trip would
+    fn ack_window(conn: &mut TcpControlBlock) {
+        let mut left = conn.cwnd;
+        while left > 0 {
+            let n = left.min(SMSS);
+            conn.grow_cwnd(n);
+            left -= n;
         }
+    }
 
-        let listener_port = CONNECTIONS[sock].local_port;
-        if CONNECTIONS[sock].state != TcpState::Listen {
-            return None;
+    #[test]
+    fn test_slow_start_caps_growth_per_ack() {
+        let mut conn = TcpControlBlock::new();
+        // A stretch ACK for four segments still only adds one MSS
+        conn.grow_cwnd(4 * SMSS);
+        assert_eq!(conn.cwnd, 2 * SMSS);
+        // A full window of ACKs doubles cwnd
+        ack_window(&mut conn);
+        assert_eq!(conn.cwnd, 4 * SMSS);
+    }
+
+    #[test]
+    fn test_lossy_path_cwnd() {
+        let mut conn = TcpControlBlock::new();
+        conn.ssthresh = 8 * SMSS;
+        while conn.cwnd < conn.ssthresh {
+            ack_window(&mut conn);
         }
+        assert_eq!(conn.cwnd, 8 * SMSS);
 
-        // Prefer connections with data waiting.
-        for (i, conn) in CONNECTIONS.iter_mut().enumerate() {
-            if i == sock || !conn.in_use || conn.local_port != listener_port || conn.accepted {
-                continue;
-            }
-            if (conn.state == TcpState::Established || conn.state == TcpState::CloseWait)
-                && conn.bytes_available() > 0 {
-                conn.accepted = true;
-                return Some(i);
-            }
+        // Congestion avoidance: one MSS per round trip, whatever the ACK size
+        ack_window(&mut conn);
+        assert_eq!(conn.cwnd, 9 * SMSS);
+        for _ in 0..9 * 4 {
+            conn.grow_cwnd(SMSS / 4);
         }
+        assert_eq!(conn.cwnd, 10 * SMSS);
+
+        // Timeout: ssthresh halves and slow start restarts from one MSS
+        conn.grow_cwnd(SMSS);
+        conn.collapse_cwnd();
+        assert_eq!((conn.cwnd, conn.ssthresh, conn.bytes_acked_ca), (SMSS, 5 * SMSS, 0));
+        ack_window(&mut conn);
+        ack_window(&mut conn);
+        assert_eq!(conn.cwnd, 4 * SMSS);
+        ack_window(&mut conn);
+        assert_eq!(conn.cwnd, 5 * SMSS);
+        ack_window(&mut conn);
+        assert_eq!(conn.cwnd, 6 * SMSS);
+
+        // Losing again at the floor keeps ssthresh at two segments
+        conn.cwnd = 3 * SMSS;
+        conn.collapse_cwnd();
+        assert_eq!(conn.ssthresh, 2 * SMSS);
+    }
 
-        // Otherwise accept any established connection that hasn't been accepted yet.
-        for (i, conn) in CONNECTIONS.iter_mut().enumerate() {
-            if i != sock
-                && conn.in_use
-                && conn.local_port == listener_port
-                && !conn.accepted
-                && (conn.state == TcpState::Established || conn.state == TcpState::CloseWait)
-            {
-                conn.accepted = true;
-                return Some(i);
-            }
+    #[test]
+    fn test_fast_recovery_deflates_on_full_ack() {
+        let mut conn = TcpControlBlock::new();
+        conn.cwnd = 10 * SMSS;
+        conn.snd_una = 1000;
+        conn.snd_nxt = 1000 + 10 * SMSS;
+
+        conn.enter_fast_recovery();
+        assert_eq!((conn.ssthresh, conn.cwnd), (5 * SMSS, 8 * SMSS));
+        assert_eq!(conn.recover, 1000 + 10 * SMSS);
+
+        // Partial ACK: two segments acked, another hole to retransmit
+        assert!(conn.on_new_ack(1000 + 2 * SMSS, 2 * SMSS));
+        assert!(conn.in_recovery);
+        assert_eq!(conn.cwnd, 7 * SMSS);
+
+        // Full ACK: leave recovery with cwnd back at ssthresh
+        assert!(!conn.on_new_ack(1000 + 10 * SMSS, 8 * SMSS));
+        assert!(!conn.in_recovery);
+        assert_eq!(conn.cwnd, 5 * SMSS);
+
+        // Back to congestion avoidance
+        assert!(!conn.on_new_ack(1000 + 11 * SMSS, SMSS));
+        assert_eq!((conn.cwnd, conn.bytes_acked_ca), (5 * SMSS, SMSS));
+    }
+
+    /// The buffered ranges as (offset from `base`, length) pairs
+    fn ooo_ranges(ooo: &OooQueue, base: u32) -> Vec<(u32, usize)> {
+        ooo.ranges.iter().map(|r| (r.seq.wrapping_sub(base), r.data.len())).collect()
+    }
+
+    #[test]
+    fn test_ooo_coalesces_overlapping_segments() {
+        let base = u32::MAX - 100; // exercise sequence wrap-around
+        let mut ooo = OooQueue::new();
+        assert!(ooo.insert(base, 4096, base.wrapping_add(300), &[3; 100]));
+        assert!(ooo.insert(base, 4096, base.wrapping_add(100), &[1; 100]));
+        assert!(ooo.insert(base, 4096, base.wrapping_add(150), &[2; 150]));
+        assert_eq!(ooo_ranges(&ooo, base), [(100, 300)]);
+
+        // Nothing is delivered until the hole at the front is filled
+        assert!(ooo.pop_in_order(base).is_none());
+        let data = ooo.pop_in_order(base.wrapping_add(120)).unwrap();
+        assert_eq!(data.len(), 280);
+        assert_eq!((data[0], data[100], data[279]), (1, 2, 3));
+        assert!(ooo.ranges.is_empty());
+    }
+
+    #[test]
+    fn test_ooo_drops_farthest_range_when_full() {
+        let mut ooo = OooQueue::new();
+        for i in 1..=OOO_BUFFER_SIZE as u32 {
+            assert!(ooo.insert(0, 4096, i * 200, &[0; 100]));
         }
+        // Closer than everything buffered: the last range makes way
+        assert!(ooo.insert(0, 4096, 50, &[0; 10]));
+        // Farther than everything buffered: not kept
+        assert!(!ooo.insert(0, 4096, 2000, &[0; 10]));
+        assert_eq!(ooo_ranges(&ooo, 0), [(50, 10), (200, 100), (400, 100), (600, 100)]);
+
+        // Data past the window is cut off; data entirely past it is refused
+        let mut ooo = OooQueue::new();
+        assert!(ooo.insert(0, 1024, 1000, &[0; 600]));
+        assert!(!ooo.insert(0, 1024, 1024, &[0; 10]));
+        assert_eq!(ooo_ranges(&ooo, 0), [(1000, 24)]);
+    }
 
-        None
+    #[test]
+    fn test_next_segment_size_and_push() {
+        let mss = MSS as usize;
+        // A short write goes out whole, with PSH
+        assert_eq!(next_segment(100, 0, 4 * mss, mss), Some((100, true)));
+        // A long one is cut into MSS-sized segments; only the last pushes
+        assert_eq!(next_segment(3000, 0, 4 * mss, mss), Some((mss, false)));
+        assert_eq!(next_segment(3000, 2 * mss, 4 * mss, mss), Some((3000 - 2 * mss, true)));
+        // So does a smaller peer MSS
+        assert_eq!(next_segment(3000, 0, 4 * mss, 536), Some((536, false)));
+        // The window limits the segment
+        assert_eq!(next_segment(3000, 0, 500, mss), Some((500, false)));
+        // Nothing new to send, or no room in the window
+        assert_eq!(next_segment(0, 0, 4 * mss, mss), None);
+        assert_eq!(next_segment(500, 500, 4 * mss, mss), None);
+        assert_eq!(next_segment(3000, 1000, 1000, mss), None);
+    }
+
+    #[test]
+    fn test_delayed_ack() {
+        // The first segment waits; the second, or a filling buffer, doesn't
+        assert!(!ack_now(1, 1024, 1024));
+        assert!(ack_now(2, 1024, 1024));
+        assert!(!ack_now(1, 512, 1024));
+        assert!(ack_now(1, 511, 1024));
+    }
+
+    #[test]
+    fn test_mss_option() {
+        let parse = |options: &[u8]| {
+            let mut seg = [0u8; 60];
+            seg[12] = (((HEADER_SIZE + options.len()) / 4) as u8) << 4;
+            seg[HEADER_SIZE..HEADER_SIZE + options.len()].copy_from_slice(options);
+            let len = HEADER_SIZE + options.len();
+            TcpHeader::parse(&seg[..len]).unwrap().mss_option(&seg[..len])
+        };
+        assert_eq!(parse(&[]), None);
+        assert_eq!(parse(&[2, 4, 0x02, 0x18]), Some(536));
+        // NOPs and unknown options (window scale) are skipped
+        assert_eq!(parse(&[1, 3, 3, 7, 2, 4, 0x05, 0xb4, 1, 1, 1, 0]), Some(1460));
+        // End of options stops the walk
+        assert_eq!(parse(&[0, 0, 0, 0, 2, 4, 0x02, 0x18]), None);
+        // Lengths that are too short or run past the header are rejected
+        assert_eq!(parse(&[8, 0, 2, 4, 0x02, 0x18, 0, 0]), None);
+        assert_eq!(parse(&[1, 1, 8, 10]), None);
+    }
+
+    #[test]
+    fn test_segment_acceptability() {
+        let nxt = u32::MAX - 10; // window straddles the sequence wrap
+        // Empty segments (pure ACKs)
+        assert!(segment_acceptable(nxt, 0, nxt, 0));
+        assert!(!segment_acceptable(nxt, 0, nxt.wrapping_add(1), 0));
+        assert!(segment_acceptable(nxt, 100, nxt.wrapping_add(99), 0));
+        assert!(!segment_acceptable(nxt, 100, nxt.wrapping_add(100), 0));
+        // Data overlapping the window at either end
+        assert!(segment_acceptable(nxt, 100, nxt.wrapping_sub(5), 10));
+        assert!(segment_acceptable(nxt, 100, nxt.wrapping_add(95), 10));
+        // Old duplicates, data beyond the window, and anything into a zero window
+        assert!(!segment_acceptable(nxt, 100, nxt.wrapping_sub(10), 10));
+        assert!(!segment_acceptable(nxt, 100, nxt.wrapping_add(100), 10));
+        assert!(!segment_acceptable(nxt, 0, nxt, 1));
     }
 }
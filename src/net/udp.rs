@@ -0,0 +1,258 @@
+//! UDP (User Datagram Protocol) implementation
+//!
+//! Sockets are entries in a small table of bound ports. Each bound port
+//! queues up to `QUEUE_LEN` received datagrams; further datagrams are
+//! dropped until the application reads some. Datagrams for ports nobody
+//! has bound are dropped silently.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use crate::allocator::Spinlock;
+use crate::net::{checksum, config, ipv4};
+use crate::{scheduler, timer};
+
+/// UDP header size
+pub const HEADER_SIZE: usize = 8;
+
+/// Largest payload that fits an unfragmented IPv4 packet
+pub const MAX_PAYLOAD: usize = ipv4::MAX_PACKET_SIZE - ipv4::HEADER_SIZE - HEADER_SIZE;
+
+/// Maximum number of bound UDP sockets
+const MAX_UDP_SOCKETS: usize = 8;
+
+/// Datagrams queued per socket before new ones are dropped
+const QUEUE_LEN: usize = 8;
+
+/// How long `send_to` waits for ARP to resolve the next hop
+const ARP_WAIT_MS: u64 = 500;
+
+/// First and last ephemeral port handed out by `bind(0)`
+pub const EPHEMERAL_FIRST: u16 = 49152;
+const EPHEMERAL_LAST: u16 = 65535;
+
+/// Parsed UDP header
+#[derive(Debug, Clone, Copy)]
+pub struct UdpHeader {
+    pub src_port: u16,
+    pub dst_port: u16,
+    /// Length of header plus payload
+    pub length: u16,
+    pub checksum: u16,
+}
+
+impl UdpHeader {
+    /// Parse a UDP header from raw bytes
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < HEADER_SIZE {
+            return None;
+        }
+
+        let length = u16::from_be_bytes([data[4], data[5]]);
+        if (length as usize) < HEADER_SIZE || length as usize > data.len() {
+            return None;
+        }
+
+        Some(UdpHeader {
+            src_port: u16::from_be_bytes([data[0], data[1]]),
+            dst_port: u16::from_be_bytes([data[2], data[3]]),
+            length,
+            checksum: u16::from_be_bytes([data[6], data[7]]),
+        })
+    }
+
+    /// Get the payload (after header, up to the UDP length)
+    pub fn payload<'a>(&self, data: &'a [u8]) -> &'a [u8] {
+        &data[HEADER_SIZE..self.length as usize]
+    }
+}
+
+/// A received datagram
+pub struct Datagram {
+    pub src_ip: [u8; 4],
+    pub src_port: u16,
+    pub data: Vec<u8>,
+}
+
+/// A bound port and its receive queue
+struct Endpoint {
+    port: u16,
+    queue: VecDeque<Datagram>,
+}
+
+struct SocketTable {
+    slots: [Option<Endpoint>; MAX_UDP_SOCKETS],
+    next_port: u16,
+}
+
+static SOCKETS: Spinlock<SocketTable> = Spinlock::new(SocketTable {
+    slots: [const { None }; MAX_UDP_SOCKETS],
+    next_port: EPHEMERAL_FIRST,
+});
+
+impl SocketTable {
+    fn is_bound(&self, port: u16) -> bool {
+        self.slots.iter().flatten().any(|ep| ep.port == port)
+    }
+
+    fn ephemeral_port(&mut self) -> Option<u16> {
+        for _ in EPHEMERAL_FIRST..=EPHEMERAL_LAST {
+            let port = self.next_port;
+            self.next_port = if port == EPHEMERAL_LAST { EPHEMERAL_FIRST } else { port + 1 };
+            if !self.is_bound(port) {
+                return Some(port);
+            }
+        }
+        None
+    }
+}
+
+/// Build a UDP datagram (header, payload and checksum) into `buffer`
+///
+/// Returns the datagram length, or 0 if it doesn't fit.
+pub fn build_datagram(
+    buffer: &mut [u8],
+    src_ip: [u8; 4],
+    dst_ip: [u8; 4],
+    src_port: u16,
+    dst_port: u16,
+    payload: &[u8],
+) -> usize {
+    let total = HEADER_SIZE + payload.len();
+    if total > buffer.len() || total > u16::MAX as usize {
+        return 0;
+    }
+
+    buffer[0..2].copy_from_slice(&src_port.to_be_bytes());
+    buffer[2..4].copy_from_slice(&dst_port.to_be_bytes());
+    buffer[4..6].copy_from_slice(&(total as u16).to_be_bytes());
+    buffer[6..8].copy_from_slice(&[0, 0]);
+    buffer[HEADER_SIZE..total].copy_from_slice(payload);
+
+    // A computed checksum of zero is sent as all ones (zero means "none")
+    let cksum = match checksum::tcp_udp_checksum(src_ip, dst_ip, ipv4::PROTO_UDP, &buffer[..total]) {
+        0 => 0xFFFF,
+        c => c,
+    };
+    buffer[6..8].copy_from_slice(&cksum.to_be_bytes());
+    total
+}
+
+/// Process a received UDP datagram (called from the IPv4 dispatch)
+pub fn process_packet(ip_header: &ipv4::Ipv4Header, data: &[u8]) {
+    let Some(udp) = UdpHeader::parse(data) else {
+        return;
+    };
+    let datagram = &data[..udp.length as usize];
+
+    // A zero checksum means the sender didn't compute one
+    if udp.checksum != 0
+        && checksum::tcp_udp_checksum(ip_header.src_ip, ip_header.dst_ip, ipv4::PROTO_UDP, datagram) != 0
+    {
+        net_log!(Errors, "[udp] Bad checksum, dropping");
+        return;
+    }
+
+    let mut table = SOCKETS.lock();
+    let Some(ep) = table.slots.iter_mut().flatten().find(|ep| ep.port == udp.dst_port) else {
+        return;
+    };
+    if ep.queue.len() >= QUEUE_LEN {
+        return;
+    }
+    ep.queue.push_back(Datagram {
+        src_ip: ip_header.src_ip,
+        src_port: udp.src_port,
+        data: udp.payload(datagram).to_vec(),
+    });
+}
+
+// ============================================================================
+// Public API
+// ============================================================================
+
+/// Bind a socket to `port` (0 picks an ephemeral port)
+///
+/// Returns the socket handle, or None if the port is taken or the table
+/// is full.
+pub fn bind(port: u16) -> Option<usize> {
+    let mut table = SOCKETS.lock();
+    let slot = table.slots.iter().position(Option::is_none)?;
+    let port = match port {
+        0 => table.ephemeral_port()?,
+        p if table.is_bound(p) => return None,
+        p => p,
+    };
+    table.slots[slot] = Some(Endpoint {
+        port,
+        queue: VecDeque::with_capacity(QUEUE_LEN),
+    });
+    Some(slot)
+}
+
+/// Local port of a socket
+pub fn local_port(sock: usize) -> Option<u16> {
+    SOCKETS.lock().slots.get(sock)?.as_ref().map(|ep| ep.port)
+}
+
+/// Take the oldest queued datagram (non-blocking)
+pub fn recv_from(sock: usize) -> Option<Datagram> {
+    SOCKETS.lock().slots.get_mut(sock)?.as_mut()?.queue.pop_front()
+}
+
+/// Release a socket and drop its queued datagrams
+pub fn close(sock: usize) {
+    if let Some(slot) = SOCKETS.lock().slots.get_mut(sock) {
+        *slot = None;
+    }
+}
+
+/// Send a datagram from `src_port` to `dst_ip:dst_port`
+///
+/// Waits up to `ARP_WAIT_MS` for the next hop to resolve, so it must be
+/// called from a task other than the network task. Returns false if the
+/// payload is too large or the address could not be resolved.
+pub fn send_to(dst_ip: &[u8; 4], dst_port: u16, src_port: u16, data: &[u8]) -> bool {
+    if data.len() > MAX_PAYLOAD {
+        return false;
+    }
+
+    let mut datagram = [0u8; HEADER_SIZE + MAX_PAYLOAD];
+    let len = build_datagram(&mut datagram, config().ip, *dst_ip, src_port, dst_port, data);
+
+    let deadline = timer::uptime_ms().saturating_add(ARP_WAIT_MS);
+    loop {
+        if ipv4::send_packet(dst_ip, ipv4::PROTO_UDP, &datagram[..len]) {
+            return true;
+        }
+        if timer::uptime_ms() >= deadline {
+            return false;
+        }
+        scheduler::sleep_ms(10);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_datagram_round_trip() {
+        let src = [10, 0, 2, 15];
+        let dst = [10, 0, 2, 3];
+        let mut buf = [0u8; 64];
+        let len = build_datagram(&mut buf, src, dst, 5000, 53, b"query");
+        assert_eq!(len, HEADER_SIZE + 5);
+
+        let header = UdpHeader::parse(&buf[..len]).unwrap();
+        assert_eq!(header.src_port, 5000);
+        assert_eq!(header.dst_port, 53);
+        assert_eq!(header.payload(&buf[..len]), b"query");
+        assert_ne!(header.checksum, 0);
+        assert_eq!(checksum::tcp_udp_checksum(src, dst, ipv4::PROTO_UDP, &buf[..len]), 0);
+
+        // Truncated datagrams and bogus lengths are rejected
+        assert!(UdpHeader::parse(&buf[..len - 1]).is_none());
+        buf[4..6].copy_from_slice(&4u16.to_be_bytes());
+        assert!(UdpHeader::parse(&buf[..len]).is_none());
+    }
+}
@@ -0,0 +1,126 @@
+//! Pipes between program tasks
+//!
+//! A pipe connects the stdout of one task (everything it prints through the
+//! kernel API) to the stdin of another (read with the `read_line` API).
+//! Each pipe has one writer and one reader task; when a task exits its end
+//! is closed, the reader sees EOF once the buffer drains, and output written
+//! after the reader is gone is discarded.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use crate::allocator::Spinlock;
+use crate::scheduler;
+use crate::task::TaskId;
+
+/// Bytes buffered in a pipe before the writer has to wait
+pub const PIPE_CAPACITY: usize = 4096;
+
+/// Index into the pipe table
+pub type PipeId = usize;
+
+struct Pipe {
+    buf: VecDeque<u8>,
+    writer: Option<TaskId>,
+    reader: Option<TaskId>,
+}
+
+static PIPES: Spinlock<Vec<Option<Pipe>>> = Spinlock::new(Vec::new());
+
+/// Result of a non-blocking read
+pub enum PipeRead {
+    /// This many bytes were copied
+    Data(usize),
+    /// Nothing buffered yet, but the writer is still running
+    Empty,
+    /// Nothing buffered and the writer has exited
+    Eof,
+}
+
+/// Create a pipe from `writer`'s stdout to `reader`'s stdin
+pub fn create(writer: TaskId, reader: TaskId) -> PipeId {
+    let pipe = Pipe {
+        buf: VecDeque::with_capacity(PIPE_CAPACITY),
+        writer: Some(writer),
+        reader: Some(reader),
+    };
+
+    let mut pipes = PIPES.lock();
+    if let Some(id) = pipes.iter().position(Option::is_none) {
+        pipes[id] = Some(pipe);
+        id
+    } else {
+        pipes.push(Some(pipe));
+        pipes.len() - 1
+    }
+}
+
+/// The pipe `task`'s output goes to, if any
+pub fn stdout_of(task: TaskId) -> Option<PipeId> {
+    PIPES.lock().iter().position(|p| matches!(p, Some(p) if p.writer == Some(task)))
+}
+
+/// The pipe `task` reads its input from, if any
+pub fn stdin_of(task: TaskId) -> Option<PipeId> {
+    PIPES.lock().iter().position(|p| matches!(p, Some(p) if p.reader == Some(task)))
+}
+
+/// Write all of `data`, yielding while the pipe is full
+///
+/// Returns false (dropping the rest) if the reader has gone away.
+pub fn write(id: PipeId, mut data: &[u8]) -> bool {
+    while !data.is_empty() {
+        {
+            let mut pipes = PIPES.lock();
+            let Some(Some(pipe)) = pipes.get_mut(id) else {
+                return false;
+            };
+            if pipe.reader.is_none() {
+                return false;
+            }
+            let n = data.len().min(PIPE_CAPACITY - pipe.buf.len());
+            pipe.buf.extend(&data[..n]);
+            data = &data[n..];
+        }
+        if !data.is_empty() {
+            scheduler::yield_now();
+        }
+    }
+    true
+}
+
+/// Read whatever is buffered into `buf` without waiting
+pub fn read(id: PipeId, buf: &mut [u8]) -> PipeRead {
+    let mut pipes = PIPES.lock();
+    let Some(Some(pipe)) = pipes.get_mut(id) else {
+        return PipeRead::Eof;
+    };
+    if pipe.buf.is_empty() {
+        return if pipe.writer.is_some() { PipeRead::Empty } else { PipeRead::Eof };
+    }
+    let n = buf.len().min(pipe.buf.len());
+    for (dst, src) in buf.iter_mut().zip(pipe.buf.drain(..n)) {
+        *dst = src;
+    }
+    PipeRead::Data(n)
+}
+
+/// Close every pipe end held by `task` (called when the task is reaped)
+///
+/// A pipe is released once both ends are closed.
+pub fn task_exited(task: TaskId) {
+    let mut pipes = PIPES.lock();
+    for slot in pipes.iter_mut() {
+        if let Some(pipe) = slot {
+            if pipe.writer == Some(task) {
+                pipe.writer = None;
+            }
+            if pipe.reader == Some(task) {
+                pipe.reader = None;
+                pipe.buf.clear();
+            }
+            if pipe.writer.is_none() && pipe.reader.is_none() {
+                *slot = None;
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Seedable pseudo-random numbers
+//!
+//! A xorshift32 generator: fast, tiny and fully determined by its seed,
+//! which is what guest programs and BASIC want (not cryptographic quality).
+//! Each task gets its own state, so one task's draws never perturb another
+//! task's sequence, and a seeded task always replays the same numbers.
+
+use alloc::collections::BTreeMap;
+use crate::allocator::Spinlock;
+use crate::task::TaskId;
+
+/// Substitute for a zero seed, which would make xorshift stick at zero
+const ZERO_SEED: u32 = 0x9E37_79B9;
+
+/// xorshift32 (Marsaglia, shifts 13/17/5)
+#[derive(Clone, Copy)]
+pub struct Xorshift32 {
+    state: u32,
+}
+
+impl Xorshift32 {
+    pub const fn new(seed: u32) -> Self {
+        Xorshift32 {
+            state: if seed == 0 { ZERO_SEED } else { seed },
+        }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+}
+
+/// Generator state per task
+static TASK_STATE: Spinlock<BTreeMap<TaskId, Xorshift32>> = Spinlock::new(BTreeMap::new());
+
+/// Restart `task`'s sequence from `seed`
+pub fn seed(task: TaskId, seed: u32) {
+    TASK_STATE.lock().insert(task, Xorshift32::new(seed));
+}
+
+/// A seed that differs from run to run, taken from the timestamp counter
+pub fn clock_seed() -> u32 {
+    let tsc = crate::timer::rdtsc();
+    (tsc ^ (tsc >> 32)) as u32
+}
+
+/// Next number in `task`'s sequence
+///
+/// A task that never seeded starts from the timestamp counter, so unseeded
+/// tasks get different sequences on every run.
+pub fn next(task: TaskId) -> u32 {
+    let mut states = TASK_STATE.lock();
+    states
+        .entry(task)
+        .or_insert_with(|| Xorshift32::new(clock_seed() ^ task))
+        .next_u32()
+}
+
+/// Forget a task's state (called when the task is reaped)
+pub fn task_exited(task: TaskId) {
+    TASK_STATE.lock().remove(&task);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_sequence() {
+        let mut rng = Xorshift32::new(1);
+        assert_eq!(rng.next_u32(), 270369);
+        assert_eq!(rng.next_u32(), 67634689);
+    }
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let mut a = Xorshift32::new(12345);
+        let mut b = Xorshift32::new(12345);
+        for _ in 0..100 {
+            assert_eq!(a.next_u32(), b.next_u32());
+        }
+    }
+
+    #[test]
+    fn test_zero_seed_does_not_stick() {
+        let mut rng = Xorshift32::new(0);
+        assert_ne!(rng.next_u32(), 0);
+    }
+}
@@ -65,6 +65,15 @@ impl SchedulerCell {
         }
     }
 
+    /// Shared access that never panics, for the panic handler
+    fn try_get(&self) -> Option<&Scheduler> {
+        if !self.initialized.load(Ordering::SeqCst) {
+            return None;
+        }
+        // Safety: single-threaded; callers only read
+        unsafe { (*self.inner.get()).as_ref() }
+    }
+
     /// Access for the run() function which needs special handling.
     /// Returns a raw pointer - caller must ensure safe usage.
     unsafe fn get_mut(&self) -> &mut Scheduler {
@@ -168,7 +177,8 @@ impl Scheduler {
 
     /// Remove finished tasks from the task list to free memory.
     /// Adjusts the current index to maintain correct task tracking.
-    /// Also cleans up program memory for finished program tasks.
+    /// Also cleans up program memory, pipe ends, RNG state and capability
+    /// restrictions of finished tasks.
     fn reap_finished_tasks(&mut self) {
         // Collect IDs of finished tasks for cleanup
         let finished_ids: alloc::vec::Vec<TaskId> = self.tasks
@@ -180,6 +190,9 @@ impl Scheduler {
         // Clean up program memory for finished tasks
         for task_id in finished_ids {
             executable::unload_task(task_id);
+            crate::pipe::task_exited(task_id);
+            crate::rand::task_exited(task_id);
+            crate::caps::task_exited(task_id);
         }
 
         // Count finished tasks before current for index adjustment
@@ -359,6 +372,26 @@ pub fn exit_task() {
     }
 }
 
+/// Terminate another task
+///
+/// The task is marked finished and reaped on the next schedule, which frees
+/// its stack and program memory. Kernel heap it allocated (and sockets it
+/// opened) are not reclaimed. Returns false if no such task exists, it has
+/// already finished, or it is the current task (use `exit_task` instead).
+pub fn kill(id: TaskId) -> bool {
+    SCHEDULER.with(|sched| {
+        let current = sched.tasks.get(sched.current).map(|t| t.id);
+        match sched.tasks.iter_mut().find(|t| t.id == id) {
+            Some(task) if Some(id) != current && task.state != TaskState::Finished => {
+                crate::println!("[{}] Task killed", task.name);
+                task.state = TaskState::Finished;
+                true
+            }
+            _ => false,
+        }
+    })
+}
+
 /// Get information about running tasks (for debugging)
 pub fn task_count() -> usize {
     SCHEDULER.with(|sched| sched.tasks.len())
@@ -375,6 +408,15 @@ pub fn current_task_id() -> Option<TaskId> {
     })
 }
 
+/// ID and name of the running task, without panicking
+///
+/// Used by the panic handler, which may run before the scheduler is
+/// initialized or while it is in an inconsistent state.
+pub fn try_current_task() -> Option<(TaskId, &'static str)> {
+    let sched = SCHEDULER.try_get()?;
+    sched.tasks.get(sched.current).map(|t| (t.id, t.name))
+}
+
 /// Information about a task (for MEMSTATS)
 #[derive(Debug)]
 pub struct TaskInfo {
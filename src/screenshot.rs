@@ -0,0 +1,112 @@
+//! VGA Screenshot Dump
+//!
+//! Encodes the displayed VGA image as a binary PPM (P6) and writes it to
+//! the serial port as base64, framed by marker lines:
+//!
+//! ```text
+//! -----BEGIN SCREENSHOT-----
+//! UDYKMzIwIDIwMAoyNTUK...
+//! -----END SCREENSHOT-----
+//! ```
+//!
+//! Pixels are converted to RGB through the current DAC palette, so custom
+//! palettes are captured too. On the host, extract the lines between the
+//! markers and run `base64 -d > screen.ppm`.
+
+use crate::base64;
+use crate::scheduler;
+use crate::serial::SERIAL;
+use crate::vga;
+
+/// Base64 output line length (characters)
+const LINE_LEN: usize = 76;
+
+/// Rows encoded between yields to the scheduler
+const ROWS_PER_YIELD: usize = 8;
+
+/// Streaming base64 encoder writing wrapped lines to serial
+struct Base64Writer {
+    pending: [u8; 3],
+    pending_len: usize,
+    column: usize,
+}
+
+impl Base64Writer {
+    fn new() -> Self {
+        Base64Writer {
+            pending: [0; 3],
+            pending_len: 0,
+            column: 0,
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.pending[self.pending_len] = byte;
+            self.pending_len += 1;
+            if self.pending_len == 3 {
+                self.emit_group();
+            }
+        }
+    }
+
+    /// Encode the pending bytes as one 4-character group (padded with '=')
+    fn emit_group(&mut self) {
+        for &ch in base64::encode_group(&self.pending[..self.pending_len]).iter() {
+            SERIAL.write_byte(ch);
+        }
+        self.pending_len = 0;
+
+        self.column += 4;
+        if self.column >= LINE_LEN {
+            SERIAL.write_str("\n");
+            self.column = 0;
+        }
+    }
+
+    /// Flush the final partial group and end the current line
+    fn finish(mut self) {
+        if self.pending_len > 0 {
+            self.emit_group();
+        }
+        if self.column > 0 {
+            SERIAL.write_str("\n");
+        }
+    }
+}
+
+/// Dump the displayed VGA image to serial
+///
+/// Yields to the scheduler every few rows so other tasks keep running
+/// during the (roughly 256 KB) transfer. Returns false if VGA is not enabled.
+pub fn dump_to_serial() -> bool {
+    if !vga::is_enabled() {
+        return false;
+    }
+
+    // Snapshot the palette once so the whole image uses consistent colors
+    let mut palette = [(0u8, 0u8, 0u8); 256];
+    for (i, entry) in palette.iter_mut().enumerate() {
+        *entry = vga::get_palette(i as u8);
+    }
+
+    SERIAL.write_str("-----BEGIN SCREENSHOT-----\n");
+    let mut enc = Base64Writer::new();
+    enc.write(b"P6\n320 200\n255\n");
+
+    let mut row = [0u8; vga::WIDTH];
+    for y in 0..vga::HEIGHT {
+        vga::read_row(y, &mut row);
+        for &index in row.iter() {
+            let (r, g, b) = palette[index as usize];
+            enc.write(&[r, g, b]);
+        }
+        if y % ROWS_PER_YIELD == ROWS_PER_YIELD - 1 {
+            scheduler::yield_now();
+        }
+    }
+
+    enc.finish();
+    SERIAL.write_str("-----END SCREENSHOT-----\n");
+    true
+}
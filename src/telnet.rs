@@ -1,6 +1,6 @@
 use core::fmt;
 
-use crate::basic::terminal::{ReadStatus, Terminal};
+use crate::basic::terminal::{OutputCursor, ReadStatus, Terminal};
 use crate::net::tcp;
 use crate::scheduler;
 
@@ -35,6 +35,7 @@ pub struct TelnetTerminal {
     rx_state: RxState,
     swallow_lf: bool,
     closed: bool,
+    cursor: OutputCursor,
 }
 
 impl TelnetTerminal {
@@ -47,6 +48,7 @@ impl TelnetTerminal {
             rx_state: RxState::Data,
             swallow_lf: false,
             closed: false,
+            cursor: OutputCursor::new(),
         }
     }
 
@@ -91,10 +93,9 @@ impl TelnetTerminal {
 
         let _ = self.send_bytes(&[IAC, resp_cmd, resp_opt]);
     }
-}
 
-impl fmt::Write for TelnetTerminal {
-    fn write_str(&mut self, s: &str) -> fmt::Result {
+    /// Send text with telnet newline and IAC escaping (no tab expansion)
+    fn write_escaped(&mut self, s: &str) -> fmt::Result {
         if self.closed {
             return Err(fmt::Error);
         }
@@ -141,6 +142,15 @@ impl fmt::Write for TelnetTerminal {
     }
 }
 
+impl fmt::Write for TelnetTerminal {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut cursor = core::mem::take(&mut self.cursor);
+        let result = cursor.write(s, &mut |chunk| self.write_escaped(chunk));
+        self.cursor = cursor;
+        result
+    }
+}
+
 impl Terminal for TelnetTerminal {
     fn poll_byte(&mut self) -> ReadStatus {
         if self.closed {
@@ -159,6 +169,8 @@ impl Terminal for TelnetTerminal {
                 }
                 let n = n as usize;
                 if n == 0 {
+                    // About to wait for input: get any prompt out now
+                    tcp::flush(self.sock);
                     return ReadStatus::NoData;
                 }
                 self.rx_len = n;
@@ -233,6 +245,14 @@ impl Terminal for TelnetTerminal {
             }
         }
     }
+
+    fn cursor(&self) -> (usize, usize) {
+        self.cursor.position()
+    }
+
+    fn set_tab_width(&mut self, width: usize) {
+        self.cursor.set_tab_width(width);
+    }
 }
 
 pub fn telnetd_task() {
@@ -265,7 +285,7 @@ fn telnet_session_task(sock: usize) {
     let mut term = TelnetTerminal::new(sock);
     term.negotiate();
 
-    crate::basic::run_repl_on_terminal(&mut term);
+    crate::basic::run_repl_on_terminal(&mut term, false);
 
     tcp::close(sock);
     crate::println!("[telnet] Session ended (sock={})", sock);
@@ -1,11 +1,12 @@
 //! PIT (Programmable Interval Timer) driver
 //!
-//! Configures the 8253/8254 PIT for time tracking at ~100 Hz.
+//! Configures the 8253/8254 PIT for time tracking at ~100 Hz by default;
+//! the rate can be changed at runtime with `set_frequency`.
 //! Uses interrupt-driven tick counting for accurate timekeeping.
 
+use crate::idt;
 use crate::io::outb;
-use crate::{cursor, mouse, vga};
-use core::sync::atomic::{AtomicU64, Ordering};
+use core::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
 
 // PIT I/O ports
 const PIT_CHANNEL0: u16 = 0x40;
@@ -13,17 +14,76 @@ const PIT_COMMAND: u16 = 0x43;
 
 // PIT configuration
 const PIT_FREQUENCY: u32 = 1_193_182; // Base frequency in Hz
-const TARGET_HZ: u32 = 100; // 100 Hz = 10ms per tick
-const DIVISOR: u16 = (PIT_FREQUENCY / TARGET_HZ) as u16; // ~11932
+const DEFAULT_HZ: u32 = 100; // 100 Hz = 10ms per tick
+
+/// Lowest supported tick rate (the PIT divisor is 16 bits)
+pub const MIN_HZ: u32 = PIT_FREQUENCY / 65535 + 1;
+
+/// Highest supported tick rate
+pub const MAX_HZ: u32 = 10_000;
 
 // Global tick counter (incremented by timer interrupt handler)
 static TICK_COUNT: AtomicU64 = AtomicU64::new(0);
 
+/// Current tick rate in Hz
+static TICK_HZ: AtomicU32 = AtomicU32::new(DEFAULT_HZ);
+
+/// Tick count and elapsed milliseconds at the last frequency change,
+/// so uptime stays correct across changes
+static EPOCH_TICKS: AtomicU64 = AtomicU64::new(0);
+static EPOCH_MS: AtomicU64 = AtomicU64::new(0);
+
+/// Maximum number of registered timer callbacks
+pub const MAX_CALLBACKS: usize = 8;
+
+/// A periodic routine run from the timer interrupt
+#[derive(Clone, Copy)]
+struct Callback {
+    interval_ticks: u64,
+    func: fn(),
+}
+
+/// Registered callbacks; only the first CALLBACK_COUNT entries are valid
+static mut CALLBACKS: [Option<Callback>; MAX_CALLBACKS] = [None; MAX_CALLBACKS];
+static CALLBACK_COUNT: AtomicUsize = AtomicUsize::new(0);
+
 /// Initialize the PIT timer
 ///
 /// Configures the PIT to generate interrupts at 100 Hz.
 /// The actual interrupt handling is done in the interrupts module.
 pub fn init() {
+    program_pit(DEFAULT_HZ);
+}
+
+/// Change the tick rate
+///
+/// Higher rates give finer sleep resolution at the cost of more
+/// interrupts. Deadlines already computed in ticks (pending sleeps, TCP
+/// retransmit timers) are not rescaled and will fire early or late once.
+/// Returns false if `hz` is outside `MIN_HZ..=MAX_HZ`.
+pub fn set_frequency(hz: u32) -> bool {
+    if !(MIN_HZ..=MAX_HZ).contains(&hz) {
+        return false;
+    }
+
+    let were_enabled = idt::are_interrupts_enabled();
+    idt::disable_interrupts();
+
+    // Start a new epoch so uptime_ms() doesn't jump
+    EPOCH_MS.store(uptime_ms(), Ordering::Relaxed);
+    EPOCH_TICKS.store(ticks(), Ordering::Relaxed);
+    TICK_HZ.store(hz, Ordering::Relaxed);
+    program_pit(hz);
+
+    if were_enabled {
+        idt::enable_interrupts();
+    }
+    true
+}
+
+/// Program PIT channel 0 as a rate generator at `hz`
+fn program_pit(hz: u32) {
+    let divisor = (PIT_FREQUENCY / hz) as u16;
     unsafe {
         // Configure PIT channel 0:
         // 0x34 = 00110100
@@ -34,8 +94,8 @@ pub fn init() {
         outb(PIT_COMMAND, 0x34);
 
         // Set divisor (low byte first, then high byte)
-        outb(PIT_CHANNEL0, (DIVISOR & 0xFF) as u8);
-        outb(PIT_CHANNEL0, ((DIVISOR >> 8) & 0xFF) as u8);
+        outb(PIT_CHANNEL0, (divisor & 0xFF) as u8);
+        outb(PIT_CHANNEL0, ((divisor >> 8) & 0xFF) as u8);
     }
 }
 
@@ -44,13 +104,94 @@ pub fn init() {
 /// This function is called from the interrupt handler in interrupts.rs.
 /// It should not be called from anywhere else.
 pub fn tick() {
-    TICK_COUNT.fetch_add(1, Ordering::Relaxed);
+    let now = TICK_COUNT.fetch_add(1, Ordering::Relaxed) + 1;
+
+    let count = CALLBACK_COUNT.load(Ordering::Acquire);
+    let callbacks = unsafe { &*core::ptr::addr_of!(CALLBACKS) };
+    for cb in callbacks[..count].iter().flatten() {
+        if now.is_multiple_of(cb.interval_ticks) {
+            (cb.func)();
+        }
+    }
+}
+
+/// Register a routine to run every `interval_ticks` timer ticks
+///
+/// Callbacks run inside the timer interrupt handler with interrupts
+/// disabled, so they must be short and must not allocate, sleep, yield,
+/// or touch state that tasks modify without disabling interrupts.
+/// Work that needs any of that belongs in a task instead.
+///
+/// Callbacks cannot be unregistered. Must be called from task context
+/// (not from another callback). Returns false if `interval_ticks` is 0
+/// or all `MAX_CALLBACKS` slots are taken.
+pub fn register_callback(interval_ticks: u64, func: fn()) -> bool {
+    if interval_ticks == 0 {
+        return false;
+    }
+
+    let count = CALLBACK_COUNT.load(Ordering::Acquire);
+    if count >= MAX_CALLBACKS {
+        return false;
+    }
 
-    // Poll for mouse data and update cursor if VGA mode is active
-    if vga::is_enabled() {
-        // Poll mouse since IRQ12 may not be working in QEMU
-        mouse::poll();
-        cursor::update();
+    // Fill the slot before publishing it to the interrupt handler
+    unsafe {
+        CALLBACKS[count] = Some(Callback { interval_ticks, func });
+    }
+    CALLBACK_COUNT.store(count + 1, Ordering::Release);
+    true
+}
+
+/// TSC cycles per microsecond (0 until calibrated)
+static TSC_PER_US: AtomicU64 = AtomicU64::new(0);
+
+/// Ticks measured when calibrating the TSC
+const CALIBRATION_TICKS: u64 = 5;
+
+/// Read the CPU timestamp counter
+#[inline]
+pub fn rdtsc() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+/// Measure the TSC rate against the timer tick
+///
+/// Busy-waits for `CALIBRATION_TICKS` ticks (~50ms at 100 Hz), so
+/// interrupts must be enabled. Returns cycles per microsecond.
+pub fn calibrate_tsc() -> u64 {
+    // Start on a tick edge so the measured span is whole ticks
+    let start_tick = ticks();
+    while ticks() == start_tick {
+        core::hint::spin_loop();
+    }
+    let t0 = ticks();
+    let c0 = rdtsc();
+    while ticks() < t0 + CALIBRATION_TICKS {
+        core::hint::spin_loop();
+    }
+    let cycles = rdtsc() - c0;
+
+    let us = ticks_to_ms(CALIBRATION_TICKS) * 1000;
+    let per_us = (cycles / us).max(1);
+    TSC_PER_US.store(per_us, Ordering::Relaxed);
+    per_us
+}
+
+/// Busy-wait for `us` microseconds
+///
+/// For sub-tick delays; does not yield, so other tasks don't run while
+/// waiting. Calibrates the TSC on first use if that hasn't happened yet.
+pub fn delay_us(us: u64) {
+    let mut per_us = TSC_PER_US.load(Ordering::Relaxed);
+    if per_us == 0 {
+        per_us = calibrate_tsc();
+    }
+
+    let start = rdtsc();
+    let cycles = us * per_us;
+    while rdtsc().wrapping_sub(start) < cycles {
+        core::hint::spin_loop();
     }
 }
 
@@ -59,19 +200,24 @@ pub fn ticks() -> u64 {
     TICK_COUNT.load(Ordering::Relaxed)
 }
 
-/// Get ticks per second (100)
-pub const fn ticks_per_second() -> u64 {
-    TARGET_HZ as u64
+/// Get ticks per second (100 unless changed with `set_frequency`)
+pub fn ticks_per_second() -> u64 {
+    TICK_HZ.load(Ordering::Relaxed) as u64
+}
+
+/// Milliseconds since boot
+pub fn uptime_ms() -> u64 {
+    let elapsed = ticks().saturating_sub(EPOCH_TICKS.load(Ordering::Relaxed));
+    EPOCH_MS.load(Ordering::Relaxed) + ticks_to_ms(elapsed)
 }
 
-/// Convert milliseconds to ticks
+/// Convert milliseconds to ticks at the current rate
 pub fn ms_to_ticks(ms: u64) -> u64 {
-    // 100 ticks/sec = 1 tick per 10ms
-    // ms * 100 / 1000 = ms / 10
-    (ms + 9) / 10 // Round up
+    // Round up so a sleep never ends early
+    (ms * ticks_per_second()).div_ceil(1000)
 }
 
-/// Convert ticks to milliseconds
+/// Convert ticks at the current rate to milliseconds
 pub fn ticks_to_ms(t: u64) -> u64 {
-    t * 10
+    t * 1000 / ticks_per_second()
 }
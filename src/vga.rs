@@ -1,8 +1,13 @@
 //! VGA Mode 13h Driver
 //!
-//! Provides direct framebuffer access for 320x200x256 VGA graphics mode.
+//! Provides drawing primitives for 320x200x256 VGA graphics mode.
 //! Used for memory visualization when debug mode is enabled.
+//!
+//! All drawing goes to an off-screen back buffer; `present()` copies it to
+//! the hardware framebuffer. This lets a whole scene (memory map, cursor,
+//! tooltip) be composed before it becomes visible, avoiding flicker.
 
+use core::ptr::addr_of_mut;
 use core::sync::atomic::{AtomicBool, Ordering};
 
 /// VGA framebuffer address (linear, identity-mapped)
@@ -15,6 +20,21 @@ pub const HEIGHT: usize = 200;
 /// Total pixels
 pub const TOTAL_PIXELS: usize = WIDTH * HEIGHT;
 
+/// DAC read index port (selects the palette entry to read)
+const DAC_READ_INDEX: u16 = 0x3C7;
+
+/// DAC write index port (selects the palette entry to write)
+const DAC_WRITE_INDEX: u16 = 0x3C8;
+
+/// DAC data port (r, g, b in sequence, 6 bits each)
+const DAC_DATA: u16 = 0x3C9;
+
+/// VGA input status register #1 (color mode)
+const INPUT_STATUS_1: u16 = 0x3DA;
+
+/// Input status bit set while the display is in vertical retrace
+const STATUS_VRETRACE: u8 = 0x08;
+
 /// Magic address where bootloader stores VGA mode status
 const VGA_STATUS_ADDR: usize = 0x501;
 
@@ -44,6 +64,18 @@ pub mod colors {
 /// Static flag indicating whether VGA mode is active
 static VGA_ENABLED: AtomicBool = AtomicBool::new(false);
 
+/// Off-screen buffer all drawing functions write to
+static mut BACK_BUFFER: [u8; TOTAL_PIXELS] = [0; TOTAL_PIXELS];
+
+/// Set when the back buffer has changed since the last `present()`
+static BACK_DIRTY: AtomicBool = AtomicBool::new(false);
+
+/// Pointer to the first byte of the back buffer
+#[inline]
+fn back_buffer() -> *mut u8 {
+    addr_of_mut!(BACK_BUFFER) as *mut u8
+}
+
 /// Initialize the VGA driver
 ///
 /// Checks if the bootloader set VGA mode 13h by reading the status byte
@@ -57,6 +89,7 @@ pub fn init() {
 
         // Clear screen to black
         clear(colors::BLACK);
+        present();
 
         crate::println!("[vga] Mode 13h active (320x200x256)");
     }
@@ -68,6 +101,161 @@ pub fn is_enabled() -> bool {
     VGA_ENABLED.load(Ordering::Acquire)
 }
 
+/// Back buffer base address, or None if VGA is not enabled
+///
+/// This is the surface every drawing function writes to, linear with one
+/// byte per pixel, so the stride (bytes per row) equals `WIDTH`. Bytes
+/// written directly to it don't mark the buffer dirty; they appear at the
+/// next `present()`. The hardware framebuffer itself is not handed out,
+/// since `present()` overwrites it.
+pub fn back_buffer_address() -> Option<usize> {
+    if is_enabled() {
+        Some(back_buffer() as usize)
+    } else {
+        None
+    }
+}
+
+/// Wait for the start of the next vertical retrace
+///
+/// Waits for any retrace in progress to end first, so callers get the whole
+/// retrace period for drawing. Busy-waits (at most one frame, ~14ms at
+/// 70Hz) because yielding could miss the short retrace window.
+/// Returns immediately if VGA is not enabled.
+pub fn wait_vblank() {
+    if !is_enabled() {
+        return;
+    }
+
+    unsafe {
+        while crate::io::inb(INPUT_STATUS_1) & STATUS_VRETRACE != 0 {
+            core::hint::spin_loop();
+        }
+        while crate::io::inb(INPUT_STATUS_1) & STATUS_VRETRACE == 0 {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+/// Set a palette entry from 8-bit (r, g, b) components
+///
+/// The DAC only has 6 bits per channel, so the low 2 bits of each
+/// component are dropped. Does nothing if VGA is not enabled.
+pub fn set_palette(index: u8, r: u8, g: u8, b: u8) {
+    if !is_enabled() {
+        return;
+    }
+
+    unsafe {
+        crate::io::outb(DAC_WRITE_INDEX, index);
+        crate::io::outb(DAC_DATA, r >> 2);
+        crate::io::outb(DAC_DATA, g >> 2);
+        crate::io::outb(DAC_DATA, b >> 2);
+    }
+}
+
+/// Restore the default mode 13h palette
+pub fn reset_palette() {
+    if !is_enabled() {
+        return;
+    }
+
+    unsafe {
+        // The write index auto-increments after each blue component
+        crate::io::outb(DAC_WRITE_INDEX, 0);
+        for i in 0..=255u8 {
+            let (r, g, b) = default_palette_entry(i);
+            crate::io::outb(DAC_DATA, r);
+            crate::io::outb(DAC_DATA, g);
+            crate::io::outb(DAC_DATA, b);
+        }
+    }
+}
+
+/// Default VGA palette entry as 6-bit (r, g, b)
+///
+/// 0-15 are the EGA colors, 16-31 a gray ramp, 32-247 nine 24-step hue
+/// wheels (three saturations at each of three brightnesses), 248-255 black.
+fn default_palette_entry(index: u8) -> (u8, u8, u8) {
+    const EGA: [(u8, u8, u8); 16] = [
+        (0x00, 0x00, 0x00), (0x00, 0x00, 0x2A), (0x00, 0x2A, 0x00), (0x00, 0x2A, 0x2A),
+        (0x2A, 0x00, 0x00), (0x2A, 0x00, 0x2A), (0x2A, 0x15, 0x00), (0x2A, 0x2A, 0x2A),
+        (0x15, 0x15, 0x15), (0x15, 0x15, 0x3F), (0x15, 0x3F, 0x15), (0x15, 0x3F, 0x3F),
+        (0x3F, 0x15, 0x15), (0x3F, 0x15, 0x3F), (0x3F, 0x3F, 0x15), (0x3F, 0x3F, 0x3F),
+    ];
+    const GRAYS: [u8; 16] = [
+        0x00, 0x05, 0x08, 0x0B, 0x0E, 0x11, 0x14, 0x18,
+        0x1C, 0x20, 0x24, 0x28, 0x2D, 0x32, 0x38, 0x3F,
+    ];
+    // Channel levels (low to high) for each hue wheel
+    const WHEELS: [[u8; 5]; 9] = [
+        [0x00, 0x10, 0x1F, 0x2F, 0x3F],
+        [0x1F, 0x27, 0x2F, 0x37, 0x3F],
+        [0x2D, 0x31, 0x36, 0x3A, 0x3F],
+        [0x00, 0x07, 0x0E, 0x15, 0x1C],
+        [0x0E, 0x11, 0x15, 0x18, 0x1C],
+        [0x14, 0x16, 0x18, 0x1A, 0x1C],
+        [0x00, 0x04, 0x08, 0x0C, 0x10],
+        [0x08, 0x0A, 0x0C, 0x0E, 0x10],
+        [0x0B, 0x0C, 0x0D, 0x0F, 0x10],
+    ];
+
+    let index = index as usize;
+    match index {
+        0..=15 => EGA[index],
+        16..=31 => {
+            let v = GRAYS[index - 16];
+            (v, v, v)
+        }
+        32..=247 => {
+            let levels = &WHEELS[(index - 32) / 24];
+            let (lo, hi) = (levels[0], levels[4]);
+            // Walk blue -> magenta -> red -> yellow -> green -> cyan -> blue,
+            // moving one channel per 4-step segment
+            let pos = (index - 32) % 24;
+            let (up, down) = (levels[pos % 4], levels[4 - pos % 4]);
+            match pos / 4 {
+                0 => (up, lo, hi),
+                1 => (hi, lo, down),
+                2 => (hi, up, lo),
+                3 => (down, hi, lo),
+                4 => (lo, hi, up),
+                _ => (lo, down, hi),
+            }
+        }
+        _ => (0, 0, 0),
+    }
+}
+
+/// Read a palette entry from the DAC as 8-bit (r, g, b)
+///
+/// The DAC stores 6 bits per channel; values are scaled to 0-255.
+pub fn get_palette(index: u8) -> (u8, u8, u8) {
+    let scale = |v: u8| (v << 2) | (v >> 4);
+    unsafe {
+        crate::io::outb(DAC_READ_INDEX, index);
+        let r = crate::io::inb(DAC_DATA) & 0x3F;
+        let g = crate::io::inb(DAC_DATA) & 0x3F;
+        let b = crate::io::inb(DAC_DATA) & 0x3F;
+        (scale(r), scale(g), scale(b))
+    }
+}
+
+/// Copy row `y` of the displayed image (hardware framebuffer) into `buf`
+///
+/// Copies at most `WIDTH` bytes. Does nothing if VGA is not enabled
+/// or `y` is out of bounds.
+pub fn read_row(y: usize, buf: &mut [u8]) {
+    if !is_enabled() || y >= HEIGHT {
+        return;
+    }
+
+    let fb = FRAMEBUFFER as *const u8;
+    for (x, px) in buf.iter_mut().take(WIDTH).enumerate() {
+        *px = unsafe { fb.add(y * WIDTH + x).read_volatile() };
+    }
+}
+
 /// Set a single pixel at (x, y) to the specified color
 ///
 /// Does nothing if VGA is not enabled or coordinates are out of bounds.
@@ -79,9 +267,9 @@ pub fn set_pixel(x: usize, y: usize, color: u8) {
 
     let offset = y * WIDTH + x;
     unsafe {
-        let fb = FRAMEBUFFER as *mut u8;
-        fb.add(offset).write_volatile(color);
+        *back_buffer().add(offset) = color;
     }
+    BACK_DIRTY.store(true, Ordering::Release);
 }
 
 /// Set a pixel by linear index (0..64000)
@@ -94,9 +282,9 @@ pub fn set_pixel_index(index: usize, color: u8) {
     }
 
     unsafe {
-        let fb = FRAMEBUFFER as *mut u8;
-        fb.add(index).write_volatile(color);
+        *back_buffer().add(index) = color;
     }
+    BACK_DIRTY.store(true, Ordering::Release);
 }
 
 /// Fill a range of pixels with a color
@@ -109,13 +297,10 @@ pub fn fill_range(start_index: usize, count: usize, color: u8) {
     }
 
     let end = (start_index + count).min(TOTAL_PIXELS);
-    let fb = FRAMEBUFFER as *mut u8;
-
     unsafe {
-        for i in start_index..end {
-            fb.add(i).write_volatile(color);
-        }
+        core::ptr::write_bytes(back_buffer().add(start_index), color, end - start_index);
     }
+    BACK_DIRTY.store(true, Ordering::Release);
 }
 
 /// Fill a rectangular region with a color
@@ -124,15 +309,16 @@ pub fn fill_rect(x: usize, y: usize, w: usize, h: usize, color: u8) {
         return;
     }
 
+    if x >= WIDTH {
+        return;
+    }
+    let w = w.min(WIDTH - x);
     for row in y..(y + h).min(HEIGHT) {
-        for col in x..(x + w).min(WIDTH) {
-            let offset = row * WIDTH + col;
-            unsafe {
-                let fb = FRAMEBUFFER as *mut u8;
-                fb.add(offset).write_volatile(color);
-            }
+        unsafe {
+            core::ptr::write_bytes(back_buffer().add(row * WIDTH + x), color, w);
         }
     }
+    BACK_DIRTY.store(true, Ordering::Release);
 }
 
 /// Clear the entire screen to a color
@@ -141,12 +327,10 @@ pub fn clear(color: u8) {
         return;
     }
 
-    let fb = FRAMEBUFFER as *mut u8;
     unsafe {
-        for i in 0..TOTAL_PIXELS {
-            fb.add(i).write_volatile(color);
-        }
+        core::ptr::write_bytes(back_buffer(), color, TOTAL_PIXELS);
     }
+    BACK_DIRTY.store(true, Ordering::Release);
 }
 
 /// Draw a horizontal line
@@ -155,13 +339,52 @@ pub fn hline(x: usize, y: usize, length: usize, color: u8) {
         return;
     }
 
-    let start = y * WIDTH + x;
-    let end = (start + length).min(y * WIDTH + WIDTH);
+    if x >= WIDTH {
+        return;
+    }
+    let length = length.min(WIDTH - x);
+    unsafe {
+        core::ptr::write_bytes(back_buffer().add(y * WIDTH + x), color, length);
+    }
+    BACK_DIRTY.store(true, Ordering::Release);
+}
+
+/// Copy a full screen of pixels into the back buffer
+///
+/// Copies at most `TOTAL_PIXELS` bytes from `pixels`.
+pub fn blit(pixels: &[u8]) {
+    if !is_enabled() {
+        return;
+    }
 
-    let fb = FRAMEBUFFER as *mut u8;
+    let count = pixels.len().min(TOTAL_PIXELS);
     unsafe {
-        for i in start..end {
-            fb.add(i).write_volatile(color);
+        core::ptr::copy_nonoverlapping(pixels.as_ptr(), back_buffer(), count);
+    }
+    BACK_DIRTY.store(true, Ordering::Release);
+}
+
+/// Copy the back buffer to the hardware framebuffer
+///
+/// Copies in 8-byte words to keep the number of VGA memory writes low.
+pub fn present() {
+    if !is_enabled() {
+        return;
+    }
+
+    BACK_DIRTY.store(false, Ordering::Release);
+    let src = back_buffer() as *const u64;
+    let fb = FRAMEBUFFER as *mut u64;
+    unsafe {
+        for i in 0..TOTAL_PIXELS / 8 {
+            fb.add(i).write_volatile(src.add(i).read_unaligned());
         }
     }
 }
+
+/// Present the back buffer only if something was drawn since the last present
+pub fn present_if_dirty() {
+    if BACK_DIRTY.load(Ordering::Acquire) {
+        present();
+    }
+}